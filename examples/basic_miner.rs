@@ -1,10 +1,6 @@
+use rust_stratum::stratum::prelude::*;
 use rust_stratum::stratum::v1::jobs::TestMiner;
-use rust_stratum::stratum::{
-    create_client,
-    types::{Share, StratumVersion},
-};
 use std::error::Error;
-use tokio::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -19,7 +15,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     println!("Connecting to stratum+tcp://{}:{}", host, port);
 
     // Create and connect a client
-    let mut client = create_client(StratumVersion::V1, host.clone(), port, TestMiner).await?;
+    let mut client = StratumV1Client::new(host.clone(), port, TestMiner).await?;
 
     // Subscribe to the pool
     let subscription = client.subscribe().await?;
@@ -34,71 +30,55 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
     println!("Successfully authorized worker!");
 
-    // Main mining loop
+    // Jobs arrive through the dispatcher; waiting on them keeps the loop
+    // idle between jobs instead of polling the pool
+    client.set_auto_reconnect(true).await;
+    client.start_dispatcher().await;
+    let mut jobs = client.jobs();
+
     println!("Starting mining loop...");
     let mut shares_accepted = 0u64;
     let mut shares_rejected = 0u64;
-    let mut last_job_id = String::new();
 
-    loop {
-        // Handle any new notifications (new jobs, difficulty changes)
-        if let Err(e) = client.handle_notifications().await {
-            eprintln!("Error handling notifications: {}", e);
-            // Try to reconnect
-            println!("Attempting to reconnect...");
-            if let Err(e) = client.reconnect().await {
-                eprintln!("Reconnection failed: {}", e);
-                return Err("Failed to reconnect to pool".into());
-            }
+    while jobs.changed().await.is_ok() {
+        let Some(job) = jobs.borrow_and_update().clone() else {
             continue;
+        };
+        if let Some(target) = &job.target {
+            println!("Mining at difficulty {}", target.difficulty);
         }
+        println!("Job ID: {}", job.job_id);
+        println!("Previous block hash: {}", job.prev_hash);
 
-        // Get current job
-        if let Some(job) = client.get_current_job().await? {
-            // Only process new jobs
-            if job.job_id != last_job_id {
-                last_job_id = job.job_id.clone();
-
-                // Get current target
-                if let Ok(target) = client.get_target().await {
-                    println!("Mining at difficulty {}", target.difficulty);
-                    println!("Job ID: {}", job.job_id);
-                    println!("Previous block hash: {}", job.prev_hash);
-
-                    // Generate a random share for testing
-                    let share = Share {
-                        job_id: job.job_id,
-                        extranonce2: format!("{:08x}", rand::random::<u32>()),
-                        ntime: job.ntime,
-                        nonce: format!("{:08x}", rand::random::<u32>()),
-                    };
+        // Generate a random share for testing
+        let share = Share {
+            job_id: job.job_id.clone(),
+            extranonce2: format!("{:08x}", rand::random::<u32>()),
+            ntime: job.ntime.clone(),
+            nonce: format!("{:08x}", rand::random::<u32>()),
+            version_bits: None,
+        };
 
-                    match client.submit_share(share).await {
-                        Ok(accepted) => {
-                            if accepted {
-                                shares_accepted += 1;
-                                println!(
-                                    "Share accepted! ({} accepted, {} rejected)",
-                                    shares_accepted, shares_rejected
-                                );
-                            } else {
-                                shares_rejected += 1;
-                                println!(
-                                    "Share rejected ({} accepted, {} rejected)",
-                                    shares_accepted, shares_rejected
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to submit share: {}", e);
-                            shares_rejected += 1;
-                        }
-                    }
-                }
+        match client.submit_share(share).await {
+            Ok(true) => {
+                shares_accepted += 1;
+                println!(
+                    "Share accepted! ({} accepted, {} rejected)",
+                    shares_accepted, shares_rejected
+                );
+            }
+            Ok(false) => {
+                shares_rejected += 1;
+                println!(
+                    "Share rejected ({} accepted, {} rejected)",
+                    shares_accepted, shares_rejected
+                );
+            }
+            Err(e) => {
+                eprintln!("Failed to submit share: {}", e);
+                shares_rejected += 1;
             }
         }
-
-        // Sleep briefly to avoid hammering the pool
-        tokio::time::sleep(Duration::from_secs(1)).await;
     }
+    Ok(())
 }
@@ -0,0 +1,23 @@
+use rust_stratum::stratum::{capture::Capture, corpus};
+use std::error::Error;
+
+/// Convert a recorded pool capture into a fuzz corpus
+///
+/// Usage: cargo run --example capture_to_corpus --features corpus -- <capture.jsonl> <corpus dir>
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(capture_path), Some(corpus_dir)) = (args.next(), args.next()) else {
+        eprintln!("Usage: capture_to_corpus <capture.jsonl> <corpus dir>");
+        std::process::exit(2);
+    };
+
+    let capture = Capture::from_file(&capture_path)?;
+    let written = corpus::write_corpus(&capture, &corpus_dir)?;
+    println!(
+        "Wrote {} corpus entries and {} notify seeds to {}",
+        written,
+        corpus::notify_seeds(&capture).len(),
+        corpus_dir
+    );
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use rust_stratum::stratum::{capture::Capture, tracediff};
+use std::error::Error;
+
+/// Compare the requests two recorded sessions sent to a pool
+///
+/// Usage: cargo run --example trace_diff --features tracediff -- <reference.jsonl> <ours.jsonl>
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(reference_path), Some(ours_path)) = (args.next(), args.next()) else {
+        eprintln!("Usage: trace_diff <reference.jsonl> <ours.jsonl>");
+        std::process::exit(2);
+    };
+
+    let reference = Capture::from_file(&reference_path)?;
+    let ours = Capture::from_file(&ours_path)?;
+    let differences = tracediff::diff(&reference, &ours);
+    if differences.is_empty() {
+        println!("No structural differences in the requests sent");
+        return Ok(());
+    }
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    std::process::exit(1);
+}
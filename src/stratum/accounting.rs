@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long hourly share totals are kept
+pub const LEDGER_RETENTION: Duration = Duration::from_secs(35 * 24 * 60 * 60);
+
+const SECS_PER_HOUR: u64 = 60 * 60;
+const HOURS_PER_DAY: u64 = 24;
+
+/// Accepted shares and their summed difficulty
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShareTotal {
+    pub shares: u64,
+    /// Sum of the pool difficulty accepted shares were credited at
+    pub difficulty: f64,
+}
+
+impl ShareTotal {
+    fn add(&mut self, other: ShareTotal) {
+        self.shares += other.shares;
+        self.difficulty += other.difficulty;
+    }
+}
+
+/// Accepted share value of one worker at one pool on one UTC day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyShareTotal {
+    /// UTC date as `YYYY-MM-DD`
+    pub date: String,
+    pub pool: String,
+    pub worker: String,
+    #[serde(flatten)]
+    pub total: ShareTotal,
+}
+
+/// Share value report for reconciling against pool payouts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShareReport {
+    /// Unix time the report was generated at, in seconds
+    pub generated_at: u64,
+    /// Daily totals, ordered by date, pool and worker
+    pub daily: Vec<DailyShareTotal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct LedgerKey {
+    hour: u64,
+    pool: String,
+    worker: String,
+}
+
+/// Accepted share difficulty per pool, worker and hour
+///
+/// PPS and PPLNS payouts are proportional to the difficulty of accepted
+/// shares, so these totals are what earnings estimates and payout
+/// reconciliation are based on. Totals are kept for [`LEDGER_RETENTION`].
+#[derive(Debug, Clone, Default)]
+pub struct ShareLedger {
+    hours: BTreeMap<LedgerKey, ShareTotal>,
+}
+
+fn unix_secs(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Civil date of a day counted from the Unix epoch
+pub(crate) fn civil_date(days: u64) -> String {
+    // Howard Hinnant's days_from_civil inverse
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl ShareLedger {
+    /// Create an empty ledger
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a share accepted now
+    pub fn record(&mut self, pool: &str, worker: &str, difficulty: f64) {
+        self.record_at(pool, worker, difficulty, SystemTime::now());
+    }
+
+    /// Record a share accepted at the given time
+    pub fn record_at(&mut self, pool: &str, worker: &str, difficulty: f64, at: SystemTime) {
+        let hour = unix_secs(at) / SECS_PER_HOUR;
+        let total = self
+            .hours
+            .entry(LedgerKey {
+                hour,
+                pool: pool.to_string(),
+                worker: worker.to_string(),
+            })
+            .or_default();
+        total.add(ShareTotal {
+            shares: 1,
+            difficulty,
+        });
+
+        let oldest = hour.saturating_sub(LEDGER_RETENTION.as_secs() / SECS_PER_HOUR);
+        while self
+            .hours
+            .first_key_value()
+            .is_some_and(|(key, _)| key.hour < oldest)
+        {
+            self.hours.pop_first();
+        }
+    }
+
+    /// Totals per UTC day, pool and worker
+    pub fn daily(&self) -> Vec<DailyShareTotal> {
+        let mut days: BTreeMap<(u64, &str, &str), ShareTotal> = BTreeMap::new();
+        for (key, total) in &self.hours {
+            days.entry((key.hour / HOURS_PER_DAY, &key.pool, &key.worker))
+                .or_default()
+                .add(*total);
+        }
+        days.into_iter()
+            .map(|((day, pool, worker), total)| DailyShareTotal {
+                date: civil_date(day),
+                pool: pool.to_string(),
+                worker: worker.to_string(),
+                total,
+            })
+            .collect()
+    }
+
+    /// Totals over the trailing `window`, with hourly resolution
+    ///
+    /// `pool` and `worker` restrict the totals when given.
+    pub fn rolling(
+        &self,
+        window: Duration,
+        pool: Option<&str>,
+        worker: Option<&str>,
+    ) -> ShareTotal {
+        self.rolling_at(window, pool, worker, SystemTime::now())
+    }
+
+    fn rolling_at(
+        &self,
+        window: Duration,
+        pool: Option<&str>,
+        worker: Option<&str>,
+        now: SystemTime,
+    ) -> ShareTotal {
+        let current = unix_secs(now) / SECS_PER_HOUR;
+        let first = current.saturating_sub(window.as_secs().div_ceil(SECS_PER_HOUR).max(1) - 1);
+        let mut sum = ShareTotal::default();
+        for (key, total) in &self.hours {
+            if key.hour >= first
+                && key.hour <= current
+                && pool.is_none_or(|pool| key.pool == pool)
+                && worker.is_none_or(|worker| key.worker == worker)
+            {
+                sum.add(*total);
+            }
+        }
+        sum
+    }
+
+    /// Report of all retained daily totals
+    pub fn report(&self) -> ShareReport {
+        ShareReport {
+            generated_at: unix_secs(SystemTime::now()),
+            daily: self.daily(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(11_016), "2000-02-29");
+        assert_eq!(civil_date(20_454), "2026-01-01");
+    }
+
+    #[test]
+    fn test_daily_totals() {
+        // 2026-01-01 23:30 and 2026-01-02 00:30 UTC
+        let day = 20_454 * 86_400;
+        let mut ledger = ShareLedger::new();
+        ledger.record_at("pool:3333", "rig1", 8.0, at(day + 23 * 3600 + 1800));
+        ledger.record_at("pool:3333", "rig1", 8.0, at(day + 23 * 3600 + 1900));
+        ledger.record_at("pool:3333", "rig2", 4.0, at(day + 23 * 3600));
+        ledger.record_at("pool:3333", "rig1", 16.0, at(day + 24 * 3600 + 1800));
+
+        let daily = ledger.daily();
+        assert_eq!(daily.len(), 3);
+        assert_eq!(daily[0].date, "2026-01-01");
+        assert_eq!(daily[0].worker, "rig1");
+        assert_eq!(daily[0].total.shares, 2);
+        assert_eq!(daily[0].total.difficulty, 16.0);
+        assert_eq!(daily[2].date, "2026-01-02");
+        assert_eq!(daily[2].total.difficulty, 16.0);
+
+        let now = at(day + 24 * 3600 + 1800);
+        let hour = Duration::from_secs(3600);
+        assert_eq!(ledger.rolling_at(hour, None, None, now).difficulty, 16.0);
+        assert_eq!(
+            ledger.rolling_at(2 * hour, None, None, now).difficulty,
+            36.0
+        );
+        assert_eq!(
+            ledger
+                .rolling_at(2 * hour, None, Some("rig1"), now)
+                .difficulty,
+            32.0
+        );
+        assert_eq!(
+            ledger
+                .rolling_at(2 * hour, Some("other:3333"), None, now)
+                .shares,
+            0
+        );
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut ledger = ShareLedger::new();
+        ledger.record_at("pool", "rig", 1.0, at(0));
+        ledger.record_at("pool", "rig", 1.0, at(LEDGER_RETENTION.as_secs() + 7200));
+        assert_eq!(ledger.daily().len(), 1);
+    }
+
+    #[test]
+    fn test_report_serialization() {
+        let mut ledger = ShareLedger::new();
+        ledger.record_at("pool", "rig", 2.5, at(86_400));
+        let report = ledger.report();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["daily"][0]["date"], "1970-01-02");
+        assert_eq!(json["daily"][0]["difficulty"], 2.5);
+        assert_eq!(json["daily"][0]["shares"], 1);
+
+        let parsed: ShareReport = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, report);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::stratum::error::StratumError;
+use sha2::{Digest, Sha256};
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+/// Address formats accepted for a chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainParams {
+    pub name: String,
+    /// Allowed Base58Check version bytes (P2PKH, P2SH, ...)
+    pub base58_versions: Vec<u8>,
+    /// Human readable part of segwit addresses, if the chain supports them
+    pub bech32_hrp: Option<String>,
+}
+
+impl ChainParams {
+    pub fn bitcoin() -> Self {
+        Self {
+            name: "bitcoin".into(),
+            base58_versions: vec![0x00, 0x05],
+            bech32_hrp: Some("bc".into()),
+        }
+    }
+
+    pub fn bitcoin_testnet() -> Self {
+        Self {
+            name: "bitcoin-testnet".into(),
+            base58_versions: vec![0x6f, 0xc4],
+            bech32_hrp: Some("tb".into()),
+        }
+    }
+
+    pub fn litecoin() -> Self {
+        Self {
+            name: "litecoin".into(),
+            base58_versions: vec![0x30, 0x32, 0x05],
+            bech32_hrp: Some("ltc".into()),
+        }
+    }
+}
+
+/// Format a valid address was recognized as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressKind {
+    Base58,
+    /// Segwit address with its witness version
+    Bech32 {
+        witness_version: u8,
+    },
+}
+
+/// Validate a wallet address against the chain's formats
+pub fn validate_address(address: &str, chain: &ChainParams) -> Result<AddressKind, StratumError> {
+    if let Some(hrp) = &chain.bech32_hrp {
+        if address.to_lowercase().starts_with(&format!("{}1", hrp)) {
+            return validate_bech32(address, hrp);
+        }
+    }
+
+    validate_base58(address, &chain.base58_versions)
+}
+
+fn invalid(address: &str, reason: &str) -> StratumError {
+    StratumError::Config(format!("Invalid wallet address {} - {}", address, reason))
+}
+
+fn validate_base58(address: &str, versions: &[u8]) -> Result<AddressKind, StratumError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in address.bytes() {
+        let mut carry = BASE58_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| invalid(address, "invalid base58 character"))?
+            as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let leading_zeros = address.bytes().take_while(|&c| c == b'1').count();
+    let mut decoded = vec![0u8; leading_zeros];
+    decoded.extend(bytes);
+
+    if decoded.len() != 25 {
+        return Err(invalid(address, "unexpected length"));
+    }
+
+    let (payload, checksum) = decoded.split_at(21);
+    let hash = Sha256::digest(Sha256::digest(payload));
+    if &hash[..4] != checksum {
+        return Err(invalid(address, "checksum mismatch"));
+    }
+
+    if !versions.contains(&payload[0]) {
+        return Err(invalid(address, "address belongs to a different chain"));
+    }
+
+    Ok(AddressKind::Base58)
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut chk: u32 = 1;
+    for &value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x01ff_ffff) << 5) ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+fn validate_bech32(address: &str, hrp: &str) -> Result<AddressKind, StratumError> {
+    if address != address.to_lowercase() && address != address.to_uppercase() {
+        return Err(invalid(address, "mixed case"));
+    }
+    let lower = address.to_lowercase();
+    let data_part = &lower[hrp.len() + 1..];
+    if data_part.len() < 7 {
+        return Err(invalid(address, "too short"));
+    }
+
+    let data = data_part
+        .bytes()
+        .map(|c| BECH32_CHARSET.iter().position(|&a| a == c).map(|p| p as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| invalid(address, "invalid bech32 character"))?;
+
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 0x1f));
+    values.extend(&data);
+
+    let witness_version = data[0];
+    let expected = if witness_version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    };
+    if bech32_polymod(&values) != expected {
+        return Err(invalid(address, "checksum mismatch"));
+    }
+    if witness_version > 16 {
+        return Err(invalid(address, "invalid witness version"));
+    }
+
+    // Witness program, excluding the version and the 6 checksum characters
+    let program_bits = (data.len() - 7) * 5;
+    let program_len = program_bits / 8;
+    if !(2..=40).contains(&program_len)
+        || (witness_version == 0 && ![20, 32].contains(&program_len))
+    {
+        return Err(invalid(address, "invalid witness program length"));
+    }
+
+    Ok(AddressKind::Bech32 { witness_version })
+}
+
+/// What to do when the configured username isn't a valid address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Log a warning and continue
+    Warn,
+    /// Refuse to connect
+    Error,
+}
+
+/// Sanity-checks that a pool username is a wallet address before connecting
+///
+/// Catches typos in the wallet address for pools that pay out directly to the
+/// username. The optional `.worker` suffix is ignored.
+#[derive(Debug, Clone)]
+pub struct UsernameValidator {
+    pub chain: ChainParams,
+    pub mode: ValidationMode,
+}
+
+impl UsernameValidator {
+    pub fn new(chain: ChainParams, mode: ValidationMode) -> Self {
+        Self { chain, mode }
+    }
+
+    /// Validate the address part of a `address.worker` username
+    pub fn check(&self, username: &str) -> Result<(), StratumError> {
+        let address = username.split('.').next().unwrap_or_default();
+        match validate_address(address, &self.chain) {
+            Ok(_) => Ok(()),
+            Err(err) if self.mode == ValidationMode::Warn => {
+                log::warn!(target: "stratum", "{} ({} chain)", err, self.chain.name);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base58_addresses() {
+        let bitcoin = ChainParams::bitcoin();
+        assert_eq!(
+            validate_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", &bitcoin).unwrap(),
+            AddressKind::Base58
+        );
+        // Typo in the last character
+        assert!(validate_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb", &bitcoin).is_err());
+        // Invalid base58 character
+        assert!(validate_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7Divf0a", &bitcoin).is_err());
+        // Mainnet address on testnet
+        assert!(validate_address(
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            &ChainParams::bitcoin_testnet()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_bech32_addresses() {
+        let bitcoin = ChainParams::bitcoin();
+        assert_eq!(
+            validate_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", &bitcoin).unwrap(),
+            AddressKind::Bech32 { witness_version: 0 }
+        );
+        assert_eq!(
+            validate_address(
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                &bitcoin
+            )
+            .unwrap(),
+            AddressKind::Bech32 { witness_version: 1 }
+        );
+        assert!(validate_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5", &bitcoin).is_err());
+        assert!(validate_address("bc1qw508d6qejxtdg4y5r3zarvaRy0c5xw7kv8f3t4", &bitcoin).is_err());
+    }
+
+    #[test]
+    fn test_username_validator() {
+        let strict = UsernameValidator::new(ChainParams::bitcoin(), ValidationMode::Error);
+        assert!(strict
+            .check("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4.rig1")
+            .is_ok());
+        assert!(strict.check("myaccount.rig1").is_err());
+
+        let lenient = UsernameValidator::new(ChainParams::bitcoin(), ValidationMode::Warn);
+        assert!(lenient.check("myaccount.rig1").is_ok());
+    }
+}
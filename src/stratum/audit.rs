@@ -0,0 +1,340 @@
+use crate::stratum::error::StratumError;
+use crate::stratum::header::sha256d;
+use crate::stratum::types::{MiningJob, Share};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `prev_hash` of the first entry of a log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Session activity recorded in an audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum AuditRecord {
+    /// The pool accepted a subscription
+    Subscribed {
+        pool: String,
+        extranonce1: String,
+        extranonce2_size: usize,
+    },
+    /// The pool answered an authorization
+    Authorized {
+        pool: String,
+        username: String,
+        authorized: bool,
+    },
+    /// The pool sent a job
+    Job { pool: String, job: Arc<MiningJob> },
+    /// A share was submitted, with the pool's verdict
+    Share {
+        pool: String,
+        share: Share,
+        accepted: bool,
+    },
+}
+
+/// Signs audit log entries, so a log can't be rewritten without the key
+///
+/// The built-in [`HmacSigner`] needs the key to verify; an asymmetric scheme
+/// can be plugged in to let customers verify with a public key only.
+pub trait AuditSigner: Send + Sync {
+    /// Name of the scheme, stored with each signature
+    fn algorithm(&self) -> &str;
+
+    /// Signature of an entry hash
+    fn sign(&self, hash: &[u8; 32]) -> Vec<u8>;
+
+    /// Check a signature of an entry hash
+    fn verify(&self, hash: &[u8; 32], signature: &[u8]) -> bool {
+        self.sign(hash) == signature
+    }
+}
+
+/// HMAC-SHA256 signing with a shared secret
+pub struct HmacSigner {
+    key: Vec<u8>,
+}
+
+impl HmacSigner {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+}
+
+impl AuditSigner for HmacSigner {
+    fn algorithm(&self) -> &str {
+        "hmac-sha256"
+    }
+
+    fn sign(&self, hash: &[u8; 32]) -> Vec<u8> {
+        hmac_sha256(&self.key, hash).to_vec()
+    }
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_LEN: usize = 64;
+    let mut block = [0u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner = Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// One line of an audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, starting at 0
+    pub seq: u64,
+    /// Unix time of the entry in milliseconds
+    pub at: u64,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    /// `hash` of the previous entry, [`GENESIS_HASH`] for the first one
+    pub prev_hash: String,
+    /// Hex SHA-256d over the other fields, chaining the entry to its predecessors
+    pub hash: String,
+    /// Hex signature of `hash`, when the log is signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+}
+
+/// Fields covered by an entry's hash
+#[derive(Serialize)]
+struct Chained<'a> {
+    seq: u64,
+    at: u64,
+    record: &'a AuditRecord,
+    prev_hash: &'a str,
+}
+
+impl AuditEntry {
+    /// Hash over everything but the hash and signature themselves
+    fn compute_hash(&self) -> Result<[u8; 32], StratumError> {
+        let body = serde_json::to_vec(&Chained {
+            seq: self.seq,
+            at: self.at,
+            record: &self.record,
+            prev_hash: &self.prev_hash,
+        })?;
+        Ok(sha256d(&body))
+    }
+}
+
+fn tampered(seq: u64, reason: &str) -> StratumError {
+    StratumError::Protocol(format!("Audit log entry {} {}", seq, reason))
+}
+
+/// Append-only, hash-chained log of a mining session
+///
+/// Every entry carries the hash of its predecessor, so removing, reordering
+/// or editing entries breaks the chain, see [`verify`](Self::verify). With a
+/// signer each hash is signed as well, so the chain can't be recomputed
+/// without the key either.
+pub struct AuditLog {
+    file: File,
+    next_seq: u64,
+    last_hash: String,
+    signer: Option<Box<dyn AuditSigner>>,
+}
+
+impl AuditLog {
+    /// Open a log for appending, continuing the chain of existing entries
+    ///
+    /// Fails if the existing entries don't verify.
+    pub fn open(
+        path: impl AsRef<Path>,
+        signer: Option<Box<dyn AuditSigner>>,
+    ) -> Result<Self, StratumError> {
+        let path = path.as_ref();
+        let (next_seq, last_hash) = if path.exists() {
+            match Self::verify(path, signer.as_deref())? {
+                Some(last) => (last.seq + 1, last.hash),
+                None => (0, GENESIS_HASH.to_string()),
+            }
+        } else {
+            (0, GENESIS_HASH.to_string())
+        };
+
+        Ok(Self {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+            next_seq,
+            last_hash,
+            signer,
+        })
+    }
+
+    /// Append an entry for `record`
+    pub fn append(&mut self, record: AuditRecord) -> Result<AuditEntry, StratumError> {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut entry = AuditEntry {
+            seq: self.next_seq,
+            at,
+            record,
+            prev_hash: self.last_hash.clone(),
+            hash: String::new(),
+            signature: None,
+            algorithm: None,
+        };
+        let hash = entry.compute_hash()?;
+        entry.hash = hex::encode(hash);
+        if let Some(signer) = &self.signer {
+            entry.signature = Some(hex::encode(signer.sign(&hash)));
+            entry.algorithm = Some(signer.algorithm().to_string());
+        }
+
+        writeln!(self.file, "{}", serde_json::to_string(&entry)?)?;
+        self.file.flush()?;
+        self.next_seq += 1;
+        self.last_hash = entry.hash.clone();
+        Ok(entry)
+    }
+
+    /// Check the chain of a log, and its signatures if a signer is given
+    ///
+    /// Returns the last entry, `None` for an empty log.
+    pub fn verify(
+        path: impl AsRef<Path>,
+        signer: Option<&dyn AuditSigner>,
+    ) -> Result<Option<AuditEntry>, StratumError> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut last: Option<AuditEntry> = None;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: AuditEntry = serde_json::from_str(&line)?;
+            let (seq, prev_hash) = match &last {
+                Some(last) => (last.seq + 1, last.hash.as_str()),
+                None => (0, GENESIS_HASH),
+            };
+            if entry.seq != seq {
+                return Err(tampered(
+                    entry.seq,
+                    &format!("found where {} was expected", seq),
+                ));
+            }
+            if entry.prev_hash != prev_hash {
+                return Err(tampered(entry.seq, "doesn't chain to its predecessor"));
+            }
+            let hash = entry.compute_hash()?;
+            if entry.hash != hex::encode(hash) {
+                return Err(tampered(entry.seq, "doesn't match its hash"));
+            }
+            if let Some(signer) = signer {
+                let valid = entry
+                    .signature
+                    .as_deref()
+                    .and_then(|signature| hex::decode(signature).ok())
+                    .is_some_and(|signature| signer.verify(&hash, &signature));
+                if !valid {
+                    return Err(tampered(entry.seq, "has no valid signature"));
+                }
+            }
+            last = Some(entry);
+        }
+        Ok(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "stratum-audit-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn share(nonce: &str) -> AuditRecord {
+        AuditRecord::Share {
+            pool: "pool:3333".into(),
+            share: Share {
+                job_id: "1".into(),
+                extranonce2: "00000000".into(),
+                ntime: "60509af9".into(),
+                nonce: nonce.into(),
+                version_bits: None,
+            },
+            accepted: true,
+        }
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_chain_and_resume() {
+        let path = path("chain");
+        let mut log = AuditLog::open(&path, None).unwrap();
+        let first = log.append(share("00000001")).unwrap();
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+        drop(log);
+
+        let mut log = AuditLog::open(&path, None).unwrap();
+        let second = log.append(share("00000002")).unwrap();
+        assert_eq!(second.seq, 1);
+        assert_eq!(second.prev_hash, first.hash);
+
+        let last = AuditLog::verify(&path, None).unwrap().unwrap();
+        assert_eq!(last, second);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let path = path("tamper");
+        let mut log = AuditLog::open(&path, Some(Box::new(HmacSigner::new("secret")))).unwrap();
+        log.append(share("00000001")).unwrap();
+        log.append(share("00000002")).unwrap();
+        drop(log);
+
+        let signer = HmacSigner::new("secret");
+        assert!(AuditLog::verify(&path, Some(&signer)).is_ok());
+        assert!(AuditLog::verify(&path, Some(&HmacSigner::new("guess"))).is_err());
+
+        // Claim a different nonce for the first share
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, content.replacen("00000001", "0000000f", 1)).unwrap();
+        assert!(AuditLog::verify(&path, None).is_err());
+
+        // Drop the first entry
+        let second = content.lines().nth(1).unwrap();
+        std::fs::write(&path, format!("{}\n", second)).unwrap();
+        assert!(AuditLog::verify(&path, None).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+use crate::stratum::error::StratumError;
+use crate::stratum::v1::protocol::{JsonRpcRequest, MINING_AUTHORIZE};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Stands in for the password of recorded `mining.authorize` requests
+pub const REDACTED_PASSWORD: &str = "<redacted>";
+
+/// Which side of the connection sent a captured line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureDirection {
+    /// Sent by the client to the pool
+    Sent,
+    /// Received by the client from the pool
+    Received,
+}
+
+/// A single line of recorded pool traffic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// Milliseconds since the recording started
+    pub offset_ms: u64,
+    pub direction: CaptureDirection,
+    /// Raw JSON-RPC line, without the trailing newline
+    pub line: String,
+}
+
+/// Recorded traffic of one pool connection
+///
+/// Captures are stored as JSON lines, one [`CaptureRecord`] per line, so they
+/// can be attached to bug reports and replayed in tests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capture {
+    pub records: Vec<CaptureRecord>,
+}
+
+impl Capture {
+    /// Parse a capture from JSON lines
+    pub fn from_jsonl(data: &str) -> Result<Self, StratumError> {
+        let records = data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|e| {
+                    StratumError::Config(format!("Invalid capture record {} - {}", line, e))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { records })
+    }
+
+    /// Load a capture from a JSON lines file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, StratumError> {
+        Self::from_jsonl(&std::fs::read_to_string(path)?)
+    }
+
+    /// Serialize the capture as JSON lines
+    pub fn to_jsonl(&self) -> String {
+        self.records
+            .iter()
+            .filter_map(|record| serde_json::to_string(record).ok())
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// Records sent by the pool
+    pub fn received(&self) -> impl Iterator<Item = &CaptureRecord> {
+        self.records
+            .iter()
+            .filter(|record| record.direction == CaptureDirection::Received)
+    }
+
+    /// Serve the pool side of the capture on a local port
+    ///
+    /// The server accepts a single connection. Pool lines are written at their
+    /// recorded offsets divided by `speed`, so `1.0` keeps the original timing
+    /// and larger values replay faster. Before moving past a line the client
+    /// sent in the recording, the server waits for the client to send a line,
+    /// keeping responses behind the requests they answer.
+    pub async fn replay(&self, speed: f64) -> Result<(String, u16), StratumError> {
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(StratumError::Config(format!(
+                "Replay speed must be positive, got {}",
+                speed
+            )));
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let records = self.records.clone();
+
+        tokio::spawn(async move {
+            let Ok((socket, _)) = listener.accept().await else {
+                return;
+            };
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let started = tokio::time::Instant::now();
+
+            for record in records {
+                match record.direction {
+                    CaptureDirection::Sent => {
+                        let mut line = String::new();
+                        if !matches!(reader.read_line(&mut line).await, Ok(n) if n > 0) {
+                            log::warn!(target: "stratum", "Replay client disconnected");
+                            return;
+                        }
+                    }
+                    CaptureDirection::Received => {
+                        let due = Duration::from_millis(record.offset_ms).div_f64(speed);
+                        tokio::time::sleep_until(started + due).await;
+                        if writer
+                            .write_all(format!("{}\n", record.line).as_bytes())
+                            .await
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            // Keep the connection open until the client goes away
+            let mut line = String::new();
+            while matches!(reader.read_line(&mut line).await, Ok(n) if n > 0) {
+                line.clear();
+            }
+        });
+
+        Ok((addr.ip().to_string(), addr.port()))
+    }
+}
+
+/// Records the traffic of a connection into a [`Capture`]
+#[derive(Debug, Clone)]
+pub struct CaptureRecorder {
+    started: Instant,
+    records: Arc<Mutex<Vec<CaptureRecord>>>,
+}
+
+impl Default for CaptureRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CaptureRecorder {
+    /// Start a new recording
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            records: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Record a line sent or received at the current time
+    ///
+    /// Passwords of `mining.authorize` requests are replaced with
+    /// [`REDACTED_PASSWORD`], so captures can be shared in bug reports.
+    pub fn record(&self, direction: CaptureDirection, line: &str) {
+        let line = line.trim_end();
+        let line = match direction {
+            CaptureDirection::Sent => redact_authorize(line).unwrap_or_else(|| line.to_string()),
+            CaptureDirection::Received => line.to_string(),
+        };
+        let record = CaptureRecord {
+            offset_ms: self.started.elapsed().as_millis() as u64,
+            direction,
+            line,
+        };
+        if let Ok(mut records) = self.records.lock() {
+            records.push(record);
+        }
+    }
+
+    /// Get the traffic recorded so far
+    pub fn capture(&self) -> Capture {
+        Capture {
+            records: self
+                .records
+                .lock()
+                .map(|records| records.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// `line` with its password redacted if it's a `mining.authorize` request
+fn redact_authorize(line: &str) -> Option<String> {
+    let mut request: JsonRpcRequest = serde_json::from_str(line).ok()?;
+    if request.method != MINING_AUTHORIZE {
+        return None;
+    }
+    *request.params.get_mut(1)? = REDACTED_PASSWORD.into();
+    serde_json::to_string(&request).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_roundtrip() {
+        let recorder = CaptureRecorder::new();
+        recorder.record(CaptureDirection::Sent, "{\"id\":1}\n");
+        recorder.record(CaptureDirection::Received, "{\"id\":1,\"result\":true}");
+
+        let capture = recorder.capture();
+        assert_eq!(capture.records[0].line, "{\"id\":1}");
+        assert_eq!(capture.received().count(), 1);
+
+        let parsed = Capture::from_jsonl(&capture.to_jsonl()).unwrap();
+        assert_eq!(parsed, capture);
+        assert!(Capture::from_jsonl("not json").is_err());
+    }
+
+    #[test]
+    fn test_authorize_password_redacted() {
+        let recorder = CaptureRecorder::new();
+        let authorize = JsonRpcRequest::authorize(2, "worker", "secret");
+        recorder.record(
+            CaptureDirection::Sent,
+            &serde_json::to_string(&authorize).unwrap(),
+        );
+        recorder.record(
+            CaptureDirection::Sent,
+            r#"{"id":3,"method":"mining.submit","params":["worker","job1"]}"#,
+        );
+
+        let capture = recorder.capture();
+        assert!(!capture.records[0].line.contains("secret"));
+        let recorded: JsonRpcRequest = serde_json::from_str(&capture.records[0].line).unwrap();
+        assert_eq!(recorded.params[0], "worker");
+        assert_eq!(recorded.params[1], REDACTED_PASSWORD);
+        assert_eq!(
+            capture.records[1].line,
+            r#"{"id":3,"method":"mining.submit","params":["worker","job1"]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_waits_for_requests() {
+        let capture = Capture::from_jsonl(concat!(
+            r#"{"offset_ms":0,"direction":"sent","line":"ping"}"#,
+            "\n",
+            r#"{"offset_ms":5,"direction":"received","line":"pong"}"#,
+        ))
+        .unwrap();
+        let (host, port) = capture.replay(10.0).await.unwrap();
+
+        let socket = tokio::net::TcpStream::connect((host.as_str(), port))
+            .await
+            .unwrap();
+        let (read_half, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(read_half);
+
+        // Nothing is sent before the request
+        let mut line = String::new();
+        let early = tokio::time::timeout(Duration::from_millis(50), reader.read_line(&mut line));
+        assert!(early.await.is_err());
+
+        writer.write_all(b"ping\n").await.unwrap();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "pong\n");
+    }
+}
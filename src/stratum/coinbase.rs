@@ -0,0 +1,315 @@
+use crate::stratum::error::StratumError;
+use crate::stratum::header::{self, sha256d};
+use crate::stratum::types::MiningJob;
+
+/// Offset of the scriptSig length in a coinbase transaction
+///
+/// version (4) + input count (1) + previous output hash (32) + previous output index (4)
+const SCRIPT_SIG_LEN_OFFSET: usize = 41;
+
+/// Start of a BIP141 witness commitment output script: `OP_RETURN`, a 36 byte
+/// push and the commitment header
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Parse the block height committed in the coinbase scriptSig (BIP34)
+///
+/// Takes the hex encoded `coinbase1` of a job, which contains the start of the
+/// scriptSig. Returns `None` if the data is too short or doesn't start with a
+/// height push.
+pub fn bip34_height(coinbase1: &str) -> Option<u64> {
+    let bytes = hex::decode(coinbase1).ok()?;
+    let (_, len) = read_varint(&bytes, SCRIPT_SIG_LEN_OFFSET)?;
+    script_height(&bytes[SCRIPT_SIG_LEN_OFFSET + len..])
+}
+
+/// Height pushed at the start of a coinbase scriptSig
+fn script_height(script: &[u8]) -> Option<u64> {
+    let push = *script.first()?;
+    match push {
+        // OP_1 through OP_16 encode small heights directly
+        0x51..=0x60 => Some(u64::from(push - 0x50)),
+        // Direct push of a little-endian script number
+        0x01..=0x08 => {
+            let height = script.get(1..1 + push as usize)?;
+            Some(
+                height
+                    .iter()
+                    .rev()
+                    .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte)),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Read the Bitcoin varint at `offset`, returning it and its length
+fn read_varint(bytes: &[u8], offset: usize) -> Option<(u64, usize)> {
+    let width = match *bytes.get(offset)? {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        small => return Some((u64::from(small), 1)),
+    };
+    let value = bytes.get(offset + 1..offset + 1 + width)?;
+    let value = value
+        .iter()
+        .rev()
+        .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+    Some((value, 1 + width))
+}
+
+/// Output of a coinbase transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoinbaseOutput {
+    /// Amount in satoshis
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+/// Coinbase transaction of a share, assembled and parsed
+///
+/// Pools send the coinbase in two halves around the extranonces, in the
+/// serialization without witness data that the merkle root commits to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coinbase {
+    /// Serialized transaction
+    pub bytes: Vec<u8>,
+    pub version: u32,
+    pub script_sig: Vec<u8>,
+    pub outputs: Vec<CoinbaseOutput>,
+    pub lock_time: u32,
+}
+
+impl Coinbase {
+    /// Assemble the coinbase of a job from `coinbase1`, the extranonces and
+    /// `coinbase2`, and parse it
+    pub fn assemble(
+        job: &MiningJob,
+        extranonce1: &str,
+        extranonce2: &str,
+    ) -> Result<Self, StratumError> {
+        Self::parse(header::coinbase(job, extranonce1, extranonce2)?)
+    }
+
+    /// Parse a serialized coinbase transaction
+    pub fn parse(bytes: Vec<u8>) -> Result<Self, StratumError> {
+        let invalid =
+            |reason: &str| StratumError::InvalidJob(format!("Invalid coinbase: {}", reason));
+        let mut reader = Reader {
+            bytes: &bytes,
+            offset: 0,
+        };
+
+        let version = reader.u32().ok_or_else(|| invalid("truncated version"))?;
+        if reader.varint() != Some(1) {
+            return Err(invalid("must have exactly one input"));
+        }
+        let previous = reader.take(36).ok_or_else(|| invalid("truncated input"))?;
+        if previous[..32].iter().any(|byte| *byte != 0) || previous[32..] != [0xff; 4] {
+            return Err(invalid("input must not spend a previous output"));
+        }
+        let script_len = reader
+            .varint()
+            .ok_or_else(|| invalid("truncated scriptSig"))?;
+        let script_sig = reader
+            .take(script_len)
+            .ok_or_else(|| invalid("truncated scriptSig"))?
+            .to_vec();
+        reader
+            .take(4)
+            .ok_or_else(|| invalid("truncated sequence"))?;
+
+        let count = reader
+            .varint()
+            .ok_or_else(|| invalid("truncated outputs"))?;
+        let mut outputs = Vec::new();
+        for _ in 0..count {
+            let value = reader.u64().ok_or_else(|| invalid("truncated output"))?;
+            let script_len = reader.varint().ok_or_else(|| invalid("truncated output"))?;
+            let script_pubkey = reader
+                .take(script_len)
+                .ok_or_else(|| invalid("truncated output"))?
+                .to_vec();
+            outputs.push(CoinbaseOutput {
+                value,
+                script_pubkey,
+            });
+        }
+        let lock_time = reader.u32().ok_or_else(|| invalid("truncated lock time"))?;
+        if reader.offset != bytes.len() {
+            return Err(invalid("trailing data after lock time"));
+        }
+
+        Ok(Self {
+            bytes,
+            version,
+            script_sig,
+            outputs,
+            lock_time,
+        })
+    }
+
+    /// Transaction hash, the leaf of the merkle tree the header commits to
+    pub fn hash(&self) -> [u8; 32] {
+        sha256d(&self.bytes)
+    }
+
+    /// Block height committed in the scriptSig (BIP34)
+    pub fn height(&self) -> Option<u64> {
+        script_height(&self.script_sig)
+    }
+
+    /// Sum of the output values in satoshis, the block reward plus fees
+    ///
+    /// `None` if the values overflow, which no valid coinbase does.
+    pub fn total_value(&self) -> Option<u64> {
+        self.outputs
+            .iter()
+            .try_fold(0u64, |total, output| total.checked_add(output.value))
+    }
+
+    /// Witness commitment of the block (BIP141), from the last output
+    /// carrying one
+    pub fn witness_commitment(&self) -> Option<[u8; 32]> {
+        self.outputs.iter().rev().find_map(|output| {
+            let script = &output.script_pubkey;
+            if !script.starts_with(&WITNESS_COMMITMENT_PREFIX) {
+                return None;
+            }
+            script
+                .get(WITNESS_COMMITMENT_PREFIX.len()..WITNESS_COMMITMENT_PREFIX.len() + 32)?
+                .try_into()
+                .ok()
+        })
+    }
+}
+
+/// Cursor over a serialized transaction
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: u64) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(usize::try_from(len).ok()?)?;
+        let taken = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(taken)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let (value, len) = read_varint(self.bytes, self.offset)?;
+        self.offset += len;
+        Some(value)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coinbase1_with_script(script: &str) -> String {
+        format!(
+            "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff{}",
+            script
+        )
+    }
+
+    #[test]
+    fn test_bip34_height() {
+        // Script length 0x4b, push 3 bytes: 0x0d3c0c = 867340
+        let coinbase1 = coinbase1_with_script("4b030c3c0d");
+        assert_eq!(bip34_height(&coinbase1), Some(867_340));
+    }
+
+    #[test]
+    fn test_bip34_small_height() {
+        assert_eq!(bip34_height(&coinbase1_with_script("0251")), Some(1));
+    }
+
+    fn segwit_job() -> MiningJob {
+        MiningJob {
+            job_id: "job1".into(),
+            prev_hash: "00".repeat(32),
+            coinbase1: coinbase1_with_script("0c030c3c0d"),
+            // Sequence, a 50 BTC P2PKH output and a witness commitment
+            coinbase2: format!(
+                "ffffffff02{}{}88ac{}{}00000000",
+                "00f2052a010000001976a914",
+                "00".repeat(20),
+                "0000000000000000266a24aa21a9ed",
+                "11".repeat(32)
+            ),
+            merkle_branch: vec![],
+            version: "20000000".into(),
+            nbits: "1d00ffff".into(),
+            ntime: "495fab29".into(),
+            clean_jobs: Some(true),
+            target: None,
+            raw_params: Default::default(),
+            generation: 0,
+        }
+    }
+
+    #[test]
+    fn test_assemble() {
+        let job = segwit_job();
+        let coinbase = Coinbase::assemble(&job, "08000002", "00000001").unwrap();
+        assert_eq!(
+            hex::encode(&coinbase.bytes),
+            format!("{}0800000200000001{}", job.coinbase1, job.coinbase2)
+        );
+        assert_eq!(coinbase.version, 1);
+        assert_eq!(coinbase.height(), Some(867_340));
+        assert_eq!(coinbase.height(), job.height());
+        assert_eq!(coinbase.outputs.len(), 2);
+        assert_eq!(coinbase.outputs[0].script_pubkey.len(), 25);
+        assert_eq!(coinbase.total_value(), Some(5_000_000_000));
+        assert_eq!(coinbase.witness_commitment(), Some([0x11; 32]));
+        assert_eq!(coinbase.lock_time, 0);
+        assert_eq!(coinbase.hash(), sha256d(&coinbase.bytes));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let job = segwit_job();
+        // Extranonces shorter than the scriptSig length announces
+        assert!(Coinbase::assemble(&job, "08000002", "01").is_err());
+        // Trailing data
+        let mut bytes = Coinbase::assemble(&job, "08000002", "00000001")
+            .unwrap()
+            .bytes;
+        bytes.push(0);
+        assert!(Coinbase::parse(bytes).is_err());
+        // Spending a previous output
+        let mut job = segwit_job();
+        job.coinbase1 = job.coinbase1.replacen("0000ffffffff", "0001ffffffff", 1);
+        assert!(Coinbase::assemble(&job, "08000002", "00000001").is_err());
+    }
+
+    #[test]
+    fn test_total_value_overflow() {
+        let mut coinbase = Coinbase::assemble(&segwit_job(), "08000002", "00000001").unwrap();
+        coinbase.outputs[1].value = u64::MAX;
+        assert_eq!(coinbase.total_value(), None);
+    }
+
+    #[test]
+    fn test_bip34_invalid() {
+        assert_eq!(bip34_height("01000000"), None);
+        assert_eq!(bip34_height("not hex"), None);
+        // Non push opcode at the start of the scriptSig
+        assert_eq!(bip34_height(&coinbase1_with_script("046a")), None);
+        // Truncated push
+        assert_eq!(bip34_height(&coinbase1_with_script("4b030c3d")), None);
+    }
+}
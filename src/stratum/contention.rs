@@ -0,0 +1,177 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Lock whose wait times are tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockSite {
+    /// The client's pool connection, held to send and to reconnect
+    Connection,
+    /// The socket writer, held while a message is written
+    Writer,
+    /// Current job, difficulty and job history of the job manager
+    JobState,
+}
+
+impl LockSite {
+    pub const ALL: [LockSite; 3] = [LockSite::Connection, LockSite::Writer, LockSite::JobState];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Upper bounds of the wait time buckets; waits above the last one fall in a
+/// final overflow bucket
+pub const WAIT_BUCKETS: [Duration; 5] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+];
+
+/// Histogram of the time spent waiting for one lock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockWaitHistogram {
+    /// Acquisitions per bucket of [`WAIT_BUCKETS`], plus the overflow bucket
+    pub buckets: [u64; WAIT_BUCKETS.len() + 1],
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl LockWaitHistogram {
+    /// Number of acquisitions recorded
+    pub fn acquisitions(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Mean wait per acquisition
+    pub fn mean_wait(&self) -> Duration {
+        match self.acquisitions() {
+            0 => Duration::ZERO,
+            n => self.total_wait / n as u32,
+        }
+    }
+
+    /// Acquisitions in buckets entirely above `wait`
+    pub fn waited_longer_than(&self, wait: Duration) -> u64 {
+        let first = WAIT_BUCKETS.partition_point(|bound| *bound < wait) + 1;
+        self.buckets[first..].iter().sum()
+    }
+}
+
+/// Lock wait histograms of a client, see [`StratumV1Client::session_snapshot`](crate::stratum::v1::StratumV1Client::session_snapshot)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentionSnapshot {
+    sites: [LockWaitHistogram; LockSite::ALL.len()],
+}
+
+impl ContentionSnapshot {
+    /// Wait histogram of one lock
+    pub fn site(&self, site: LockSite) -> &LockWaitHistogram {
+        &self.sites[site.index()]
+    }
+}
+
+#[derive(Debug, Default)]
+struct SiteCounters {
+    buckets: [AtomicU64; WAIT_BUCKETS.len() + 1],
+    total_wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+}
+
+/// Lock wait time counters shared by a client's components
+#[derive(Debug, Default)]
+pub struct Contention {
+    sites: [SiteCounters; LockSite::ALL.len()],
+}
+
+impl Contention {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a wait for `site`
+    pub fn record(&self, site: LockSite, wait: Duration) {
+        let counters = &self.sites[site.index()];
+        let bucket = WAIT_BUCKETS.partition_point(|bound| *bound < wait);
+        counters.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX);
+        counters
+            .total_wait_nanos
+            .fetch_add(nanos, Ordering::Relaxed);
+        counters.max_wait_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Lock `mutex`, recording how long that took
+    pub async fn lock<'a, T>(&self, site: LockSite, mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+        if let Ok(guard) = mutex.try_lock() {
+            self.record(site, Duration::ZERO);
+            return guard;
+        }
+        let started = Instant::now();
+        let guard = mutex.lock().await;
+        self.record(site, started.elapsed());
+        guard
+    }
+
+    /// Current histograms
+    pub fn snapshot(&self) -> ContentionSnapshot {
+        ContentionSnapshot {
+            sites: self.sites.each_ref().map(|counters| LockWaitHistogram {
+                buckets: counters
+                    .buckets
+                    .each_ref()
+                    .map(|bucket| bucket.load(Ordering::Relaxed)),
+                total_wait: Duration::from_nanos(counters.total_wait_nanos.load(Ordering::Relaxed)),
+                max_wait: Duration::from_nanos(counters.max_wait_nanos.load(Ordering::Relaxed)),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let contention = Contention::new();
+        contention.record(LockSite::Writer, Duration::ZERO);
+        contention.record(LockSite::Writer, Duration::from_micros(10));
+        contention.record(LockSite::Writer, Duration::from_millis(5));
+        contention.record(LockSite::Writer, Duration::from_secs(1));
+
+        let snapshot = contention.snapshot();
+        let writer = snapshot.site(LockSite::Writer);
+        assert_eq!(writer.buckets, [2, 0, 0, 1, 0, 1]);
+        assert_eq!(writer.acquisitions(), 4);
+        assert_eq!(writer.max_wait, Duration::from_secs(1));
+        assert_eq!(writer.waited_longer_than(Duration::from_millis(1)), 2);
+        assert_eq!(writer.waited_longer_than(Duration::from_millis(100)), 1);
+        assert_eq!(snapshot.site(LockSite::Connection).acquisitions(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_timed_lock() {
+        let contention = std::sync::Arc::new(Contention::new());
+        let mutex = std::sync::Arc::new(Mutex::new(()));
+
+        let guard = mutex.lock().await;
+        let waiter = {
+            let (contention, mutex) = (contention.clone(), mutex.clone());
+            tokio::spawn(async move {
+                let _guard = contention.lock(LockSite::JobState, &mutex).await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(guard);
+        waiter.await.unwrap();
+
+        let snapshot = contention.snapshot();
+        let job_state = snapshot.site(LockSite::JobState);
+        assert_eq!(job_state.acquisitions(), 1);
+        assert!(job_state.max_wait >= Duration::from_millis(10));
+    }
+}
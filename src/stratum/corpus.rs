@@ -0,0 +1,110 @@
+use crate::stratum::capture::Capture;
+use crate::stratum::error::StratumError;
+use crate::stratum::v1::protocol::MINING_NOTIFY;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// File name of the notify-params seeds written next to the parser corpus
+pub const NOTIFY_SEEDS_FILE: &str = "notify_seeds.json";
+
+/// Unique lines received from the pool, as parser fuzz inputs
+pub fn parser_corpus(capture: &Capture) -> Vec<String> {
+    let mut seen = HashSet::new();
+    capture
+        .received()
+        .map(|record| record.line.clone())
+        .filter(|line| seen.insert(line.clone()))
+        .collect()
+}
+
+/// Unique `mining.notify` params, as seeds for job parsing property tests
+pub fn notify_seeds(capture: &Capture) -> Vec<Vec<Value>> {
+    let mut seeds: Vec<Vec<Value>> = Vec::new();
+    for record in capture.received() {
+        let Ok(message) = serde_json::from_str::<Value>(&record.line) else {
+            continue;
+        };
+        if message.get("method").and_then(Value::as_str) != Some(MINING_NOTIFY) {
+            continue;
+        }
+        if let Some(params) = message.get("params").and_then(Value::as_array) {
+            if !seeds.contains(params) {
+                seeds.push(params.clone());
+            }
+        }
+    }
+    seeds
+}
+
+/// Write a capture out as a fuzz corpus directory
+///
+/// Each parser input is stored in its own file named after its SHA-256, the
+/// layout cargo-fuzz and libFuzzer expect, so re-running the conversion on
+/// overlapping captures doesn't duplicate entries. The notify seeds are
+/// written to [`NOTIFY_SEEDS_FILE`]. Returns the number of corpus entries.
+pub fn write_corpus(capture: &Capture, dir: impl AsRef<Path>) -> Result<usize, StratumError> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+
+    let corpus = parser_corpus(capture);
+    for line in &corpus {
+        let name = hex::encode(Sha256::digest(line.as_bytes()));
+        std::fs::write(dir.join(name), line)?;
+    }
+
+    let seeds = serde_json::to_string_pretty(&notify_seeds(capture))?;
+    std::fs::write(dir.join(NOTIFY_SEEDS_FILE), seeds)?;
+
+    Ok(corpus.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::capture::{CaptureDirection, CaptureRecorder};
+
+    fn capture() -> Capture {
+        let notify = r#"{"id":null,"method":"mining.notify","params":["job1","00","01","02",[],"20000000","1d00ffff","60509af9",true]}"#;
+        let recorder = CaptureRecorder::new();
+        recorder.record(
+            CaptureDirection::Sent,
+            r#"{"id":1,"method":"mining.subscribe","params":[]}"#,
+        );
+        recorder.record(
+            CaptureDirection::Received,
+            r#"{"id":1,"result":true,"error":null}"#,
+        );
+        recorder.record(CaptureDirection::Received, notify);
+        recorder.record(CaptureDirection::Received, notify);
+        recorder.record(CaptureDirection::Received, "garbage");
+        recorder.capture()
+    }
+
+    #[test]
+    fn test_extract_corpus() {
+        let capture = capture();
+        assert_eq!(parser_corpus(&capture).len(), 3);
+
+        let seeds = notify_seeds(&capture);
+        assert_eq!(seeds.len(), 1);
+        assert_eq!(seeds[0][0], "job1");
+    }
+
+    #[test]
+    fn test_write_corpus() {
+        let dir = std::env::temp_dir().join(format!("stratum-corpus-{}", std::process::id()));
+        let written = write_corpus(&capture(), &dir).unwrap();
+        assert_eq!(written, 3);
+        // Same entries are not duplicated
+        assert_eq!(write_corpus(&capture(), &dir).unwrap(), 3);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+
+        let seeds: Vec<Vec<Value>> =
+            serde_json::from_str(&std::fs::read_to_string(dir.join(NOTIFY_SEEDS_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(seeds.len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
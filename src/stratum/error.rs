@@ -1,6 +1,13 @@
+use std::fmt;
 use thiserror::Error;
 
+/// Errors returned by Stratum clients
+///
+/// New variants may be added in minor releases; match on [`kind`](Self::kind)
+/// or include a wildcard arm. Errors may be wrapped in
+/// [`Context`](Self::Context), match on [`root`](Self::root) to see through it.
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum StratumError {
     #[error("JSON error: {0}")]
     Json(String),
@@ -25,6 +32,200 @@ pub enum StratumError {
 
     #[error("Connection error: {0}")]
     Connection(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// The pool answered a request with one of the canonical Stratum error
+    /// codes; other error responses are [`Protocol`](Self::Protocol) errors
+    #[error("Pool error {}: {message}", .error.code())]
+    Rpc {
+        error: StratumRpcError,
+        message: String,
+    },
+
+    /// An error with the operation it interrupted, see
+    /// [`context`](Self::context)
+    #[error("{0}")]
+    Context(Box<ContextError>),
+}
+
+/// What was being done when an error occurred, so a single logged line
+/// says where it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// E.g. the JSON-RPC method or `reconnect`
+    pub operation: String,
+    /// Pool as `host:port`
+    pub pool: Option<String>,
+    pub request_id: Option<u64>,
+    /// Attempt the error ended, counting from 1
+    pub attempt: Option<u32>,
+}
+
+impl ErrorContext {
+    pub fn new(operation: impl Into<String>) -> Self {
+        Self {
+            operation: operation.into(),
+            pool: None,
+            request_id: None,
+            attempt: None,
+        }
+    }
+
+    pub fn pool(mut self, pool: impl Into<String>) -> Self {
+        self.pool = Some(pool.into());
+        self
+    }
+
+    pub fn request_id(mut self, request_id: u64) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    pub fn attempt(mut self, attempt: u32) -> Self {
+        self.attempt = Some(attempt);
+        self
+    }
+}
+
+/// E.g. `mining.submit to pool.example.com:3333 (request 7, attempt 2)`
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operation)?;
+        if let Some(pool) = &self.pool {
+            write!(f, " to {}", pool)?;
+        }
+        match (self.request_id, self.attempt) {
+            (Some(id), Some(attempt)) => write!(f, " (request {}, attempt {})", id, attempt),
+            (Some(id), None) => write!(f, " (request {})", id),
+            (None, Some(attempt)) => write!(f, " (attempt {})", attempt),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// An error with its [`ErrorContext`]
+#[derive(Debug, Clone)]
+pub struct ContextError {
+    pub context: ErrorContext,
+    pub error: StratumError,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.error)
+    }
+}
+
+/// Canonical Stratum error codes of `[code, message, data]` error responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StratumRpcError {
+    /// 20, any other error
+    Other,
+    /// 21, the share's job is unknown or no longer valid
+    Stale,
+    /// 22, the share was already submitted
+    Duplicate,
+    /// 23, the share doesn't meet the session's target
+    LowDifficulty,
+    /// 24, the worker isn't authorized
+    Unauthorized,
+    /// 25, the session isn't subscribed
+    NotSubscribed,
+}
+
+impl StratumRpcError {
+    /// Error with the given code, `None` for non-canonical codes
+    pub fn from_code(code: i64) -> Option<Self> {
+        match code {
+            20 => Some(Self::Other),
+            21 => Some(Self::Stale),
+            22 => Some(Self::Duplicate),
+            23 => Some(Self::LowDifficulty),
+            24 => Some(Self::Unauthorized),
+            25 => Some(Self::NotSubscribed),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Self::Other => 20,
+            Self::Stale => 21,
+            Self::Duplicate => 22,
+            Self::LowDifficulty => 23,
+            Self::Unauthorized => 24,
+            Self::NotSubscribed => 25,
+        }
+    }
+}
+
+/// Category of a [`StratumError`], without its details
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Json,
+    Io,
+    HexDecode,
+    Protocol,
+    AuthenticationFailed,
+    SubscriptionFailed,
+    InvalidJob,
+    Connection,
+    Config,
+    Rpc,
+}
+
+impl StratumError {
+    /// Category of this error, looking through any context
+    pub fn kind(&self) -> ErrorKind {
+        match self.root() {
+            StratumError::Json(_) => ErrorKind::Json,
+            StratumError::Io(_) => ErrorKind::Io,
+            StratumError::HexDecode(_) => ErrorKind::HexDecode,
+            StratumError::Protocol(_) => ErrorKind::Protocol,
+            StratumError::AuthenticationFailed(_) => ErrorKind::AuthenticationFailed,
+            StratumError::SubscriptionFailed(_) => ErrorKind::SubscriptionFailed,
+            StratumError::InvalidJob(_) => ErrorKind::InvalidJob,
+            StratumError::Connection(_) => ErrorKind::Connection,
+            StratumError::Config(_) => ErrorKind::Config,
+            StratumError::Rpc { .. } => ErrorKind::Rpc,
+            StratumError::Context(_) => unreachable!("root errors carry no context"),
+        }
+    }
+
+    /// Wrap this error with what was being done when it occurred
+    ///
+    /// Context can be chained, the outermost being the broadest operation:
+    /// `reconnect to pool.example.com:3333: mining.subscribe (request 3,
+    /// attempt 1): Connection error: ...`
+    pub fn context(self, context: ErrorContext) -> Self {
+        StratumError::Context(Box::new(ContextError {
+            context,
+            error: self,
+        }))
+    }
+
+    /// The error without any context, to match on its variant
+    pub fn root(&self) -> &StratumError {
+        match self {
+            StratumError::Context(context) => context.error.root(),
+            err => err,
+        }
+    }
+
+    /// Context of the error, outermost first
+    pub fn contexts(&self) -> Vec<&ErrorContext> {
+        let mut contexts = Vec::new();
+        let mut err = self;
+        while let StratumError::Context(context) = err {
+            contexts.push(&context.context);
+            err = &context.error;
+        }
+        contexts
+    }
 }
 
 impl From<std::io::Error> for StratumError {
@@ -44,3 +245,49 @@ impl From<hex::FromHexError> for StratumError {
         StratumError::HexDecode(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        let err: StratumError = std::io::Error::other("boom").into();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(StratumError::Config("bad".into()).kind(), ErrorKind::Config);
+    }
+
+    #[test]
+    fn test_rpc_error_codes() {
+        for code in 20..=25 {
+            assert_eq!(StratumRpcError::from_code(code).unwrap().code(), code);
+        }
+        assert_eq!(StratumRpcError::from_code(-1), None);
+
+        let err = StratumError::Rpc {
+            error: StratumRpcError::Duplicate,
+            message: "Duplicate share".into(),
+        };
+        assert_eq!(err.to_string(), "Pool error 22: Duplicate share");
+    }
+
+    #[test]
+    fn test_context() {
+        let err = StratumError::Connection("Connection closed".into())
+            .context(
+                ErrorContext::new("mining.subscribe")
+                    .request_id(3)
+                    .attempt(1),
+            )
+            .context(ErrorContext::new("reconnect").pool("pool.example.com:3333"));
+        assert_eq!(
+            err.to_string(),
+            "reconnect to pool.example.com:3333: mining.subscribe (request 3, attempt 1): \
+             Connection error: Connection closed"
+        );
+        assert_eq!(err.kind(), ErrorKind::Connection);
+        assert!(matches!(err.root(), StratumError::Connection(_)));
+        let operations: Vec<_> = err.contexts().iter().map(|c| &c.operation).collect();
+        assert_eq!(operations, ["reconnect", "mining.subscribe"]);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::stratum::stats::{ShareCounts, StatsSummary};
+use crate::stratum::types::{MiningJob, RejectReason, Share};
+use crate::stratum::v1::connection::ConnectionStats;
+use crate::stratum::v1::jobs::Inconsistency;
+use crate::stratum::verbosity::Category;
+#[cfg(feature = "watchdog")]
+use crate::stratum::watchdog::StallReason;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Capacity of the event broadcast channel; slow receivers will observe lag
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Why a pool connection ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DisconnectReason {
+    /// The pool closed the connection
+    PeerClosed,
+    /// The connection was reset or aborted by the peer or the network
+    PeerReset,
+    /// The pool stopped responding
+    Timeout,
+    /// The client closed the connection, e.g. to reconnect
+    LocalClose,
+    /// The pool asked the client to reconnect, possibly to another host
+    PoolReconnect,
+    /// Any other I/O error
+    Error(String),
+}
+
+impl DisconnectReason {
+    /// Classify an I/O error that ended a connection
+    pub fn from_io(err: &std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        match err.kind() {
+            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe => {
+                Self::PeerReset
+            }
+            ErrorKind::TimedOut => Self::Timeout,
+            ErrorKind::UnexpectedEof => Self::PeerClosed,
+            _ => Self::Error(err.to_string()),
+        }
+    }
+}
+
+/// Events emitted by a Stratum client while it is running
+///
+/// New events may be added in minor releases, so matches need a wildcard arm.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum StratumEvent {
+    /// Periodic summary of recent session statistics
+    StatsTick(StatsSummary),
+    /// The pool sent a new job
+    NewJob { job: Arc<MiningJob> },
+    /// The pool changed the share difficulty
+    DifficultyChanged {
+        difficulty: f64,
+        /// Difficulty before the change, `None` for the first one
+        previous: Option<f64>,
+    },
+    /// A job built on a different previous block than the job before it
+    NewBlock {
+        prev_hash: String,
+        /// Block height parsed from the coinbase (BIP34), if present
+        height_hint: Option<u64>,
+    },
+    /// A connection to the pool was established
+    Connected {
+        /// Pool address as `host:port`
+        addr: String,
+        tls: bool,
+    },
+    /// The connection to the pool was lost or closed
+    Disconnected { reason: DisconnectReason },
+    /// Final statistics of a connection the client replaced with a new one,
+    /// whose statistics start from zero
+    ConnectionClosedStats {
+        /// Pool address of the closed connection as `host:port`
+        addr: String,
+        stats: ConnectionStats,
+    },
+    /// The client is reconnecting, to the same pool or another one
+    Reconnecting {
+        /// Pool address as `host:port`
+        addr: String,
+    },
+    /// The pool sent `client.reconnect`, see
+    /// [`RedirectPolicy`](crate::stratum::v1::redirect::RedirectPolicy)
+    PoolRedirect {
+        /// Pool address as `host:port`
+        from: String,
+        to: String,
+        /// Whether the policy allowed moving the session
+        followed: bool,
+    },
+    /// The pool's message of the day, emitted once per client
+    Motd { message: String },
+    /// The pool sent `client.show_message`, emitted for every message
+    PoolMessage {
+        /// Pool address as `host:port`
+        pool: String,
+        message: String,
+    },
+    /// Share acceptance latency of a pool breached the configured SLA
+    LatencySlaViolated {
+        pool: String,
+        /// Latency at the SLA percentile
+        latency: Duration,
+        threshold: Duration,
+    },
+    /// The miner found a share for a job
+    ShareFound {
+        /// Device that found the share, see [`Miner::device_id`](crate::stratum::miner::Miner::device_id)
+        device: Option<String>,
+        /// Job the share was found on, shared with the job history
+        job: Arc<MiningJob>,
+        share: Share,
+    },
+    /// The pool accepted a share
+    ShareAccepted {
+        device: Option<String>,
+        job_id: String,
+        /// Session generation of the share's job, see [`MiningJob::generation`]
+        generation: u64,
+        /// Pool difficulty the share was submitted at, if known
+        difficulty: Option<f64>,
+        latency: Duration,
+        /// Share outcomes of the job so far, including this one
+        job_shares: ShareCounts,
+    },
+    /// The pool rejected a share
+    ShareRejected {
+        device: Option<String>,
+        job_id: String,
+        generation: u64,
+        /// Error reported by the pool, `None` when it just answered `false`
+        reason: Option<String>,
+        reject_reason: RejectReason,
+        job_shares: ShareCounts,
+    },
+    /// A share was dropped locally instead of being submitted
+    ShareDiscarded {
+        device: Option<String>,
+        job_id: String,
+        /// `Stale` for jobs of an earlier session generation or past their
+        /// submit window, `LowDifficulty` for shares above the target,
+        /// `Duplicate` for shares submitted before, `Other` for shares that
+        /// failed validation, e.g. of an unknown job
+        reject_reason: RejectReason,
+    },
+    /// Submitting a share failed before the pool answered, e.g. on a
+    /// dropped connection; unlike a rejection the pool never judged it
+    ShareSubmitFailed {
+        device: Option<String>,
+        job_id: String,
+        generation: u64,
+        error: String,
+    },
+    /// The sequential extranonce2 values of a job are about to run out, see
+    /// [`JobManager::next_extranonce2`](crate::stratum::v1::jobs::JobManager::next_extranonce2)
+    Extranonce2Low {
+        job_id: String,
+        /// Values left before the exhaustion policy applies
+        remaining: u64,
+        space: u64,
+    },
+    /// The watchdog found the mining pipeline wedged and restarted the
+    /// miner worker and the pool connection
+    #[cfg(feature = "watchdog")]
+    WatchdogRestart { reason: StallReason },
+    /// The pool didn't answer a keepalive ping sent after `idle` without
+    /// traffic, so the client reconnects
+    #[cfg(feature = "watchdog")]
+    ConnectionStale { idle: Duration },
+    /// The consistency check found a target disagreeing with the pool's
+    /// difficulty and corrected it
+    TargetInconsistency { inconsistency: Inconsistency },
+}
+
+/// Type of a [`StratumEvent`], without its payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EventKind {
+    StatsTick,
+    NewJob,
+    DifficultyChanged,
+    NewBlock,
+    Connected,
+    Disconnected,
+    ConnectionClosedStats,
+    Reconnecting,
+    PoolRedirect,
+    Motd,
+    PoolMessage,
+    LatencySlaViolated,
+    ShareFound,
+    ShareAccepted,
+    ShareRejected,
+    ShareDiscarded,
+    ShareSubmitFailed,
+    Extranonce2Low,
+    #[cfg(feature = "watchdog")]
+    WatchdogRestart,
+    #[cfg(feature = "watchdog")]
+    ConnectionStale,
+    TargetInconsistency,
+}
+
+impl StratumEvent {
+    /// Type of this event, e.g. for filtering
+    pub fn kind(&self) -> EventKind {
+        match self {
+            StratumEvent::StatsTick(_) => EventKind::StatsTick,
+            StratumEvent::NewJob { .. } => EventKind::NewJob,
+            StratumEvent::DifficultyChanged { .. } => EventKind::DifficultyChanged,
+            StratumEvent::NewBlock { .. } => EventKind::NewBlock,
+            StratumEvent::Connected { .. } => EventKind::Connected,
+            StratumEvent::Disconnected { .. } => EventKind::Disconnected,
+            StratumEvent::ConnectionClosedStats { .. } => EventKind::ConnectionClosedStats,
+            StratumEvent::Reconnecting { .. } => EventKind::Reconnecting,
+            StratumEvent::PoolRedirect { .. } => EventKind::PoolRedirect,
+            StratumEvent::Motd { .. } => EventKind::Motd,
+            StratumEvent::PoolMessage { .. } => EventKind::PoolMessage,
+            StratumEvent::LatencySlaViolated { .. } => EventKind::LatencySlaViolated,
+            StratumEvent::ShareFound { .. } => EventKind::ShareFound,
+            StratumEvent::ShareAccepted { .. } => EventKind::ShareAccepted,
+            StratumEvent::ShareRejected { .. } => EventKind::ShareRejected,
+            StratumEvent::ShareDiscarded { .. } => EventKind::ShareDiscarded,
+            StratumEvent::ShareSubmitFailed { .. } => EventKind::ShareSubmitFailed,
+            StratumEvent::Extranonce2Low { .. } => EventKind::Extranonce2Low,
+            #[cfg(feature = "watchdog")]
+            StratumEvent::WatchdogRestart { .. } => EventKind::WatchdogRestart,
+            #[cfg(feature = "watchdog")]
+            StratumEvent::ConnectionStale { .. } => EventKind::ConnectionStale,
+            StratumEvent::TargetInconsistency { .. } => EventKind::TargetInconsistency,
+        }
+    }
+}
+
+impl EventKind {
+    /// Category the event is filtered by, `None` if it's always emitted
+    pub fn category(self) -> Option<Category> {
+        match self {
+            EventKind::Connected
+            | EventKind::Disconnected
+            | EventKind::ConnectionClosedStats
+            | EventKind::Reconnecting
+            | EventKind::PoolRedirect
+            | EventKind::Motd
+            | EventKind::PoolMessage => Some(Category::Connection),
+            #[cfg(feature = "watchdog")]
+            EventKind::WatchdogRestart | EventKind::ConnectionStale => Some(Category::Connection),
+            EventKind::NewJob | EventKind::NewBlock | EventKind::Extranonce2Low => {
+                Some(Category::Jobs)
+            }
+            EventKind::DifficultyChanged | EventKind::TargetInconsistency => {
+                Some(Category::Difficulty)
+            }
+            EventKind::ShareFound
+            | EventKind::ShareAccepted
+            | EventKind::ShareRejected
+            | EventKind::ShareDiscarded
+            | EventKind::ShareSubmitFailed
+            | EventKind::LatencySlaViolated => Some(Category::Shares),
+            EventKind::StatsTick => None,
+        }
+    }
+}
+
+/// Create a new event broadcast channel
+pub fn channel() -> broadcast::Sender<StratumEvent> {
+    broadcast::channel(EVENT_CHANNEL_CAPACITY).0
+}
@@ -0,0 +1,261 @@
+use crate::stratum::accounting::civil_date;
+use crate::stratum::error::StratumError;
+use crate::stratum::stats::{SessionSnapshot, HASHES_PER_DIFF1_SHARE};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header row of exported CSV files
+pub const CSV_HEADER: &str =
+    "timestamp,shares_accepted,shares_rejected,hashrate,uptime_secs,reconnects";
+
+/// Options of the periodic CSV statistics export
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvExportConfig {
+    /// Directory the files are written to, created if missing
+    pub dir: PathBuf,
+    /// File name prefix, files are named `<prefix>-<YYYY-MM-DD>T<HHMMSS>.csv`
+    /// after the time of their first row
+    pub prefix: String,
+    /// Period covered by each row
+    pub interval: Duration,
+    /// Rows written before starting a new file
+    pub rows_per_file: usize,
+    /// Files kept in `dir`, the oldest are deleted on rotation
+    pub max_files: usize,
+}
+
+impl Default for CsvExportConfig {
+    /// Hourly rows in daily files, kept for a month
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+            prefix: "stratum-stats".into(),
+            interval: Duration::from_secs(60 * 60),
+            rows_per_file: 24,
+            max_files: 31,
+        }
+    }
+}
+
+/// Share activity over one export interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsRow {
+    /// End of the interval
+    pub at: SystemTime,
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+    /// Hashrate in H/s derived from the difficulty accepted in the interval
+    pub hashrate: f64,
+    /// Session uptime at the end of the interval
+    pub uptime: Duration,
+    pub reconnects: u64,
+}
+
+impl StatsRow {
+    /// Row for the interval between two snapshots of the same session
+    pub fn between(previous: &SessionSnapshot, current: &SessionSnapshot, reconnects: u64) -> Self {
+        let elapsed = current.uptime.saturating_sub(previous.uptime);
+        let difficulty = current.accepted_difficulty - previous.accepted_difficulty;
+        Self {
+            at: SystemTime::now(),
+            shares_accepted: current.shares.accepted - previous.shares.accepted,
+            shares_rejected: current.shares.rejected - previous.shares.rejected,
+            hashrate: if elapsed.is_zero() {
+                0.0
+            } else {
+                difficulty * HASHES_PER_DIFF1_SHARE
+                    / current.difficulty_multiplier
+                    / elapsed.as_secs_f64()
+            },
+            uptime: current.uptime,
+            reconnects,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{:.0},{},{}",
+            timestamp(self.at),
+            self.shares_accepted,
+            self.shares_rejected,
+            self.hashrate,
+            self.uptime.as_secs(),
+            self.reconnects
+        )
+    }
+}
+
+/// UTC time as `YYYY-MM-DD HH:MM:SS`, which spreadsheets parse as a date
+fn timestamp(at: SystemTime) -> String {
+    let secs = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let time = secs % 86_400;
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        civil_date(secs / 86_400),
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Writes statistics rows to CSV files, rotating them by row count
+#[derive(Debug)]
+pub struct CsvExporter {
+    config: CsvExportConfig,
+    file: Option<File>,
+    rows_in_file: usize,
+}
+
+impl CsvExporter {
+    /// Create an exporter, no file is opened before the first row
+    pub fn new(config: CsvExportConfig) -> Self {
+        Self {
+            config,
+            file: None,
+            rows_in_file: 0,
+        }
+    }
+
+    /// Configuration of this exporter
+    pub fn config(&self) -> &CsvExportConfig {
+        &self.config
+    }
+
+    /// Append a row, starting a new file when the current one is full
+    pub fn write_row(&mut self, row: &StatsRow) -> Result<(), StratumError> {
+        if self.file.is_none() || self.rows_in_file >= self.config.rows_per_file.max(1) {
+            self.rotate(row.at)?;
+        }
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", row.to_csv())?;
+            file.flush()?;
+            self.rows_in_file += 1;
+        }
+        Ok(())
+    }
+
+    /// Start a new file and delete the oldest ones beyond `max_files`
+    fn rotate(&mut self, at: SystemTime) -> Result<(), StratumError> {
+        std::fs::create_dir_all(&self.config.dir)?;
+        let name = format!(
+            "{}-{}.csv",
+            self.config.prefix,
+            timestamp(at).replace(' ', "T").replace(':', "")
+        );
+        let path = self.config.dir.join(name);
+        let is_new = !path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if is_new {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        log::info!(target: "stratum", "Exporting statistics to {}", path.display());
+        self.file = Some(file);
+        self.rows_in_file = 0;
+
+        let mut files = self.exported_files()?;
+        files.sort();
+        let excess = files.len().saturating_sub(self.config.max_files.max(1));
+        for old in &files[..excess] {
+            std::fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+
+    /// Files in the export directory written by this configuration
+    fn exported_files(&self) -> Result<Vec<PathBuf>, StratumError> {
+        let prefix = format!("{}-", self.config.prefix);
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.config.dir)? {
+            let path = entry?.path();
+            if is_export(&path, &prefix) {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+}
+
+fn is_export(path: &Path, prefix: &str) -> bool {
+    path.extension().is_some_and(|ext| ext == "csv")
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::stats::SessionStats;
+    use crate::stratum::types::RejectReason;
+
+    fn row(secs: u64) -> StatsRow {
+        StatsRow {
+            at: UNIX_EPOCH + Duration::from_secs(secs),
+            shares_accepted: 10,
+            shares_rejected: 1,
+            hashrate: 1.5e12,
+            uptime: Duration::from_secs(secs),
+            reconnects: 2,
+        }
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let at = UNIX_EPOCH + Duration::from_secs(20_454 * 86_400 + 13 * 3600 + 5 * 60 + 9);
+        assert_eq!(timestamp(at), "2026-01-01 13:05:09");
+    }
+
+    #[test]
+    fn test_row_between_snapshots() {
+        let mut stats = SessionStats::new();
+        let previous = stats.snapshot();
+        stats.record_accepted(Some(4.0), Duration::from_millis(20));
+        stats.record_rejected(RejectReason::Other);
+        let mut current = stats.snapshot();
+        current.uptime = previous.uptime + Duration::from_secs(3600);
+
+        let row = StatsRow::between(&previous, &current, 1);
+        assert_eq!(row.shares_accepted, 1);
+        assert_eq!(row.shares_rejected, 1);
+        assert_eq!(row.hashrate, 4.0 * HASHES_PER_DIFF1_SHARE / 3600.0);
+        assert_eq!(row.reconnects, 1);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("stratum-csv-{}", std::process::id()));
+        let mut exporter = CsvExporter::new(CsvExportConfig {
+            dir: dir.clone(),
+            prefix: "stats".into(),
+            rows_per_file: 2,
+            max_files: 2,
+            ..Default::default()
+        });
+
+        for hour in 0..5 {
+            exporter.write_row(&row(hour * 3600)).unwrap();
+        }
+
+        // Files of hours 0, 2 and 4, of which the oldest was deleted
+        let mut files = exporter.exported_files().unwrap();
+        files.sort();
+        let names: Vec<_> = files
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["stats-1970-01-01T020000.csv", "stats-1970-01-01T040000.csv"]
+        );
+
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "1970-01-01 02:00:00,10,1,1500000000000,7200,2");
+        assert_eq!(lines.len(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
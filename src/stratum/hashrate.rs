@@ -0,0 +1,47 @@
+use crate::stratum::stats::HASHES_PER_DIFF1_SHARE;
+use std::time::Duration;
+
+/// Windows the hashrate is estimated over
+pub const HASHRATE_WINDOWS: [Duration; 3] = [
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(15 * 60),
+];
+
+/// Estimated hashrates in H/s over the [`HASHRATE_WINDOWS`], see
+/// [`SessionStats::hashrate`](crate::stratum::stats::SessionStats::hashrate)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Hashrate {
+    pub one_minute: f64,
+    pub five_minutes: f64,
+    pub fifteen_minutes: f64,
+}
+
+/// Bitcoin difficulty at which a miner hashing at `hashrate` H/s finds a
+/// share every `share_interval` on average
+///
+/// Pools with a difficulty multiplier, such as scrypt pools, count
+/// difficulty in other units, so suggest
+/// [`StratumV1Client::recommend_difficulty`](crate::stratum::v1::StratumV1Client::recommend_difficulty)
+/// to them instead, which converts. `None` without a usable hashrate or
+/// interval.
+pub fn recommend_difficulty(hashrate: f64, share_interval: Duration) -> Option<f64> {
+    let difficulty = hashrate * share_interval.as_secs_f64() / HASHES_PER_DIFF1_SHARE;
+    (difficulty.is_finite() && difficulty > 0.0).then_some(difficulty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recommend_difficulty() {
+        let hashrate = 1024.0 * HASHES_PER_DIFF1_SHARE / 10.0;
+        assert_eq!(
+            recommend_difficulty(hashrate, Duration::from_secs(10)),
+            Some(1024.0)
+        );
+        assert_eq!(recommend_difficulty(0.0, Duration::from_secs(10)), None);
+        assert_eq!(recommend_difficulty(hashrate, Duration::ZERO), None);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::stratum::error::StratumError;
+use crate::stratum::merkle;
+#[cfg(feature = "profiling")]
+use crate::stratum::profiling::{Scope, Timer};
+use crate::stratum::types::MiningJob;
+use sha2::digest::generic_array::GenericArray;
+use sha2::{Digest, Sha256};
+
+/// Length of a serialized block header
+pub const HEADER_LEN: usize = 80;
+
+/// Initial SHA-256 state, before any block is compressed
+const SHA256_INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Double SHA-256, the hash used for block headers and transactions
+pub fn sha256d(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, StratumError> {
+    hex::decode(value)
+        .map_err(|e| StratumError::HexDecode(format!("Invalid {} {} - {}", field, value, e)))
+}
+
+fn decode_hash(field: &str, value: &str) -> Result<[u8; 32], StratumError> {
+    decode(field, value)?
+        .try_into()
+        .map_err(|_| StratumError::InvalidJob(format!("{} must be 32 bytes", field)))
+}
+
+/// Parse a 32-bit header field sent as big-endian hex, e.g. `version`
+fn decode_u32(field: &str, value: &str) -> Result<u32, StratumError> {
+    if value.len() != 8 {
+        return Err(StratumError::InvalidJob(format!(
+            "{} must be 4 bytes",
+            field
+        )));
+    }
+    u32::from_str_radix(value, 16)
+        .map_err(|e| StratumError::HexDecode(format!("Invalid {} {} - {}", field, value, e)))
+}
+
+/// Assemble the coinbase transaction of a job
+pub fn coinbase(
+    job: &MiningJob,
+    extranonce1: &str,
+    extranonce2: &str,
+) -> Result<Vec<u8>, StratumError> {
+    let mut coinbase = decode("coinbase1", &job.coinbase1)?;
+    coinbase.extend(decode("extranonce1", extranonce1)?);
+    coinbase.extend(decode("extranonce2", extranonce2)?);
+    coinbase.extend(decode("coinbase2", &job.coinbase2)?);
+    Ok(coinbase)
+}
+
+/// Block header built from a job and a miner's solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub version: u32,
+    /// Previous block hash in header byte order
+    pub prev_hash: [u8; 32],
+    /// Merkle root in header byte order
+    pub merkle_root: [u8; 32],
+    pub ntime: u32,
+    pub nbits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    /// Build the header of a share
+    ///
+    /// `ntime` and `nonce` are the hex values submitted with the share and
+    /// `version` the block version after any version rolling.
+    pub fn from_job(
+        job: &MiningJob,
+        extranonce1: &str,
+        extranonce2: &str,
+        ntime: &str,
+        nonce: &str,
+        version: u32,
+    ) -> Result<Self, StratumError> {
+        #[cfg(feature = "profiling")]
+        let _profile = Timer::enter(Scope::WorkBuild);
+        // Stratum sends the previous hash as 32-bit words in reversed byte order
+        let mut prev_hash = decode_hash("prev_hash", &job.prev_hash)?;
+        for word in prev_hash.chunks_exact_mut(4) {
+            word.reverse();
+        }
+        let coinbase = coinbase(job, extranonce1, extranonce2)?;
+
+        Ok(Self {
+            version,
+            prev_hash,
+            merkle_root: merkle::compute_root(sha256d(&coinbase), &job.merkle_branch)?,
+            ntime: decode_u32("ntime", ntime)?,
+            nbits: decode_u32("nbits", &job.nbits)?,
+            nonce: decode_u32("nonce", nonce)?,
+        })
+    }
+
+    /// Version of a job as sent in `mining.notify`
+    pub fn job_version(job: &MiningJob) -> Result<u32, StratumError> {
+        decode_u32("version", &job.version)
+    }
+
+    /// Serialize for hashing
+    pub fn serialize(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        header[0..4].copy_from_slice(&self.version.to_le_bytes());
+        header[4..36].copy_from_slice(&self.prev_hash);
+        header[36..68].copy_from_slice(&self.merkle_root);
+        header[68..72].copy_from_slice(&self.ntime.to_le_bytes());
+        header[72..76].copy_from_slice(&self.nbits.to_le_bytes());
+        header[76..80].copy_from_slice(&self.nonce.to_le_bytes());
+        header
+    }
+
+    /// Block hash as a big-endian number, the way block explorers show it
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hash = sha256d(&self.serialize());
+        hash.reverse();
+        hash
+    }
+
+    /// SHA-256 midstate of the header, see [`midstate`]
+    pub fn midstate(&self) -> [u32; 8] {
+        midstate(&self.serialize())
+    }
+
+    /// Whether the hash is at or below a big-endian `target`
+    pub fn meets_target(&self, target: &[u8; 32]) -> bool {
+        self.hash() <= *target
+    }
+}
+
+/// SHA-256 state after the first 64 bytes of a serialized header
+///
+/// Those bytes only change with the version, previous hash and most of the
+/// merkle root, so ASICs and FPGAs take the midstate and hash just the last
+/// 16 bytes, `serialize()[64..]`, while rolling the nonce. The state words
+/// are in SHA-256's own order; devices wanting bytes usually take each word
+/// little-endian.
+pub fn midstate(header: &[u8; HEADER_LEN]) -> [u32; 8] {
+    let mut state = SHA256_INITIAL_STATE;
+    sha2::compress256(&mut state, &[GenericArray::clone_from_slice(&header[..64])]);
+    state
+}
+
+/// Block version after rolling the bits allowed by `mask`
+pub fn rolled_version(version: u32, version_bits: u32, mask: u32) -> u32 {
+    (version & !mask) | (version_bits & mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The genesis block, with its coinbase split around a made up extranonce
+    fn genesis() -> (MiningJob, &'static str, &'static str) {
+        let job = MiningJob {
+            job_id: "genesis".into(),
+            prev_hash: "00".repeat(32),
+            coinbase1: "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d".into(),
+            coinbase2: "5468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000".into(),
+            merkle_branch: vec![],
+            version: "00000001".into(),
+            nbits: "1d00ffff".into(),
+            ntime: "495fab29".into(),
+            clean_jobs: Some(true),
+            target: None,
+            raw_params: Default::default(),
+            generation: 0,
+        };
+        (job, "04ffff00", "1d010445")
+    }
+
+    fn genesis_header() -> BlockHeader {
+        let (job, extranonce1, extranonce2) = genesis();
+        BlockHeader::from_job(&job, extranonce1, extranonce2, "495fab29", "7c2bac1d", 1).unwrap()
+    }
+
+    #[test]
+    fn test_genesis_header() {
+        let header = genesis_header();
+        let mut merkle_root = header.merkle_root;
+        merkle_root.reverse();
+        assert_eq!(
+            hex::encode(merkle_root),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert_eq!(
+            hex::encode(header.hash()),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+    }
+
+    #[test]
+    fn test_meets_target() {
+        let header = genesis_header();
+        let mut diff1 = [0u8; 32];
+        diff1[4] = 0xff;
+        diff1[5] = 0xff;
+        assert!(header.meets_target(&diff1));
+
+        let mut harder = [0u8; 32];
+        harder[8] = 0xff;
+        assert!(!header.meets_target(&harder));
+
+        let wrong_nonce = BlockHeader { nonce: 0, ..header };
+        assert!(!wrong_nonce.meets_target(&diff1));
+    }
+
+    #[test]
+    fn test_midstate() {
+        let header = genesis_header();
+        let bytes = header.serialize();
+        let mut state = header.midstate();
+        assert_eq!(state, midstate(&bytes));
+        assert_ne!(state, SHA256_INITIAL_STATE);
+
+        // Finishing the first hash from the midstate, as hardware does
+        let mut tail = [0u8; 64];
+        tail[..16].copy_from_slice(&bytes[64..]);
+        tail[16] = 0x80;
+        tail[56..].copy_from_slice(&(HEADER_LEN as u64 * 8).to_be_bytes());
+        sha2::compress256(&mut state, &[GenericArray::clone_from_slice(&tail)]);
+        let first: Vec<u8> = state.iter().flat_map(|word| word.to_be_bytes()).collect();
+        assert_eq!(first, Sha256::digest(bytes).to_vec());
+    }
+
+    #[test]
+    fn test_prev_hash_word_order() {
+        let (mut job, extranonce1, extranonce2) = genesis();
+        job.prev_hash = format!("00010203{}", "00".repeat(28));
+        let header =
+            BlockHeader::from_job(&job, extranonce1, extranonce2, "495fab29", "7c2bac1d", 1)
+                .unwrap();
+        assert_eq!(header.prev_hash[..4], [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_rolled_version() {
+        assert_eq!(
+            rolled_version(0x20000000, 0x1fffe000, 0x1fffe000),
+            0x3fffe000
+        );
+        assert_eq!(
+            rolled_version(0x20000000, 0xffffffff, 0x00002000),
+            0x20002000
+        );
+    }
+}
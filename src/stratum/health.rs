@@ -0,0 +1,213 @@
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Overall health of a client, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HealthStatus {
+    Healthy,
+    /// Working, but a threshold was crossed that deserves attention
+    Degraded,
+    /// Not mining usefully, a supervisor should restart the client
+    Unhealthy,
+}
+
+/// Limits deciding between [`HealthStatus`] levels
+///
+/// A `None` limit is never crossed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthThresholds {
+    /// Job age above which the client is degraded
+    pub job_age_degraded: Option<Duration>,
+    /// Job age above which the client is unhealthy
+    pub job_age_unhealthy: Option<Duration>,
+    /// Age of the last share above which the client is degraded
+    ///
+    /// Depends on hashrate and pool difficulty, so unset by default.
+    pub share_age_degraded: Option<Duration>,
+    /// Age of the last share above which the client is unhealthy
+    pub share_age_unhealthy: Option<Duration>,
+    /// Reject rate (0.0-1.0) above which the client is degraded
+    pub reject_rate_degraded: Option<f64>,
+    /// Reject rate (0.0-1.0) above which the client is unhealthy
+    pub reject_rate_unhealthy: Option<f64>,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            job_age_degraded: Some(Duration::from_secs(2 * 60)),
+            job_age_unhealthy: Some(Duration::from_secs(10 * 60)),
+            share_age_degraded: None,
+            share_age_unhealthy: None,
+            reject_rate_degraded: Some(0.05),
+            reject_rate_unhealthy: Some(0.25),
+        }
+    }
+}
+
+/// Point-in-time health report, meant to be polled by supervisors such as
+/// a systemd watchdog or a Kubernetes liveness probe
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub status: HealthStatus,
+    pub connected: bool,
+    pub authorized: bool,
+    /// Time since the pool sent the last job, `None` before the first one
+    pub last_job_age: Option<Duration>,
+    /// Time since the last share was acknowledged, `None` before the first one
+    pub last_share_age: Option<Duration>,
+    /// Fraction of recently acknowledged shares that were rejected (0.0-1.0)
+    pub reject_rate: f64,
+}
+
+impl Health {
+    /// Build a report, deriving its status from `thresholds`
+    ///
+    /// A disconnected client is unhealthy. A client that isn't authorized
+    /// yet, or hasn't received a job yet, is degraded.
+    pub fn new(
+        connected: bool,
+        authorized: bool,
+        last_job_age: Option<Duration>,
+        last_share_age: Option<Duration>,
+        reject_rate: f64,
+        thresholds: &HealthThresholds,
+    ) -> Self {
+        let mut health = Self {
+            status: HealthStatus::Healthy,
+            connected,
+            authorized,
+            last_job_age,
+            last_share_age,
+            reject_rate,
+        };
+        health.status = health.evaluate(thresholds);
+        health
+    }
+
+    /// Status of this report under `thresholds`
+    pub fn evaluate(&self, thresholds: &HealthThresholds) -> HealthStatus {
+        fn level<T: PartialOrd>(
+            value: Option<T>,
+            degraded: Option<T>,
+            unhealthy: Option<T>,
+        ) -> HealthStatus {
+            let crossed = |limit: Option<T>| match (&value, limit) {
+                (Some(value), Some(limit)) => *value > limit,
+                _ => false,
+            };
+            if crossed(unhealthy) {
+                HealthStatus::Unhealthy
+            } else if crossed(degraded) {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Healthy
+            }
+        }
+
+        if !self.connected {
+            return HealthStatus::Unhealthy;
+        }
+        let startup = if self.authorized && self.last_job_age.is_some() {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Degraded
+        };
+
+        [
+            startup,
+            level(
+                self.last_job_age,
+                thresholds.job_age_degraded,
+                thresholds.job_age_unhealthy,
+            ),
+            level(
+                self.last_share_age,
+                thresholds.share_age_degraded,
+                thresholds.share_age_unhealthy,
+            ),
+            level(
+                Some(self.reject_rate),
+                thresholds.reject_rate_degraded,
+                thresholds.reject_rate_unhealthy,
+            ),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(HealthStatus::Healthy)
+    }
+
+    /// Whether the status is [`HealthStatus::Healthy`]
+    pub fn is_healthy(&self) -> bool {
+        self.status == HealthStatus::Healthy
+    }
+}
+
+/// Health reporting of a client, for embedding it in a supervisor
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Current health report
+    async fn health(&self) -> Health;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(job_age: u64, reject_rate: f64) -> Health {
+        Health::new(
+            true,
+            true,
+            Some(Duration::from_secs(job_age)),
+            None,
+            reject_rate,
+            &HealthThresholds::default(),
+        )
+    }
+
+    #[test]
+    fn test_status() {
+        assert_eq!(health(30, 0.0).status, HealthStatus::Healthy);
+        assert_eq!(health(5 * 60, 0.0).status, HealthStatus::Degraded);
+        assert_eq!(health(30 * 60, 0.0).status, HealthStatus::Unhealthy);
+        assert_eq!(health(30, 0.1).status, HealthStatus::Degraded);
+        assert_eq!(health(30, 0.5).status, HealthStatus::Unhealthy);
+        // The worst level wins
+        assert_eq!(health(5 * 60, 0.5).status, HealthStatus::Unhealthy);
+    }
+
+    #[test]
+    fn test_startup_and_disconnect() {
+        let thresholds = HealthThresholds::default();
+        let starting = Health::new(true, false, None, None, 0.0, &thresholds);
+        assert_eq!(starting.status, HealthStatus::Degraded);
+
+        let disconnected = Health::new(
+            false,
+            true,
+            Some(Duration::from_secs(1)),
+            None,
+            0.0,
+            &thresholds,
+        );
+        assert_eq!(disconnected.status, HealthStatus::Unhealthy);
+    }
+
+    #[test]
+    fn test_share_age_threshold() {
+        let thresholds = HealthThresholds {
+            share_age_degraded: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let health = Health::new(
+            true,
+            true,
+            Some(Duration::from_secs(1)),
+            Some(Duration::from_secs(90)),
+            0.0,
+            &thresholds,
+        );
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert!(!health.is_healthy());
+    }
+}
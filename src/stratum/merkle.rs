@@ -0,0 +1,143 @@
+//! Merkle roots of blocks mined from Stratum jobs
+//!
+//! Hashes come in two byte orders. Internal order is the raw output of
+//! [`sha256d`], used when hashing and in the block header; `mining.notify`
+//! sends the merkle branch in this order. Display order is reversed, the way
+//! block explorers and RPC show txids and merkle roots. Everything here takes
+//! and returns internal order, [`to_display_hex`] and [`from_display_hex`]
+//! convert.
+
+use crate::stratum::error::StratumError;
+use crate::stratum::header::sha256d;
+
+/// Merkle root of a block whose coinbase has `coinbase_txid`
+///
+/// The branch holds the hex hashes the coinbase is combined with on its way
+/// up the tree, as sent in `mining.notify`. The root is in header byte order.
+pub fn compute_root(
+    coinbase_txid: [u8; 32],
+    merkle_branch: &[String],
+) -> Result<[u8; 32], StratumError> {
+    let mut root = coinbase_txid;
+    for branch in merkle_branch {
+        let hash = hex::decode(branch)
+            .map_err(|e| {
+                StratumError::HexDecode(format!("Invalid merkle branch {} - {}", branch, e))
+            })?
+            .try_into()
+            .map_err(|_| StratumError::InvalidJob("merkle branch must be 32 bytes".into()))?;
+        root = combine(&root, &hash);
+    }
+    Ok(root)
+}
+
+/// Merkle branch of the coinbase in a block with the other transactions'
+/// `txids`, as a pool sends it in `mining.notify`
+pub fn coinbase_branch(txids: &[[u8; 32]]) -> Vec<String> {
+    let mut branch = Vec::new();
+    // The coinbase's slot is left empty, it only ever combines with the branch
+    let mut level: Vec<Option<[u8; 32]>> = std::iter::once(None)
+        .chain(txids.iter().copied().map(Some))
+        .collect();
+    while level.len() > 1 {
+        branch.extend(level[1].map(hex::encode));
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let left = pair[0]?;
+                // The last hash of an odd level pairs with itself
+                let right = pair.get(1).copied().flatten().unwrap_or(left);
+                Some(combine(&left, &right))
+            })
+            .collect();
+    }
+    branch
+}
+
+/// Hash of a pair of tree nodes
+fn combine(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut pair = [0u8; 64];
+    pair[..32].copy_from_slice(left);
+    pair[32..].copy_from_slice(right);
+    sha256d(&pair)
+}
+
+/// Hex of a hash in display order, e.g. a txid as block explorers show it
+pub fn to_display_hex(hash: &[u8; 32]) -> String {
+    let mut reversed = *hash;
+    reversed.reverse();
+    hex::encode(reversed)
+}
+
+/// Hash in internal order from its display order hex
+pub fn from_display_hex(value: &str) -> Result<[u8; 32], StratumError> {
+    let mut hash: [u8; 32] = hex::decode(value)
+        .map_err(|e| StratumError::HexDecode(format!("Invalid hash {} - {}", value, e)))?
+        .try_into()
+        .map_err(|_| StratumError::InvalidJob(format!("Hash {} must be 32 bytes", value)))?;
+    hash.reverse();
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transactions of mainnet block 100000, the coinbase first
+    const BLOCK_100000_TXIDS: [&str; 4] = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+    const BLOCK_100000_MERKLE_ROOT: &str =
+        "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+
+    fn parse_txids(block: &[&str]) -> Vec<[u8; 32]> {
+        block
+            .iter()
+            .map(|txid| from_display_hex(txid).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_mainnet_root() {
+        let txids = parse_txids(&BLOCK_100000_TXIDS);
+        let branch = coinbase_branch(&txids[1..]);
+        assert_eq!(branch.len(), 2);
+        assert_eq!(branch[0], hex::encode(txids[1]));
+
+        let root = compute_root(txids[0], &branch).unwrap();
+        assert_eq!(to_display_hex(&root), BLOCK_100000_MERKLE_ROOT);
+
+        // Block 170, the first with a transaction besides the coinbase
+        let txids = parse_txids(&[
+            "b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082",
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+        ]);
+        let root = compute_root(txids[0], &coinbase_branch(&txids[1..])).unwrap();
+        assert_eq!(
+            to_display_hex(&root),
+            "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff"
+        );
+    }
+
+    #[test]
+    fn test_odd_levels() {
+        let leaf = |byte: u8| [byte; 32];
+        assert!(coinbase_branch(&[]).is_empty());
+        assert_eq!(compute_root(leaf(0), &[]).unwrap(), leaf(0));
+
+        // Three transactions: the last pairs with itself
+        let branch = coinbase_branch(&[leaf(1), leaf(2)]);
+        let expected = combine(&combine(&leaf(0), &leaf(1)), &combine(&leaf(2), &leaf(2)));
+        assert_eq!(compute_root(leaf(0), &branch).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_invalid_branch() {
+        assert!(compute_root([0; 32], &["abcd".into()]).is_err());
+        assert!(compute_root([0; 32], &["zz".repeat(32)]).is_err());
+        assert!(from_display_hex("abcd").is_err());
+    }
+}
@@ -1,8 +1,234 @@
 use crate::stratum::error::StratumError;
-use crate::stratum::types::MiningJob;
+use crate::stratum::throttle::DeviceStats;
+use crate::stratum::types::{MiningJob, Share, VersionRolling};
+use crate::stratum::v1::jobs::{Extranonce2Slot, MinerResult};
 use async_trait::async_trait;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Future returned by [`Miner`] methods
+///
+/// `async fn` implementations only compile for miners that are `Sync`. Miners
+/// that aren't write the method out and return a future that doesn't borrow
+/// `self`.
+pub type MinerFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
 #[async_trait]
 pub trait Miner: Clone + Send + 'static {
-    async fn on_job_received(&self, job: MiningJob) -> Result<(u32, MiningJob), StratumError>;
+    /// Mine a job, returning the nonce of a share and the job it was found on
+    ///
+    /// Jobs are shared with the job history and event subscribers, so the
+    /// miner gets a cheap handle rather than its own copy. The share is
+    /// submitted at the extranonce2 and ntime of [`ShareSink::slot`]; miners
+    /// finding more than one share per job, or rolling extranonce2, ntime or
+    /// version bits, implement [`mine`](Self::mine) as well, which is what
+    /// the client calls.
+    async fn on_job_received(
+        &self,
+        job: Arc<MiningJob>,
+    ) -> Result<(u32, Arc<MiningJob>), StratumError>;
+
+    /// Mine a job, reporting every share found to `shares` until the job is
+    /// superseded or mining is paused
+    ///
+    /// The future is dropped when the job is cancelled; work running outside
+    /// of it, e.g. on hardware or other threads, should stop once
+    /// [`ShareSink::is_cancelled`]. The default reports the single result of
+    /// [`on_job_received`](Self::on_job_received).
+    // The default methods are written out rather than `async fn`, which
+    // `#[async_trait]` would only provide to miners that are `Sync`
+    fn mine<'a, 'async_trait>(
+        &'a self,
+        job: Arc<MiningJob>,
+        shares: ShareSink,
+    ) -> MinerFuture<'async_trait, ()>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        let result = self.on_job_received(job);
+        Box::pin(async move {
+            match result.await {
+                Ok((nonce, _)) => shares.submit(nonce),
+                Err(err) => shares.report(Err(err)),
+            };
+        })
+    }
+
+    /// Report device statistics consulted by throttle policies
+    fn device_stats<'a, 'async_trait>(&'a self) -> MinerFuture<'async_trait, Option<DeviceStats>>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async { None })
+    }
+
+    /// Change the mining intensity (0.0-1.0) at the request of a throttle policy
+    fn set_intensity<'a, 'async_trait>(&'a self, _intensity: f64) -> MinerFuture<'async_trait, ()>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async {})
+    }
+
+    /// Version rolling granted by the pool, or `None` when it isn't allowed
+    ///
+    /// Hardware rolling version bits must stay within the mask and report
+    /// the rolled bits with [`ShareSink::submit_rolled`].
+    fn set_version_rolling<'a, 'async_trait>(
+        &'a self,
+        _rolling: Option<VersionRolling>,
+    ) -> MinerFuture<'async_trait, ()>
+    where
+        'a: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async {})
+    }
+
+    /// Identifier of the device producing this miner's results, e.g. a board
+    /// serial, used to attribute shares to specific hardware
+    fn device_id(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Where a miner reports the shares it finds on a job, see [`Miner::mine`]
+///
+/// Cheap to clone, e.g. to hand to each thread or device mining the job.
+#[derive(Clone)]
+pub struct ShareSink {
+    job: Arc<MiningJob>,
+    slot: Extranonce2Slot,
+    report: Arc<dyn Fn(MinerResult) -> bool + Send + Sync>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ShareSink {
+    /// Sink for `job` mined at `slot`, handing results to `report`, which
+    /// tells whether a result was taken
+    pub(crate) fn new(
+        job: Arc<MiningJob>,
+        slot: Extranonce2Slot,
+        report: impl Fn(MinerResult) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            job,
+            slot,
+            report: Arc::new(report),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Job the shares are found on
+    pub fn job(&self) -> &Arc<MiningJob> {
+        &self.job
+    }
+
+    /// Extranonce2 and ntime allocated to this miner for the job
+    ///
+    /// Every miner handed the job gets its own extranonce2, so they don't
+    /// search the same space. The coinbase, and so the merkle root, is built
+    /// with it.
+    pub fn slot(&self) -> &Extranonce2Slot {
+        &self.slot
+    }
+
+    /// Report a share found at `nonce`, with the slot's extranonce2 and ntime
+    ///
+    /// Returns `false` if the share was dropped because the job was
+    /// cancelled or is from before a reconnect; keep mining otherwise.
+    pub fn submit(&self, nonce: u32) -> bool {
+        self.submit_rolled(nonce, &self.slot.extranonce2, &self.slot.ntime, None)
+    }
+
+    /// Report a share found after rolling extranonce2, ntime or the version
+    /// bits granted by [`Miner::set_version_rolling`]
+    pub fn submit_rolled(
+        &self,
+        nonce: u32,
+        extranonce2: &str,
+        ntime: &str,
+        version_bits: Option<u32>,
+    ) -> bool {
+        let share = Share {
+            job_id: self.job.job_id.clone(),
+            extranonce2: extranonce2.to_string(),
+            ntime: ntime.to_string(),
+            nonce: format!("{:08x}", nonce),
+            version_bits: version_bits.map(|bits| format!("{:08x}", bits)),
+        };
+        self.report(Ok((share, self.job.clone())))
+    }
+
+    /// Report a share, or that mining the job failed
+    pub fn report(&self, result: MinerResult) -> bool {
+        !self.is_cancelled() && (self.report)(result)
+    }
+
+    /// Whether the job was superseded or mining paused, so results are no
+    /// longer taken
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Object-safe view of the [`Miner`] callbacks used outside the job pipeline
+#[async_trait]
+pub(crate) trait MinerControl: Send + Sync {
+    async fn device_stats(&self) -> Option<DeviceStats>;
+
+    async fn set_intensity(&self, intensity: f64);
+
+    async fn set_version_rolling(&self, rolling: Option<VersionRolling>);
+
+    fn device_id(&self) -> Option<String>;
+}
+
+/// [`MinerControl`] of a miner, which need not be `Sync`
+///
+/// Calls take turns on the miner. Its device id is read once.
+pub(crate) struct SharedMiner<M> {
+    miner: Mutex<M>,
+    device_id: Option<String>,
+}
+
+impl<M: Miner> SharedMiner<M> {
+    pub(crate) fn new(miner: M) -> Self {
+        Self {
+            device_id: miner.device_id(),
+            miner: Mutex::new(miner),
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Miner> MinerControl for SharedMiner<M> {
+    async fn device_stats(&self) -> Option<DeviceStats> {
+        let miner = self.miner.lock().await;
+        miner.device_stats().await
+    }
+
+    async fn set_intensity(&self, intensity: f64) {
+        let miner = self.miner.lock().await;
+        miner.set_intensity(intensity).await
+    }
+
+    async fn set_version_rolling(&self, rolling: Option<VersionRolling>) {
+        let miner = self.miner.lock().await;
+        miner.set_version_rolling(rolling).await
+    }
+
+    fn device_id(&self) -> Option<String> {
+        self.device_id.clone()
+    }
 }
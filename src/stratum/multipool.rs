@@ -0,0 +1,251 @@
+use crate::stratum::types::MiningJob;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Latest job announced by one pool
+#[derive(Debug, Clone)]
+struct PoolJob {
+    prev_hash: String,
+    /// When the pool first announced a job on `prev_hash`
+    received_at: Instant,
+}
+
+/// A block some pool builds on
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    /// When any pool first announced a job on it
+    first_seen: Instant,
+    /// Height parsed from the coinbase, see [`MiningJob::height`]
+    height: Option<u64>,
+}
+
+/// Picks which pool to take work from when several pools feed the same miners
+///
+/// After a new block, pools switch to the new `prev_hash` at different times.
+/// Work from a pool still announcing the previous block is stale, so the
+/// selector prefers pools whose latest job builds on the newest block, and
+/// among those the pool that announced it first. Blocks are ordered by the
+/// height in their jobs' coinbase; only when a height is missing, or two
+/// blocks compete at the same height, does the one seen first win.
+#[derive(Debug, Default)]
+pub struct JobSourceSelector {
+    latest: HashMap<String, PoolJob>,
+    /// Blocks pools are working on, by prev_hash
+    blocks: HashMap<String, Block>,
+    /// prev_hash of the newest block
+    newest_prev_hash: Option<String>,
+}
+
+impl JobSourceSelector {
+    /// Create an empty selector
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a job received from the given pool
+    pub fn record_job(&mut self, pool: &str, job: &MiningJob) {
+        self.record_job_at(pool, job, Instant::now());
+    }
+
+    /// Record a job received from the given pool at a specific instant
+    ///
+    /// Further jobs of a pool on the same block keep the instant the pool
+    /// first announced it.
+    pub fn record_job_at(&mut self, pool: &str, job: &MiningJob, received_at: Instant) {
+        if self
+            .latest
+            .get(pool)
+            .is_some_and(|latest| latest.prev_hash == job.prev_hash)
+        {
+            return;
+        }
+
+        if !self.blocks.contains_key(&job.prev_hash) {
+            let block = Block {
+                first_seen: received_at,
+                height: job.height(),
+            };
+            if self.is_newer(&block) {
+                self.newest_prev_hash = Some(job.prev_hash.clone());
+            }
+            self.blocks.insert(job.prev_hash.clone(), block);
+        }
+
+        self.latest.insert(
+            pool.to_string(),
+            PoolJob {
+                prev_hash: job.prev_hash.clone(),
+                received_at,
+            },
+        );
+
+        // Forget blocks no pool is working on anymore
+        let latest = &self.latest;
+        let newest = &self.newest_prev_hash;
+        self.blocks.retain(|hash, _| {
+            newest.as_ref() == Some(hash) || latest.values().any(|job| &job.prev_hash == hash)
+        });
+    }
+
+    /// Whether a block first seen now supersedes the newest one
+    fn is_newer(&self, block: &Block) -> bool {
+        let Some(newest) = self
+            .newest_prev_hash
+            .as_ref()
+            .and_then(|hash| self.blocks.get(hash))
+        else {
+            return true;
+        };
+        match (block.height, newest.height) {
+            (Some(height), Some(newest_height)) => height > newest_height,
+            // Without heights, the block seen last is the newest
+            _ => true,
+        }
+    }
+
+    /// Forget a pool, e.g. after it disconnected
+    pub fn remove_pool(&mut self, pool: &str) {
+        self.latest.remove(pool);
+    }
+
+    /// How long after the first pool the given pool announced its current block
+    ///
+    /// Returns `None` if the pool is unknown or still working on an older block.
+    pub fn block_lag(&self, pool: &str) -> Option<Duration> {
+        let job = self.latest.get(pool)?;
+        if self.newest_prev_hash.as_ref() != Some(&job.prev_hash) {
+            return None;
+        }
+        let block = self.blocks.get(&job.prev_hash)?;
+        Some(job.received_at.saturating_duration_since(block.first_seen))
+    }
+
+    /// Check whether the pool's latest job builds on an outdated block
+    pub fn is_stale(&self, pool: &str) -> bool {
+        self.latest
+            .get(pool)
+            .is_some_and(|job| self.newest_prev_hash.as_ref() != Some(&job.prev_hash))
+    }
+
+    /// Pool whose work is freshest, preferring the lowest block lag
+    pub fn preferred_source(&self) -> Option<&str> {
+        self.latest
+            .keys()
+            .filter_map(|pool| self.block_lag(pool).map(|lag| (pool, lag)))
+            .min_by(|(a_pool, a_lag), (b_pool, b_lag)| {
+                a_lag.cmp(b_lag).then_with(|| a_pool.cmp(b_pool))
+            })
+            .map(|(pool, _)| pool.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(prev_hash: &str) -> MiningJob {
+        job_at(prev_hash, None)
+    }
+
+    /// Job whose coinbase pushes `height` as BIP34 asks
+    fn job_at(prev_hash: &str, height: Option<u32>) -> MiningJob {
+        let coinbase1 = match height {
+            Some(height) => format!(
+                "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4b03{}",
+                hex::encode(&height.to_le_bytes()[..3])
+            ),
+            None => String::new(),
+        };
+        MiningJob {
+            job_id: "1".into(),
+            prev_hash: prev_hash.into(),
+            coinbase1,
+            coinbase2: String::new(),
+            merkle_branch: vec![],
+            version: "20000000".into(),
+            nbits: "1d00ffff".into(),
+            ntime: "60509af9".into(),
+            clean_jobs: Some(true),
+            target: None,
+            raw_params: Default::default(),
+            generation: 0,
+        }
+    }
+
+    #[test]
+    fn test_prefers_first_pool_on_new_block() {
+        let mut selector = JobSourceSelector::new();
+        let start = Instant::now();
+
+        selector.record_job_at("a", &job("block1"), start);
+        selector.record_job_at("b", &job("block1"), start + Duration::from_millis(10));
+        assert_eq!(selector.preferred_source(), Some("a"));
+
+        // Pool b sees the new block first
+        selector.record_job_at("b", &job("block2"), start + Duration::from_secs(600));
+        assert_eq!(selector.preferred_source(), Some("b"));
+        assert!(selector.is_stale("a"));
+        assert_eq!(selector.block_lag("a"), None);
+
+        // Pool a catches up 300ms later
+        selector.record_job_at("a", &job("block2"), start + Duration::from_millis(600_300));
+        assert!(!selector.is_stale("a"));
+        assert_eq!(selector.block_lag("a"), Some(Duration::from_millis(300)));
+        assert_eq!(selector.preferred_source(), Some("b"));
+
+        selector.remove_pool("b");
+        assert_eq!(selector.preferred_source(), Some("a"));
+    }
+
+    #[test]
+    fn test_orders_blocks_by_height() {
+        let mut selector = JobSourceSelector::new();
+        let start = Instant::now();
+
+        selector.record_job_at("a", &job_at("block2", Some(101)), start);
+        // Pool b shows up later, still on the block before
+        selector.record_job_at(
+            "b",
+            &job_at("block1", Some(100)),
+            start + Duration::from_secs(1),
+        );
+        assert!(selector.is_stale("b"));
+        assert!(!selector.is_stale("a"));
+        assert_eq!(selector.preferred_source(), Some("a"));
+
+        selector.record_job_at(
+            "b",
+            &job_at("block2", Some(101)),
+            start + Duration::from_secs(2),
+        );
+        assert!(!selector.is_stale("b"));
+        assert_eq!(selector.block_lag("b"), Some(Duration::from_secs(2)));
+
+        // A competing block at the same height doesn't replace the first one
+        selector.record_job_at(
+            "c",
+            &job_at("block2b", Some(101)),
+            start + Duration::from_secs(3),
+        );
+        assert!(selector.is_stale("c"));
+    }
+
+    #[test]
+    fn test_repeated_jobs_keep_block_lag() {
+        let mut selector = JobSourceSelector::new();
+        let start = Instant::now();
+
+        selector.record_job_at("a", &job("block1"), start);
+        selector.record_job_at("b", &job("block1"), start + Duration::from_millis(10));
+        // New jobs of b on the same block don't add to its lag
+        selector.record_job_at("b", &job("block1"), start + Duration::from_secs(30));
+        assert_eq!(selector.block_lag("b"), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_empty_selector() {
+        let selector = JobSourceSelector::new();
+        assert_eq!(selector.preferred_source(), None);
+        assert!(!selector.is_stale("a"));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::stratum::error::StratumError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Pool options encoded in the password field, e.g. `d=8192,c=BTC`
+///
+/// Pools commonly use the otherwise unused password to carry settings such as
+/// a fixed/initial difficulty (`d=`) or the payout coin (`c=`). A bare `x` is
+/// the conventional placeholder for "no options".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolPassword {
+    options: Vec<(String, String)>,
+    flags: Vec<String>,
+}
+
+impl PoolPassword {
+    /// Create an empty password, rendered as `x`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request an initial/fixed share difficulty (`d=`)
+    pub fn difficulty(self, difficulty: f64) -> Self {
+        self.option("d", difficulty.to_string())
+    }
+
+    /// Select the payout coin (`c=`)
+    pub fn coin(self, coin: impl Into<String>) -> Self {
+        self.option("c", coin)
+    }
+
+    /// Set an arbitrary `key=value` option, replacing any previous value
+    pub fn option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        let value = value.into();
+        match self.options.iter_mut().find(|(k, _)| *k == key) {
+            Some(existing) => existing.1 = value,
+            None => self.options.push((key, value)),
+        }
+        self
+    }
+
+    /// Get the value of an option
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Bare entries without a value, other than the `x` placeholder
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// Difficulty requested with `d=`, used as the initial suggested difficulty
+    pub fn suggested_difficulty(&self) -> Option<f64> {
+        self.get("d")
+            .and_then(|d| d.parse::<f64>().ok())
+            .filter(|d| *d > 0.0)
+    }
+
+    /// Payout coin requested with `c=`
+    pub fn coin_symbol(&self) -> Option<&str> {
+        self.get("c")
+    }
+}
+
+impl FromStr for PoolPassword {
+    type Err = StratumError;
+
+    fn from_str(password: &str) -> Result<Self, Self::Err> {
+        let mut parsed = Self::new();
+        for entry in password.split([',', ';']).map(str::trim) {
+            match entry.split_once('=') {
+                Some((key, _)) if key.trim().is_empty() => {
+                    return Err(StratumError::Config(format!(
+                        "Invalid pool password option {}",
+                        entry
+                    )));
+                }
+                Some((key, value)) => parsed = parsed.option(key.trim(), value.trim()),
+                None if entry.is_empty() || entry == "x" => {}
+                None => parsed.flags.push(entry.to_string()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl fmt::Display for PoolPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.options.is_empty() && self.flags.is_empty() {
+            return write!(f, "x");
+        }
+
+        let entries: Vec<String> = self
+            .options
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .chain(self.flags.iter().cloned())
+            .collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let password = PoolPassword::new().difficulty(8192.0).coin("BTC");
+        assert_eq!(password.to_string(), "d=8192,c=BTC");
+        assert_eq!(PoolPassword::new().to_string(), "x");
+        assert_eq!(
+            PoolPassword::new()
+                .difficulty(1.0)
+                .difficulty(2.0)
+                .to_string(),
+            "d=2"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let password: PoolPassword = "d=8192, c=BTC;sd=1024".parse().unwrap();
+        assert_eq!(password.suggested_difficulty(), Some(8192.0));
+        assert_eq!(password.coin_symbol(), Some("BTC"));
+        assert_eq!(password.get("sd"), Some("1024"));
+
+        let password: PoolPassword = "x".parse().unwrap();
+        assert_eq!(password, PoolPassword::new());
+        assert_eq!(password.suggested_difficulty(), None);
+
+        let password: PoolPassword = "x,mypass".parse().unwrap();
+        assert_eq!(password.flags(), ["mypass"]);
+
+        assert!("=5".parse::<PoolPassword>().is_err());
+        let password: PoolPassword = "d=abc".parse().unwrap();
+        assert_eq!(password.suggested_difficulty(), None);
+    }
+}
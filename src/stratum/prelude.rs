@@ -0,0 +1,41 @@
+//! One import path for building a mining client
+//!
+//! ```
+//! use rust_stratum::stratum::prelude::*;
+//! ```
+//!
+//! Items are re-exported from their defining modules, so code using the
+//! prelude keeps compiling when those modules are reorganized.
+
+#[cfg(feature = "audit")]
+pub use crate::stratum::audit::{AuditLog, AuditRecord, AuditSigner, HmacSigner};
+pub use crate::stratum::contention::{ContentionSnapshot, LockSite};
+pub use crate::stratum::error::{ErrorContext, ErrorKind, StratumError, StratumRpcError};
+pub use crate::stratum::events::{DisconnectReason, EventKind, StratumEvent};
+pub use crate::stratum::hashrate::{recommend_difficulty, Hashrate};
+pub use crate::stratum::health::{Health, HealthCheck, HealthStatus};
+pub use crate::stratum::miner::{Miner, MinerFuture, ShareSink};
+pub use crate::stratum::password::PoolPassword;
+pub use crate::stratum::quickstart::{MiningSession, ShutdownReport};
+pub use crate::stratum::stats::{SessionSnapshot, ShareCounts, StatsSummary};
+pub use crate::stratum::target::Target;
+pub use crate::stratum::types::{
+    AuthRejectReason, AuthResponse, ConfigureResult, MiningJob, MiningTarget, RejectReason,
+    ServerInfo, Share, StratumVersion, SubscribeResponse, VersionRolling,
+};
+pub use crate::stratum::url::PoolUrl;
+pub use crate::stratum::v1::builder::StratumClientBuilder;
+pub use crate::stratum::v1::connection::{
+    ConnectionConfig, ReconnectPolicy, StreamTransport, Transport,
+};
+pub use crate::stratum::v1::failover::{FailoverClient, FailoverPolicy, PoolEndpoint};
+pub use crate::stratum::v1::jobs::{
+    Extranonce2Config, Extranonce2Exhaustion, Extranonce2Slot, LateShare, MinerResult, SubmitWindow,
+};
+pub use crate::stratum::v1::redirect::RedirectPolicy;
+pub use crate::stratum::v1::resubmit::SubmitRetryPolicy;
+#[cfg(feature = "tls")]
+pub use crate::stratum::v1::tls::TlsConfig;
+pub use crate::stratum::v1::{AuthState, StratumV1Client};
+pub use crate::stratum::verbosity::{Category, Verbosity};
+pub use crate::stratum::{create_client, create_client_from_url, mine, StratumClient};
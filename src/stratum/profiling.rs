@@ -0,0 +1,187 @@
+//! Timing of the client's hot paths, enabled by the `profiling` feature
+//!
+//! Each [`Scope`] sums the calls and time spent in it across the process, so
+//! a proxy handling many connections shows where its time goes without an
+//! external profiler. Synchronous scopes are also entered as `trace` level
+//! spans under the `stratum::profile` target, which `tracing` flamegraph
+//! layers such as `tracing-flame` render directly.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Hot path whose time is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Validating a `mining.notify` into a job
+    Parse,
+    /// Computing a share target from a pool difficulty
+    Target,
+    /// Building a block header from a job and share
+    WorkBuild,
+    /// Checking and submitting a share, up to the pool's answer
+    Submit,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::Parse, Scope::Target, Scope::WorkBuild, Scope::Submit];
+
+    /// Name of the scope's span, e.g. `work_build`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Scope::Parse => "parse",
+            Scope::Target => "target",
+            Scope::WorkBuild => "work_build",
+            Scope::Submit => "submit",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Time spent in one scope
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScopeTiming {
+    pub calls: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl ScopeTiming {
+    /// Mean time per call
+    pub fn mean(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.total.as_nanos() / u128::from(n)) as u64),
+        }
+    }
+}
+
+/// Scope timings of the process, see [`snapshot`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProfileSnapshot {
+    scopes: [ScopeTiming; Scope::ALL.len()],
+}
+
+impl ProfileSnapshot {
+    /// Timing of one scope
+    pub fn scope(&self, scope: Scope) -> &ScopeTiming {
+        &self.scopes[scope.index()]
+    }
+}
+
+#[derive(Debug)]
+struct ScopeCounters {
+    calls: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl ScopeCounters {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+static SCOPES: [ScopeCounters; Scope::ALL.len()] = [
+    ScopeCounters::new(),
+    ScopeCounters::new(),
+    ScopeCounters::new(),
+    ScopeCounters::new(),
+];
+
+/// Record a call of `scope` that took `elapsed`
+pub fn record(scope: Scope, elapsed: Duration) {
+    let counters = &SCOPES[scope.index()];
+    let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+    counters.calls.fetch_add(1, Ordering::Relaxed);
+    counters.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+    counters.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+}
+
+/// Current timings of every scope
+pub fn snapshot() -> ProfileSnapshot {
+    ProfileSnapshot {
+        scopes: SCOPES.each_ref().map(|counters| ScopeTiming {
+            calls: counters.calls.load(Ordering::Relaxed),
+            total: Duration::from_nanos(counters.total_nanos.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(counters.max_nanos.load(Ordering::Relaxed)),
+        }),
+    }
+}
+
+/// Start the timings over, e.g. between benchmark runs
+pub fn reset() {
+    for counters in &SCOPES {
+        counters.calls.store(0, Ordering::Relaxed);
+        counters.total_nanos.store(0, Ordering::Relaxed);
+        counters.max_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Records the time spent in a scope when dropped
+#[derive(Debug)]
+pub(crate) struct Timer {
+    scope: Scope,
+    started: Instant,
+}
+
+impl Timer {
+    /// Time `scope` without a span, for async code where an entered span
+    /// must not be held across an await
+    pub(crate) fn start(scope: Scope) -> Self {
+        Self {
+            scope,
+            started: Instant::now(),
+        }
+    }
+
+    /// Time `scope` and enter its span, for synchronous code
+    pub(crate) fn enter(scope: Scope) -> (Self, tracing::span::EnteredSpan) {
+        let span = tracing::trace_span!(target: "stratum::profile", "scope", name = scope.name());
+        (Self::start(scope), span.entered())
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        record(self.scope, self.started.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_timing() {
+        let before = *snapshot().scope(Scope::WorkBuild);
+        {
+            let _profile = Timer::enter(Scope::WorkBuild);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        record(Scope::WorkBuild, Duration::from_secs(1));
+
+        let after = *snapshot().scope(Scope::WorkBuild);
+        // Other tests may build headers meanwhile
+        assert!(after.calls >= before.calls + 2);
+        assert!(after.total >= before.total + Duration::from_millis(1005));
+        assert!(after.max >= Duration::from_secs(1));
+        assert!(after.mean() > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_mean_beyond_u32_calls() {
+        let timing = ScopeTiming {
+            calls: 1 << 32,
+            total: Duration::from_secs(1 << 32),
+            max: Duration::from_secs(1),
+        };
+        assert_eq!(timing.mean(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::stratum::error::StratumError;
+use crate::stratum::events::StratumEvent;
+use crate::stratum::miner::Miner;
+pub use crate::stratum::stats::ShutdownReport;
+use crate::stratum::v1::connection::ReconnectPolicy;
+use crate::stratum::v1::StratumV1Client;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Interval of the `StatsTick` events of a session started by [`mine`]
+pub const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Reconnect policy of a session started by [`mine`]
+pub const RECONNECT_POLICY: ReconnectPolicy = ReconnectPolicy {
+    max_attempts: 10,
+    initial_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(60),
+};
+
+/// Mine on a pool with sensible defaults, in one call
+///
+/// Connects to `url`, over TLS for `stratum+ssl://` URLs, subscribes and
+/// authorizes, then keeps mining in the background:
+/// - notifications are dispatched to `miner` as they arrive
+/// - the miner's results are submitted, see
+///   [`start_auto_submit`](StratumV1Client::start_auto_submit)
+/// - lost connections are restored, see
+///   [`set_auto_reconnect`](StratumV1Client::set_auto_reconnect) and
+///   [`RECONNECT_POLICY`]
+/// - a `StatsTick` event is emitted every [`STATS_INTERVAL`]
+///
+/// Returns the session's events and the handle to stop it.
+///
+/// ```no_run
+/// # use rust_stratum::stratum::prelude::*;
+/// # use rust_stratum::stratum::v1::jobs::TestMiner;
+/// # async fn run() -> Result<(), StratumError> {
+/// let url = "stratum+tcp://pool.example.com:3333";
+/// let (mut events, session) = mine(url, "worker", "x", TestMiner).await?;
+/// while let Ok(event) = events.recv().await {
+///     if let StratumEvent::ShareAccepted { job_id, .. } = event {
+///         println!("Share for job {} accepted", job_id);
+///     }
+/// }
+/// let report = session.shutdown().await?;
+/// println!("{}", report);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn mine<M: Miner>(
+    url: &str,
+    username: &str,
+    password: &str,
+    miner: M,
+) -> Result<(broadcast::Receiver<StratumEvent>, MiningSession), StratumError> {
+    let client = StratumV1Client::builder()
+        .url(url)
+        .credentials(username, password)
+        .reconnect_policy(RECONNECT_POLICY)
+        .miner(miner)
+        .build()
+        .await?;
+
+    let events = client.events();
+    client.set_stats_interval(Some(STATS_INTERVAL)).await;
+    client.start_auto_submit().await?;
+    client.set_auto_reconnect(true).await;
+    client.start_dispatcher().await;
+    Ok((events, MiningSession { client }))
+}
+
+/// Handle to a session started by [`mine`]
+pub struct MiningSession {
+    client: StratumV1Client,
+}
+
+impl MiningSession {
+    /// The session's client, e.g. to pause mining or read statistics
+    pub fn client(&self) -> &StratumV1Client {
+        &self.client
+    }
+
+    /// Stop mining and close the connection, summing up the session, see
+    /// [`StratumV1Client::shutdown`]
+    pub async fn shutdown(mut self) -> Result<ShutdownReport, StratumError> {
+        self.client.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::v1::jobs::TestMiner;
+    use crate::stratum::v1::protocol::{MINING_AUTHORIZE, MINING_SUBMIT, MINING_SUBSCRIBE};
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// Pool sending a job any share meets after authorizing, dropping its
+    /// first connection once a share was submitted on it
+    async fn pool(listener: TcpListener, submitted: mpsc::UnboundedSender<Value>) {
+        for connection in 0.. {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let method = request["method"].as_str().unwrap();
+                let result = match method {
+                    MINING_SUBSCRIBE => json!([[["mining.notify", "1"]], "08000002", 4]),
+                    _ => json!(true),
+                };
+                let mut reply =
+                    json!({"id": request["id"], "result": result, "error": null}).to_string();
+                if method == MINING_AUTHORIZE {
+                    let difficulty =
+                        json!({"id": null, "method": "mining.set_difficulty", "params": [1e-10]});
+                    let job = json!({"id": null, "method": "mining.notify", "params": [format!("job{}", connection), "4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000", "01000000", "02000000", [], "00000002", "1c2ac4af", "504e86b9", true]});
+                    reply = format!("{}\n{}\n{}", reply, difficulty, job);
+                }
+                writer
+                    .write_all(format!("{}\n", reply).as_bytes())
+                    .await
+                    .unwrap();
+                if method == MINING_SUBMIT {
+                    submitted.send(request["params"].clone()).unwrap();
+                    if connection == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mine() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (submitted_tx, mut submitted) = mpsc::unbounded_channel();
+        tokio::spawn(pool(listener, submitted_tx));
+
+        let url = format!("stratum+tcp://127.0.0.1:{}", port);
+        let (mut events, session) = mine(&url, "worker", "x", TestMiner).await.unwrap();
+
+        let wait = Duration::from_secs(5);
+        let share = tokio::time::timeout(wait, submitted.recv()).await.unwrap();
+        assert_eq!(
+            share,
+            Some(json!([
+                "worker", "job0", "00000000", "504e86b9", "00000000"
+            ]))
+        );
+
+        // The pool dropped the connection, the session carries on on a new one
+        let share = tokio::time::timeout(wait, submitted.recv()).await.unwrap();
+        assert_eq!(share.unwrap()[1], "job1");
+        let mut reconnected = false;
+        while let Ok(event) = events.try_recv() {
+            reconnected |= matches!(event, StratumEvent::Reconnecting { .. });
+        }
+        assert!(reconnected);
+
+        let report = session.shutdown().await.unwrap();
+        assert!(report.shares.accepted >= 1);
+        assert_eq!(report.pending_shares_dropped, 0);
+        assert_eq!(report.shares.rejected, 0);
+        // The shares' hashes meet far more than the pool's difficulty
+        assert!(report
+            .best_share_difficulty
+            .is_some_and(|best| best > 1e-10));
+    }
+}
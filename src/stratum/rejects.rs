@@ -0,0 +1,211 @@
+use crate::stratum::error::StratumRpcError;
+use crate::stratum::types::RejectReason;
+use serde_json::Value;
+
+/// Pool software whose reject messages are known
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PoolFamily {
+    /// Wording common to most pools and the Stratum spec
+    Generic,
+    Braiins,
+    F2Pool,
+    ViaBtc,
+    NiceHash,
+    /// P2Pool and the ckpool-derived pools sharing its wording
+    P2Pool,
+    /// Block template rejections passed through from bitcoind
+    Bitcoind,
+}
+
+/// Known reject messages, matched case-insensitively as substrings
+///
+/// Checked in order, so messages naming several reasons, e.g. "duplicate
+/// stale share", resolve to the first listed.
+pub const BUILTIN_REJECTS: &[(PoolFamily, &str, RejectReason)] = &[
+    (PoolFamily::Generic, "duplicate", RejectReason::Duplicate),
+    (PoolFamily::P2Pool, "dupe", RejectReason::Duplicate),
+    (
+        PoolFamily::NiceHash,
+        "already submitted",
+        RejectReason::Duplicate,
+    ),
+    (PoolFamily::Generic, "stale", RejectReason::Stale),
+    (PoolFamily::Generic, "job not found", RejectReason::Stale),
+    (PoolFamily::Generic, "old job", RejectReason::Stale),
+    (PoolFamily::Generic, "expired", RejectReason::Stale),
+    (PoolFamily::Braiins, "unknown job", RejectReason::Stale),
+    (PoolFamily::F2Pool, "job id not found", RejectReason::Stale),
+    (PoolFamily::ViaBtc, "job not exist", RejectReason::Stale),
+    (
+        PoolFamily::NiceHash,
+        "job not found (=stale)",
+        RejectReason::Stale,
+    ),
+    (PoolFamily::P2Pool, "invalid jobid", RejectReason::Stale),
+    (PoolFamily::P2Pool, "not in work queue", RejectReason::Stale),
+    (
+        PoolFamily::Bitcoind,
+        "prevhash-not-tip",
+        RejectReason::Stale,
+    ),
+    (PoolFamily::Bitcoind, "unknown-work", RejectReason::Stale),
+    (
+        PoolFamily::Generic,
+        "low difficulty",
+        RejectReason::LowDifficulty,
+    ),
+    (PoolFamily::Generic, "low diff", RejectReason::LowDifficulty),
+    (
+        PoolFamily::Generic,
+        "above target",
+        RejectReason::LowDifficulty,
+    ),
+    (
+        PoolFamily::Braiins,
+        "does not meet target",
+        RejectReason::LowDifficulty,
+    ),
+    (
+        PoolFamily::F2Pool,
+        "difficulty too low",
+        RejectReason::LowDifficulty,
+    ),
+    (
+        PoolFamily::ViaBtc,
+        "share difficulty low",
+        RejectReason::LowDifficulty,
+    ),
+    (
+        PoolFamily::NiceHash,
+        "invalid difficulty",
+        RejectReason::LowDifficulty,
+    ),
+    (
+        PoolFamily::P2Pool,
+        "share is not under target",
+        RejectReason::LowDifficulty,
+    ),
+    (
+        PoolFamily::Bitcoind,
+        "high-hash",
+        RejectReason::LowDifficulty,
+    ),
+];
+
+/// Maps pools' reject messages to a [`RejectReason`]
+///
+/// Knows the [`BUILTIN_REJECTS`] of common pool software; messages of other
+/// pools can be added, taking precedence over the built-in ones. Set per pool
+/// through [`PoolQuirks::rejects`](crate::stratum::v1::quirks::PoolQuirks::rejects).
+///
+/// ```
+/// # use rust_stratum::stratum::prelude::*;
+/// # use rust_stratum::stratum::rejects::RejectCatalogue;
+/// let rejects = RejectCatalogue::default().with("work too old", RejectReason::Stale);
+/// assert_eq!(rejects.classify("Work too old"), RejectReason::Stale);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RejectCatalogue {
+    /// Lowercased messages added on top of the built-in ones
+    custom: Vec<(String, RejectReason)>,
+}
+
+impl RejectCatalogue {
+    /// Also recognize messages containing `message` as `reason`
+    pub fn with(mut self, message: &str, reason: RejectReason) -> Self {
+        self.custom.push((message.to_lowercase(), reason));
+        self
+    }
+
+    /// Recognize the reason from a pool's error text
+    pub fn classify(&self, message: &str) -> RejectReason {
+        let message = message.to_lowercase();
+        let custom = self
+            .custom
+            .iter()
+            .map(|(pattern, reason)| (pattern.as_str(), *reason));
+        let builtin = BUILTIN_REJECTS
+            .iter()
+            .map(|(_, pattern, reason)| (*pattern, *reason));
+        custom
+            .chain(builtin)
+            .find(|(pattern, _)| message.contains(pattern))
+            .map_or(RejectReason::Other, |(_, reason)| reason)
+    }
+
+    /// Recognize the reason from a Stratum error code, or else its message
+    pub fn from_rpc(&self, error: StratumRpcError, message: &str) -> RejectReason {
+        match error {
+            StratumRpcError::Stale => RejectReason::Stale,
+            StratumRpcError::Duplicate => RejectReason::Duplicate,
+            StratumRpcError::LowDifficulty => RejectReason::LowDifficulty,
+            _ => self.classify(message),
+        }
+    }
+
+    /// Recognize the reason from the error of a `mining.submit` response,
+    /// by its Stratum error code (21 stale, 22 duplicate, 23 low difficulty)
+    /// or else its message
+    pub fn from_error(&self, error: &Value) -> RejectReason {
+        let (code, message) = match error {
+            Value::String(message) => (None, Some(message.as_str())),
+            Value::Array(items) => (
+                items.first().and_then(Value::as_i64),
+                items.get(1).and_then(Value::as_str),
+            ),
+            Value::Object(fields) => (
+                fields.get("code").and_then(Value::as_i64),
+                fields.get("message").and_then(Value::as_str),
+            ),
+            _ => (None, None),
+        };
+        match code.and_then(StratumRpcError::from_code) {
+            Some(error) => self.from_rpc(error, message.unwrap_or_default()),
+            None => message.map_or(RejectReason::Other, |message| self.classify(message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_rejects() {
+        let rejects = RejectCatalogue::default();
+        for (message, reason) in [
+            ("Job not found (=stale)", RejectReason::Stale),
+            ("Dupe", RejectReason::Duplicate),
+            ("high-hash", RejectReason::LowDifficulty),
+            ("Share difficulty too low", RejectReason::LowDifficulty),
+            ("prevhash-not-tip", RejectReason::Stale),
+            ("Duplicate stale share", RejectReason::Duplicate),
+            ("Invalid nonce", RejectReason::Other),
+        ] {
+            assert_eq!(rejects.classify(message), reason, "{message}");
+        }
+        // No entry is shadowed by an earlier one with another reason
+        for (family, message, reason) in BUILTIN_REJECTS {
+            assert_eq!(rejects.classify(message), *reason, "{family:?} {message}");
+        }
+    }
+
+    #[test]
+    fn test_custom_rejects() {
+        let rejects = RejectCatalogue::default()
+            .with("Work too old", RejectReason::Stale)
+            .with("stale nonce range", RejectReason::Other);
+        assert_eq!(rejects.classify("work too old"), RejectReason::Stale);
+        assert_eq!(rejects.classify("Stale nonce range"), RejectReason::Other);
+        assert_eq!(
+            rejects.from_error(&json!([20, "Work too old", null])),
+            RejectReason::Stale
+        );
+        assert_eq!(
+            rejects.from_error(&json!([22, "Work too old", null])),
+            RejectReason::Duplicate
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::stratum::error::StratumError;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How often the scheduler re-evaluates whether mining should be active
+pub const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A time-of-day window during which mining is allowed
+///
+/// Windows whose `end` is earlier than `start` wrap past midnight into the
+/// following day, e.g. `start = "22:00"`, `end = "06:00"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleWindow {
+    /// Days on which the window starts; defaults to every day
+    #[serde(default = "all_days")]
+    pub days: Vec<Weekday>,
+    /// Local time the window opens (inclusive)
+    pub start: NaiveTime,
+    /// Local time the window closes (exclusive)
+    pub end: NaiveTime,
+}
+
+fn all_days() -> Vec<Weekday> {
+    vec![
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ]
+}
+
+impl ScheduleWindow {
+    /// Check whether the window is open at the given local time
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        let time = at.time();
+        let today = at.weekday();
+
+        if self.start <= self.end {
+            self.days.contains(&today) && time >= self.start && time < self.end
+        } else {
+            (self.days.contains(&today) && time >= self.start)
+                || (self.days.contains(&today.pred()) && time < self.end)
+        }
+    }
+}
+
+/// Time-of-day mining schedule
+///
+/// Mining is only active inside one of the configured windows. Schedules are
+/// usually loaded from the `[[windows]]` tables of a TOML config:
+///
+/// ```toml
+/// [[windows]]
+/// days = ["Mon", "Tue", "Wed", "Thu", "Fri"]
+/// start = "22:00:00"
+/// end = "06:00:00"
+///
+/// [[windows]]
+/// days = ["Sat", "Sun"]
+/// start = "00:00:00"
+/// end = "23:59:59"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MiningSchedule {
+    #[serde(default)]
+    pub windows: Vec<ScheduleWindow>,
+}
+
+impl MiningSchedule {
+    /// Parse a schedule from TOML
+    pub fn from_toml(config: &str) -> Result<Self, StratumError> {
+        toml::from_str(config)
+            .map_err(|e| StratumError::Config(format!("Invalid mining schedule - {}", e)))
+    }
+
+    /// Check whether mining is allowed at the given local time
+    pub fn is_active_at(&self, at: NaiveDateTime) -> bool {
+        self.windows.iter().any(|window| window.contains(at))
+    }
+
+    /// Check whether mining is allowed right now, in local time
+    pub fn is_active_now(&self) -> bool {
+        self.is_active_at(chrono::Local::now().naive_local())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2024-01-01 is a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let schedule = MiningSchedule::from_toml(
+            r#"
+            [[windows]]
+            days = ["Mon", "Tue"]
+            start = "22:00:00"
+            end = "06:00:00"
+
+            [[windows]]
+            start = "12:00:00"
+            end = "13:00:00"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(schedule.windows.len(), 2);
+        assert_eq!(schedule.windows[0].days, vec![Weekday::Mon, Weekday::Tue]);
+        assert_eq!(schedule.windows[1].days.len(), 7);
+
+        assert!(MiningSchedule::from_toml("windows = 5").is_err());
+    }
+
+    #[test]
+    fn test_window_same_day() {
+        let schedule = MiningSchedule {
+            windows: vec![ScheduleWindow {
+                days: vec![Weekday::Mon],
+                start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            }],
+        };
+
+        assert!(schedule.is_active_at(at(1, 9, 0)));
+        assert!(schedule.is_active_at(at(1, 16, 59)));
+        assert!(!schedule.is_active_at(at(1, 17, 0)));
+        assert!(!schedule.is_active_at(at(1, 8, 59)));
+        // Tuesday is not in the window
+        assert!(!schedule.is_active_at(at(2, 12, 0)));
+    }
+
+    #[test]
+    fn test_window_wraps_midnight() {
+        let schedule = MiningSchedule {
+            windows: vec![ScheduleWindow {
+                days: vec![Weekday::Fri],
+                start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+            }],
+        };
+
+        // Friday 2024-01-05 evening and the following Saturday morning
+        assert!(schedule.is_active_at(at(5, 23, 0)));
+        assert!(schedule.is_active_at(at(6, 5, 59)));
+        assert!(!schedule.is_active_at(at(6, 6, 0)));
+        // Friday morning belongs to Thursday's window, which doesn't exist
+        assert!(!schedule.is_active_at(at(5, 3, 0)));
+    }
+
+    #[test]
+    fn test_empty_schedule_never_mines() {
+        assert!(!MiningSchedule::default().is_active_at(at(1, 12, 0)));
+    }
+}
@@ -0,0 +1,907 @@
+use crate::stratum::contention::ContentionSnapshot;
+use crate::stratum::error::StratumError;
+use crate::stratum::hashrate::{Hashrate, HASHRATE_WINDOWS};
+#[cfg(feature = "profiling")]
+use crate::stratum::profiling::{self, ProfileSnapshot};
+use crate::stratum::types::RejectReason;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Expected number of hashes needed to find a difficulty 1 share
+pub const HASHES_PER_DIFF1_SHARE: f64 = 4_294_967_296.0;
+
+/// How long individual share outcomes are kept for windowed summaries
+pub const RECENT_SHARE_HORIZON: Duration = Duration::from_secs(15 * 60);
+
+/// Number of submit latency samples kept per pool
+pub const LATENCY_SAMPLE_WINDOW: usize = 500;
+
+/// Number of recent jobs share counts are kept for
+pub const SHARE_STATS_JOBS: usize = 64;
+
+/// Per-session records (best share, longest streak, fastest accept)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionRecords {
+    /// Highest difficulty met by the hash of any accepted share, which may
+    /// be well above the difficulty it was submitted at
+    pub best_share_difficulty: Option<f64>,
+    /// Longest run of consecutively accepted shares
+    pub longest_accept_streak: u64,
+    /// Shortest time between submitting a share and the pool accepting it
+    pub fastest_accept_latency: Option<Duration>,
+}
+
+/// Share counts attributed to a single device
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceShareStats {
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+    /// Sum of the difficulty accepted shares were credited at
+    pub accepted_difficulty: f64,
+}
+
+impl DeviceShareStats {
+    /// Average hashrate in H/s over `elapsed`, derived from accepted difficulty
+    pub fn hashrate(&self, elapsed: Duration) -> f64 {
+        let secs = elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.accepted_difficulty * HASHES_PER_DIFF1_SHARE / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Point-in-time view of the session statistics
+#[derive(Debug, Clone)]
+pub struct SessionSnapshot {
+    pub started_at: Instant,
+    pub uptime: Duration,
+    /// Accepted and rejected shares by reject reason
+    pub shares: ShareCounts,
+    /// Counts of the last [`SHARE_STATS_JOBS`] jobs shares were submitted
+    /// for, oldest first
+    pub jobs: VecDeque<(String, ShareCounts)>,
+    /// Shares whose submission failed on the connection, without a verdict
+    /// of the pool
+    pub submit_failures: u64,
+    /// Summed difficulty of all accepted shares
+    pub accepted_difficulty: f64,
+    pub current_accept_streak: u64,
+    pub records: SessionRecords,
+    /// Submit-to-acknowledgment latency percentiles per pool
+    pub submit_latency: HashMap<String, LatencyPercentiles>,
+    /// Share counts per device, for shares submitted with a device id
+    pub devices: HashMap<String, DeviceShareStats>,
+    /// Time spent waiting for the client's locks, filled in by the client
+    pub lock_contention: ContentionSnapshot,
+    /// Miner results and shares dropped for being from before a reconnect,
+    /// filled in by the client
+    pub stale_results_dropped: u64,
+    /// Factor the pool's difficulties are scaled by, see
+    /// [`SessionStats::set_difficulty_multiplier`]
+    pub difficulty_multiplier: f64,
+    /// Time spent in the hot paths of every client in the process
+    #[cfg(feature = "profiling")]
+    pub profile: ProfileSnapshot,
+}
+
+/// Summary of a session stopped with
+/// [`StratumV1Client::shutdown`](crate::stratum::v1::StratumV1Client::shutdown)
+#[derive(Debug, Clone)]
+pub struct ShutdownReport {
+    pub uptime: Duration,
+    /// Accepted and rejected shares by reject reason
+    pub shares: ShareCounts,
+    /// Highest difficulty of any accepted share
+    pub best_share_difficulty: Option<f64>,
+    /// Miner results left when the session stopped whose submission failed
+    pub pending_shares_dropped: usize,
+    /// See [`StratumV1Client::last_error`](crate::stratum::v1::StratumV1Client::last_error)
+    pub last_error: Option<StratumError>,
+}
+
+/// One line, for logging
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "uptime {:?}, {} shares accepted, {} rejected",
+            self.uptime, self.shares.accepted, self.shares.rejected
+        )?;
+        if let Some(best) = self.best_share_difficulty {
+            write!(f, ", best share {}", best)?;
+        }
+        if self.pending_shares_dropped > 0 {
+            write!(
+                f,
+                ", {} pending shares dropped",
+                self.pending_shares_dropped
+            )?;
+        }
+        if let Some(err) = &self.last_error {
+            write!(f, ", last error: {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+/// Latency distribution summary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyPercentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    pub samples: usize,
+}
+
+/// Rolling window of latency samples
+#[derive(Debug, Clone, Default)]
+pub struct LatencyTracker {
+    samples: VecDeque<Duration>,
+}
+
+impl LatencyTracker {
+    /// Add a sample, evicting the oldest once the window is full
+    pub fn record(&mut self, latency: Duration) {
+        if self.samples.len() >= LATENCY_SAMPLE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+    }
+
+    /// Number of samples in the window
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Check whether no samples were recorded yet
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Nearest-rank percentile (0-100) of the samples in the window
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        Self::nearest_rank(&sorted, percentile)
+    }
+
+    fn nearest_rank(sorted: &[Duration], percentile: f64) -> Option<Duration> {
+        if sorted.is_empty() {
+            return None;
+        }
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.saturating_sub(1).min(sorted.len() - 1)])
+    }
+
+    /// Summarize the window as common percentiles
+    pub fn percentiles(&self) -> Option<LatencyPercentiles> {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        Some(LatencyPercentiles {
+            p50: Self::nearest_rank(&sorted, 50.0)?,
+            p90: Self::nearest_rank(&sorted, 90.0)?,
+            p99: Self::nearest_rank(&sorted, 99.0)?,
+            max: *sorted.last()?,
+            samples: sorted.len(),
+        })
+    }
+}
+
+/// Acceptance latency objective for a pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencySla {
+    /// Percentile (0-100) the threshold applies to
+    pub percentile: f64,
+    /// Maximum acceptable latency at that percentile
+    pub threshold: Duration,
+    /// Minimum samples before the SLA is evaluated
+    pub min_samples: usize,
+}
+
+impl LatencySla {
+    /// Check whether the tracked latency breaches the objective
+    pub fn is_violated(&self, tracker: &LatencyTracker) -> bool {
+        tracker.len() >= self.min_samples
+            && tracker
+                .percentile(self.percentile)
+                .is_some_and(|latency| latency > self.threshold)
+    }
+}
+
+/// Compact summary of recent share activity over a time window
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsSummary {
+    /// Length of the window the summary covers
+    pub window: Duration,
+    /// Estimated hashrate in H/s derived from accepted share difficulty
+    pub hashrate: f64,
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+    /// Percentage of shares rejected in the window (0-100)
+    pub reject_percent: f64,
+    /// Average submit-to-accept latency in the window
+    pub avg_accept_latency: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+struct ShareOutcome {
+    at: Instant,
+    accepted: bool,
+    difficulty: Option<f64>,
+    latency: Option<Duration>,
+}
+
+/// Share outcomes of a job or a session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShareCounts {
+    pub accepted: u64,
+    /// All rejected shares, broken down in the fields below
+    pub rejected: u64,
+    pub stale: u64,
+    pub duplicate: u64,
+    pub low_difficulty: u64,
+    pub other: u64,
+}
+
+impl ShareCounts {
+    /// Record an accepted share, or a rejected one with its reason
+    pub fn record(&mut self, rejection: Option<RejectReason>) {
+        let Some(reason) = rejection else {
+            self.accepted += 1;
+            return;
+        };
+        self.rejected += 1;
+        match reason {
+            RejectReason::Stale => self.stale += 1,
+            RejectReason::Duplicate => self.duplicate += 1,
+            RejectReason::LowDifficulty => self.low_difficulty += 1,
+            _ => self.other += 1,
+        }
+    }
+}
+
+/// Accumulates share outcomes for a single mining session
+#[derive(Debug, Clone)]
+pub struct SessionStats {
+    started_at: Instant,
+    shares: ShareCounts,
+    jobs: VecDeque<(String, ShareCounts)>,
+    submit_failures: u64,
+    accepted_difficulty: f64,
+    current_accept_streak: u64,
+    records: SessionRecords,
+    recent: VecDeque<ShareOutcome>,
+    submit_latency: HashMap<String, LatencyTracker>,
+    devices: HashMap<String, DeviceShareStats>,
+    difficulty_multiplier: f64,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionStats {
+    /// Start a new session at the current instant
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            shares: ShareCounts::default(),
+            jobs: VecDeque::new(),
+            submit_failures: 0,
+            accepted_difficulty: 0.0,
+            current_accept_streak: 0,
+            records: SessionRecords::default(),
+            recent: VecDeque::new(),
+            submit_latency: HashMap::new(),
+            devices: HashMap::new(),
+            difficulty_multiplier: 1.0,
+        }
+    }
+
+    /// Take share difficulties as `multiplier` times Bitcoin's when
+    /// estimating hashrates, see
+    /// [`JobManager::set_difficulty_multiplier`](crate::stratum::v1::jobs::JobManager::set_difficulty_multiplier)
+    pub fn set_difficulty_multiplier(&mut self, multiplier: f64) {
+        self.difficulty_multiplier = multiplier;
+    }
+
+    fn push_recent(&mut self, outcome: ShareOutcome) {
+        while self
+            .recent
+            .front()
+            .is_some_and(|o| outcome.at.duration_since(o.at) > RECENT_SHARE_HORIZON)
+        {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(outcome);
+    }
+
+    /// Record a share accepted by the pool
+    ///
+    /// `difficulty` is the difficulty the share was credited at, if known.
+    /// The difficulty its hash met goes to
+    /// [`record_share_difficulty`](Self::record_share_difficulty).
+    pub fn record_accepted(&mut self, difficulty: Option<f64>, latency: Duration) {
+        self.shares.record(None);
+        self.accepted_difficulty += difficulty.unwrap_or(0.0);
+        self.current_accept_streak += 1;
+
+        let records = &mut self.records;
+        if self.current_accept_streak > records.longest_accept_streak {
+            records.longest_accept_streak = self.current_accept_streak;
+        }
+
+        if records
+            .fastest_accept_latency
+            .is_none_or(|fastest| latency < fastest)
+        {
+            records.fastest_accept_latency = Some(latency);
+        }
+
+        self.push_recent(ShareOutcome {
+            at: Instant::now(),
+            accepted: true,
+            difficulty,
+            latency: Some(latency),
+        });
+    }
+
+    /// Record the difficulty the hash of an accepted share met
+    pub fn record_share_difficulty(&mut self, difficulty: f64) {
+        let records = &mut self.records;
+        if records
+            .best_share_difficulty
+            .is_none_or(|best| difficulty > best)
+        {
+            records.best_share_difficulty = Some(difficulty);
+        }
+    }
+
+    /// Record a share rejected by the pool for `reason`, breaking the current
+    /// accept streak
+    ///
+    /// Only for a verdict of the pool; submissions failing on the connection
+    /// go to [`record_submit_failed`](Self::record_submit_failed).
+    pub fn record_rejected(&mut self, reason: RejectReason) {
+        self.shares.record(Some(reason));
+        self.current_accept_streak = 0;
+
+        self.push_recent(ShareOutcome {
+            at: Instant::now(),
+            accepted: false,
+            difficulty: None,
+            latency: None,
+        });
+    }
+
+    /// Record a share whose submission failed without the pool answering,
+    /// e.g. on a dropped connection
+    ///
+    /// Neither a reject nor the end of the accept streak, the pool never
+    /// judged the share.
+    pub fn record_submit_failed(&mut self) {
+        self.submit_failures += 1;
+    }
+
+    /// Attribute a share outcome to `device`
+    ///
+    /// Session-wide counts are recorded separately through
+    /// [`record_accepted`](Self::record_accepted) and
+    /// [`record_rejected`](Self::record_rejected).
+    pub fn record_device_share(&mut self, device: &str, accepted: bool, difficulty: Option<f64>) {
+        let stats = self.devices.entry(device.to_string()).or_default();
+        if accepted {
+            stats.shares_accepted += 1;
+            stats.accepted_difficulty += difficulty.unwrap_or(0.0);
+        } else {
+            stats.shares_rejected += 1;
+        }
+    }
+
+    /// Get the share counts attributed to a device
+    pub fn device(&self, device: &str) -> Option<&DeviceShareStats> {
+        self.devices.get(device)
+    }
+
+    /// Attribute a share outcome to job `job_id`, returning the job's counts
+    ///
+    /// Only the last [`SHARE_STATS_JOBS`] jobs are kept. Session-wide counts
+    /// are recorded separately, like for
+    /// [`record_device_share`](Self::record_device_share).
+    pub fn record_job_share(
+        &mut self,
+        job_id: &str,
+        rejection: Option<RejectReason>,
+    ) -> ShareCounts {
+        let index = match self.jobs.iter().position(|(id, _)| id == job_id) {
+            Some(index) => index,
+            None => {
+                if self.jobs.len() >= SHARE_STATS_JOBS {
+                    self.jobs.pop_front();
+                }
+                self.jobs
+                    .push_back((job_id.to_string(), ShareCounts::default()));
+                self.jobs.len() - 1
+            }
+        };
+        let counts = &mut self.jobs[index].1;
+        counts.record(rejection);
+        *counts
+    }
+
+    /// Get the share counts of job `job_id`, if shares were submitted for it
+    /// recently
+    pub fn job(&self, job_id: &str) -> Option<&ShareCounts> {
+        self.jobs
+            .iter()
+            .find(|(id, _)| id == job_id)
+            .map(|(_, counts)| counts)
+    }
+
+    /// Record the time between submitting a share to `pool` and its acknowledgment
+    pub fn record_submit_latency(&mut self, pool: &str, latency: Duration) {
+        self.submit_latency
+            .entry(pool.to_string())
+            .or_default()
+            .record(latency);
+    }
+
+    /// Get the submit latency samples of a pool
+    pub fn submit_latency(&self, pool: &str) -> Option<&LatencyTracker> {
+        self.submit_latency.get(pool)
+    }
+
+    /// Time since the last share was acknowledged, accepted or rejected
+    pub fn last_share_age(&self) -> Option<Duration> {
+        self.recent.back().map(|outcome| outcome.at.elapsed())
+    }
+
+    /// Get the session records
+    pub fn records(&self) -> &SessionRecords {
+        &self.records
+    }
+
+    /// Take a snapshot of the current session
+    pub fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            started_at: self.started_at,
+            uptime: self.started_at.elapsed(),
+            shares: self.shares,
+            jobs: self.jobs.clone(),
+            submit_failures: self.submit_failures,
+            accepted_difficulty: self.accepted_difficulty,
+            current_accept_streak: self.current_accept_streak,
+            records: self.records.clone(),
+            submit_latency: self
+                .submit_latency
+                .iter()
+                .filter_map(|(pool, tracker)| Some((pool.clone(), tracker.percentiles()?)))
+                .collect(),
+            devices: self.devices.clone(),
+            lock_contention: ContentionSnapshot::default(),
+            stale_results_dropped: 0,
+            difficulty_multiplier: self.difficulty_multiplier,
+            #[cfg(feature = "profiling")]
+            profile: profiling::snapshot(),
+        }
+    }
+
+    /// Summarize share activity over the trailing `window`
+    ///
+    /// The window is capped at [`RECENT_SHARE_HORIZON`].
+    pub fn summary(&self, window: Duration) -> StatsSummary {
+        self.summary_at(Instant::now(), window)
+    }
+
+    fn summary_at(&self, now: Instant, window: Duration) -> StatsSummary {
+        let window = window.min(RECENT_SHARE_HORIZON);
+        let in_window = self
+            .recent
+            .iter()
+            .filter(|o| now.duration_since(o.at) <= window);
+
+        let mut accepted = 0u64;
+        let mut rejected = 0u64;
+        let mut accepted_difficulty = 0.0;
+        let mut latency_total = Duration::ZERO;
+        let mut latency_samples = 0u32;
+
+        for outcome in in_window {
+            if outcome.accepted {
+                accepted += 1;
+                accepted_difficulty += outcome.difficulty.unwrap_or(0.0);
+            } else {
+                rejected += 1;
+            }
+            if let Some(latency) = outcome.latency {
+                latency_total += latency;
+                latency_samples += 1;
+            }
+        }
+
+        let total = accepted + rejected;
+        let window_secs = window.as_secs_f64();
+
+        StatsSummary {
+            window,
+            hashrate: if window_secs > 0.0 {
+                accepted_difficulty * HASHES_PER_DIFF1_SHARE
+                    / self.difficulty_multiplier
+                    / window_secs
+            } else {
+                0.0
+            },
+            shares_accepted: accepted,
+            shares_rejected: rejected,
+            reject_percent: if total > 0 {
+                rejected as f64 * 100.0 / total as f64
+            } else {
+                0.0
+            },
+            avg_accept_latency: (latency_samples > 0).then(|| latency_total / latency_samples),
+        }
+    }
+
+    /// Estimated hashrates over each of the [`HASHRATE_WINDOWS`]
+    ///
+    /// Until a window has passed since the session started, its estimate
+    /// covers the time so far.
+    pub fn hashrate(&self) -> Hashrate {
+        self.hashrate_at(Instant::now())
+    }
+
+    fn hashrate_at(&self, now: Instant) -> Hashrate {
+        let uptime = now.duration_since(self.started_at);
+        let [one_minute, five_minutes, fifteen_minutes] =
+            HASHRATE_WINDOWS.map(|window| self.summary_at(now, window.min(uptime)).hashrate);
+        Hashrate {
+            one_minute,
+            five_minutes,
+            fifteen_minutes,
+        }
+    }
+}
+
+/// Network difficulty encoded by a block header's compact `nbits` target
+///
+/// Relative to the difficulty 1 target `0x1d00ffff`. Returns `None` for
+/// malformed or zero targets.
+pub fn network_difficulty(nbits: &str) -> Option<f64> {
+    let bits = u32::from_str_radix(nbits, 16).ok()?;
+    let exponent = (bits >> 24) as i32;
+    let mantissa = f64::from(bits & 0x00ff_ffff);
+    if mantissa == 0.0 {
+        return None;
+    }
+    Some(f64::from(0xffff) / mantissa * 256f64.powi(0x1d - exponent))
+}
+
+/// Source of the block reward and coin price used for earnings estimates
+///
+/// Implemented by the application, e.g. backed by a price API, so estimates
+/// follow reward halvings and market prices without the client knowing them.
+pub trait RewardFeed: Send + Sync {
+    /// Current block reward in coins, including expected fees
+    fn block_reward(&self) -> f64;
+
+    /// Current price of one coin in the display currency, if known
+    fn coin_price(&self) -> Option<f64> {
+        None
+    }
+}
+
+/// Fixed block reward and price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedReward {
+    pub block_reward: f64,
+    pub coin_price: Option<f64>,
+}
+
+impl RewardFeed for FixedReward {
+    fn block_reward(&self) -> f64 {
+        self.block_reward
+    }
+
+    fn coin_price(&self) -> Option<f64> {
+        self.coin_price
+    }
+}
+
+/// Projected revenue at the current hashrate and network difficulty
+///
+/// Assumes proportional payout of the full block reward, which is what PPS
+/// and long-run PPLNS earnings converge to before pool fees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EarningsEstimate {
+    /// Hashrate in H/s the estimate is based on
+    pub hashrate: f64,
+    pub network_difficulty: f64,
+    pub coins_per_day: f64,
+    /// `coins_per_day` in the feed's currency, if a price is known
+    pub value_per_day: Option<f64>,
+}
+
+/// Estimate earnings from a hashrate in H/s and the current job's `nbits`
+pub fn estimate_earnings(
+    hashrate: f64,
+    nbits: &str,
+    feed: &dyn RewardFeed,
+) -> Option<EarningsEstimate> {
+    let network_difficulty = network_difficulty(nbits)?;
+    let secs_per_day = 24.0 * 60.0 * 60.0;
+    let blocks_per_day = hashrate * secs_per_day / (network_difficulty * HASHES_PER_DIFF1_SHARE);
+    let coins_per_day = blocks_per_day * feed.block_reward();
+    Some(EarningsEstimate {
+        hashrate,
+        network_difficulty,
+        coins_per_day,
+        value_per_day: feed.coin_price().map(|price| coins_per_day * price),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_stats_per_job() {
+        let mut stats = SessionStats::new();
+        stats.record_job_share("a", None);
+        stats.record_job_share("a", Some(RejectReason::Duplicate));
+        let b = stats.record_job_share("b", Some(RejectReason::Stale));
+        assert_eq!((b.rejected, b.stale), (1, 1));
+        assert_eq!(stats.job("a").unwrap().duplicate, 1);
+
+        for job in 0..SHARE_STATS_JOBS {
+            stats.record_job_share(&job.to_string(), None);
+        }
+        assert_eq!(stats.snapshot().jobs.len(), SHARE_STATS_JOBS);
+        assert!(stats.job("a").is_none());
+        // Session-wide counts are recorded separately
+        assert_eq!(stats.snapshot().shares, ShareCounts::default());
+    }
+
+    #[test]
+    fn test_share_counts_by_reason() {
+        let mut stats = SessionStats::new();
+        stats.record_accepted(None, Duration::from_millis(10));
+        stats.record_rejected(RejectReason::Stale);
+        stats.record_rejected(RejectReason::Duplicate);
+        stats.record_rejected(RejectReason::Other);
+        assert_eq!(
+            stats.snapshot().shares,
+            ShareCounts {
+                accepted: 1,
+                rejected: 3,
+                stale: 1,
+                duplicate: 1,
+                other: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_best_share_difficulty() {
+        let mut stats = SessionStats::new();
+        stats.record_accepted(Some(2.0), Duration::from_millis(50));
+        assert_eq!(stats.records().best_share_difficulty, None);
+        stats.record_share_difficulty(3.5);
+        stats.record_share_difficulty(81.0);
+        stats.record_share_difficulty(4.0);
+
+        assert_eq!(stats.records().best_share_difficulty, Some(81.0));
+    }
+
+    #[test]
+    fn test_submit_failures() {
+        let mut stats = SessionStats::new();
+        stats.record_accepted(None, Duration::from_millis(10));
+        stats.record_submit_failed();
+        stats.record_accepted(None, Duration::from_millis(10));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.submit_failures, 1);
+        assert_eq!(snapshot.shares.rejected, 0);
+        assert_eq!(snapshot.current_accept_streak, 2);
+        assert_eq!(stats.summary(RECENT_SHARE_HORIZON).reject_percent, 0.0);
+    }
+
+    #[test]
+    fn test_accept_streak() {
+        let mut stats = SessionStats::new();
+        stats.record_accepted(None, Duration::from_millis(10));
+        stats.record_accepted(None, Duration::from_millis(10));
+        stats.record_accepted(None, Duration::from_millis(10));
+        stats.record_rejected(RejectReason::Other);
+        stats.record_accepted(None, Duration::from_millis(10));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.shares.accepted, 4);
+        assert_eq!(snapshot.shares.rejected, 1);
+        assert_eq!(snapshot.current_accept_streak, 1);
+        assert_eq!(snapshot.records.longest_accept_streak, 3);
+    }
+
+    #[test]
+    fn test_fastest_accept_latency() {
+        let mut stats = SessionStats::new();
+        assert_eq!(stats.records().fastest_accept_latency, None);
+
+        stats.record_accepted(None, Duration::from_millis(120));
+        stats.record_accepted(None, Duration::from_millis(35));
+        stats.record_accepted(None, Duration::from_millis(80));
+
+        assert_eq!(
+            stats.records().fastest_accept_latency,
+            Some(Duration::from_millis(35))
+        );
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut stats = SessionStats::new();
+        for ms in 1..=100 {
+            stats.record_submit_latency("pool-a:3333", Duration::from_millis(ms));
+        }
+        stats.record_submit_latency("pool-b:3333", Duration::from_millis(7));
+
+        let snapshot = stats.snapshot();
+        let pool_a = snapshot.submit_latency["pool-a:3333"];
+        assert_eq!(pool_a.p50, Duration::from_millis(50));
+        assert_eq!(pool_a.p90, Duration::from_millis(90));
+        assert_eq!(pool_a.p99, Duration::from_millis(99));
+        assert_eq!(pool_a.max, Duration::from_millis(100));
+        assert_eq!(pool_a.samples, 100);
+        assert_eq!(
+            snapshot.submit_latency["pool-b:3333"].p99,
+            Duration::from_millis(7)
+        );
+
+        let mut tracker = LatencyTracker::default();
+        assert_eq!(tracker.percentile(50.0), None);
+        for _ in 0..LATENCY_SAMPLE_WINDOW + 10 {
+            tracker.record(Duration::from_millis(1));
+        }
+        assert_eq!(tracker.len(), LATENCY_SAMPLE_WINDOW);
+    }
+
+    #[test]
+    fn test_latency_sla() {
+        let sla = LatencySla {
+            percentile: 90.0,
+            threshold: Duration::from_millis(500),
+            min_samples: 5,
+        };
+        let mut tracker = LatencyTracker::default();
+        for _ in 0..4 {
+            tracker.record(Duration::from_secs(2));
+        }
+        // Not enough samples yet
+        assert!(!sla.is_violated(&tracker));
+        tracker.record(Duration::from_secs(2));
+        assert!(sla.is_violated(&tracker));
+
+        let mut fast = LatencyTracker::default();
+        for _ in 0..10 {
+            fast.record(Duration::from_millis(100));
+        }
+        assert!(!sla.is_violated(&fast));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut stats = SessionStats::new();
+        stats.record_accepted(Some(1.0), Duration::from_millis(100));
+        stats.record_accepted(Some(3.0), Duration::from_millis(300));
+        stats.record_rejected(RejectReason::Other);
+        stats.record_rejected(RejectReason::Other);
+
+        let summary = stats.summary(Duration::from_secs(60));
+        assert_eq!(summary.window, Duration::from_secs(60));
+        assert_eq!(summary.shares_accepted, 2);
+        assert_eq!(summary.shares_rejected, 2);
+        assert_eq!(summary.reject_percent, 50.0);
+        assert_eq!(summary.avg_accept_latency, Some(Duration::from_millis(200)));
+        assert_eq!(summary.hashrate, 4.0 * HASHES_PER_DIFF1_SHARE / 60.0);
+
+        let empty = SessionStats::new().summary(Duration::from_secs(60));
+        assert_eq!(empty.hashrate, 0.0);
+        assert_eq!(empty.reject_percent, 0.0);
+        assert_eq!(empty.avg_accept_latency, None);
+    }
+
+    #[test]
+    fn test_hashrate_windows() {
+        // Instants before now may predate the clock's origin, count forward
+        let start = Instant::now();
+        let now = start + Duration::from_secs(20 * 60);
+        let ago = |secs| now - Duration::from_secs(secs);
+        let accepted = |at, difficulty| ShareOutcome {
+            at,
+            accepted: true,
+            difficulty: Some(difficulty),
+            latency: None,
+        };
+        let mut stats = SessionStats::new();
+        stats.started_at = start;
+        stats.push_recent(accepted(ago(16 * 60), 100.0));
+        stats.push_recent(accepted(ago(10 * 60), 3.0));
+        stats.push_recent(accepted(ago(2 * 60), 2.0));
+        stats.push_recent(accepted(ago(30), 1.0));
+
+        let per_diff = |difficulty: f64, secs: f64| difficulty * HASHES_PER_DIFF1_SHARE / secs;
+        assert_eq!(
+            stats.hashrate_at(now),
+            Hashrate {
+                one_minute: per_diff(1.0, 60.0),
+                five_minutes: per_diff(3.0, 300.0),
+                fifteen_minutes: per_diff(6.0, 900.0),
+            }
+        );
+
+        // Shortly after starting, the estimate covers the time so far
+        let mut stats = SessionStats::new();
+        stats.started_at = ago(30);
+        stats.push_recent(accepted(ago(10), 1.0));
+        assert_eq!(stats.hashrate_at(now).fifteen_minutes, per_diff(1.0, 30.0));
+        assert_eq!(SessionStats::new().hashrate(), Hashrate::default());
+    }
+
+    #[test]
+    fn test_device_attribution() {
+        let mut stats = SessionStats::new();
+        stats.record_device_share("asic-0", true, Some(2.0));
+        stats.record_device_share("asic-0", true, Some(4.0));
+        stats.record_device_share("asic-1", false, Some(4.0));
+
+        let asic0 = stats.device("asic-0").unwrap();
+        assert_eq!(asic0.shares_accepted, 2);
+        assert_eq!(asic0.accepted_difficulty, 6.0);
+        assert_eq!(
+            asic0.hashrate(Duration::from_secs(2)),
+            3.0 * HASHES_PER_DIFF1_SHARE
+        );
+        assert_eq!(stats.device("asic-1").unwrap().shares_rejected, 1);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.devices.len(), 2);
+        // Session-wide counts are recorded separately
+        assert_eq!(snapshot.shares.accepted, 0);
+    }
+
+    #[test]
+    fn test_network_difficulty() {
+        assert_eq!(network_difficulty("1d00ffff"), Some(1.0));
+        let difficulty = network_difficulty("1b0404cb").unwrap();
+        assert!((difficulty - 16_307.420_938_523_983).abs() < 1e-6);
+        assert_eq!(network_difficulty("1d000000"), None);
+        assert_eq!(network_difficulty("zz"), None);
+    }
+
+    #[test]
+    fn test_estimate_earnings() {
+        let feed = FixedReward {
+            block_reward: 3.125,
+            coin_price: Some(100_000.0),
+        };
+        // One block per day at difficulty 1
+        let hashrate = HASHES_PER_DIFF1_SHARE / 86_400.0;
+        let estimate = estimate_earnings(hashrate, "1d00ffff", &feed).unwrap();
+        assert!((estimate.coins_per_day - 3.125).abs() < 1e-9);
+        assert!((estimate.value_per_day.unwrap() - 312_500.0).abs() < 1e-6);
+
+        let no_price = FixedReward {
+            coin_price: None,
+            ..feed
+        };
+        let estimate = estimate_earnings(hashrate, "1d00ffff", &no_price).unwrap();
+        assert_eq!(estimate.value_per_day, None);
+    }
+}
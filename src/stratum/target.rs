@@ -0,0 +1,278 @@
+#[cfg(feature = "profiling")]
+use crate::stratum::profiling::{Scope, Timer};
+use std::fmt;
+use uints::{U256, U512};
+
+// The generated arithmetic trips lints we can't fix at the call site
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod uints {
+    use uint::construct_uint;
+
+    construct_uint! {
+        pub(super) struct U256(4);
+    }
+
+    construct_uint! {
+        /// Room for the difficulty 1 target shifted by a fractional difficulty
+        pub(super) struct U512(8);
+    }
+}
+
+impl U256 {
+    fn to_f64(self) -> f64 {
+        let bits = self.bits();
+        if bits <= 64 {
+            return self.low_u64() as f64;
+        }
+        let shift = bits - 64;
+        (self >> shift).low_u64() as f64 * 2f64.powi(shift as i32)
+    }
+}
+
+impl U512 {
+    fn widen(value: U256) -> Self {
+        let mut bytes = [0u8; 64];
+        value.to_big_endian(&mut bytes[32..]);
+        Self::from_big_endian(&bytes)
+    }
+
+    /// Narrow to 256 bits, saturating at the largest value
+    fn saturate(self) -> U256 {
+        let mut bytes = [0u8; 64];
+        self.to_big_endian(&mut bytes);
+        if bytes[..32].iter().any(|byte| *byte != 0) {
+            return U256::MAX;
+        }
+        U256::from_big_endian(&bytes[32..])
+    }
+}
+
+/// 256-bit share or block target; hashes at or below it are valid
+///
+/// Targets compare numerically, a smaller target being harder to meet.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Target(U256);
+
+impl Target {
+    /// Target of difficulty 1, compact `0x1d00ffff`
+    pub const DIFF1: Target = Target(U256([0, 0, 0, 0x0000_0000_ffff_0000]));
+
+    /// Easiest possible target
+    pub const MAX: Target = Target(U256::MAX);
+
+    /// Target of a pool difficulty, `DIFF1 / difficulty` without rounding
+    /// the difficulty first
+    ///
+    /// Difficulties at or below zero saturate to [`Target::MAX`].
+    pub fn from_difficulty(difficulty: f64) -> Self {
+        #[cfg(feature = "profiling")]
+        let _profile = Timer::enter(Scope::Target);
+        if difficulty.is_nan() || difficulty <= 0.0 {
+            return Self::MAX;
+        }
+        if difficulty.is_infinite() {
+            return Self(U256::zero());
+        }
+
+        // difficulty = mantissa * 2^exponent exactly
+        let bits = difficulty.to_bits();
+        let biased = ((bits >> 52) & 0x7ff) as i32;
+        let fraction = bits & ((1 << 52) - 1);
+        let (mantissa, exponent) = if biased == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | (1 << 52), biased - 1075)
+        };
+
+        let diff1 = U512::widen(Self::DIFF1.0);
+        let quotient = if exponent >= 0 {
+            if exponent > 256 {
+                return Self(U256::zero());
+            }
+            diff1 / (U512::from(mantissa) << exponent as usize)
+        } else {
+            let shift = -exponent as usize;
+            // Past this the target exceeds 256 bits for any mantissa
+            if shift > 512 - 225 {
+                return Self::MAX;
+            }
+            (diff1 << shift) / U512::from(mantissa)
+        };
+        Self(quotient.saturate())
+    }
+
+    /// Difficulty of this target relative to [`Target::DIFF1`]
+    pub fn difficulty(&self) -> f64 {
+        if self.0.is_zero() {
+            return f64::INFINITY;
+        }
+        Self::DIFF1.0.to_f64() / self.0.to_f64()
+    }
+
+    /// Decode a compact target as found in a block header's `nbits`
+    ///
+    /// Returns `None` for negative or overflowing encodings.
+    pub fn from_nbits(nbits: u32) -> Option<Self> {
+        let size = (nbits >> 24) as usize;
+        let word = nbits & 0x007f_ffff;
+        if nbits & 0x0080_0000 != 0 && word != 0 {
+            return None;
+        }
+        if word != 0 && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32)) {
+            return None;
+        }
+        let value = if size <= 3 {
+            U256::from(word >> (8 * (3 - size)))
+        } else {
+            U256::from(word) << (8 * (size - 3))
+        };
+        Some(Self(value))
+    }
+
+    /// Encode as a compact target, dropping all but the 3 most significant bytes
+    pub fn to_nbits(&self) -> u32 {
+        let mut size = self.0.bits().div_ceil(8);
+        let mut word = if size <= 3 {
+            (self.0.low_u64() << (8 * (3 - size))) as u32
+        } else {
+            (self.0 >> (8 * (size - 3))).low_u64() as u32
+        };
+        // The top bit of the mantissa is a sign bit
+        if word & 0x0080_0000 != 0 {
+            word >>= 8;
+            size += 1;
+        }
+        word | (size as u32) << 24
+    }
+
+    /// Target from its big-endian bytes
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        Self(U256::from_big_endian(bytes))
+    }
+
+    /// Big-endian bytes of the target
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        self.0.to_big_endian(&mut bytes);
+        bytes
+    }
+
+    /// Target from 64 big-endian hex digits, as in `MiningTarget::target`
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let bytes: [u8; 32] = hex::decode(hex).ok()?.try_into().ok()?;
+        Some(Self::from_bytes(&bytes))
+    }
+
+    /// 64 big-endian hex digits
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    /// Whether a big-endian hash is at or below the target
+    pub fn is_met_by(&self, hash: &[u8; 32]) -> bool {
+        U256::from_big_endian(hash) <= self.0
+    }
+}
+
+impl fmt::Debug for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Target({})", self.to_hex())
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(target: Target) -> String {
+        target.to_hex()
+    }
+
+    #[test]
+    fn test_from_difficulty() {
+        assert_eq!(Target::from_difficulty(1.0), Target::DIFF1);
+        assert_eq!(
+            hex(Target::from_difficulty(2.0)),
+            "000000007fff8000000000000000000000000000000000000000000000000000"
+        );
+        // Fractional difficulties ease the target beyond difficulty 1
+        assert_eq!(
+            hex(Target::from_difficulty(0.5)),
+            "00000001fffe0000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(
+            hex(Target::from_difficulty(65536.0)),
+            "000000000000ffff000000000000000000000000000000000000000000000000"
+        );
+        // 0xffff / 3 = 0x5555, the remaining digits keep dividing
+        assert_eq!(
+            hex(Target::from_difficulty(3.0)),
+            "0000000055550000000000000000000000000000000000000000000000000000"
+        );
+        assert!(Target::from_difficulty(1.5) < Target::DIFF1);
+        assert!(Target::from_difficulty(1.5) > Target::from_difficulty(2.0));
+
+        assert_eq!(Target::from_difficulty(0.0), Target::MAX);
+        assert_eq!(Target::from_difficulty(1e-80), Target::MAX);
+        assert_eq!(
+            Target::from_difficulty(f64::INFINITY).difficulty(),
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn test_difficulty_round_trip() {
+        for difficulty in [0.001, 1.0, 1.5, 1234.5678, 65536.0, 1e12, 8.2e13] {
+            let target = Target::from_difficulty(difficulty);
+            let error = (target.difficulty() - difficulty).abs() / difficulty;
+            assert!(
+                error < 1e-9,
+                "{difficulty} came back as {}",
+                target.difficulty()
+            );
+        }
+    }
+
+    #[test]
+    fn test_nbits() {
+        assert_eq!(Target::from_nbits(0x1d00ffff), Some(Target::DIFF1));
+        assert_eq!(Target::DIFF1.to_nbits(), 0x1d00ffff);
+
+        let target = Target::from_nbits(0x1b0404cb).unwrap();
+        assert_eq!(
+            hex(target),
+            "00000000000404cb000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(target.to_nbits(), 0x1b0404cb);
+        assert!((target.difficulty() - 16_307.420_938_523_983).abs() < 1e-6);
+
+        // A leading byte with the sign bit set moves to the next byte
+        let target = Target::from_bytes(&{
+            let mut bytes = [0u8; 32];
+            bytes[31] = 0x80;
+            bytes
+        });
+        assert_eq!(target.to_nbits(), 0x02008000);
+        assert_eq!(Target::from_nbits(0x02008000), Some(target));
+
+        assert_eq!(Target::from_nbits(0x04923456), None);
+        assert_eq!(Target::from_nbits(0xff123456), None);
+    }
+
+    #[test]
+    fn test_is_met_by() {
+        let target = Target::DIFF1;
+        let mut hash = target.to_bytes();
+        assert!(target.is_met_by(&hash));
+        hash[31] = 1;
+        assert!(!target.is_met_by(&hash));
+        assert_eq!(Target::from_hex(&target.to_hex()), Some(target));
+        assert_eq!(Target::from_hex("abcd"), None);
+    }
+}
@@ -0,0 +1,619 @@
+//! Helpers for testing miners and clients against mock pools
+//!
+//! Enabled by the `testing` feature.
+
+use crate::stratum::error::StratumError;
+use crate::stratum::v1::connection::{ConnectionConfig, Reader, Transport, Writer};
+use crate::stratum::v1::protocol::{
+    MINING_AUTHORIZE, MINING_CONFIGURE, MINING_NOTIFY, MINING_SET_DIFFICULTY, MINING_SUBMIT,
+    MINING_SUBSCRIBE,
+};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::mpsc;
+
+/// Bytes buffered in each direction of a mock pool connection
+const MOCK_POOL_BUFFER: usize = 64 * 1024;
+
+/// Expectations on the `mining.submit` requests a mock pool receives
+///
+/// Every checked submit must name a worker and carry a hex extranonce2,
+/// ntime and nonce, plus whatever was configured here. Failures are
+/// collected rather than panicking inside the pool's task, so the test
+/// calls [`verify`](Self::verify) at the end to fail loudly:
+///
+/// ```
+/// # use rust_stratum::stratum::testing::SubmitExpectations;
+/// # use serde_json::json;
+/// let expectations = SubmitExpectations::new()
+///     .worker("wallet.rig1")
+///     .extranonce2_size(4)
+///     .job("job1");
+/// expectations.check(&[
+///     json!("wallet.rig1"),
+///     json!("job1"),
+///     json!("00000000"),
+///     json!("504e86b9"),
+///     json!("00000000"),
+/// ]);
+/// expectations.verify();
+/// ```
+#[derive(Debug, Default)]
+pub struct SubmitExpectations {
+    worker: Option<String>,
+    extranonce2_size: Option<usize>,
+    jobs: HashSet<String>,
+    checked: Mutex<usize>,
+    failures: Mutex<Vec<String>>,
+}
+
+impl SubmitExpectations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect submits from this worker, instead of any non-empty name
+    pub fn worker(mut self, worker: impl Into<String>) -> Self {
+        self.worker = Some(worker.into());
+        self
+    }
+
+    /// Expect extranonce2 values of `size` bytes
+    pub fn extranonce2_size(mut self, size: usize) -> Self {
+        self.extranonce2_size = Some(size);
+        self
+    }
+
+    /// Expect submits only for the given job ids, once any are added
+    pub fn job(mut self, job_id: impl Into<String>) -> Self {
+        self.jobs.insert(job_id.into());
+        self
+    }
+
+    /// Check a JSON-RPC request the pool received, ignoring other methods
+    pub fn check_request(&self, request: &Value) {
+        if request["method"] != MINING_SUBMIT {
+            return;
+        }
+        match request["params"].as_array() {
+            Some(params) => self.check(params),
+            None => self.fail(format!("{} without params: {}", MINING_SUBMIT, request)),
+        }
+    }
+
+    /// Check the params of a `mining.submit`: worker, job id, extranonce2,
+    /// ntime, nonce and optional version bits
+    pub fn check(&self, params: &[Value]) {
+        *self.checked.lock().unwrap() += 1;
+        let describe = Value::from(params.to_vec());
+        if !(5..=6).contains(&params.len()) {
+            self.fail(format!("Expected 5 or 6 params, got {}", describe));
+            return;
+        }
+        let param = |index: usize, name: &str| match params[index].as_str() {
+            Some(value) => Some(value),
+            None => {
+                self.fail(format!("{} is not a string in {}", name, describe));
+                None
+            }
+        };
+
+        match (param(0, "Worker"), &self.worker) {
+            (Some(""), _) => self.fail(format!("No worker name in {}", describe)),
+            (Some(worker), Some(expected)) if worker != expected => {
+                self.fail(format!("Expected worker {}, got {}", expected, describe))
+            }
+            _ => {}
+        }
+        if let Some(job_id) = param(1, "Job id") {
+            if !self.jobs.is_empty() && !self.jobs.contains(job_id) {
+                self.fail(format!("Unexpected job id in {}", describe));
+            }
+        }
+        if let Some(extranonce2) = param(2, "Extranonce2") {
+            self.check_hex("Extranonce2", extranonce2, self.extranonce2_size, &describe);
+        }
+        if let Some(ntime) = param(3, "Ntime") {
+            self.check_hex("Ntime", ntime, Some(4), &describe);
+        }
+        if let Some(nonce) = param(4, "Nonce") {
+            self.check_hex("Nonce", nonce, Some(4), &describe);
+        }
+        if params.len() == 6 {
+            if let Some(version_bits) = param(5, "Version bits") {
+                self.check_hex("Version bits", version_bits, Some(4), &describe);
+            }
+        }
+    }
+
+    fn check_hex(&self, name: &str, value: &str, bytes: Option<usize>, describe: &Value) {
+        if value.is_empty() || hex::decode(value).is_err() {
+            self.fail(format!("{} is not hex in {}", name, describe));
+        } else if let Some(bytes) = bytes.filter(|bytes| value.len() != bytes * 2) {
+            self.fail(format!("{} is not {} bytes in {}", name, bytes, describe));
+        }
+    }
+
+    fn fail(&self, failure: String) {
+        self.failures.lock().unwrap().push(failure);
+    }
+
+    /// Number of submits checked so far
+    pub fn checked(&self) -> usize {
+        *self.checked.lock().unwrap()
+    }
+
+    /// Failed expectations so far
+    pub fn failures(&self) -> Vec<String> {
+        self.failures.lock().unwrap().clone()
+    }
+
+    /// Panic listing every failed expectation, if any
+    pub fn verify(&self) {
+        let failures = self.failures();
+        assert!(
+            failures.is_empty(),
+            "{} of {} submits failed expectations:\n{}",
+            failures.len(),
+            self.checked(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// How a [`MockPool`] answers a `mining.submit`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShareVerdict {
+    /// Answer `true`
+    Accept,
+    /// Answer `false`, without a reason
+    Reject,
+    /// Answer with a `[code, message, null]` error, e.g. 21 for stale shares
+    Error(i64, String),
+}
+
+/// Response of a mock pool to `request`, `None` for notifications and
+/// responses from the client
+///
+/// `subscription` and `verdict` are only called for the requests they answer.
+/// Shared by [`MockPool`] and
+/// [`TestPoolServer`](crate::stratum::testpool::TestPoolServer).
+pub(crate) fn respond(
+    request: &Value,
+    subscription: impl FnOnce() -> Value,
+    authorized: bool,
+    verdict: impl FnOnce() -> ShareVerdict,
+) -> Option<Value> {
+    let id = request.get("id").filter(|id| !id.is_null())?;
+    let (result, error) = match request.get("method")?.as_str().unwrap_or_default() {
+        MINING_SUBSCRIBE => (subscription(), Value::Null),
+        MINING_AUTHORIZE => (json!(authorized), Value::Null),
+        MINING_SUBMIT => match verdict() {
+            ShareVerdict::Accept => (json!(true), Value::Null),
+            ShareVerdict::Reject => (json!(false), Value::Null),
+            ShareVerdict::Error(code, message) => (Value::Null, json!([code, message, null])),
+        },
+        MINING_CONFIGURE => (json!({}), Value::Null),
+        _ => (Value::Null, json!([20, "Unsupported method", null])),
+    };
+    Some(json!({"id": id, "result": result, "error": error}))
+}
+
+/// Stratum V1 pool speaking to a client over in-memory streams
+///
+/// The pool is the [`Transport`] of the client's connection, so no socket is
+/// involved; every connection the client opens, reconnects included, is
+/// served by the pool. Requests are answered as scripted, and jobs,
+/// difficulty changes and disconnects are pushed by the test:
+///
+/// ```
+/// # use rust_stratum::stratum::testing::{MockPool, ShareVerdict};
+/// # use rust_stratum::stratum::prelude::*;
+/// # use rust_stratum::stratum::v1::jobs::TestMiner;
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), StratumError> {
+/// let pool = MockPool::new();
+/// let mut client = StratumV1Client::with_connection_config(
+///     "mock".into(),
+///     0,
+///     ConnectionConfig::with_transport(pool.clone()),
+///     TestMiner,
+/// )
+/// .await?;
+/// client.subscribe().await?;
+/// client.authorize("wallet.rig1", "x").await?;
+///
+/// pool.set_difficulty(1.0);
+/// pool.notify(MockPool::job("job1"));
+/// pool.push_verdict(ShareVerdict::Error(21, "Stale share".into()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockPool {
+    inner: Arc<MockPoolState>,
+}
+
+#[derive(Debug, Default)]
+struct MockPoolState {
+    subscribe_result: Mutex<Option<Value>>,
+    unauthorized: Mutex<bool>,
+    verdicts: Mutex<VecDeque<ShareVerdict>>,
+    expectations: Mutex<Option<Arc<SubmitExpectations>>>,
+    /// Results of methods answered as scripted, by method
+    results: Mutex<HashMap<String, Value>>,
+    /// Time to wait before answering, by method
+    delays: Mutex<HashMap<String, Duration>>,
+    /// Connection no longer answering requests, counting from 1
+    silenced: Mutex<Option<usize>>,
+    requests: Mutex<Vec<Value>>,
+    connections: Mutex<usize>,
+    /// Messages to the connected client, `None` to drop the connection
+    client: Mutex<Option<mpsc::UnboundedSender<Option<Value>>>>,
+}
+
+impl MockPool {
+    /// Pool accepting every worker and share
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `mining.subscribe` with `result` instead of a subscription with
+    /// extranonce1 `f000000f` and a 4 byte extranonce2
+    pub fn subscribe_result(self, result: Value) -> Self {
+        *self.inner.subscribe_result.lock().unwrap() = Some(result);
+        self
+    }
+
+    /// Answer `mining.authorize` with `false`
+    pub fn reject_workers(self) -> Self {
+        *self.inner.unauthorized.lock().unwrap() = true;
+        self
+    }
+
+    /// Check every submit against `expectations`
+    pub fn expect_submits(self, expectations: Arc<SubmitExpectations>) -> Self {
+        *self.inner.expectations.lock().unwrap() = Some(expectations);
+        self
+    }
+
+    /// Answer requests for `method` with `result`, e.g. calls of a protocol
+    /// extension
+    pub fn answer_with(self, method: &str, result: Value) -> Self {
+        self.inner
+            .results
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), result);
+        self
+    }
+
+    /// Wait `delay` before answering requests for `method`
+    ///
+    /// Messages to the client wait as well.
+    pub fn delay(self, method: &str, delay: Duration) -> Self {
+        self.inner
+            .delays
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), delay);
+        self
+    }
+
+    /// Answer the next submit not answered by an earlier verdict this way;
+    /// submits without a verdict are accepted
+    pub fn push_verdict(&self, verdict: ShareVerdict) {
+        self.inner.verdicts.lock().unwrap().push_back(verdict);
+    }
+
+    /// Send a notification to the connected client, returning whether one
+    /// is connected
+    pub fn send(&self, method: &str, params: Vec<Value>) -> bool {
+        self.push(json!({"id": null, "method": method, "params": params}))
+    }
+
+    /// Send a request to the connected client, returning whether one is
+    /// connected
+    ///
+    /// The client's response shows up in [`requests`](Self::requests).
+    pub fn request(&self, id: u64, method: &str, params: Vec<Value>) -> bool {
+        self.push(json!({"id": id, "method": method, "params": params}))
+    }
+
+    fn push(&self, message: Value) -> bool {
+        self.inner
+            .client
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|client| client.send(Some(message)).is_ok())
+    }
+
+    /// Send a `mining.notify` with `params`, see [`job`](Self::job)
+    pub fn notify(&self, params: Vec<Value>) -> bool {
+        self.send(MINING_NOTIFY, params)
+    }
+
+    /// Send a `mining.set_difficulty`
+    pub fn set_difficulty(&self, difficulty: f64) -> bool {
+        self.send(MINING_SET_DIFFICULTY, vec![json!(difficulty)])
+    }
+
+    /// Drop the connection to the client, as a pool restart would
+    pub fn disconnect(&self) {
+        if let Some(client) = self.inner.client.lock().unwrap().take() {
+            let _ = client.send(None);
+        }
+    }
+
+    /// Stop answering requests on the current connection, without closing it,
+    /// as a stalled pool would
+    ///
+    /// Connections the client opens later are answered again.
+    pub fn silence(&self) {
+        *self.inner.silenced.lock().unwrap() = Some(self.connections());
+    }
+
+    /// Messages received so far, oldest first: requests, and responses to
+    /// [`request`](Self::request)
+    pub fn requests(&self) -> Vec<Value> {
+        self.inner.requests.lock().unwrap().clone()
+    }
+
+    /// Number of connections the client opened so far
+    pub fn connections(&self) -> usize {
+        *self.inner.connections.lock().unwrap()
+    }
+
+    /// Params of a valid `mining.notify` for a clean job `job_id`
+    pub fn job(job_id: &str) -> Vec<Value> {
+        vec![
+            json!(job_id),
+            json!("00000000000000000000000000000000000000000000000000000000deadbeef"),
+            json!("01000000"),
+            json!("02000000"),
+            json!([]),
+            json!("20000000"),
+            json!("1d00ffff"),
+            json!("60509af9"),
+            json!(true),
+        ]
+    }
+
+    /// Answer to a request, `None` for notifications and responses from the
+    /// client
+    fn answer(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id").filter(|id| !id.is_null())?;
+        let method = request.get("method")?.as_str().unwrap_or_default();
+        if let Some(result) = self.inner.results.lock().unwrap().get(method) {
+            return Some(json!({"id": id, "result": result, "error": null}));
+        }
+        respond(
+            request,
+            || {
+                let result = self.inner.subscribe_result.lock().unwrap().clone();
+                result.unwrap_or_else(|| {
+                    json!([
+                        [["mining.set_difficulty", "1"], ["mining.notify", "1"]],
+                        "f000000f",
+                        4
+                    ])
+                })
+            },
+            !*self.inner.unauthorized.lock().unwrap(),
+            || {
+                if let Some(expectations) = &*self.inner.expectations.lock().unwrap() {
+                    expectations.check_request(request);
+                }
+                let verdict = self.inner.verdicts.lock().unwrap().pop_front();
+                verdict.unwrap_or(ShareVerdict::Accept)
+            },
+        )
+    }
+
+    /// Serve one connection until either side drops it
+    async fn serve(
+        self,
+        stream: DuplexStream,
+        connection: usize,
+        mut outgoing: mpsc::UnboundedReceiver<Option<Value>>,
+    ) {
+        let (read_half, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read_half).lines();
+        loop {
+            // Messages queued by the test go first, so a disconnect takes
+            // effect before requests sent after it are answered
+            let message = tokio::select! {
+                biased;
+                message = outgoing.recv() => match message {
+                    Some(Some(message)) => message,
+                    _ => break,
+                },
+                line = lines.next_line() => {
+                    let Ok(Some(line)) = line else { break };
+                    let Ok(request) = serde_json::from_str::<Value>(&line) else {
+                        continue;
+                    };
+                    self.inner.requests.lock().unwrap().push(request.clone());
+                    if *self.inner.silenced.lock().unwrap() == Some(connection) {
+                        continue;
+                    }
+                    let Some(response) = self.answer(&request) else {
+                        continue;
+                    };
+                    let delay = request["method"]
+                        .as_str()
+                        .and_then(|method| self.inner.delays.lock().unwrap().get(method).copied());
+                    if let Some(delay) = delay {
+                        tokio::time::sleep(delay).await;
+                    }
+                    response
+                }
+            };
+            if writer
+                .write_all(format!("{}\n", message).as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for MockPool {
+    async fn open(
+        &self,
+        _host: &str,
+        _port: u16,
+        _config: &ConnectionConfig,
+    ) -> Result<(Reader, Writer), StratumError> {
+        let (client, pool) = tokio::io::duplex(MOCK_POOL_BUFFER);
+        let (outgoing_tx, outgoing) = mpsc::unbounded_channel();
+        // A new connection replaces the previous one, like a reconnect does
+        if let Some(previous) = self.inner.client.lock().unwrap().replace(outgoing_tx) {
+            let _ = previous.send(None);
+        }
+        let connection = {
+            let mut connections = self.inner.connections.lock().unwrap();
+            *connections += 1;
+            *connections
+        };
+        tokio::spawn(self.clone().serve(pool, connection, outgoing));
+        let (reader, writer) = tokio::io::split(client);
+        Ok((Box::new(reader), Box::new(writer)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::error::StratumRpcError;
+    use crate::stratum::events::StratumEvent;
+    use crate::stratum::types::Share;
+    use crate::stratum::v1::jobs::TestMiner;
+    use crate::stratum::v1::StratumV1Client;
+    use crate::stratum::StratumClient;
+
+    #[test]
+    fn test_submit_expectations() {
+        let expectations = SubmitExpectations::new().extranonce2_size(4).job("job1");
+        expectations.check_request(&json!({
+            "id": 4,
+            "method": "mining.submit",
+            "params": ["rig1", "job1", "00000000", "504e86b9", "00000000", "1fffe000"],
+        }));
+        expectations.check_request(&json!({"id": 5, "method": "mining.authorize"}));
+        assert_eq!(expectations.checked(), 1);
+        expectations.verify();
+
+        expectations.check(&[
+            json!(""),
+            json!("job2"),
+            json!("0000"),
+            json!("xyz"),
+            json!("00000000"),
+        ]);
+        let failures = expectations.failures();
+        assert_eq!(failures.len(), 4, "{:?}", failures);
+        assert!(failures[0].starts_with("No worker name"));
+
+        expectations.check(&[json!("job1"), json!("00000000")]);
+        assert_eq!(expectations.failures().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_mock_pool_session() {
+        let expectations = Arc::new(SubmitExpectations::new().worker("rig1").extranonce2_size(4));
+        let pool = MockPool::new().expect_submits(expectations.clone());
+        let mut client = StratumV1Client::with_connection_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+            TestMiner,
+        )
+        .await
+        .unwrap();
+        assert_eq!(client.subscribe().await.unwrap().extranonce1, "f000000f");
+        assert!(client.authorize("rig1", "x").await.unwrap().authorized);
+
+        pool.set_difficulty(1e-10);
+        pool.notify(MockPool::job("job1"));
+        client.handle_notifications().await.unwrap();
+        client.handle_notifications().await.unwrap();
+        let job = client.get_current_job().await.unwrap().unwrap();
+        assert_eq!(job.job_id, "job1");
+
+        pool.push_verdict(ShareVerdict::Error(21, "Stale share".into()));
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        let err = client.submit_share(share.clone()).await.unwrap_err();
+        assert!(matches!(
+            err.root(),
+            StratumError::Rpc {
+                error: StratumRpcError::Stale,
+                ..
+            }
+        ));
+        let retry = Share {
+            nonce: "00000001".into(),
+            ..share
+        };
+        assert!(client.submit_share(retry).await.unwrap());
+        assert_eq!(expectations.checked(), 2);
+        expectations.verify();
+        let methods: Vec<_> = pool
+            .requests()
+            .iter()
+            .map(|request| request["method"].clone())
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                MINING_SUBSCRIBE,
+                MINING_AUTHORIZE,
+                MINING_SUBMIT,
+                MINING_SUBMIT
+            ]
+        );
+
+        // The client's reconnect is served by the pool as well
+        let mut events = client.events();
+        pool.disconnect();
+        client.reconnect().await.unwrap();
+        assert_eq!(pool.connections(), 2);
+        let closed = std::iter::from_fn(|| events.try_recv().ok())
+            .find_map(|event| match event {
+                StratumEvent::ConnectionClosedStats { stats, .. } => Some(stats),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(closed.messages_sent, 4);
+        assert_eq!(client.lifetime_connection_stats().await.connections, 2);
+        assert!(pool.set_difficulty(4.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "1 of 1 submits failed expectations")]
+    fn test_verify_panics() {
+        let expectations = SubmitExpectations::new().worker("rig1");
+        expectations.check(&[
+            json!("rig2"),
+            json!("job1"),
+            json!("00000000"),
+            json!("504e86b9"),
+            json!("00000000"),
+        ]);
+        expectations.verify();
+    }
+}
@@ -0,0 +1,510 @@
+//! Stratum V1 pool listening on a real socket, for integration tests and
+//! manual testing of miners
+//!
+//! Enabled by the `testing` feature. Where [`MockPool`] scripts a single
+//! in-memory connection, [`TestPoolServer`] runs on its own: it hands out
+//! jobs on a timer, changes difficulty on a schedule, judges shares by a
+//! policy and can be told to misbehave.
+//!
+//! [`MockPool`]: crate::stratum::testing::MockPool
+
+use crate::stratum::error::StratumError;
+use crate::stratum::testing::{respond, MockPool, ShareVerdict};
+use crate::stratum::v1::protocol::{MINING_NOTIFY, MINING_SET_DIFFICULTY, MINING_SUBSCRIBE};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How a [`TestPoolServer`] judges submitted shares
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcceptancePolicy {
+    AcceptAll,
+    RejectAll,
+    /// Answer every nth share with the verdict and accept the others, e.g.
+    /// `Every(10, ShareVerdict::Error(21, "Stale share".into()))`
+    Every(u64, ShareVerdict),
+}
+
+/// Misbehavior a [`TestPoolServer`] shows every connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Wait this long before answering any request
+    SlowResponses(Duration),
+    /// Answer the nth request of a connection, counting from 1, with a line
+    /// that is not JSON
+    MalformedResponse(u64),
+    /// Close the connection instead of answering the nth request
+    DisconnectAt(u64),
+}
+
+/// Settings of a [`TestPoolServer`], see [`TestPoolServer::builder`]
+#[derive(Debug, Clone)]
+pub struct TestPoolBuilder {
+    difficulty: f64,
+    schedule: Vec<(Duration, f64)>,
+    job_interval: Duration,
+    extranonce2_size: usize,
+    acceptance: AcceptancePolicy,
+    faults: Vec<Fault>,
+}
+
+impl Default for TestPoolBuilder {
+    fn default() -> Self {
+        Self {
+            difficulty: 1.0,
+            schedule: Vec::new(),
+            job_interval: Duration::from_secs(10),
+            extranonce2_size: 4,
+            acceptance: AcceptancePolicy::AcceptAll,
+            faults: Vec::new(),
+        }
+    }
+}
+
+impl TestPoolBuilder {
+    /// Difficulty sent after subscribing, 1 by default
+    pub fn difficulty(mut self, difficulty: f64) -> Self {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Difficulty changes, each sent the given time after a connection
+    /// subscribed
+    pub fn difficulty_schedule(mut self, schedule: Vec<(Duration, f64)>) -> Self {
+        self.schedule = schedule;
+        self.schedule.sort_by_key(|(after, _)| *after);
+        self
+    }
+
+    /// Time between new jobs, 10 seconds by default
+    pub fn job_interval(mut self, interval: Duration) -> Self {
+        self.job_interval = interval;
+        self
+    }
+
+    /// Extranonce2 size announced on subscribe, 4 bytes by default
+    pub fn extranonce2_size(mut self, size: usize) -> Self {
+        self.extranonce2_size = size;
+        self
+    }
+
+    pub fn acceptance(mut self, policy: AcceptancePolicy) -> Self {
+        self.acceptance = policy;
+        self
+    }
+
+    pub fn fault(mut self, fault: Fault) -> Self {
+        self.faults.push(fault);
+        self
+    }
+
+    /// Start listening on `addr`, e.g. `127.0.0.1:0` for any free port
+    pub async fn bind(self, addr: &str) -> Result<TestPoolServer, StratumError> {
+        let listener = TcpListener::bind(addr).await.map_err(|e| {
+            StratumError::Connection(format!("Failed to listen on {} - {}", addr, e))
+        })?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| StratumError::Connection(e.to_string()))?;
+        let state = Arc::new(TestPoolState {
+            acceptance: Mutex::new(self.acceptance.clone()),
+            config: self,
+            clients: Mutex::new(Vec::new()),
+            connections: AtomicU64::new(0),
+            jobs: AtomicU64::new(0),
+            submits: AtomicU64::new(0),
+            accepted: AtomicU64::new(0),
+        });
+        let accept = tokio::spawn(accept_loop(listener, state.clone()));
+        Ok(TestPoolServer {
+            addr,
+            state,
+            accept,
+        })
+    }
+}
+
+/// Stratum V1 pool for tests, serving any number of clients over TCP
+///
+/// ```
+/// # use rust_stratum::stratum::testpool::{AcceptancePolicy, Fault, TestPoolServer};
+/// # use rust_stratum::stratum::testing::ShareVerdict;
+/// # use std::time::Duration;
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), rust_stratum::stratum::error::StratumError> {
+/// let pool = TestPoolServer::builder()
+///     .difficulty(1.0)
+///     .difficulty_schedule(vec![(Duration::from_secs(30), 8.0)])
+///     .job_interval(Duration::from_secs(5))
+///     .acceptance(AcceptancePolicy::Every(10, ShareVerdict::Error(21, "Stale share".into())))
+///     .fault(Fault::SlowResponses(Duration::from_millis(200)))
+///     .bind("127.0.0.1:0")
+///     .await?;
+/// println!("Pool listening on {}", pool.addr());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The server stops when dropped.
+#[derive(Debug)]
+pub struct TestPoolServer {
+    addr: SocketAddr,
+    state: Arc<TestPoolState>,
+    accept: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct TestPoolState {
+    config: TestPoolBuilder,
+    acceptance: Mutex<AcceptancePolicy>,
+    /// Messages to each connected client, `None` to drop the connection
+    clients: Mutex<Vec<mpsc::UnboundedSender<Option<Value>>>>,
+    connections: AtomicU64,
+    jobs: AtomicU64,
+    submits: AtomicU64,
+    accepted: AtomicU64,
+}
+
+impl TestPoolServer {
+    pub fn builder() -> TestPoolBuilder {
+        TestPoolBuilder::default()
+    }
+
+    /// Address the pool listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Send every client a new difficulty
+    pub fn set_difficulty(&self, difficulty: f64) {
+        self.broadcast(json!({
+            "id": null,
+            "method": MINING_SET_DIFFICULTY,
+            "params": [difficulty],
+        }));
+    }
+
+    /// Send every client a new clean job now, rather than waiting for the
+    /// job interval
+    pub fn new_job(&self) {
+        self.broadcast(self.state.job());
+    }
+
+    /// Change how shares are judged from now on
+    pub fn set_acceptance(&self, policy: AcceptancePolicy) {
+        *self.state.acceptance.lock().unwrap() = policy;
+    }
+
+    /// Drop every connection, as a pool restart would
+    pub fn disconnect_all(&self) {
+        for client in self.state.clients.lock().unwrap().drain(..) {
+            let _ = client.send(None);
+        }
+    }
+
+    /// Connections accepted so far
+    pub fn connections(&self) -> u64 {
+        self.state.connections.load(Ordering::Relaxed)
+    }
+
+    /// Shares submitted so far
+    pub fn submits(&self) -> u64 {
+        self.state.submits.load(Ordering::Relaxed)
+    }
+
+    /// Shares accepted so far
+    pub fn accepted(&self) -> u64 {
+        self.state.accepted.load(Ordering::Relaxed)
+    }
+
+    fn broadcast(&self, message: Value) {
+        self.state
+            .clients
+            .lock()
+            .unwrap()
+            .retain(|client| client.send(Some(message.clone())).is_ok());
+    }
+}
+
+impl Drop for TestPoolServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+        self.disconnect_all();
+    }
+}
+
+impl TestPoolState {
+    /// `mining.notify` for the next clean job
+    fn job(&self) -> Value {
+        let id = self.jobs.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut params = MockPool::job(&format!("{:x}", id));
+        params[1] = json!(format!("{:064x}", id));
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        params[7] = json!(format!("{:08x}", now as u32));
+        json!({"id": null, "method": MINING_NOTIFY, "params": params})
+    }
+
+    fn judge(&self) -> ShareVerdict {
+        let submit = self.submits.fetch_add(1, Ordering::Relaxed) + 1;
+        let verdict = match &*self.acceptance.lock().unwrap() {
+            AcceptancePolicy::AcceptAll => ShareVerdict::Accept,
+            AcceptancePolicy::RejectAll => ShareVerdict::Reject,
+            AcceptancePolicy::Every(n, verdict) if submit.is_multiple_of((*n).max(1)) => {
+                verdict.clone()
+            }
+            AcceptancePolicy::Every(..) => ShareVerdict::Accept,
+        };
+        if verdict == ShareVerdict::Accept {
+            self.accepted.fetch_add(1, Ordering::Relaxed);
+        }
+        verdict
+    }
+
+    /// Answer to a request, `None` for notifications and responses from the
+    /// client
+    fn answer(&self, request: &Value, extranonce1: &str) -> Option<Value> {
+        respond(
+            request,
+            || {
+                json!([
+                    [
+                        [MINING_SET_DIFFICULTY, extranonce1],
+                        [MINING_NOTIFY, extranonce1]
+                    ],
+                    extranonce1,
+                    self.config.extranonce2_size
+                ])
+            },
+            true,
+            || self.judge(),
+        )
+    }
+}
+
+async fn accept_loop(listener: TcpListener, state: Arc<TestPoolState>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let connection = state.connections.fetch_add(1, Ordering::Relaxed) + 1;
+        let (client_tx, client_rx) = mpsc::unbounded_channel();
+        state.clients.lock().unwrap().push(client_tx);
+        tokio::spawn(serve(state.clone(), stream, connection, client_rx));
+    }
+}
+
+async fn write(writer: &mut OwnedWriteHalf, line: &str) -> bool {
+    writer
+        .write_all(format!("{}\n", line).as_bytes())
+        .await
+        .is_ok()
+}
+
+/// Serve one connection until either side drops it
+async fn serve(
+    state: Arc<TestPoolState>,
+    stream: TcpStream,
+    connection: u64,
+    mut outgoing: mpsc::UnboundedReceiver<Option<Value>>,
+) {
+    let config = &state.config;
+    let extranonce1 = format!("{:08x}", connection as u32);
+    let (read_half, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(read_half).lines();
+    let mut requests = 0u64;
+    // Jobs and scheduled difficulty start once the client subscribed
+    let mut next_job: Option<Instant> = None;
+    let mut schedule: VecDeque<(Instant, f64)> = VecDeque::new();
+    let idle = Duration::from_secs(3600);
+
+    loop {
+        let next_change = schedule.front().map(|(at, _)| *at);
+        let message = tokio::select! {
+            line = lines.next_line() => {
+                let Ok(Some(line)) = line else { break };
+                let Ok(request) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                requests += 1;
+                if config.faults.contains(&Fault::DisconnectAt(requests)) {
+                    break;
+                }
+                let Some(response) = state.answer(&request, &extranonce1) else {
+                    continue;
+                };
+                for fault in &config.faults {
+                    if let Fault::SlowResponses(delay) = fault {
+                        tokio::time::sleep(*delay).await;
+                    }
+                }
+                if config.faults.contains(&Fault::MalformedResponse(requests)) {
+                    if !write(&mut writer, "{\"id\": ").await {
+                        break;
+                    }
+                    continue;
+                }
+                if !write(&mut writer, &response.to_string()).await {
+                    break;
+                }
+                if request["method"] != MINING_SUBSCRIBE || next_job.is_some() {
+                    continue;
+                }
+                let now = Instant::now();
+                next_job = Some(now + config.job_interval);
+                schedule = config
+                    .schedule
+                    .iter()
+                    .map(|(after, difficulty)| (now + *after, *difficulty))
+                    .collect();
+                let difficulty = json!({
+                    "id": null,
+                    "method": MINING_SET_DIFFICULTY,
+                    "params": [config.difficulty],
+                });
+                if !write(&mut writer, &difficulty.to_string()).await {
+                    break;
+                }
+                state.job()
+            }
+            message = outgoing.recv() => match message {
+                Some(Some(message)) => message,
+                _ => break,
+            },
+            _ = tokio::time::sleep_until(next_job.unwrap_or_else(|| Instant::now() + idle)),
+                if next_job.is_some() =>
+            {
+                next_job = Some(Instant::now() + config.job_interval);
+                state.job()
+            }
+            _ = tokio::time::sleep_until(next_change.unwrap_or_else(|| Instant::now() + idle)),
+                if next_change.is_some() =>
+            {
+                let difficulty = schedule.pop_front().map_or(config.difficulty, |(_, d)| d);
+                json!({"id": null, "method": MINING_SET_DIFFICULTY, "params": [difficulty]})
+            }
+        };
+        if !write(&mut writer, &message.to_string()).await {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::v1::jobs::TestMiner;
+    use crate::stratum::v1::protocol::MINING_SUBMIT;
+    use crate::stratum::v1::StratumV1Client;
+    use crate::stratum::StratumClient;
+
+    #[tokio::test]
+    async fn test_pool_session() {
+        let pool = TestPoolServer::builder()
+            .difficulty(2.0)
+            .difficulty_schedule(vec![(Duration::from_millis(100), 8.0)])
+            .job_interval(Duration::from_millis(50))
+            .bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let mut client = StratumV1Client::new("127.0.0.1".into(), pool.addr().port(), TestMiner)
+            .await
+            .unwrap();
+        assert_eq!(client.subscribe().await.unwrap().extranonce1, "00000001");
+        assert!(client.authorize("rig1", "x").await.unwrap().authorized);
+
+        let mut difficulties = Vec::new();
+        while !difficulties.contains(&8.0) {
+            client.handle_notifications().await.unwrap();
+            if client.get_current_job().await.unwrap().is_none() {
+                continue;
+            }
+            let difficulty = client.get_target().await.unwrap().difficulty;
+            if difficulties.last() != Some(&difficulty) {
+                difficulties.push(difficulty);
+            }
+        }
+        assert_eq!(difficulties, [2.0, 8.0]);
+        let job = client.get_current_job().await.unwrap().unwrap();
+        assert!(u64::from_str_radix(&job.job_id, 16).unwrap() > 1);
+        assert_eq!(pool.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_acceptance_policy() {
+        let stale = ShareVerdict::Error(21, "Stale share".into());
+        let pool = TestPoolServer::builder()
+            .acceptance(AcceptancePolicy::Every(2, stale))
+            .bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let stream = TcpStream::connect(pool.addr()).await.unwrap();
+        let (read_half, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(read_half).lines();
+        let mut answers = Vec::new();
+        for id in 1..=3 {
+            let submit = json!({"id": id, "method": MINING_SUBMIT, "params": []});
+            write(&mut writer, &submit.to_string()).await;
+            let line = lines.next_line().await.unwrap().unwrap();
+            let response: Value = serde_json::from_str(&line).unwrap();
+            answers.push((response["result"].clone(), response["error"][0].clone()));
+        }
+        assert_eq!(
+            answers,
+            [
+                (json!(true), Value::Null),
+                (Value::Null, json!(21)),
+                (json!(true), Value::Null),
+            ]
+        );
+        assert_eq!((pool.submits(), pool.accepted()), (3, 2));
+
+        pool.set_acceptance(AcceptancePolicy::RejectAll);
+        let submit = json!({"id": 4, "method": MINING_SUBMIT, "params": []});
+        write(&mut writer, &submit.to_string()).await;
+        let line = lines.next_line().await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap()["result"],
+            false
+        );
+    }
+
+    #[tokio::test]
+    async fn test_faults() {
+        let pool = TestPoolServer::builder()
+            .fault(Fault::SlowResponses(Duration::from_millis(100)))
+            .fault(Fault::MalformedResponse(1))
+            .fault(Fault::DisconnectAt(2))
+            .bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let stream = TcpStream::connect(pool.addr()).await.unwrap();
+        let (read_half, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(read_half).lines();
+
+        let started = Instant::now();
+        write(
+            &mut writer,
+            r#"{"id":1,"method":"mining.subscribe","params":[]}"#,
+        )
+        .await;
+        let line = lines.next_line().await.unwrap().unwrap();
+        assert!(serde_json::from_str::<Value>(&line).is_err());
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        write(
+            &mut writer,
+            r#"{"id":2,"method":"mining.authorize","params":[]}"#,
+        )
+        .await;
+        assert_eq!(lines.next_line().await.unwrap(), None);
+    }
+}
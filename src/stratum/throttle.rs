@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+/// Default interval between throttle policy evaluations
+pub const DEFAULT_THROTTLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Environmental and performance readings reported by a miner's device
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceStats {
+    /// Device temperature in degrees Celsius
+    pub temperature_c: Option<f64>,
+    /// Current power draw in watts
+    pub power_watts: Option<f64>,
+    /// Device-measured hashrate in H/s
+    pub hashrate: Option<f64>,
+    /// Current intensity in the range 0.0-1.0
+    pub intensity: Option<f64>,
+}
+
+/// Action requested by a [`ThrottlePolicy`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThrottleAction {
+    /// Leave mining as it is
+    None,
+    /// Pause mining
+    Pause,
+    /// Resume mining
+    Resume,
+    /// Ask the miner to run at the given intensity (0.0-1.0)
+    SetIntensity(f64),
+}
+
+/// Policy bridging power/thermal control into the mining loop
+///
+/// The policy is consulted every [`interval`](ThrottlePolicy::interval) with the
+/// latest [`DeviceStats`] reported by the miner.
+pub trait ThrottlePolicy: Send + 'static {
+    /// Decide what to do given the latest device stats
+    ///
+    /// `paused` tells whether this policy holds mining paused; a pause of a
+    /// schedule or the user is tracked apart and isn't lifted by `Resume`.
+    fn evaluate(&mut self, stats: &DeviceStats, paused: bool) -> ThrottleAction;
+
+    /// How often the policy is consulted
+    fn interval(&self) -> Duration {
+        DEFAULT_THROTTLE_INTERVAL
+    }
+}
+
+/// Pauses mining above a temperature and resumes once the device cooled down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureThrottle {
+    /// Pause when the temperature reaches this value
+    pub pause_above_c: f64,
+    /// Resume when the temperature drops below this value
+    pub resume_below_c: f64,
+}
+
+impl ThrottlePolicy for TemperatureThrottle {
+    fn evaluate(&mut self, stats: &DeviceStats, paused: bool) -> ThrottleAction {
+        match stats.temperature_c {
+            Some(temp) if !paused && temp >= self.pause_above_c => ThrottleAction::Pause,
+            Some(temp) if paused && temp < self.resume_below_c => ThrottleAction::Resume,
+            _ => ThrottleAction::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_temperature(temperature_c: f64) -> DeviceStats {
+        DeviceStats {
+            temperature_c: Some(temperature_c),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_temperature_throttle() {
+        let mut policy = TemperatureThrottle {
+            pause_above_c: 85.0,
+            resume_below_c: 70.0,
+        };
+
+        assert_eq!(
+            policy.evaluate(&at_temperature(60.0), false),
+            ThrottleAction::None
+        );
+        assert_eq!(
+            policy.evaluate(&at_temperature(90.0), false),
+            ThrottleAction::Pause
+        );
+        // Hysteresis: still too hot to resume
+        assert_eq!(
+            policy.evaluate(&at_temperature(75.0), true),
+            ThrottleAction::None
+        );
+        assert_eq!(
+            policy.evaluate(&at_temperature(65.0), true),
+            ThrottleAction::Resume
+        );
+        assert_eq!(
+            policy.evaluate(&DeviceStats::default(), true),
+            ThrottleAction::None
+        );
+    }
+}
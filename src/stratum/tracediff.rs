@@ -0,0 +1,231 @@
+use crate::stratum::capture::{Capture, CaptureDirection};
+use serde_json::Value;
+use std::fmt;
+
+/// Structural difference between requests two clients sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceDifference {
+    /// JSON-RPC method of the requests compared
+    pub method: String,
+    /// Which request of the method first showed the difference, from 0
+    pub occurrence: usize,
+    /// Number of requests of the method showing it
+    pub count: usize,
+    /// Location in the request, e.g. `params[2]`, empty for the request as
+    /// a whole
+    pub path: String,
+    /// Shape in the first capture
+    pub left: String,
+    /// Shape in the second capture
+    pub right: String,
+}
+
+impl fmt::Display for TraceDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} #{}", self.method, self.occurrence)?;
+        if !self.path.is_empty() {
+            write!(f, " {}", self.path)?;
+        }
+        write!(f, ": {} vs {}", self.left, self.right)?;
+        if self.count > 1 {
+            write!(f, " ({} requests)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the requests sent in two captures, e.g. of a miner a pool
+/// accepts and of this client against the same pool
+///
+/// Requests are paired by method, the first `mining.submit` of one capture
+/// with the first of the other and so on, and compared by structure rather
+/// than value: field presence, JSON types, array lengths and the byte length
+/// and case of hex strings. Worker names, nonces and ids differing is
+/// expected; a 4 byte extranonce2 against an 8 byte one, or a missing
+/// version bits param, is what gets a share rejected. Differences repeated
+/// across requests are reported once, with their count, in the order the
+/// methods were first sent.
+pub fn diff(left: &Capture, right: &Capture) -> Vec<TraceDifference> {
+    let left = sent_requests(left);
+    let right = sent_requests(right);
+    let mut methods: Vec<&str> = Vec::new();
+    for (method, _) in left.iter().chain(&right) {
+        if !methods.contains(&method.as_str()) {
+            methods.push(method);
+        }
+    }
+
+    let mut differences: Vec<TraceDifference> = Vec::new();
+    for method in methods {
+        let of_method = |requests: &[(String, Value)]| -> Vec<Value> {
+            requests
+                .iter()
+                .filter(|(name, _)| name == method)
+                .map(|(_, request)| request.clone())
+                .collect()
+        };
+        let (left, right) = (of_method(&left), of_method(&right));
+        for occurrence in 0..left.len().max(right.len()) {
+            let mut found = Vec::new();
+            compare(
+                String::new(),
+                left.get(occurrence),
+                right.get(occurrence),
+                &mut found,
+            );
+            for (path, left, right) in found {
+                match differences.iter_mut().find(|difference| {
+                    difference.method == method
+                        && difference.path == path
+                        && difference.left == left
+                        && difference.right == right
+                }) {
+                    Some(difference) => difference.count += 1,
+                    None => differences.push(TraceDifference {
+                        method: method.to_string(),
+                        occurrence,
+                        count: 1,
+                        path,
+                        left,
+                        right,
+                    }),
+                }
+            }
+        }
+    }
+    differences
+}
+
+/// Requests the client sent, with their method
+fn sent_requests(capture: &Capture) -> Vec<(String, Value)> {
+    capture
+        .records
+        .iter()
+        .filter(|record| record.direction == CaptureDirection::Sent)
+        .filter_map(|record| serde_json::from_str::<Value>(&record.line).ok())
+        .filter_map(|request| {
+            let method = request.get("method")?.as_str()?.to_string();
+            Some((method, request))
+        })
+        .collect()
+}
+
+fn compare(
+    path: String,
+    left: Option<&Value>,
+    right: Option<&Value>,
+    found: &mut Vec<(String, String, String)>,
+) {
+    match (left, right) {
+        (Some(Value::Array(left)), Some(Value::Array(right))) => {
+            for index in 0..left.len().max(right.len()) {
+                compare(
+                    format!("{}[{}]", path, index),
+                    left.get(index),
+                    right.get(index),
+                    found,
+                );
+            }
+        }
+        (Some(Value::Object(left)), Some(Value::Object(right))) => {
+            let mut keys: Vec<&String> = left.keys().chain(right.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}.{}", path, key),
+                };
+                compare(path, left.get(key), right.get(key), found);
+            }
+        }
+        _ => {
+            let (left, right) = (shape(left), shape(right));
+            if left != right {
+                found.push((path, left, right));
+            }
+        }
+    }
+}
+
+/// Structure of a value, without the details expected to differ
+fn shape(value: Option<&Value>) -> String {
+    let Some(value) = value else {
+        return "missing".into();
+    };
+    match value {
+        Value::Null => "null".into(),
+        Value::Bool(_) => "bool".into(),
+        Value::Number(number) if number.is_f64() => "number".into(),
+        Value::Number(_) => "integer".into(),
+        Value::String(string) => {
+            let hex = !string.is_empty()
+                && string.len() % 2 == 0
+                && string.bytes().all(|byte| byte.is_ascii_hexdigit());
+            match (hex, string.bytes().any(|byte| byte.is_ascii_uppercase())) {
+                (true, false) => format!("{} byte hex", string.len() / 2),
+                (true, true) => format!("{} byte uppercase hex", string.len() / 2),
+                (false, _) => "string".into(),
+            }
+        }
+        Value::Array(items) => format!("array of {}", items.len()),
+        Value::Object(_) => "object".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::capture::CaptureRecord;
+
+    fn capture(lines: &[&str]) -> Capture {
+        Capture {
+            records: lines
+                .iter()
+                .map(|line| CaptureRecord {
+                    offset_ms: 0,
+                    direction: CaptureDirection::Sent,
+                    line: line.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let reference = capture(&[
+            r#"{"id":1,"method":"mining.subscribe","params":["cgminer/4.11"]}"#,
+            r#"{"id":2,"method":"mining.authorize","params":["rig1","x"]}"#,
+            r#"{"id":3,"method":"mining.submit","params":["rig1","j1","00000001","504e86b9","0000abcd"]}"#,
+            r#"{"id":4,"method":"mining.submit","params":["rig1","j2","00000002","504e86ba","0000abce"]}"#,
+        ]);
+        let ours = capture(&[
+            r#"{"id":"1","method":"mining.subscribe","params":["rust-stratum/1.0",null]}"#,
+            r#"{"id":2,"method":"mining.authorize","params":["rig2","y"]}"#,
+            r#"{"id":3,"method":"mining.submit","params":["rig1","j1","0000000000000001","504E86B9","0000abcd"]}"#,
+            r#"{"id":4,"method":"mining.submit","params":["rig1","j2","0000000000000002","504e86ba","0000abce"]}"#,
+        ]);
+
+        let differences = diff(&reference, &ours);
+        let described: Vec<String> = differences.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            described,
+            [
+                "mining.subscribe #0 id: integer vs string",
+                "mining.subscribe #0 params[1]: missing vs null",
+                "mining.submit #0 params[2]: 4 byte hex vs 8 byte hex (2 requests)",
+                "mining.submit #0 params[3]: 4 byte hex vs 4 byte uppercase hex",
+            ]
+        );
+        assert!(diff(&reference, &reference).is_empty());
+
+        let without_submits = capture(&[
+            r#"{"id":1,"method":"mining.subscribe","params":["cgminer/4.11"]}"#,
+            r#"{"id":2,"method":"mining.authorize","params":["rig1","x"]}"#,
+        ]);
+        let differences = diff(&reference, &without_submits);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].right, "missing");
+        assert_eq!(differences[0].count, 2);
+    }
+}
@@ -1,6 +1,21 @@
+use crate::stratum::coinbase;
+use crate::stratum::error::{StratumError, StratumRpcError};
+use crate::stratum::rejects::RejectCatalogue;
+use crate::stratum::target::Target;
+use crate::stratum::url::PoolUrl;
+use crate::stratum::v1::jobs::JobManager;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
+/// Job from a `mining.notify`
+///
+/// Jobs compare equal when their protocol fields are, whatever session they
+/// were received in. Outside this crate they are built with
+/// [`from_notify`](Self::from_notify).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MiningJob {
     pub job_id: String,
@@ -13,27 +28,214 @@ pub struct MiningJob {
     pub ntime: String,
     pub clean_jobs: Option<bool>,
     pub target: Option<MiningTarget>,
+    /// Params of the `mining.notify` the job was parsed from, for fields
+    /// specific to a pool's dialect; empty for jobs built otherwise
+    #[serde(default)]
+    pub(crate) raw_params: Arc<Vec<Value>>,
+    /// Session generation the job was received in, see
+    /// [`generation`](Self::generation)
+    #[serde(default)]
+    pub(crate) generation: u64,
+}
+
+impl PartialEq for MiningJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.job_id == other.job_id
+            && self.prev_hash == other.prev_hash
+            && self.coinbase1 == other.coinbase1
+            && self.coinbase2 == other.coinbase2
+            && self.merkle_branch == other.merkle_branch
+            && self.version == other.version
+            && self.nbits == other.nbits
+            && self.ntime == other.ntime
+            && self.clean_jobs == other.clean_jobs
+            && self.target == other.target
+    }
+}
+
+impl MiningJob {
+    /// Parse the params of a `mining.notify`
+    pub fn from_notify(params: &[Value]) -> Result<Self, StratumError> {
+        JobManager::parse_job(params)
+    }
+
+    /// Params of the `mining.notify` the job was parsed from, empty for jobs
+    /// built otherwise
+    pub fn raw_params(&self) -> &[Value] {
+        &self.raw_params
+    }
+
+    /// Param at `index` of the original `mining.notify`, including entries
+    /// past the ones parsed into the other fields
+    pub fn raw_param(&self, index: usize) -> Option<&Value> {
+        self.raw_params.get(index)
+    }
+
+    /// Session generation the job was received in
+    ///
+    /// Results of jobs from an earlier one are dropped, see
+    /// [`JobManager::new_generation`].
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Height of the block this job builds, parsed from the coinbase (BIP34)
+    pub fn height(&self) -> Option<u64> {
+        coinbase::bip34_height(&self.coinbase1)
+    }
+
+    /// Check whether this job builds a block below `height`, making its work stale
+    ///
+    /// Jobs without a parsable height are never considered stale.
+    pub fn is_stale_at_height(&self, height: u64) -> bool {
+        self.height().is_some_and(|job_height| job_height < height)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubscribeResponse {
+    /// Id of the `mining.notify` subscription
     pub subscription_id: String,
     pub extranonce1: String,
     pub extranonce2_size: usize,
+    /// All subscriptions the pool listed, by method
+    #[serde(default)]
+    pub subscriptions: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthResponse {
     pub authorized: bool,
+    /// Reason given by the pool, usually for a rejection
     pub message: Option<String>,
+    /// Recognized category of the rejection message
+    pub reject_reason: Option<AuthRejectReason>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Common reasons pools give for rejecting `mining.authorize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum AuthRejectReason {
+    /// The worker or account isn't known to the pool
+    UnknownWorker,
+    /// The username isn't a valid payout address
+    InvalidAddress,
+    InvalidPassword,
+    /// The account or IP address is banned
+    Banned,
+    /// Too many connections or workers for the account
+    TooManyConnections,
+}
+
+impl AuthRejectReason {
+    /// Recognize the reason from a pool's error text
+    pub fn classify(message: &str) -> Option<Self> {
+        let message = message.to_lowercase();
+        let mentions = |words: &[&str]| words.iter().any(|word| message.contains(word));
+
+        if mentions(&["banned", "blocked", "blacklist"]) {
+            Some(Self::Banned)
+        } else if mentions(&["address", "wallet"]) {
+            Some(Self::InvalidAddress)
+        } else if mentions(&["password"]) {
+            Some(Self::InvalidPassword)
+        } else if mentions(&["too many", "limit"]) {
+            Some(Self::TooManyConnections)
+        } else if mentions(&[
+            "unknown",
+            "not found",
+            "unauthorized",
+            "invalid worker",
+            "invalid user",
+        ]) {
+            Some(Self::UnknownWorker)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why the pool rejected a share
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum RejectReason {
+    /// The share's job is no longer current
+    Stale,
+    /// The share was submitted before
+    Duplicate,
+    /// The share doesn't meet the pool's target
+    LowDifficulty,
+    Other,
+}
+
+/// Classification with the built-in messages only, see [`RejectCatalogue`]
+impl RejectReason {
+    /// Recognize the reason from a pool's error text
+    pub fn classify(message: &str) -> Self {
+        RejectCatalogue::default().classify(message)
+    }
+
+    /// Recognize the reason from a Stratum error code, or else its message
+    pub fn from_rpc(error: StratumRpcError, message: &str) -> Self {
+        RejectCatalogue::default().from_rpc(error, message)
+    }
+
+    /// Recognize the reason from the error of a `mining.submit` response,
+    /// by its Stratum error code (21 stale, 22 duplicate, 23 low difficulty)
+    /// or else its message
+    pub fn from_error(error: &Value) -> Self {
+        RejectCatalogue::default().from_error(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Share {
     pub job_id: String,
     pub extranonce2: String,
     pub ntime: String,
     pub nonce: String,
+    /// Rolled version bits as hex, sent when version rolling was negotiated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_bits: Option<String>,
+}
+
+/// Version rolling (ASICBoost) granted by the pool through `mining.configure`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionRolling {
+    /// Block version bits the miner may change
+    pub mask: u32,
+    /// Minimum number of bits the pool asked the miner to be able to roll
+    pub min_bit_count: u32,
+}
+
+impl VersionRolling {
+    /// Check that rolled version bits stay within the granted mask
+    pub fn allows(&self, version_bits: u32) -> bool {
+        version_bits & !self.mask == 0
+    }
+}
+
+/// Outcome of the `mining.configure` handshake
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfigureResult {
+    /// Whether the pool answered `mining.configure` rather than with an error
+    pub supported: bool,
+    /// Extensions requested that the pool accepted
+    pub accepted: Vec<String>,
+    /// Extensions requested that the pool declined or left unanswered
+    pub ignored: Vec<String>,
+    /// Version rolling in effect, kept current with `mining.set_version_mask`;
+    /// `None` when ASICBoost is inactive
+    pub version_rolling: Option<VersionRolling>,
+    /// The pool's result, for extension parameters not parsed here
+    pub result: Option<Value>,
+}
+
+impl ConfigureResult {
+    /// Whether version rolling (ASICBoost) is active
+    pub fn asic_boost(&self) -> bool {
+        self.version_rolling.is_some()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -42,6 +244,15 @@ pub enum StratumVersion {
     V2,
 }
 
+/// Parses a URL scheme, the inverse of `Display`
+impl FromStr for StratumVersion {
+    type Err = StratumError;
+
+    fn from_str(scheme: &str) -> Result<Self, Self::Err> {
+        PoolUrl::scheme(scheme).map(|(version, _)| version)
+    }
+}
+
 impl fmt::Display for StratumVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -51,14 +262,168 @@ impl fmt::Display for StratumVersion {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MiningTarget {
     pub difficulty: f64,
+    /// Full target as 64 big-endian hex digits
     pub target: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl MiningTarget {
+    /// Target of a pool difficulty, see [`Target::from_difficulty`]
+    pub fn from_difficulty(difficulty: f64) -> Self {
+        Self::scaled(difficulty, 1.0)
+    }
+
+    /// Target of a pool difficulty reported `multiplier` times the Bitcoin
+    /// difficulty of the same target, as scrypt pools report 65536 times
+    ///
+    /// `difficulty` is kept as reported, so it matches the pool's dashboard.
+    pub fn scaled(difficulty: f64, multiplier: f64) -> Self {
+        Self {
+            difficulty,
+            target: Target::from_difficulty(difficulty / multiplier).to_hex(),
+        }
+    }
+
+    /// Parsed target, `None` if `target` isn't 32 bytes of hex
+    pub fn to_target(&self) -> Option<Target> {
+        Target::from_hex(&self.target)
+    }
+
+    /// Full 32-byte big-endian form of the target
+    pub fn bytes(&self) -> Option<[u8; 32]> {
+        self.to_target().map(|target| target.to_bytes())
+    }
+
+    /// Compact form of the target, as used for `nbits`
+    pub fn nbits(&self) -> Option<u32> {
+        self.to_target().map(|target| target.to_nbits())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ServerInfo {
+    /// Server software version, empty until the pool reports one
     pub version: String,
     pub connection_id: String,
+    /// Subscription ids by method, from the subscribe response
+    #[serde(default)]
+    pub subscriptions: HashMap<String, String>,
+    /// First message the pool showed with `client.show_message`, often a
+    /// maintenance notice
+    ///
+    /// The subscribe and authorize responses have no standard field for a
+    /// banner, so messages in them aren't captured here.
+    #[serde(default)]
+    pub motd: Option<String>,
+    /// Result of the `mining.configure` handshake, once it ran
+    #[serde(default)]
+    pub configure: Option<ConfigureResult>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_reject_reason() {
+        assert_eq!(
+            AuthRejectReason::classify("Unauthorized worker"),
+            Some(AuthRejectReason::UnknownWorker)
+        );
+        assert_eq!(
+            AuthRejectReason::classify("Invalid payout address"),
+            Some(AuthRejectReason::InvalidAddress)
+        );
+        assert_eq!(
+            AuthRejectReason::classify("IP banned"),
+            Some(AuthRejectReason::Banned)
+        );
+        assert_eq!(AuthRejectReason::classify("Something went wrong"), None);
+    }
+
+    #[test]
+    fn test_reject_reason() {
+        use serde_json::json;
+
+        assert_eq!(
+            RejectReason::from_error(&json!([21, "Job not found", null])),
+            RejectReason::Stale
+        );
+        assert_eq!(
+            RejectReason::from_error(&json!({"code": 22, "message": "Duplicate"})),
+            RejectReason::Duplicate
+        );
+        assert_eq!(
+            RejectReason::from_error(&json!([20, "Low difficulty share", null])),
+            RejectReason::LowDifficulty
+        );
+        assert_eq!(
+            RejectReason::from_error(&json!("Stale share")),
+            RejectReason::Stale
+        );
+        assert_eq!(RejectReason::classify("Invalid nonce"), RejectReason::Other);
+    }
+
+    fn job_with_coinbase1(coinbase1: &str) -> MiningJob {
+        MiningJob {
+            job_id: "1".into(),
+            prev_hash: "00".repeat(32),
+            coinbase1: coinbase1.into(),
+            coinbase2: String::new(),
+            merkle_branch: vec![],
+            version: "20000000".into(),
+            nbits: "1d00ffff".into(),
+            ntime: "60509af9".into(),
+            clean_jobs: None,
+            target: None,
+            raw_params: Default::default(),
+            generation: 0,
+        }
+    }
+
+    #[test]
+    fn test_job_equality_ignores_session() {
+        use serde_json::json;
+
+        let params = vec![
+            json!("job1"),
+            json!("00".repeat(32)),
+            json!("01000000"),
+            json!("02000000"),
+            json!([]),
+            json!("20000000"),
+            json!("1d00ffff"),
+            json!("60509af9"),
+            json!(true),
+            json!("pool specific"),
+        ];
+        let job = MiningJob::from_notify(&params).unwrap();
+        assert_eq!(job.raw_params()[9], "pool specific");
+
+        let mut later = job_with_coinbase1("01000000");
+        later.job_id = "job1".into();
+        later.coinbase2 = "02000000".into();
+        later.clean_jobs = Some(true);
+        later.generation = 3;
+        assert_eq!(later, job);
+        later.ntime = "60509afa".into();
+        assert_ne!(later, job);
+    }
+
+    #[test]
+    fn test_job_height() {
+        let job = job_with_coinbase1(&format!(
+            "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff{}",
+            "4b030c3c0d"
+        ));
+        assert_eq!(job.height(), Some(867_340));
+        assert!(job.is_stale_at_height(867_341));
+        assert!(!job.is_stale_at_height(867_340));
+
+        let job = job_with_coinbase1("01000000");
+        assert_eq!(job.height(), None);
+        assert!(!job.is_stale_at_height(u64::MAX));
+    }
 }
@@ -0,0 +1,407 @@
+use super::connection::{ConnectionConfig, ReconnectPolicy};
+use super::extensions::Extensions;
+use super::redirect::RedirectPolicy;
+use super::resubmit::SubmitRetryPolicy;
+#[cfg(feature = "tls")]
+use super::tls::TlsConfig;
+use super::StratumV1Client;
+#[cfg(feature = "address")]
+use crate::stratum::address::UsernameValidator;
+use crate::stratum::error::StratumError;
+use crate::stratum::miner::Miner;
+use crate::stratum::types::StratumVersion;
+use crate::stratum::url::PoolUrl;
+use crate::stratum::StratumClient;
+
+/// Where the built client connects to
+#[derive(Debug, Clone)]
+enum PoolAddress {
+    HostPort(String, u16),
+    /// Parse result of [`StratumClientBuilder::url`], failing at build time
+    Url(Result<PoolUrl, StratumError>),
+}
+
+/// Configures and connects a [`StratumV1Client`]
+///
+/// ```no_run
+/// # use rust_stratum::stratum::prelude::*;
+/// # use rust_stratum::stratum::v1::jobs::TestMiner;
+/// # async fn run() -> Result<(), StratumError> {
+/// let client = StratumV1Client::builder()
+///     .url("stratum+tcp://pool.example.com:3333")
+///     .credentials("worker", "x")
+///     .user_agent("my-miner/0.1")
+///     .reconnect_policy(ReconnectPolicy {
+///         max_attempts: 5,
+///         ..Default::default()
+///     })
+///     .miner(TestMiner)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The miner is required before [`build`](Self::build) is available.
+#[derive(Debug, Clone)]
+pub struct StratumClientBuilder<M = ()> {
+    pool: Option<PoolAddress>,
+    credentials: Option<(String, String)>,
+    config: ConnectionConfig,
+    user_agent: Option<String>,
+    reconnect_policy: ReconnectPolicy,
+    redirect_policy: RedirectPolicy,
+    submit_retry_policy: SubmitRetryPolicy,
+    smooth_difficulty_ramp: bool,
+    difficulty_multiplier: f64,
+    extensions: Extensions,
+    #[cfg(feature = "address")]
+    username_validator: Option<UsernameValidator>,
+    miner: M,
+}
+
+impl Default for StratumClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StratumClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            pool: None,
+            credentials: None,
+            config: ConnectionConfig::default(),
+            user_agent: None,
+            reconnect_policy: ReconnectPolicy::default(),
+            redirect_policy: RedirectPolicy::default(),
+            submit_retry_policy: SubmitRetryPolicy::default(),
+            smooth_difficulty_ramp: false,
+            difficulty_multiplier: 1.0,
+            extensions: Extensions::new(),
+            #[cfg(feature = "address")]
+            username_validator: None,
+            miner: (),
+        }
+    }
+}
+
+impl<M> StratumClientBuilder<M> {
+    /// Connect to `host` on `port`; the host may carry a scheme as in
+    /// [`StratumConnection::with_config`](super::connection::StratumConnection::with_config)
+    pub fn pool(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.pool = Some(PoolAddress::HostPort(host.into(), port));
+        self
+    }
+
+    /// Connect to a pool URL such as `stratum+ssl://pool.example.com:4444`,
+    /// see [`PoolUrl`]
+    ///
+    /// Credentials in the URL apply unless [`credentials`](Self::credentials)
+    /// are set. An invalid URL fails [`build`](Self::build).
+    pub fn url(mut self, url: &str) -> Self {
+        self.pool = Some(PoolAddress::Url(url.parse()));
+        self
+    }
+
+    /// Connect to a parsed pool URL, see [`url`](Self::url)
+    pub fn pool_url(mut self, url: PoolUrl) -> Self {
+        self.pool = Some(PoolAddress::Url(Ok(url)));
+        self
+    }
+
+    /// Subscribe and authorize with these credentials when building
+    ///
+    /// Without credentials, here or in the URL, the client is returned
+    /// connected only.
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Connection options, replacing any set before including TLS options
+    pub fn connection_config(mut self, config: ConnectionConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// User agent sent with `mining.subscribe`, instead of
+    /// [`CLIENT_VERSION`](super::protocol::CLIENT_VERSION)
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Connect over TLS with these options
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.config.tls = Some(tls);
+        self
+    }
+
+    /// Retry connecting as `policy` allows, both when building and when the
+    /// connection is lost later
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Hosts the pool's `client.reconnect` may move the session to
+    pub fn redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = policy;
+        self
+    }
+
+    /// `mining.submit` errors that resubmit the share, see [`SubmitRetryPolicy`]
+    pub fn submit_retry_policy(mut self, policy: SubmitRetryPolicy) -> Self {
+        self.submit_retry_policy = policy;
+        self
+    }
+
+    /// Pool-specific methods the client handles and sends, see [`Extensions`]
+    pub fn extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Hold shares to the difficulty suggested by the `d=` password option
+    /// until the pool sets its own, see
+    /// [`StratumV1Client::set_smooth_difficulty_ramp`]
+    pub fn smooth_difficulty_ramp(mut self, enabled: bool) -> Self {
+        self.smooth_difficulty_ramp = enabled;
+        self
+    }
+
+    /// Take the pool's difficulties as `multiplier` times Bitcoin's, see
+    /// [`StratumV1Client::set_difficulty_multiplier`]
+    pub fn difficulty_multiplier(mut self, multiplier: f64) -> Self {
+        self.difficulty_multiplier = multiplier;
+        self
+    }
+
+    /// Check the username is a wallet address before connecting, see
+    /// [`UsernameValidator`]
+    #[cfg(feature = "address")]
+    pub fn username_validator(mut self, validator: UsernameValidator) -> Self {
+        self.username_validator = Some(validator);
+        self
+    }
+
+    /// Miner receiving the pool's jobs
+    pub fn miner<N: Miner>(self, miner: N) -> StratumClientBuilder<N> {
+        StratumClientBuilder {
+            pool: self.pool,
+            credentials: self.credentials,
+            config: self.config,
+            user_agent: self.user_agent,
+            reconnect_policy: self.reconnect_policy,
+            redirect_policy: self.redirect_policy,
+            submit_retry_policy: self.submit_retry_policy,
+            smooth_difficulty_ramp: self.smooth_difficulty_ramp,
+            difficulty_multiplier: self.difficulty_multiplier,
+            extensions: self.extensions,
+            #[cfg(feature = "address")]
+            username_validator: self.username_validator,
+            miner,
+        }
+    }
+}
+
+impl<M: Miner> StratumClientBuilder<M> {
+    /// Connect, then subscribe and authorize if credentials were given
+    ///
+    /// Fails if no pool was set, the username fails the
+    /// [`username_validator`](Self::username_validator), the pool can't be
+    /// reached within the reconnect policy's attempts, or it rejects the
+    /// credentials.
+    pub async fn build(self) -> Result<StratumV1Client, StratumError> {
+        let mut credentials = self.credentials;
+        let (host, port) = match self.pool {
+            Some(PoolAddress::HostPort(host, port)) => (host, port),
+            Some(PoolAddress::Url(url)) => {
+                let url = url?;
+                if url.version != StratumVersion::V1 {
+                    return Err(StratumError::Config(format!(
+                        "{} is not a Stratum V1 pool",
+                        url
+                    )));
+                }
+                if credentials.is_none() {
+                    credentials = url
+                        .credentials()
+                        .map(|(username, password)| (username.into(), password.into()));
+                }
+                (url.connection_host(), url.port)
+            }
+            None => return Err(StratumError::Config("No pool set".into())),
+        };
+        #[cfg(feature = "address")]
+        if let (Some(validator), Some((username, _))) = (&self.username_validator, &credentials) {
+            validator.check(username)?;
+        }
+
+        let policy = self.reconnect_policy;
+        let mut client = policy
+            .retry(|| {
+                StratumV1Client::with_connection_config(
+                    host.clone(),
+                    port,
+                    self.config.clone(),
+                    self.miner.clone(),
+                )
+            })
+            .await?;
+        client.set_reconnect_policy(policy);
+        client.set_redirect_policy(self.redirect_policy);
+        client.set_submit_retry_policy(self.submit_retry_policy);
+        client.set_extensions(self.extensions);
+        client.set_smooth_difficulty_ramp(self.smooth_difficulty_ramp);
+        client
+            .set_difficulty_multiplier(self.difficulty_multiplier)
+            .await?;
+        if let Some(user_agent) = self.user_agent {
+            client.set_user_agent(user_agent);
+        }
+
+        if let Some((username, password)) = credentials {
+            client.subscribe().await?;
+            let auth = client.authorize(&username, &password).await?;
+            if !auth.authorized {
+                return Err(StratumError::AuthenticationFailed(format!(
+                    "Pool rejected credentials for user {}",
+                    username
+                )));
+            }
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::v1::jobs::TestMiner;
+    use crate::stratum::v1::AuthState;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_reconnect_delay() {
+        let policy = ReconnectPolicy {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+        assert_eq!(policy.delay(0), Duration::ZERO);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(300));
+        assert_eq!(policy.delay(40), Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_build_requires_pool() {
+        let result = StratumClientBuilder::new().miner(TestMiner).build().await;
+        assert!(matches!(result, Err(StratumError::Config(_))));
+
+        let result = StratumClientBuilder::new()
+            .url("stratum+tcp://pool.example.com")
+            .miner(TestMiner)
+            .build()
+            .await;
+        assert!(matches!(result, Err(StratumError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_build_subscribes_and_authorizes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut user_agent = None;
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let result = match request["method"].as_str() {
+                    Some("mining.subscribe") => {
+                        user_agent = request["params"][0].as_str().map(String::from);
+                        json!([[["mining.notify", "1"]], "f000000f", 4])
+                    }
+                    _ => json!(true),
+                };
+                let response = json!({"id": request["id"], "result": result, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            user_agent
+        });
+
+        let mut client = StratumV1Client::builder()
+            .url(&format!("stratum+tcp://worker:x@127.0.0.1:{}", port))
+            .user_agent("test-miner/0.1")
+            .miner(TestMiner)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.user_agent(), "test-miner/0.1");
+        assert_eq!(client.auth_state().await, AuthState::Authorized);
+
+        client.close().await.unwrap();
+        assert_eq!(server.await.unwrap().as_deref(), Some("test-miner/0.1"));
+    }
+
+    #[tokio::test]
+    async fn test_build_retries_connecting() {
+        // Reserve a port, then only start listening after the first attempt
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let server = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+            let _ = listener.accept().await.unwrap();
+        });
+
+        let policy = ReconnectPolicy {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(100),
+        };
+        let client = StratumClientBuilder::new()
+            .pool("127.0.0.1", port)
+            .reconnect_policy(policy)
+            .miner(TestMiner)
+            .build()
+            .await;
+        assert!(client.is_ok());
+        server.await.unwrap();
+    }
+
+    #[cfg(feature = "address")]
+    #[tokio::test]
+    async fn test_build_validates_username() {
+        use crate::stratum::address::{ChainParams, ValidationMode};
+
+        // Refused before connecting, so nothing needs to listen
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let result = StratumClientBuilder::new()
+            .pool("127.0.0.1", port)
+            .credentials("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5.rig1", "x")
+            .username_validator(UsernameValidator::new(
+                ChainParams::bitcoin(),
+                ValidationMode::Error,
+            ))
+            .miner(TestMiner)
+            .build()
+            .await;
+        assert!(matches!(result, Err(StratumError::Config(_))));
+    }
+}
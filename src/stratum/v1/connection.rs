@@ -1,20 +1,144 @@
 use super::protocol::{JsonRpcRequest, JsonRpcResponse, DEFAULT_TIMEOUT, MAX_RETRIES};
-use crate::stratum::error::StratumError;
+use super::socks;
+#[cfg(feature = "capture")]
+use crate::stratum::capture::{CaptureDirection, CaptureRecorder};
+use crate::stratum::contention::{Contention, LockSite};
+use crate::stratum::error::{ErrorContext, ErrorKind, StratumError, StratumRpcError};
+use crate::stratum::events::DisconnectReason;
+use crate::stratum::stats::LatencyTracker;
+use crate::stratum::types::StratumVersion;
+use crate::stratum::url::PoolUrl;
+use crate::stratum::verbosity::Category;
+use async_trait::async_trait;
 use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::sync::{
-    atomic::{AtomicU64, Ordering},
+    atomic::{AtomicBool, AtomicU64, Ordering},
     Arc,
 };
 use std::time::{Duration, Instant};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::{
-        tcp::{OwnedReadHalf, OwnedWriteHalf},
-        TcpStream,
-    },
-    sync::Mutex,
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::{oneshot, Mutex, Notify},
+    task::JoinHandle,
     time::{sleep, timeout},
 };
+use tracing::{field, Instrument, Span};
+
+pub use super::socks::Socks5Proxy;
+#[cfg(feature = "tls")]
+pub use super::tls::TlsConfig;
+#[cfg(feature = "websocket")]
+pub use super::websocket::WebSocketTransport;
+
+/// Read half of a pool connection
+pub type Reader = Box<dyn AsyncRead + Send + Unpin>;
+/// Write half of a pool connection
+pub type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Link line-delimited JSON-RPC is exchanged with the pool over
+///
+/// [`TcpTransport`] is used unless [`ConnectionConfig::transport`] is set.
+#[async_trait]
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Open a link to `host` on `port`, returning its read and write halves
+    ///
+    /// Called for the first connection and again on every reconnect.
+    async fn open(
+        &self,
+        host: &str,
+        port: u16,
+        config: &ConnectionConfig,
+    ) -> Result<(Reader, Writer), StratumError>;
+}
+
+/// Link over a stream opened by the caller, e.g. a Unix socket, a serial
+/// line or an in-memory duplex in tests
+///
+/// The stream serves the first connection only; reconnecting fails, as
+/// there is nothing to reopen. Implement [`Transport`] for links that can be
+/// reopened.
+pub struct StreamTransport {
+    stream: std::sync::Mutex<Option<(Reader, Writer)>>,
+}
+
+impl StreamTransport {
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            stream: std::sync::Mutex::new(Some((Box::new(reader), Box::new(writer)))),
+        }
+    }
+}
+
+impl fmt::Debug for StreamTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamTransport")
+            .field("used", &self.stream.lock().unwrap().is_none())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Transport for StreamTransport {
+    async fn open(
+        &self,
+        _host: &str,
+        _port: u16,
+        _config: &ConnectionConfig,
+    ) -> Result<(Reader, Writer), StratumError> {
+        self.stream.lock().unwrap().take().ok_or_else(|| {
+            StratumError::Connection("The caller's stream was used already, can't reopen it".into())
+        })
+    }
+}
+
+/// TCP connection, through [`ConnectionConfig::proxy`] and with TLS on top
+/// if configured
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpTransport;
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn open(
+        &self,
+        host: &str,
+        port: u16,
+        config: &ConnectionConfig,
+    ) -> Result<(Reader, Writer), StratumError> {
+        let stream = match &config.proxy {
+            Some(proxy) => proxy.connect(host, port).await?,
+            None if socks::is_onion(host) => return Err(socks::onion_without_proxy(host)),
+            None => {
+                let addr = format!("{}:{}", host, port);
+                TcpStream::connect(&addr).await.map_err(|e| {
+                    StratumError::Connection(format!("Failed to connect to {} - {}", addr, e))
+                })?
+            }
+        };
+
+        if config.keepalive {
+            stream
+                .set_nodelay(true)
+                .map_err(|e| StratumError::Connection(format!("Failed to set nodelay - {}", e)))?;
+        }
+
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &config.tls {
+            let stream = tls.connect(stream, host).await?;
+            let (reader, writer) = tokio::io::split(stream);
+            return Ok((Box::new(reader), Box::new(writer)));
+        }
+
+        let (reader, writer) = stream.into_split();
+        Ok((Box::new(reader), Box::new(writer)))
+    }
+}
 
 /// Configuration for connection behavior
 #[derive(Debug, Clone)]
@@ -27,6 +151,17 @@ pub struct ConnectionConfig {
     pub retry_delay: u64,
     /// Whether to enable TCP keepalive
     pub keepalive: bool,
+    /// Maximum number of notifications held while the consumer isn't reading
+    /// them; the oldest is dropped when full, responses are never dropped
+    pub max_buffered_notifications: usize,
+    /// Connect over TLS, implied by a `stratum+ssl://` host
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+    /// Connect through a SOCKS5 proxy, required for `.onion` hosts
+    pub proxy: Option<Socks5Proxy>,
+    /// Link to the pool instead of [`TcpTransport`], implied by a `ws://` or
+    /// `wss://` host
+    pub transport: Option<Arc<dyn Transport>>,
 }
 
 impl Default for ConnectionConfig {
@@ -36,10 +171,119 @@ impl Default for ConnectionConfig {
             max_retries: MAX_RETRIES,
             retry_delay: 1,
             keepalive: true,
+            max_buffered_notifications: MAX_BUFFERED_NOTIFICATIONS,
+            #[cfg(feature = "tls")]
+            tls: None,
+            proxy: None,
+            transport: None,
         }
     }
 }
 
+impl ConnectionConfig {
+    /// Default options, connecting through the Tor SOCKS proxy at
+    /// `proxy_addr`, e.g. [`TOR_SOCKS_ADDR`](super::socks::TOR_SOCKS_ADDR)
+    ///
+    /// Pool host names, `.onion` addresses included, are resolved by Tor,
+    /// so no DNS query leaks the pool being mined on.
+    ///
+    /// ```no_run
+    /// # use rust_stratum::stratum::prelude::*;
+    /// # use rust_stratum::stratum::v1::jobs::TestMiner;
+    /// # async fn run() -> Result<(), StratumError> {
+    /// let client = StratumV1Client::builder()
+    ///     .url("stratum+tcp://pool2abcdefghijklmnop.onion:3333")
+    ///     .connection_config(ConnectionConfig::tor("127.0.0.1:9050"))
+    ///     .credentials("worker", "x")
+    ///     .miner(TestMiner)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tor(proxy_addr: impl Into<String>) -> Self {
+        Self {
+            proxy: Some(Socks5Proxy::remote_dns(proxy_addr)),
+            ..Default::default()
+        }
+    }
+
+    /// Default options, opening links with `transport` instead of TCP
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Some(Arc::new(transport)),
+            ..Default::default()
+        }
+    }
+}
+
+/// How often and how fast to retry connecting to the pool
+///
+/// Applies when the client reconnects after losing the connection and when
+/// [`StratumClientBuilder::build`](super::builder::StratumClientBuilder::build)
+/// opens the first one. The delay doubles after each failed attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Connection attempts before giving up, at least one is made
+    pub max_attempts: u32,
+    /// Delay before the second attempt
+    pub initial_delay: Duration,
+    /// Upper bound of the delay between attempts
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before attempt `attempt`, counting from 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match attempt {
+            0 => Duration::ZERO,
+            _ => self
+                .initial_delay
+                .saturating_mul(1u32 << (attempt - 1).min(31))
+                .min(self.max_delay),
+        }
+    }
+
+    /// Run `connect` until it succeeds or the attempts run out, returning
+    /// the last error
+    pub(crate) async fn retry<T, F, Fut>(&self, mut connect: F) -> Result<T, StratumError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, StratumError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            sleep(self.delay(attempt)).await;
+            match connect().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt + 1 >= self.max_attempts => return Err(err),
+                Err(err) => {
+                    tracing::warn!(
+                        target: Category::Connection.target(),
+                        attempt = attempt + 1,
+                        error = %err,
+                        "Connection attempt failed"
+                    );
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Default maximum number of notifications buffered while waiting for responses
+pub const MAX_BUFFERED_NOTIFICATIONS: usize = 64;
+
 /// Statistics for the connection
 #[derive(Debug, Default, Clone)]
 pub struct ConnectionStats {
@@ -47,19 +291,227 @@ pub struct ConnectionStats {
     pub messages_received: u64,
     pub errors: u64,
     pub retries: u64,
+    /// Notifications dropped because the inbound buffer was full
+    pub notifications_dropped: u64,
     pub last_message_at: Option<Instant>,
     pub connected_since: Option<Instant>,
+    /// Round-trip times of the requests answered, by method
+    pub request_timing: HashMap<String, RequestTiming>,
+}
+
+/// Round-trip times of the requests of one JSON-RPC method, from sending a
+/// request to receiving its response
+///
+/// Pool latency adds directly to the time a share takes to reach the pool,
+/// and so to its chance of being stale.
+#[derive(Debug, Default, Clone)]
+pub struct RequestTiming {
+    /// Responses received
+    pub count: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub total: Duration,
+    /// The last [`LATENCY_SAMPLE_WINDOW`](crate::stratum::stats::LATENCY_SAMPLE_WINDOW)
+    /// round trips, for percentiles
+    pub recent: LatencyTracker,
+}
+
+impl RequestTiming {
+    fn record(&mut self, latency: Duration) {
+        self.min = match self.count {
+            0 => latency,
+            _ => self.min.min(latency),
+        };
+        self.max = self.max.max(latency);
+        self.total += latency;
+        self.count += 1;
+        self.recent.record(latency);
+    }
+
+    /// Mean round trip
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.total.as_nanos() / u128::from(n)) as u64),
+        }
+    }
+
+    /// 95th percentile of the recent round trips
+    pub fn p95(&self) -> Option<Duration> {
+        self.recent.percentile(95.0)
+    }
+}
+
+/// Counters of [`ConnectionStats`] summed over every connection a
+/// [`StratumConnection`] opened, reconnects included
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LifetimeStats {
+    pub connections: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub errors: u64,
+    pub retries: u64,
+    pub notifications_dropped: u64,
+}
+
+impl LifetimeStats {
+    /// Add the counters of one connection
+    fn add(&mut self, stats: &ConnectionStats) {
+        self.connections += 1;
+        self.messages_sent += stats.messages_sent;
+        self.messages_received += stats.messages_received;
+        self.errors += stats.errors;
+        self.retries += stats.retries;
+        self.notifications_dropped += stats.notifications_dropped;
+    }
+}
+
+/// Messages received on a connection but not consumed yet
+#[derive(Default)]
+pub(crate) struct Inbox {
+    notifications: Mutex<VecDeque<Value>>,
+    /// Requests waiting for their response while the read loop runs, by id
+    pending: std::sync::Mutex<HashMap<u64, oneshot::Sender<JsonRpcResponse>>>,
+    disconnect: std::sync::Mutex<Option<DisconnectReason>>,
+    /// Whether the read loop stopped because the connection ended
+    closed: AtomicBool,
+    arrived: Notify,
+}
+
+impl Inbox {
+    /// Remember why the connection ended, keeping the first reason observed
+    fn mark_disconnected(&self, reason: DisconnectReason) {
+        if let Ok(mut disconnect) = self.disconnect.lock() {
+            disconnect.get_or_insert(reason);
+        }
+    }
+
+    /// Buffer a notification, dropping the oldest ones beyond `limit`
+    async fn push_notification(
+        &self,
+        notification: Value,
+        limit: usize,
+        stats: &Mutex<ConnectionStats>,
+    ) {
+        let mut buffered = self.notifications.lock().await;
+        let mut stats = stats.lock().await;
+        while buffered.len() >= limit.max(1) {
+            tracing::warn!(
+                target: Category::Connection.target(),
+                limit,
+                "Notification buffer full, dropping oldest notification"
+            );
+            buffered.pop_front();
+            stats.notifications_dropped += 1;
+        }
+        buffered.push_back(notification);
+
+        stats.messages_received += 1;
+        stats.last_message_at = Some(Instant::now());
+        drop(buffered);
+        self.arrived.notify_waiters();
+    }
+
+    /// Wait for the response to request `id`
+    ///
+    /// Must be called before the request is sent so the response can't
+    /// arrive first. Once the connection ended the receiver fails right away.
+    fn expect_response(&self, id: u64) -> oneshot::Receiver<JsonRpcResponse> {
+        let (tx, rx) = oneshot::channel();
+        if !self.closed.load(Ordering::SeqCst) {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.insert(id, tx);
+            }
+        }
+        rx
+    }
+
+    /// Stop waiting for the response to request `id`
+    fn forget(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+
+    /// Hand a response to the request waiting for it
+    fn respond(&self, response: JsonRpcResponse) {
+        let waiter = response
+            .id
+            .and_then(|id| self.pending.lock().ok()?.remove(&id));
+        match waiter {
+            Some(waiter) => {
+                let _ = waiter.send(response);
+            }
+            None => tracing::warn!(
+                target: Category::Connection.target(),
+                id = ?response.id,
+                response = ?response,
+                "Dropping response nobody waits for"
+            ),
+        }
+    }
+
+    /// Fail all pending requests and wake up notification readers
+    fn shut(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+        self.arrived.notify_waiters();
+    }
+
+    /// Get the next notification, waiting for one to arrive
+    ///
+    /// Returns `null` once the connection ended, like
+    /// [`StratumConnection::read_notification`].
+    pub(crate) async fn next_notification(&self) -> Value {
+        loop {
+            // Registered before checking so no wakeup is missed
+            let arrived = self.arrived.notified();
+            if let Some(notification) = self.notifications.lock().await.pop_front() {
+                return notification;
+            }
+            if self.closed.load(Ordering::SeqCst) {
+                return json!(null);
+            }
+            arrived.await;
+        }
+    }
+}
+
+/// Sending side of a connection, see [`StratumConnection::requester`]
+///
+/// Shares the socket with its connection, also across reconnects, so a
+/// request can wait for its response without holding on to the connection.
+#[derive(Clone)]
+pub struct Requester {
+    writer: Arc<Mutex<Writer>>,
+    reader: Arc<Mutex<BufReader<Reader>>>,
+    id_counter: Arc<AtomicU64>,
+    config: ConnectionConfig,
+    stats: Arc<Mutex<ConnectionStats>>,
+    inbox: Arc<Inbox>,
+    recorder: RecorderSlot,
+    contention: Arc<Contention>,
+    /// Whether a read loop owns the socket
+    reading: Arc<AtomicBool>,
+    /// Pool as `host:port`, for the context of request errors
+    pool: Arc<std::sync::Mutex<String>>,
+    /// `connection` span of the current stream, parent of request spans
+    span: Arc<std::sync::Mutex<Span>>,
 }
 
 /// Handles the low-level network connection and message passing
 pub struct StratumConnection {
-    writer: Arc<Mutex<OwnedWriteHalf>>,
-    reader: Arc<Mutex<BufReader<OwnedReadHalf>>>,
-    id_counter: AtomicU64,
+    requester: Requester,
     host: String,
     port: u16,
-    config: ConnectionConfig,
-    stats: Arc<Mutex<ConnectionStats>>,
+    /// Task reading the socket, see [`start_read_loop`](Self::start_read_loop)
+    read_loop: Option<JoinHandle<()>>,
+    /// Counters of the connections replaced so far
+    closed: LifetimeStats,
+    /// Final statistics of the last connection replaced, until taken
+    last_closed: Option<ConnectionStats>,
 }
 
 impl StratumConnection {
@@ -68,57 +520,707 @@ impl StratumConnection {
         Self::with_config(host, port, ConnectionConfig::default()).await
     }
 
+    /// Create a connection over a stream the caller opened, see
+    /// [`StreamTransport`]
+    ///
+    /// The connection can't reconnect once the stream ends.
+    pub async fn from_stream<S>(stream: S, config: ConnectionConfig) -> Result<Self, StratumError>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let config = ConnectionConfig {
+            transport: Some(Arc::new(StreamTransport::new(stream))),
+            ..config
+        };
+        Self::with_config("stream".into(), 0, config).await
+    }
+
     /// Create a new connection with custom configuration
+    ///
+    /// The host may carry a `stratum+tcp://` or `stratum+ssl://` scheme; the
+    /// latter connects over TLS with default options unless `config.tls` is set.
+    /// With the `websocket` feature, a `ws://` or `wss://` URL such as
+    /// `wss://pool.example.com/stratum` connects over WebSocket, see
+    /// [`WebSocketTransport`].
     pub async fn with_config(
         host: String,
         port: u16,
         config: ConnectionConfig,
     ) -> Result<Self, StratumError> {
-        let addr = format!("{}:{}", host, port);
-        let stream = TcpStream::connect(&addr).await.map_err(|e| {
-            StratumError::Connection(format!("Failed to connect to {} - {}", addr, e))
-        })?;
+        let (host, config) = Self::resolve(&host, config)?;
+        let (reader, writer) = Self::open(&host, port, &config).await?;
+        let pool = format!("{}:{}", host, port);
 
-        if config.keepalive {
-            stream
-                .set_nodelay(true)
-                .map_err(|e| StratumError::Connection(format!("Failed to set nodelay - {}", e)))?;
+        let connection = Self {
+            requester: Requester {
+                writer: Arc::new(Mutex::new(writer)),
+                reader: Arc::new(Mutex::new(BufReader::new(reader))),
+                id_counter: Arc::new(AtomicU64::new(1)),
+                config,
+                stats: Arc::new(Mutex::new(ConnectionStats {
+                    connected_since: Some(Instant::now()),
+                    ..Default::default()
+                })),
+                inbox: Arc::new(Inbox::default()),
+                recorder: RecorderSlot::default(),
+                contention: Arc::new(Contention::new()),
+                reading: Arc::new(AtomicBool::new(false)),
+                span: Arc::new(std::sync::Mutex::new(connection_span(&pool))),
+                pool: Arc::new(std::sync::Mutex::new(pool)),
+            },
+            host,
+            port,
+            read_loop: None,
+            closed: LifetimeStats::default(),
+            last_closed: None,
+        };
+
+        Ok(connection)
+    }
+
+    /// Strip the scheme from `host`, enabling TLS for `stratum+ssl://` and
+    /// `wss://`, and the WebSocket transport for `ws://` and `wss://`
+    fn resolve(
+        host: &str,
+        config: ConnectionConfig,
+    ) -> Result<(String, ConnectionConfig), StratumError> {
+        let (host, tls, config) = match host.split_once("://") {
+            Some((scheme @ ("ws" | "wss"), rest)) => {
+                let (host, config) = Self::websocket(rest, config)?;
+                (host, scheme == "wss", config)
+            }
+            _ => {
+                let (host, tls) = split_scheme(host)?;
+                (host, tls, config)
+            }
+        };
+        #[cfg(feature = "tls")]
+        let config = match config.tls {
+            None if tls => ConnectionConfig {
+                tls: Some(TlsConfig::default()),
+                ..config
+            },
+            _ => config,
+        };
+        #[cfg(not(feature = "tls"))]
+        if tls {
+            return Err(StratumError::Config(format!(
+                "Connecting to {} requires the `tls` feature",
+                host
+            )));
         }
+        Ok((host.to_string(), config))
+    }
+
+    /// Split a WebSocket URL without its scheme into host and path,
+    /// selecting the WebSocket transport for the path
+    #[cfg(feature = "websocket")]
+    fn websocket(
+        url: &str,
+        config: ConnectionConfig,
+    ) -> Result<(&str, ConnectionConfig), StratumError> {
+        let (host, path) = match url.find('/') {
+            Some(slash) => url.split_at(slash),
+            None => (url, "/"),
+        };
+        Ok((
+            host,
+            ConnectionConfig {
+                transport: Some(Arc::new(WebSocketTransport::new(path))),
+                ..config
+            },
+        ))
+    }
+
+    #[cfg(not(feature = "websocket"))]
+    fn websocket(
+        url: &str,
+        _config: ConnectionConfig,
+    ) -> Result<(&str, ConnectionConfig), StratumError> {
+        Err(StratumError::Config(format!(
+            "Connecting to {} requires the `websocket` feature",
+            url
+        )))
+    }
+
+    /// Open the link to the pool with the configured transport
+    async fn open(
+        host: &str,
+        port: u16,
+        config: &ConnectionConfig,
+    ) -> Result<(Reader, Writer), StratumError> {
+        match &config.transport {
+            Some(transport) => transport.open(host, port, config).await,
+            None => TcpTransport.open(host, port, config).await,
+        }
+    }
+
+    /// Whether the connection runs over TLS
+    pub fn is_tls(&self) -> bool {
+        #[cfg(feature = "tls")]
+        return self.requester.config.tls.is_some();
+        #[cfg(not(feature = "tls"))]
+        false
+    }
+
+    /// Host this connection points to
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Port this connection points to
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Configuration of this connection
+    pub fn config(&self) -> &ConnectionConfig {
+        &self.requester.config
+    }
+
+    /// Record all traffic of this connection, or stop recording with `None`
+    #[cfg(feature = "capture")]
+    pub fn set_recorder(&mut self, recorder: Option<CaptureRecorder>) {
+        if let Ok(mut current) = self.requester.recorder.lock() {
+            *current = recorder;
+        }
+    }
+
+    #[cfg(feature = "capture")]
+    fn record(&self, direction: CaptureDirection, line: &str) {
+        self.requester.record(direction, line);
+    }
+
+    fn mark_disconnected(&self, reason: DisconnectReason) {
+        self.requester.mark_disconnected(reason);
+    }
+
+    /// Take the reason the connection ended, if it was observed since the last call
+    pub fn take_disconnect(&self) -> Option<DisconnectReason> {
+        self.requester.take_disconnect()
+    }
+
+    /// Handle for sending requests without holding on to the connection
+    pub fn requester(&self) -> Requester {
+        self.requester.clone()
+    }
 
-        let (read_half, write_half) = stream.into_split();
+    /// Lock wait counters of this connection
+    pub fn contention(&self) -> Arc<Contention> {
+        self.requester.contention.clone()
+    }
+
+    /// Share lock wait counters with other components of a client
+    pub fn set_contention(&mut self, contention: Arc<Contention>) {
+        self.requester.contention = contention;
+    }
+
+    /// Read the socket from a background task from now on
+    ///
+    /// Responses are routed to the requests waiting for them by id and
+    /// notifications are buffered as they arrive, so requests no longer wait
+    /// on whoever reads notifications. The loop ends with the connection and
+    /// is restarted by [`reconnect`](Self::reconnect).
+    pub fn start_read_loop(&mut self) {
+        if self.read_loop.is_some() {
+            return;
+        }
+        self.requester.reading.store(true, Ordering::SeqCst);
+        self.read_loop = Some(tokio::spawn(
+            read_loop(
+                self.requester.reader.clone(),
+                self.requester.inbox.clone(),
+                self.requester.stats.clone(),
+                self.requester.recorder.clone(),
+                self.requester.config.max_buffered_notifications,
+            )
+            .instrument(self.requester.span()),
+        ));
+    }
+
+    /// Whether a read loop owns the socket
+    pub fn has_read_loop(&self) -> bool {
+        self.read_loop.is_some()
+    }
+
+    /// Inbound messages, for waiting on notifications without holding the connection
+    pub(crate) fn inbox(&self) -> Arc<Inbox> {
+        self.requester.inbox.clone()
+    }
+
+    /// Get current connection statistics
+    pub async fn stats(&self) -> ConnectionStats {
+        self.requester.stats.lock().await.clone()
+    }
+
+    /// Counters summed over every connection opened, the current one included
+    ///
+    /// [`stats`](Self::stats) start from zero on each reconnect, these don't.
+    pub async fn lifetime_stats(&self) -> LifetimeStats {
+        let mut lifetime = self.closed.clone();
+        lifetime.add(&*self.requester.stats.lock().await);
+        lifetime
+    }
+
+    /// Take the final statistics of the connection the last reconnect
+    /// replaced, if not taken since
+    pub fn take_closed_stats(&mut self) -> Option<ConnectionStats> {
+        self.last_closed.take()
+    }
+
+    /// Send a request and wait for response with automatic retries,
+    /// see [`Requester::send_request`]
+    pub async fn send_request(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+    ) -> Result<JsonRpcResponse, StratumError> {
+        self.requester.send_request(method, params).await
+    }
+
+    /// Send a request once and wait up to `wait` for its response,
+    /// see [`Requester::send_request_once`]
+    pub async fn send_request_once(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+        wait: Duration,
+    ) -> Result<JsonRpcResponse, StratumError> {
+        self.requester.send_request_once(method, params, wait).await
+    }
+
+    /// Time since the last message was sent or received
+    pub async fn idle_time(&self) -> Duration {
+        self.requester.idle_time().await
+    }
+
+    /// Check without blocking whether the socket still looks usable,
+    /// see [`Requester::probe`]
+    pub async fn probe(&self) -> bool {
+        self.requester.probe().await
+    }
+
+    /// Read a notification if one arrives within `wait`
+    ///
+    /// Returns `None` when nothing was received in time. Waiting doesn't
+    /// consume any data, so a partially received line is never lost.
+    pub async fn poll_notification(&self, wait: Duration) -> Result<Option<Value>, StratumError> {
+        if self.read_loop.is_some() {
+            return Ok(timeout(wait, self.requester.inbox.next_notification())
+                .await
+                .ok());
+        }
+        if let Some(notification) = self.requester.inbox.notifications.lock().await.pop_front() {
+            return Ok(Some(notification));
+        }
+
+        {
+            let mut reader = self.requester.reader.lock().await;
+            match timeout(wait, reader.fill_buf()).await {
+                Err(_) => return Ok(None),
+                Ok(Err(e)) => return Err(StratumError::Protocol(format!("Read error: {}", e))),
+                Ok(Ok(_)) => {}
+            }
+        }
+
+        self.read_notification().await.map(Some)
+    }
+
+    /// Read a single notification from the server
+    pub async fn read_notification(&self) -> Result<Value, StratumError> {
+        if self.read_loop.is_some() {
+            return Ok(self.requester.inbox.next_notification().await);
+        }
+        if let Some(notification) = self.requester.inbox.notifications.lock().await.pop_front() {
+            return Ok(notification);
+        }
+
+        let reader_lock = timeout(
+            Duration::from_secs(self.requester.config.timeout),
+            self.requester.reader.lock(),
+        )
+        .await
+        .map_err(|_| StratumError::Protocol("Reader lock timeout in notifications".into()))?;
+
+        // Update error stats if we got a timeout
+        {
+            let mut stats = self.requester.stats.lock().await;
+            stats.errors += 1;
+        }
+
+        let mut reader = reader_lock;
+        let mut line = String::new();
+
+        loop {
+            match timeout(
+                Duration::from_secs(self.requester.config.timeout),
+                reader.read_line(&mut line),
+            )
+            .await
+            {
+                Ok(Ok(0)) => {
+                    // The pool closed the connection
+                    self.mark_disconnected(DisconnectReason::PeerClosed);
+                    return Ok(json!(null));
+                }
+                Ok(Ok(_)) => {
+                    #[cfg(feature = "capture")]
+                    self.record(CaptureDirection::Received, &line);
+                    return match serde_json::from_str(line.trim()) {
+                        Ok(value) => {
+                            // Update stats
+                            let mut stats = self.requester.stats.lock().await;
+                            stats.messages_received += 1;
+                            stats.last_message_at = Some(Instant::now());
+                            Ok(value)
+                        }
+                        Err(e) => {
+                            if line.trim().is_empty() {
+                                Ok(json!(null))
+                            } else {
+                                let err = StratumError::Protocol(format!(
+                                    "Invalid JSON notification: {}",
+                                    e
+                                ));
+                                let mut stats = self.requester.stats.lock().await;
+                                stats.errors += 1;
+                                Err(err)
+                            }
+                        }
+                    };
+                }
+                Ok(Err(e)) => {
+                    self.mark_disconnected(DisconnectReason::from_io(&e));
+                    let err = StratumError::Protocol(format!("Read error in notifications: {}", e));
+                    let mut stats = self.requester.stats.lock().await;
+                    stats.errors += 1;
+                    return Err(err);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        target: Category::Connection.target(),
+                        error = %e,
+                        "Read timeout in notifications, retrying"
+                    );
+                    continue;
+                }
+            }
+        }
+    }
+
+    /// Reconnect to the server
+    pub async fn reconnect(&mut self) -> Result<(), StratumError> {
+        let (reader, writer) = Self::open(&self.host, self.port, &self.requester.config).await?;
+        self.replace_stream(reader, writer).await;
+        Ok(())
+    }
+
+    /// Reconnect to the server, retrying as `policy` allows
+    pub async fn reconnect_with(&mut self, policy: &ReconnectPolicy) -> Result<(), StratumError> {
+        let (reader, writer) = policy
+            .retry(|| Self::open(&self.host, self.port, &self.requester.config))
+            .await?;
+        self.replace_stream(reader, writer).await;
+        Ok(())
+    }
+
+    /// Connect to another pool, keeping the connection options
+    ///
+    /// The host may carry a scheme as in [`with_config`](Self::with_config).
+    /// If the new pool can't be reached, the current connection is kept.
+    pub async fn connect_to(&mut self, host: &str, port: u16) -> Result<(), StratumError> {
+        let (host, config) = Self::resolve(host, self.requester.config.clone())?;
+        let (reader, writer) = Self::open(&host, port, &config).await?;
+        *self.requester.pool.lock().unwrap() = format!("{}:{}", host, port);
+        self.host = host;
+        self.port = port;
+        self.requester.config = config;
+        self.replace_stream(reader, writer).await;
+        Ok(())
+    }
+
+    /// Switch to a freshly opened stream, resetting the connection state
+    async fn replace_stream(&mut self, reader: Reader, writer: Writer) {
+        let read_loop = self.read_loop.take().inspect(JoinHandle::abort).is_some();
+        self.requester.reading.store(false, Ordering::SeqCst);
+        *self.requester.writer.lock().await = writer;
+        *self.requester.reader.lock().await = BufReader::new(reader);
+        self.requester.inbox.shut();
+        self.requester.inbox.notifications.lock().await.clear();
+        self.requester.inbox.closed.store(false, Ordering::SeqCst);
+        self.take_disconnect();
+        let pool = self.requester.pool.lock().unwrap().clone();
+        *self.requester.span.lock().unwrap() = connection_span(&pool);
+        if read_loop {
+            self.start_read_loop();
+        }
+
+        // Reset stats, keeping the final ones of the replaced connection
+        let mut stats = self.requester.stats.lock().await;
+        let closed = std::mem::replace(
+            &mut *stats,
+            ConnectionStats {
+                connected_since: Some(Instant::now()),
+                ..Default::default()
+            },
+        );
+        drop(stats);
+        self.closed.add(&closed);
+        self.last_closed = Some(closed);
+    }
+
+    /// Close the connection
+    pub async fn close(&mut self) -> Result<(), StratumError> {
+        if let Some(read_loop) = self.read_loop.take() {
+            read_loop.abort();
+            self.requester.reading.store(false, Ordering::SeqCst);
+            self.requester.inbox.shut();
+        }
+        let mut writer = self.requester.writer.lock().await;
+        writer.shutdown().await?;
+
+        // Clear stats
+        let mut stats = self.requester.stats.lock().await;
+        stats.connected_since = None;
+
+        Ok(())
+    }
+}
+
+impl Requester {
+    /// Time since the last message was sent or received
+    pub async fn idle_time(&self) -> Duration {
+        let stats = self.stats.lock().await;
+        stats
+            .last_message_at
+            .or(stats.connected_since)
+            .map(|at| at.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Check without blocking whether the socket still looks usable
+    ///
+    /// Polls the socket once: a closed or failed socket is reported right away
+    /// while pending data stays buffered for the next read. A peer that
+    /// vanished without closing the connection can't be detected this way.
+    pub async fn probe(&self) -> bool {
+        if self.reading.load(Ordering::SeqCst) {
+            return !self.inbox.closed.load(Ordering::SeqCst);
+        }
+        if !self.inbox.notifications.lock().await.is_empty() {
+            return true;
+        }
+        let Ok(mut reader) = self.reader.try_lock() else {
+            // Someone is reading, so the connection is in use
+            return true;
+        };
+        match timeout(Duration::ZERO, reader.fill_buf()).await {
+            Err(_) => true,
+            Ok(Ok(buffered)) if !buffered.is_empty() => true,
+            Ok(Ok(_)) => {
+                self.mark_disconnected(DisconnectReason::PeerClosed);
+                false
+            }
+            Ok(Err(e)) => {
+                self.mark_disconnected(DisconnectReason::from_io(&e));
+                false
+            }
+        }
+    }
+
+    #[cfg(feature = "capture")]
+    fn record(&self, direction: CaptureDirection, line: &str) {
+        record(&self.recorder, direction, line);
+    }
+
+    fn mark_disconnected(&self, reason: DisconnectReason) {
+        self.inbox.mark_disconnected(reason);
+    }
+
+    /// `connection` span of the current stream
+    fn span(&self) -> Span {
+        self.span.lock().unwrap().clone()
+    }
+
+    /// `request` span of a request sent over the current stream
+    fn request_span(&self, method: &str, id: Option<u64>) -> Span {
+        tracing::info_span!(
+            target: Category::Connection.target(),
+            parent: &self.span(),
+            "request",
+            method,
+            id,
+            attempt = field::Empty,
+        )
+    }
+
+    /// Take the reason the connection ended, if it was observed since the last call
+    pub fn take_disconnect(&self) -> Option<DisconnectReason> {
+        self.inbox.disconnect.lock().ok()?.take()
+    }
+
+    /// Wait up to `wait` for the response to request `id`
+    ///
+    /// With a read loop running the response is routed to `response`.
+    /// Otherwise the socket is read directly: notifications are buffered and
+    /// responses to other outstanding requests are handed to their callers,
+    /// which may in turn deliver this request's response while it waits for
+    /// the reader. `last_attempt` marks a timeout as a disconnect.
+    async fn await_response(
+        &self,
+        method: &str,
+        id: u64,
+        mut response: oneshot::Receiver<JsonRpcResponse>,
+        wait: Duration,
+        last_attempt: bool,
+    ) -> Result<JsonRpcResponse, StratumError> {
+        let closed =
+            || StratumError::Connection("Connection closed while waiting for response".into());
+        let sent_at = Instant::now();
+        let result = timeout(wait, async {
+            if self.reading.load(Ordering::SeqCst) {
+                return (&mut response).await.map_err(|_| closed());
+            }
+
+            let mut reader = tokio::select! {
+                routed = &mut response => return routed.map_err(|_| closed()),
+                reader = self.reader.lock() => reader,
+            };
+            // Whoever held the reader may have routed the response meanwhile
+            if let Ok(routed) = response.try_recv() {
+                return Ok(routed);
+            }
+
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line).await {
+                    Ok(0) => {
+                        self.mark_disconnected(DisconnectReason::PeerClosed);
+                        return Err(closed());
+                    }
+                    Err(e) => {
+                        self.mark_disconnected(DisconnectReason::from_io(&e));
+                        return Err(StratumError::Protocol(format!("Read error: {}", e)));
+                    }
+                    Ok(_) => {}
+                }
+                #[cfg(feature = "capture")]
+                self.record(CaptureDirection::Received, &line);
+                if self.buffer_if_notification(&line).await {
+                    continue;
+                }
+                tracing::debug!(
+                    target: Category::Connection.target(),
+                    line = line.trim(),
+                    "Received response"
+                );
+
+                let parsed: JsonRpcResponse = match serde_json::from_str(line.trim()) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        let mut stats = self.stats.lock().await;
+                        stats.errors += 1;
+                        stats.retries += 1;
+                        return Err(StratumError::Protocol(format!(
+                            "Invalid JSON response: {}",
+                            e
+                        )));
+                    }
+                };
+                let mut stats = self.stats.lock().await;
+                stats.messages_received += 1;
+                stats.last_message_at = Some(Instant::now());
+                drop(stats);
+
+                // Pools answering without an id are assumed to answer in order
+                if parsed.id.is_none_or(|response_id| response_id == id) {
+                    return Ok(parsed);
+                }
+                self.inbox.respond(parsed);
+            }
+        })
+        .await;
+
+        self.inbox.forget(id);
+        if let Ok(Ok(_)) = &result {
+            self.stats
+                .lock()
+                .await
+                .request_timing
+                .entry(method.to_string())
+                .or_default()
+                .record(sent_at.elapsed());
+        }
+        result.unwrap_or_else(|_| {
+            if last_attempt {
+                self.mark_disconnected(DisconnectReason::Timeout);
+            }
+            Err(StratumError::Protocol(format!(
+                "No response to {} within {:?}",
+                method, wait
+            )))
+        })
+    }
 
-        let connection = Self {
-            writer: Arc::new(Mutex::new(write_half)),
-            reader: Arc::new(Mutex::new(BufReader::new(read_half))),
-            id_counter: AtomicU64::new(1),
-            host,
-            port,
-            config,
-            stats: Arc::new(Mutex::new(ConnectionStats {
-                connected_since: Some(Instant::now()),
-                ..Default::default()
-            })),
+    /// Buffer the line if it is a notification rather than a response
+    ///
+    /// Pools may interleave notifications with responses; buffered notifications
+    /// are handed out by [`read_notification`](StratumConnection::read_notification) first.
+    async fn buffer_if_notification(&self, line: &str) -> bool {
+        let Ok(value) = serde_json::from_str::<Value>(line.trim()) else {
+            return false;
         };
 
-        Ok(connection)
-    }
+        if value.get("method").and_then(Value::as_str).is_none() {
+            return false;
+        }
 
-    /// Get current connection statistics
-    pub async fn stats(&self) -> ConnectionStats {
-        self.stats.lock().await.clone()
+        self.inbox
+            .push_notification(value, self.config.max_buffered_notifications, &self.stats)
+            .await;
+        true
     }
 
     /// Send a request and wait for response with automatic retries
+    ///
+    /// Responses are matched to requests by id, so several requests may be
+    /// outstanding at once and notifications arriving in between are buffered
+    /// for [`read_notification`](StratumConnection::read_notification).
+    ///
+    /// Errors carry the method, pool, request id and attempt as
+    /// [`ErrorContext`].
     pub async fn send_request(
         &self,
         method: &str,
         params: Vec<Value>,
+    ) -> Result<JsonRpcResponse, StratumError> {
+        let pool = self.pool.lock().unwrap().clone();
+        let mut context = ErrorContext::new(method).pool(pool);
+        let span = self.request_span(method, None);
+        self.send_request_retrying(method, params, &mut context, &span)
+            .instrument(span.clone())
+            .await
+            .map_err(|err| err.context(context))
+    }
+
+    /// Body of [`send_request`](Self::send_request), keeping `context` and
+    /// the request's `span` up to date with the current attempt
+    async fn send_request_retrying(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+        context: &mut ErrorContext,
+        span: &Span,
     ) -> Result<JsonRpcResponse, StratumError> {
         let mut retry_count = 0;
         let mut last_error = None;
 
         while retry_count < self.config.max_retries {
             let id = self.id_counter.fetch_add(1, Ordering::SeqCst);
+            context.request_id = Some(id);
+            context.attempt = Some(retry_count + 1);
+            span.record("id", id).record("attempt", retry_count + 1);
             let request = JsonRpcRequest {
                 id,
                 method: method.to_string(),
@@ -128,146 +1230,87 @@ impl StratumConnection {
             let json = serde_json::to_string(&request).map_err(|e| {
                 StratumError::Protocol(format!("Failed to serialize request - {}", e))
             })?;
+            let response = self.inbox.expect_response(id);
 
             // Try to acquire locks with timeout
-            let writer_lock = timeout(Duration::from_secs(self.config.timeout), self.writer.lock())
-                .await
-                .map_err(|_| {
-                    let err = StratumError::Protocol("Writer lock timeout".into());
-                    last_error = Some(err.clone());
-                    err
-                })?;
+            let writer_lock = timeout(
+                Duration::from_secs(self.config.timeout),
+                self.contention.lock(LockSite::Writer, &self.writer),
+            )
+            .await
+            .map_err(|_| {
+                self.inbox.forget(id);
+                StratumError::Protocol("Writer lock timeout".into())
+            })?;
 
             let mut writer = writer_lock;
 
             // Send with timeout
-            match timeout(
+            let written = timeout(
                 Duration::from_secs(self.config.timeout),
                 writer.write_all(format!("{}\n", json).as_bytes()),
             )
-            .await
-            {
+            .await;
+            drop(writer);
+            let err = match written {
                 Ok(Ok(_)) => {
+                    #[cfg(feature = "capture")]
+                    self.record(CaptureDirection::Sent, &json);
                     // Update stats
                     let mut stats = self.stats.lock().await;
                     stats.messages_sent += 1;
                     stats.last_message_at = Some(Instant::now());
-                }
-                Ok(Err(e)) => {
-                    let err = StratumError::Protocol(format!("Write error: {}", e));
-                    last_error = Some(err.clone());
-                    retry_count += 1;
-                    if retry_count == self.config.max_retries {
-                        return Err(err);
-                    }
-                    sleep(Duration::from_secs(self.config.retry_delay << retry_count)).await;
-                    continue;
-                }
-                Err(e) => {
-                    let err = StratumError::Protocol(format!("Write timeout: {}", e));
-                    last_error = Some(err.clone());
-                    retry_count += 1;
-                    if retry_count == self.config.max_retries {
-                        return Err(err);
-                    }
-                    sleep(Duration::from_secs(self.config.retry_delay << retry_count)).await;
-                    continue;
-                }
-            }
-
-            let reader_lock = timeout(Duration::from_secs(self.config.timeout), self.reader.lock())
-                .await
-                .map_err(|_| {
-                    let err = StratumError::Protocol("Reader lock timeout".into());
-                    last_error = Some(err.clone());
-                    err
-                })?;
-
-            let mut reader = reader_lock;
-            let mut line = String::new();
-
-            // Read with timeout
-            match timeout(
-                Duration::from_secs(self.config.timeout),
-                reader.read_line(&mut line),
-            )
-            .await
-            {
-                Ok(Ok(0)) => {
-                    let err = StratumError::Protocol("Empty response from server".into());
-                    last_error = Some(err.clone());
-                    retry_count += 1;
-                    if retry_count == self.config.max_retries {
-                        return Err(err);
-                    }
-                    sleep(Duration::from_secs(self.config.retry_delay << retry_count)).await;
-                    continue;
-                }
-                Ok(Ok(_)) => {
-                    log::info!(target: "stratum", "[Raw] Client received response: {}", line.trim());
-                    match serde_json::from_str(&line) {
+                    drop(stats);
+
+                    let wait = Duration::from_secs(self.config.timeout);
+                    let last_attempt = retry_count + 1 == self.config.max_retries;
+                    match self
+                        .await_response(method, id, response, wait, last_attempt)
+                        .await
+                    {
                         Ok(response) => {
-                            let response: JsonRpcResponse = response;
-                            println!("Client received response: {}", line.trim());
-                            println!("Parsed response: {:?}", response);
-
-                            // Update stats
-                            let mut stats = self.stats.lock().await;
-                            stats.messages_received += 1;
-                            stats.last_message_at = Some(Instant::now());
-
                             if let Some(error) = response.error.as_ref() {
-                                let err = StratumError::Protocol(
-                                    serde_json::to_string(error)
-                                        .unwrap_or_else(|_| error.to_string()),
-                                );
-                                return Err(err);
+                                let code = response.error_code();
+                                return Err(match code.and_then(StratumRpcError::from_code) {
+                                    Some(error) => StratumError::Rpc {
+                                        error,
+                                        message: response.error_message().unwrap_or_default(),
+                                    },
+                                    None => StratumError::Protocol(
+                                        serde_json::to_string(error)
+                                            .unwrap_or_else(|_| error.to_string()),
+                                    ),
+                                });
                             }
                             return Ok(response);
                         }
-                        Err(e) => {
-                            eprintln!("Error parsing response: {}", e);
-                            eprintln!("Raw response line: {}", line);
-                            let err =
-                                StratumError::Protocol(format!("Invalid JSON response: {}", e));
-                            last_error = Some(err.clone());
-                            retry_count += 1;
-
-                            // Update error stats
-                            let mut stats = self.stats.lock().await;
-                            stats.errors += 1;
-                            stats.retries += 1;
-
-                            if retry_count == self.config.max_retries {
-                                return Err(err);
-                            }
-                            sleep(Duration::from_secs(self.config.retry_delay << retry_count))
-                                .await;
-                            continue;
-                        }
+                        // Resending is pointless once the connection ended
+                        Err(err) if err.kind() == ErrorKind::Connection => return Err(err),
+                        Err(err) => err,
                     }
                 }
                 Ok(Err(e)) => {
-                    let err = StratumError::Protocol(format!("Read error: {}", e));
-                    last_error = Some(err.clone());
-                    retry_count += 1;
-                    if retry_count == self.config.max_retries {
-                        return Err(err);
-                    }
-                    sleep(Duration::from_secs(self.config.retry_delay << retry_count)).await;
-                    continue;
+                    self.inbox.forget(id);
+                    self.mark_disconnected(DisconnectReason::from_io(&e));
+                    StratumError::Protocol(format!("Write error: {}", e))
                 }
                 Err(e) => {
-                    let err = StratumError::Protocol(format!("Read timeout: {}", e));
-                    last_error = Some(err.clone());
-                    retry_count += 1;
-                    if retry_count == self.config.max_retries {
-                        return Err(err);
-                    }
-                    sleep(Duration::from_secs(self.config.retry_delay << retry_count)).await;
-                    continue;
+                    self.inbox.forget(id);
+                    StratumError::Protocol(format!("Write timeout: {}", e))
                 }
+            };
+
+            tracing::debug!(
+                target: Category::Connection.target(),
+                error = %err,
+                "Request attempt failed"
+            );
+            last_error = Some(err.clone());
+            retry_count += 1;
+            if retry_count == self.config.max_retries {
+                return Err(err);
             }
+            sleep(Duration::from_secs(self.config.retry_delay << retry_count)).await;
         }
 
         // Update error stats
@@ -284,104 +1327,239 @@ impl StratumConnection {
         }))
     }
 
-    /// Read a single notification from the server
-    pub async fn read_notification(&self) -> Result<Value, StratumError> {
-        let reader_lock = timeout(Duration::from_secs(self.config.timeout), self.reader.lock())
-            .await
-            .map_err(|_| StratumError::Protocol("Reader lock timeout in notifications".into()))?;
-
-        // Update error stats if we got a timeout
-        {
-            let mut stats = self.stats.lock().await;
-            stats.errors += 1;
+    /// Send a request once and wait up to `wait` for its response
+    ///
+    /// Unlike [`send_request`](Self::send_request) the request is never
+    /// resent, which matters for requests the pool may answer late such as
+    /// `mining.authorize`. Notifications arriving in the meantime are buffered.
+    /// The response is returned as is, including any error it carries.
+    pub async fn send_request_once(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+        wait: Duration,
+    ) -> Result<JsonRpcResponse, StratumError> {
+        let id = self.id_counter.fetch_add(1, Ordering::SeqCst);
+        let json = serde_json::to_string(&JsonRpcRequest::new(id, method, params))
+            .map_err(|e| StratumError::Protocol(format!("Failed to serialize request - {}", e)))?;
+        let response = self.inbox.expect_response(id);
+
+        async {
+            if let Err(err) = self.write_line(&json).await {
+                self.inbox.forget(id);
+                return Err(err);
+            }
+            self.await_response(method, id, response, wait, false).await
         }
+        .instrument(self.request_span(method, Some(id)))
+        .await
+    }
 
-        let mut reader = reader_lock;
-        let mut line = String::new();
-
-        loop {
-            match timeout(
-                Duration::from_secs(self.config.timeout),
-                reader.read_line(&mut line),
-            )
-            .await
-            {
-                Ok(Ok(0)) => return Ok(json!(null)), // No data available
-                Ok(Ok(_)) => {
-                    return match serde_json::from_str(&line.trim()) {
-                        Ok(value) => {
-                            // Update stats
-                            let mut stats = self.stats.lock().await;
-                            stats.messages_received += 1;
-                            stats.last_message_at = Some(Instant::now());
-                            Ok(value)
-                        }
-                        Err(e) => {
-                            if line.trim().is_empty() {
-                                Ok(json!(null))
-                            } else {
-                                let err = StratumError::Protocol(format!(
-                                    "Invalid JSON notification: {}",
-                                    e
-                                ));
-                                let mut stats = self.stats.lock().await;
-                                stats.errors += 1;
-                                Err(err)
-                            }
-                        }
-                    };
-                }
-                Ok(Err(e)) => {
-                    let err = StratumError::Protocol(format!("Read error in notifications: {}", e));
-                    let mut stats = self.stats.lock().await;
-                    stats.errors += 1;
-                    return Err(err);
-                }
-                Err(e) => {
-                    log::warn!(target: "stratum", "Read timeout in notifications, retrying ...: {e}");
-                    continue;
+    /// Send a request without waiting for its response
+    ///
+    /// For hints like `mining.suggest_difficulty` that many pools never
+    /// answer. A response arriving within `wait` is taken in the background
+    /// rather than reported as unexpected.
+    pub async fn send_request_detached(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+        wait: Duration,
+    ) -> Result<(), StratumError> {
+        let id = self.id_counter.fetch_add(1, Ordering::SeqCst);
+        let json = serde_json::to_string(&JsonRpcRequest::new(id, method, params))
+            .map_err(|e| StratumError::Protocol(format!("Failed to serialize request - {}", e)))?;
+        let response = self.inbox.expect_response(id);
+
+        if let Err(err) = self.write_line(&json).await {
+            self.inbox.forget(id);
+            return Err(err);
+        }
+        let inbox = self.inbox.clone();
+        let span = self.request_span(method, Some(id));
+        tokio::spawn(
+            async move {
+                if let Ok(Ok(response)) = timeout(wait, response).await {
+                    tracing::debug!(
+                        target: Category::Connection.target(),
+                        response = ?response,
+                        "Received response to detached request"
+                    );
                 }
+                inbox.forget(id);
             }
-        }
+            .instrument(span),
+        );
+        Ok(())
     }
 
-    /// Reconnect to the server
-    pub async fn reconnect(&mut self) -> Result<(), StratumError> {
-        let addr = format!("{}:{}", self.host, self.port);
-        let stream = TcpStream::connect(&addr).await.map_err(|e| {
-            StratumError::Connection(format!("Failed to connect to {} - {}", addr, e))
-        })?;
+    /// Answer request `id` sent by the pool, such as `client.get_version`
+    pub async fn send_response(&self, id: Value, result: Value) -> Result<(), StratumError> {
+        let json = json!({"id": id, "result": result, "error": null}).to_string();
+        self.write_line(&json).await
+    }
 
-        if self.config.keepalive {
-            stream
-                .set_nodelay(true)
-                .map_err(|e| StratumError::Connection(format!("Failed to set nodelay - {}", e)))?;
+    /// Answer request `id` sent by the pool with an error
+    ///
+    /// Errors take the Stratum V1 form `[code, message, traceback]`, with
+    /// JSON-RPC codes such as -32601 for methods this client doesn't know.
+    pub async fn send_error_response(
+        &self,
+        id: Value,
+        code: i64,
+        message: &str,
+    ) -> Result<(), StratumError> {
+        let json = json!({"id": id, "result": null, "error": [code, message, null]}).to_string();
+        self.write_line(&json).await
+    }
+
+    /// Write one message, without retrying
+    async fn write_line(&self, json: &str) -> Result<(), StratumError> {
+        let written = timeout(
+            Duration::from_secs(self.config.timeout),
+            self.contention
+                .lock(LockSite::Writer, &self.writer)
+                .await
+                .write_all(format!("{}\n", json).as_bytes()),
+        )
+        .await;
+        written
+            .map_err(|_| StratumError::Protocol("Write timeout".into()))
+            .and_then(|written| {
+                written.map_err(|e| StratumError::Protocol(format!("Write error: {}", e)))
+            })?;
+        #[cfg(feature = "capture")]
+        self.record(CaptureDirection::Sent, json);
+        let mut stats = self.stats.lock().await;
+        stats.messages_sent += 1;
+        stats.last_message_at = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl Drop for StratumConnection {
+    fn drop(&mut self) {
+        if let Some(read_loop) = self.read_loop.take() {
+            read_loop.abort();
         }
+    }
+}
 
-        let (read_half, write_half) = stream.into_split();
-        *self.writer.lock().await = write_half;
-        *self.reader.lock().await = BufReader::new(read_half);
+/// Where a connection's traffic is recorded, see
+/// [`StratumConnection::set_recorder`]
+#[cfg(feature = "capture")]
+type RecorderSlot = Arc<std::sync::Mutex<Option<CaptureRecorder>>>;
+/// Nothing is recorded without the `capture` feature
+#[cfg(not(feature = "capture"))]
+type RecorderSlot = Arc<()>;
+
+#[cfg(feature = "capture")]
+fn record(
+    recorder: &std::sync::Mutex<Option<CaptureRecorder>>,
+    direction: CaptureDirection,
+    line: &str,
+) {
+    if let Ok(recorder) = recorder.lock() {
+        if let Some(recorder) = recorder.as_ref() {
+            recorder.record(direction, line);
+        }
+    }
+}
 
-        // Reset stats
-        let mut stats = self.stats.lock().await;
-        *stats = ConnectionStats {
-            connected_since: Some(Instant::now()),
-            ..Default::default()
+/// Read lines until the connection ends, routing responses and notifications
+async fn read_loop(
+    reader: Arc<Mutex<BufReader<Reader>>>,
+    inbox: Arc<Inbox>,
+    stats: Arc<Mutex<ConnectionStats>>,
+    #[cfg_attr(not(feature = "capture"), allow(unused_variables))] recorder: RecorderSlot,
+    max_buffered_notifications: usize,
+) {
+    let mut reader = reader.lock().await;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) => {
+                inbox.mark_disconnected(DisconnectReason::PeerClosed);
+                break;
+            }
+            Err(e) => {
+                inbox.mark_disconnected(DisconnectReason::from_io(&e));
+                break;
+            }
+            Ok(_) => {}
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        #[cfg(feature = "capture")]
+        record(&recorder, CaptureDirection::Received, &line);
+
+        let value = match serde_json::from_str::<Value>(line.trim()) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!(
+                    target: Category::Connection.target(),
+                    error = %e,
+                    line = line.trim(),
+                    "Ignoring invalid JSON from pool"
+                );
+                stats.lock().await.errors += 1;
+                continue;
+            }
         };
+        if value.get("method").and_then(Value::as_str).is_some() {
+            inbox
+                .push_notification(value, max_buffered_notifications, &stats)
+                .await;
+            continue;
+        }
 
-        Ok(())
+        match serde_json::from_value::<JsonRpcResponse>(value) {
+            Ok(response) => {
+                let mut stats = stats.lock().await;
+                stats.messages_received += 1;
+                stats.last_message_at = Some(Instant::now());
+                drop(stats);
+                inbox.respond(response);
+            }
+            Err(e) => {
+                tracing::warn!(
+                    target: Category::Connection.target(),
+                    error = %e,
+                    line = line.trim(),
+                    "Ignoring invalid response from pool"
+                );
+                stats.lock().await.errors += 1;
+            }
+        }
     }
+    inbox.shut();
+}
 
-    /// Close the connection
-    pub async fn close(&mut self) -> Result<(), StratumError> {
-        let mut writer = self.writer.lock().await;
-        writer.shutdown().await?;
-
-        // Clear stats
-        let mut stats = self.stats.lock().await;
-        stats.connected_since = None;
+/// `connection` span of a stream opened to `pool`, given as `host:port`
+fn connection_span(pool: &str) -> Span {
+    tracing::info_span!(
+        target: Category::Connection.target(),
+        parent: None,
+        "connection",
+        pool
+    )
+}
 
-        Ok(())
+/// Strip a `stratum+tcp://` or `stratum+ssl://` scheme from a host,
+/// returning the bare host and whether it asks for TLS
+fn split_scheme(host: &str) -> Result<(&str, bool), StratumError> {
+    let Some((scheme, rest)) = host.split_once("://") else {
+        return Ok((host, false));
+    };
+    let rest = rest.trim_end_matches('/');
+    match PoolUrl::scheme(scheme)? {
+        (StratumVersion::V1, tls) => Ok((rest, tls)),
+        _ => Err(StratumError::Config(format!(
+            "Unsupported pool scheme {}://",
+            scheme
+        ))),
     }
 }
 
@@ -404,6 +1582,11 @@ mod tests {
             max_retries: 5,
             retry_delay: 2,
             keepalive: true,
+            max_buffered_notifications: 16,
+            #[cfg(feature = "tls")]
+            tls: None,
+            proxy: None,
+            transport: None,
         };
 
         let (listener, host, port) = setup_test_server().await;
@@ -411,7 +1594,7 @@ mod tests {
         tokio::spawn(async move {
             let (mut socket, _) = listener.accept().await.unwrap();
             let mut buf = vec![0; 1024];
-            socket.read(&mut buf).await.unwrap();
+            let _ = socket.read(&mut buf).await.unwrap();
 
             let response = json!({
                 "id": 1,
@@ -428,10 +1611,10 @@ mod tests {
         let conn = StratumConnection::with_config(host, port, config)
             .await
             .unwrap();
-        assert_eq!(conn.config.timeout, 10);
-        assert_eq!(conn.config.max_retries, 5);
-        assert_eq!(conn.config.retry_delay, 2);
-        assert!(conn.config.keepalive);
+        assert_eq!(conn.config().timeout, 10);
+        assert_eq!(conn.config().max_retries, 5);
+        assert_eq!(conn.config().retry_delay, 2);
+        assert!(conn.config().keepalive);
     }
 
     #[tokio::test]
@@ -441,7 +1624,7 @@ mod tests {
         tokio::spawn(async move {
             let (mut socket, _) = listener.accept().await.unwrap();
             let mut buf = vec![0; 1024];
-            socket.read(&mut buf).await.unwrap();
+            let _ = socket.read(&mut buf).await.unwrap();
 
             let response = json!({
                 "id": 1,
@@ -476,6 +1659,270 @@ mod tests {
         assert!(stats.last_message_at.is_some());
     }
 
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_request_timing() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new().delay("mining.subscribe", Duration::from_millis(20));
+        let conn = StratumConnection::with_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool),
+        )
+        .await
+        .unwrap();
+        conn.send_request("mining.subscribe", vec![]).await.unwrap();
+        conn.send_request("mining.subscribe", vec![]).await.unwrap();
+        conn.send_request_once("mining.submit", vec![], Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let timing = conn.stats().await.request_timing;
+        let subscribe = &timing["mining.subscribe"];
+        assert_eq!(subscribe.count, 2);
+        assert!(subscribe.min >= Duration::from_millis(20));
+        assert!(subscribe.min <= subscribe.mean() && subscribe.mean() <= subscribe.max);
+        assert_eq!(subscribe.p95(), Some(subscribe.max));
+        assert_eq!(timing["mining.submit"].count, 1);
+        assert!(!timing.contains_key("mining.authorize"));
+    }
+
+    #[test]
+    fn test_request_timing_mean_beyond_u32_count() {
+        let timing = RequestTiming {
+            count: 1 << 32,
+            total: Duration::from_millis(1 << 32),
+            ..Default::default()
+        };
+        assert_eq!(timing.mean(), Duration::from_millis(1));
+    }
+
+    #[tokio::test]
+    async fn test_notification_backlog_is_bounded() {
+        let (listener, host, port) = setup_test_server().await;
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+
+            let mut lines = String::new();
+            for i in 0..5 {
+                let notify = json!({"id": null, "method": "mining.set_difficulty", "params": [i]});
+                lines.push_str(&format!("{}\n", notify));
+            }
+            lines.push_str(&format!(
+                "{}\n",
+                json!({"id": 1, "result": true, "error": null})
+            ));
+            socket.write_all(lines.as_bytes()).await.unwrap();
+        });
+
+        let config = ConnectionConfig {
+            max_buffered_notifications: 2,
+            ..Default::default()
+        };
+        let conn = StratumConnection::with_config(host, port, config)
+            .await
+            .unwrap();
+
+        // The response is never dropped
+        let response = conn.send_request("test", vec![]).await.unwrap();
+        assert_eq!(response.result, Some(json!(true)));
+
+        // Only the newest notifications are kept
+        assert_eq!(conn.stats().await.notifications_dropped, 3);
+        assert_eq!(conn.read_notification().await.unwrap()["params"][0], 3);
+        assert_eq!(conn.read_notification().await.unwrap()["params"][0], 4);
+    }
+
+    /// Pool answering two requests out of order, with a notification in between
+    async fn out_of_order_server() -> (String, u16) {
+        use tokio::io::AsyncBufReadExt;
+
+        let (listener, host, port) = setup_test_server().await;
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(read_half).lines();
+            let first: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            let second: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+
+            let mut reply = String::new();
+            for message in [
+                json!({"id": second["id"], "result": second["method"], "error": null}),
+                json!({"id": null, "method": "mining.set_difficulty", "params": [8]}),
+                json!({"id": first["id"], "result": first["method"], "error": null}),
+            ] {
+                reply.push_str(&format!("{}\n", message));
+            }
+            writer.write_all(reply.as_bytes()).await.unwrap();
+        });
+        (host, port)
+    }
+
+    async fn assert_correlated(conn: &StratumConnection) {
+        let (first, second) = tokio::join!(
+            conn.send_request("first", vec![]),
+            conn.send_request("second", vec![])
+        );
+        assert_eq!(first.unwrap().result, Some(json!("first")));
+        assert_eq!(second.unwrap().result, Some(json!("second")));
+        assert_eq!(conn.read_notification().await.unwrap()["params"][0], 8);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests() {
+        let (host, port) = out_of_order_server().await;
+        let conn = StratumConnection::new(host, port).await.unwrap();
+        assert_correlated(&conn).await;
+        assert_eq!(conn.stats().await.messages_received, 3);
+    }
+
+    /// Subscriber keeping the name, explicit parent and fields of every span
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: Arc<std::sync::Mutex<Vec<RecordedSpan>>>,
+    }
+
+    #[derive(Debug)]
+    struct RecordedSpan {
+        name: &'static str,
+        parent: Option<u64>,
+        fields: HashMap<&'static str, String>,
+    }
+
+    struct FieldWriter<'a>(&'a mut HashMap<&'static str, String>);
+
+    impl tracing::field::Visit for FieldWriter<'_> {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut span = RecordedSpan {
+                name: attributes.metadata().name(),
+                parent: attributes.parent().map(tracing::span::Id::into_u64),
+                fields: HashMap::new(),
+            };
+            attributes.record(&mut FieldWriter(&mut span.fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span);
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            let span = &mut spans[id.into_u64() as usize - 1];
+            values.record(&mut FieldWriter(&mut span.fields));
+        }
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+        fn event(&self, _: &tracing::Event<'_>) {}
+        fn enter(&self, _: &tracing::span::Id) {}
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_tracing_spans() {
+        let recorder = SpanRecorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let (host, port) = out_of_order_server().await;
+        let conn = StratumConnection::new(host.clone(), port).await.unwrap();
+        assert_correlated(&conn).await;
+
+        let spans = recorder.spans.lock().unwrap();
+        let connection = spans
+            .iter()
+            .position(|span| span.name == "connection")
+            .unwrap();
+        assert_eq!(
+            spans[connection].fields["pool"],
+            format!("{}:{}", host, port)
+        );
+        let mut requests: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name == "request")
+            .map(|span| {
+                assert_eq!(span.parent, Some(connection as u64 + 1));
+                assert_eq!(span.fields["attempt"], "1");
+                (span.fields["method"].as_str(), span.fields["id"].as_str())
+            })
+            .collect();
+        requests.sort();
+        assert_eq!(requests, [("first", "1"), ("second", "2")]);
+    }
+
+    #[tokio::test]
+    async fn test_read_loop_routes_by_id() {
+        let (host, port) = out_of_order_server().await;
+        let mut conn = StratumConnection::new(host, port).await.unwrap();
+        conn.start_read_loop();
+        assert!(conn.has_read_loop());
+        assert_correlated(&conn).await;
+
+        // The pool closed the connection
+        assert!(conn.read_notification().await.unwrap().is_null());
+        assert_eq!(conn.take_disconnect(), Some(DisconnectReason::PeerClosed));
+        let err = conn.send_request("third", vec![]).await.unwrap_err();
+        assert!(matches!(err.root(), StratumError::Connection(_)));
+        assert!(err.to_string().starts_with("third to 127.0.0.1:"));
+    }
+
+    #[tokio::test]
+    async fn test_from_stream() {
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let (read_half, mut writer) = tokio::io::split(server);
+            let mut lines = BufReader::new(read_half).lines();
+            let request: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            let response = json!({"id": request["id"], "result": true, "error": null});
+            writer
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+        });
+
+        let mut conn = StratumConnection::from_stream(client, ConnectionConfig::default())
+            .await
+            .unwrap();
+        let response = conn.send_request("mining.ping", vec![]).await.unwrap();
+        assert_eq!(response.result, Some(json!(true)));
+        assert!(matches!(
+            conn.reconnect().await.unwrap_err(),
+            StratumError::Connection(_)
+        ));
+    }
+
+    #[test]
+    fn test_split_scheme() {
+        assert_eq!(split_scheme("pool.com").unwrap(), ("pool.com", false));
+        assert_eq!(
+            split_scheme("stratum+tcp://pool.com").unwrap(),
+            ("pool.com", false)
+        );
+        assert_eq!(
+            split_scheme("stratum+ssl://pool.com/").unwrap(),
+            ("pool.com", true)
+        );
+        assert!(split_scheme("http://pool.com").is_err());
+    }
+
     #[tokio::test]
     async fn test_connection_errors() {
         let (listener, host, port) = setup_test_server().await;
@@ -483,7 +1930,7 @@ mod tests {
         tokio::spawn(async move {
             let (mut socket, _) = listener.accept().await.unwrap();
             let mut buf = vec![0; 1024];
-            socket.read(&mut buf).await.unwrap();
+            let _ = socket.read(&mut buf).await.unwrap();
 
             // Send invalid JSON to trigger error
             socket.write_all(b"invalid json\n").await.unwrap();
@@ -511,7 +1958,7 @@ mod tests {
             // Accept new connection after reconnect
             let (mut socket, _) = listener.accept().await.unwrap();
             let mut buf = vec![0; 1024];
-            socket.read(&mut buf).await.unwrap();
+            let _ = socket.read(&mut buf).await.unwrap();
 
             let response = json!({
                 "id": 1,
@@ -541,4 +1988,33 @@ mod tests {
         assert_eq!(stats.errors, 0);
         assert!(stats.connected_since.is_some());
     }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_lifetime_stats() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new();
+        let mut conn = StratumConnection::with_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+        )
+        .await
+        .unwrap();
+        conn.send_request("mining.configure", vec![]).await.unwrap();
+        conn.send_request("mining.configure", vec![]).await.unwrap();
+        assert!(conn.take_closed_stats().is_none());
+
+        conn.reconnect().await.unwrap();
+        let closed = conn.take_closed_stats().unwrap();
+        assert_eq!((closed.messages_sent, closed.messages_received), (2, 2));
+        assert!(conn.take_closed_stats().is_none());
+
+        conn.send_request("mining.configure", vec![]).await.unwrap();
+        assert_eq!(conn.stats().await.messages_sent, 1);
+        let lifetime = conn.lifetime_stats().await;
+        assert_eq!(lifetime.connections, 2);
+        assert_eq!((lifetime.messages_sent, lifetime.messages_received), (3, 3));
+    }
 }
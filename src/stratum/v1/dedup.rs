@@ -0,0 +1,132 @@
+use super::jobs::{JobKey, JOB_HISTORY_LEN};
+use crate::stratum::types::Share;
+use std::collections::{HashSet, VecDeque};
+
+/// Most shares remembered per job
+pub const SHARES_PER_JOB: usize = 4096;
+
+/// What makes a share of a job unique: extranonce2, ntime, nonce and
+/// version bits
+type ShareTuple = (String, String, String, Option<String>);
+
+fn share_tuple(share: &Share) -> ShareTuple {
+    (
+        share.extranonce2.to_lowercase(),
+        share.ntime.to_lowercase(),
+        share.nonce.to_lowercase(),
+        share.version_bits.as_ref().map(|bits| bits.to_lowercase()),
+    )
+}
+
+/// Least recently submitted shares of one job
+#[derive(Debug, Default)]
+struct JobShares {
+    order: VecDeque<ShareTuple>,
+    seen: HashSet<ShareTuple>,
+}
+
+/// Shares submitted for the last [`JOB_HISTORY_LEN`] jobs, so duplicates
+/// from buggy miners are dropped rather than rejected by the pool
+///
+/// Jobs and their shares are forgotten least recently used first.
+#[derive(Debug, Default)]
+pub(crate) struct SubmittedShares {
+    jobs: VecDeque<(JobKey, JobShares)>,
+}
+
+impl SubmittedShares {
+    /// Whether `share` of `job` was submitted before
+    pub fn contains(&self, job: &JobKey, share: &Share) -> bool {
+        self.jobs
+            .iter()
+            .find(|(key, _)| key == job)
+            .is_some_and(|(_, shares)| shares.seen.contains(&share_tuple(share)))
+    }
+
+    /// Remember `share` of `job`, returning `false` if it was submitted before
+    pub fn insert(&mut self, job: JobKey, share: &Share) -> bool {
+        let position = self.jobs.iter().position(|(key, _)| *key == job);
+        let mut entry = match position.and_then(|position| self.jobs.remove(position)) {
+            Some(entry) => entry,
+            None => (job, JobShares::default()),
+        };
+        let shares = &mut entry.1;
+        let share = share_tuple(share);
+        let new = shares.seen.insert(share.clone());
+        if new {
+            shares.order.push_back(share);
+            if shares.order.len() > SHARES_PER_JOB {
+                if let Some(oldest) = shares.order.pop_front() {
+                    shares.seen.remove(&oldest);
+                }
+            }
+        }
+        self.jobs.push_back(entry);
+        if self.jobs.len() > JOB_HISTORY_LEN {
+            self.jobs.pop_front();
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(job_id: &str) -> JobKey {
+        JobKey {
+            job_id: job_id.into(),
+            prev_hash: "00".repeat(32),
+            ntime: "504e86b9".into(),
+        }
+    }
+
+    fn share(nonce: u32) -> Share {
+        Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "504e86b9".into(),
+            nonce: format!("{:08x}", nonce),
+            version_bits: None,
+        }
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let mut submitted = SubmittedShares::default();
+        assert!(!submitted.contains(&key("job1"), &share(1)));
+        assert!(submitted.insert(key("job1"), &share(1)));
+        assert!(submitted.contains(&key("job1"), &share(1)));
+        assert!(!submitted.insert(key("job1"), &share(1)));
+        assert!(!submitted.contains(&key("job2"), &share(1)));
+        assert!(submitted.insert(key("job2"), &share(1)));
+
+        let upper = Share {
+            nonce: "0000000A".into(),
+            ..share(0)
+        };
+        assert!(submitted.insert(key("job1"), &upper));
+        assert!(!submitted.insert(key("job1"), &share(10)));
+        let rolled = Share {
+            version_bits: Some("00002000".into()),
+            ..share(1)
+        };
+        assert!(submitted.insert(key("job1"), &rolled));
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut submitted = SubmittedShares::default();
+        for nonce in 0..=SHARES_PER_JOB as u32 {
+            assert!(submitted.insert(key("job1"), &share(nonce)));
+        }
+        // The oldest share was forgotten, the newest is still known
+        assert!(submitted.insert(key("job1"), &share(0)));
+        assert!(!submitted.insert(key("job1"), &share(SHARES_PER_JOB as u32)));
+
+        for job in 0..JOB_HISTORY_LEN {
+            submitted.insert(key(&format!("other{}", job)), &share(0));
+        }
+        assert!(submitted.insert(key("job1"), &share(SHARES_PER_JOB as u32)));
+    }
+}
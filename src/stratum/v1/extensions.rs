@@ -0,0 +1,138 @@
+use super::protocol::Method;
+use crate::stratum::error::StratumError;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Handler of a method the pool sends that this client doesn't implement
+#[async_trait]
+pub trait MethodHandler: Send + Sync {
+    /// Handle a message with `params`
+    ///
+    /// When the pool sent a request, with an id, a returned value answers
+    /// it and `None` or an error answers with an error response;
+    /// notifications have nobody to answer.
+    async fn handle(&self, params: &[Value]) -> Result<Option<Value>, StratumError>;
+}
+
+#[async_trait]
+impl<F> MethodHandler for F
+where
+    F: Fn(&[Value]) -> Result<Option<Value>, StratumError> + Send + Sync,
+{
+    async fn handle(&self, params: &[Value]) -> Result<Option<Value>, StratumError> {
+        self(params)
+    }
+}
+
+/// Builds the params of a custom method from the caller's arguments
+type RequestBuilder = dyn Fn(Value) -> Result<Vec<Value>, StratumError> + Send + Sync;
+
+/// Pool-specific methods on top of Stratum V1, see
+/// [`StratumV1Client::set_extensions`](super::StratumV1Client::set_extensions)
+///
+/// ```
+/// use rust_stratum::stratum::v1::extensions::Extensions;
+/// use serde_json::{json, Value};
+///
+/// let mut extensions = Extensions::new();
+/// extensions
+///     .on("client.custom_thing", |params: &[Value]| {
+///         println!("Pool sent {:?}", params);
+///         Ok(Some(json!(true)))
+///     })
+///     .request("mining.suggest_hashrate", |hashrate| Ok(vec![hashrate]));
+/// ```
+#[derive(Clone, Default)]
+pub struct Extensions {
+    handlers: HashMap<String, Arc<dyn MethodHandler>>,
+    requests: HashMap<String, Arc<RequestBuilder>>,
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("handlers", &self.handlers.keys().collect::<Vec<_>>())
+            .field("requests", &self.requests.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle `method` when the pool sends it
+    ///
+    /// Only methods this client doesn't implement reach a handler, the
+    /// [`Method::Unknown`] ones; registering a known method has no effect.
+    pub fn on(&mut self, method: &str, handler: impl MethodHandler + 'static) -> &mut Self {
+        self.handlers.insert(method.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Allow sending `method`, with params built from the caller's arguments
+    /// by `build`, see
+    /// [`StratumV1Client::call_extension`](super::StratumV1Client::call_extension)
+    pub fn request(
+        &mut self,
+        method: &str,
+        build: impl Fn(Value) -> Result<Vec<Value>, StratumError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.requests.insert(method.to_string(), Arc::new(build));
+        self
+    }
+
+    /// Handler of `method`, if it is an unknown method with one registered
+    pub fn handler(&self, method: &Method) -> Option<Arc<dyn MethodHandler>> {
+        match method {
+            Method::Unknown(name) => self.handlers.get(name).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Params of a `method` request for `args`
+    pub fn build_request(&self, method: &str, args: Value) -> Result<Vec<Value>, StratumError> {
+        let build = self.requests.get(method).ok_or_else(|| {
+            StratumError::Config(format!("No extension registered for {}", method))
+        })?;
+        build(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_registry() {
+        let mut extensions = Extensions::new();
+        extensions
+            .on("client.custom_thing", |params: &[Value]| {
+                Ok(Some(json!(params.len())))
+            })
+            .on("mining.notify", |_: &[Value]| Ok(None))
+            .request("mining.custom", |args| Ok(vec![args, json!("x")]));
+
+        let handler = extensions
+            .handler(&Method::from("client.custom_thing"))
+            .unwrap();
+        assert_eq!(
+            handler.handle(&[json!(1), json!(2)]).await.unwrap(),
+            Some(json!(2))
+        );
+        // Known methods stay with the client
+        assert!(extensions.handler(&Method::Notify).is_none());
+        assert!(extensions.handler(&Method::from("client.other")).is_none());
+
+        assert_eq!(
+            extensions.build_request("mining.custom", json!(7)).unwrap(),
+            vec![json!(7), json!("x")]
+        );
+        assert!(extensions.build_request("mining.other", json!(7)).is_err());
+    }
+}
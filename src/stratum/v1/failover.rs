@@ -0,0 +1,915 @@
+use super::connection::{ConnectionConfig, StratumConnection};
+#[cfg(feature = "multipool")]
+use super::jobs::JobManager;
+use super::protocol::MINING_SUBSCRIBE;
+#[cfg(feature = "multipool")]
+use super::protocol::{MINING_AUTHORIZE, MINING_NOTIFY};
+use super::StratumV1Client;
+use crate::stratum::error::{ErrorKind, StratumError};
+use crate::stratum::miner::Miner;
+#[cfg(feature = "multipool")]
+use crate::stratum::multipool::JobSourceSelector;
+use crate::stratum::types::*;
+use crate::stratum::verbosity::{log_at, Category, Verbosity};
+use crate::stratum::StratumClient;
+use async_trait::async_trait;
+use log::Level;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// One pool a [`FailoverClient`] can mine on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolEndpoint {
+    /// Pool host, optionally with a `stratum+tcp://` or `stratum+ssl://` scheme
+    pub host: String,
+    pub port: u16,
+    /// Username and password for this pool, `None` keeps the last ones used
+    pub credentials: Option<(String, String)>,
+}
+
+/// How long a failback probe waits for the pool to announce a job
+#[cfg(feature = "multipool")]
+const PROBE_JOB_WAIT: Duration = Duration::from_secs(2);
+
+impl PoolEndpoint {
+    /// Endpoint authorizing with the credentials last used by the client
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            credentials: None,
+        }
+    }
+
+    /// Authorize with the given credentials on this pool
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Pool address as `host:port`
+    #[cfg(feature = "multipool")]
+    fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// When a [`FailoverClient`] switches pools
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverPolicy {
+    /// Connection failures within `failure_window` after which the client
+    /// moves to the next endpoint instead of reconnecting
+    pub max_failures: usize,
+    pub failure_window: Duration,
+    /// How often a higher priority endpoint is probed while failed over
+    pub failback_interval: Duration,
+    /// Grace period after each connection in which the reject rate doesn't
+    /// degrade health and latency SLA breaches don't fail over, see
+    /// [`StratumV1Client::set_warm_up`]; connection failures always count
+    /// toward `max_failures`
+    pub warm_up: Duration,
+    /// Move to the next endpoint when the active one breaches the client's
+    /// latency SLA, see [`StratumV1Client::set_latency_sla`]; off by default
+    ///
+    /// Breaches during the `warm_up` don't count.
+    pub fail_over_on_latency: bool,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 3,
+            failure_window: Duration::from_secs(5 * 60),
+            failback_interval: Duration::from_secs(5 * 60),
+            warm_up: Duration::from_secs(60),
+            fail_over_on_latency: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FailoverState {
+    /// Index of the endpoint in use
+    active: usize,
+    /// Recent connection failures on the active endpoint
+    failures: VecDeque<Instant>,
+    /// Last time higher priority endpoints were probed, or the last switch
+    last_failback_check: Instant,
+}
+
+/// Client mining on a prioritized list of pools
+///
+/// Wraps a [`StratumV1Client`] whose session moves between endpoints, so
+/// jobs, statistics and events carry over a switch. Connection errors are
+/// answered by reconnecting to the active endpoint; after
+/// [`FailoverPolicy::max_failures`] of them, or when reconnecting fails, the
+/// client moves on to the next endpoint. While not on the primary endpoint,
+/// [`handle_notifications`](StratumClient::handle_notifications) regularly
+/// probes the endpoints before the active one and fails back to the first
+/// that answers a subscription. With the `multipool` feature, an endpoint
+/// whose first job builds on an older block than the active pool's isn't
+/// failed back to, see `JobSourceSelector`.
+///
+/// All endpoints share the connection options of the client.
+#[derive(Clone)]
+pub struct FailoverClient {
+    client: StratumV1Client,
+    endpoints: Arc<Vec<PoolEndpoint>>,
+    policy: FailoverPolicy,
+    state: Arc<Mutex<FailoverState>>,
+    /// Blocks the active and probed pools announced jobs on
+    #[cfg(feature = "multipool")]
+    selector: Arc<std::sync::Mutex<JobSourceSelector>>,
+}
+
+impl FailoverClient {
+    /// Connect to the first reachable endpoint, in order of priority
+    ///
+    /// The session is subscribed and, if the endpoint has credentials,
+    /// authorized. Fails with the error of the last endpoint if none works.
+    pub async fn connect<M: Miner>(
+        endpoints: Vec<PoolEndpoint>,
+        policy: FailoverPolicy,
+        config: ConnectionConfig,
+        miner: M,
+    ) -> Result<Self, StratumError> {
+        let mut last_error = StratumError::Config("No pool endpoints given".into());
+        // No client to take the verbosity of yet; clients start with everything enabled
+        let verbosity = Verbosity::new();
+        for (index, endpoint) in endpoints.iter().enumerate() {
+            match Self::open(endpoint, config.clone(), miner.clone()).await {
+                Ok(client) => {
+                    client.set_warm_up(Some(policy.warm_up));
+                    return Ok(Self {
+                        client,
+                        endpoints: Arc::new(endpoints),
+                        policy,
+                        state: Arc::new(Mutex::new(FailoverState {
+                            active: index,
+                            failures: VecDeque::new(),
+                            last_failback_check: Instant::now(),
+                        })),
+                        #[cfg(feature = "multipool")]
+                        selector: Arc::new(std::sync::Mutex::new(JobSourceSelector::new())),
+                    });
+                }
+                Err(err) => {
+                    log_at!(
+                        verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Pool {}:{} unavailable: {err}",
+                        endpoint.host,
+                        endpoint.port
+                    );
+                    last_error = err;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn open<M: Miner>(
+        endpoint: &PoolEndpoint,
+        config: ConnectionConfig,
+        miner: M,
+    ) -> Result<StratumV1Client, StratumError> {
+        let mut client = StratumV1Client::with_connection_config(
+            endpoint.host.clone(),
+            endpoint.port,
+            config,
+            miner,
+        )
+        .await?;
+        client.subscribe().await?;
+        if let Some((username, password)) = &endpoint.credentials {
+            client.authorize(username, password).await?;
+        }
+        Ok(client)
+    }
+
+    /// The client of the current session
+    pub fn client(&self) -> &StratumV1Client {
+        &self.client
+    }
+
+    /// Endpoints in order of priority
+    pub fn endpoints(&self) -> &[PoolEndpoint] {
+        &self.endpoints
+    }
+
+    /// Index of the endpoint in use
+    pub async fn active(&self) -> usize {
+        self.state.lock().await.active
+    }
+
+    /// Handle a lost connection to the active endpoint
+    ///
+    /// Reconnects to it, or moves to the next endpoint when it failed too
+    /// often or can't be reached. Failures count during the
+    /// [`FailoverPolicy::warm_up`] too, so a pool dropping every connection
+    /// soon after accepting it is failed over from.
+    pub async fn recover(&mut self) -> Result<(), StratumError> {
+        let state = self.state.clone();
+        let mut state = state.lock().await;
+        let now = Instant::now();
+        state.failures.push_back(now);
+        while state
+            .failures
+            .front()
+            .is_some_and(|failure| now.duration_since(*failure) > self.policy.failure_window)
+        {
+            state.failures.pop_front();
+        }
+
+        if state.failures.len() < self.policy.max_failures {
+            let endpoint = &self.endpoints[state.active];
+            match self.client.switch_pool(endpoint).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    log_at!(
+                        self.client.verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Reconnecting to {}:{} failed: {err}",
+                        endpoint.host,
+                        endpoint.port
+                    )
+                }
+            }
+        }
+        self.fail_over(&mut state).await
+    }
+
+    /// Move to the next endpoint that works, wrapping around the list
+    async fn fail_over(&mut self, state: &mut FailoverState) -> Result<(), StratumError> {
+        let count = self.endpoints.len();
+        let mut last_error = None;
+        for offset in 1..=count {
+            let index = (state.active + offset) % count;
+            let endpoint = &self.endpoints[index];
+            match self.client.switch_pool(endpoint).await {
+                Ok(()) => {
+                    log_at!(
+                        self.client.verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Failed over to pool {}:{}",
+                        endpoint.host,
+                        endpoint.port
+                    );
+                    #[cfg(feature = "multipool")]
+                    self.forget(state.active);
+                    state.active = index;
+                    state.failures.clear();
+                    state.last_failback_check = Instant::now();
+                    return Ok(());
+                }
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| StratumError::Config("No pool endpoints given".into())))
+    }
+
+    /// Return to the highest priority endpoint that is healthy again
+    ///
+    /// Probes are spaced by [`FailoverPolicy::failback_interval`]. With the
+    /// `multipool` feature, an endpoint whose first job builds on an older
+    /// block than the active pool's is skipped until it caught up. Returns
+    /// whether the client switched.
+    pub async fn fail_back(&mut self) -> Result<bool, StratumError> {
+        let state = self.state.clone();
+        let mut state = state.lock().await;
+        if state.active == 0 || state.last_failback_check.elapsed() < self.policy.failback_interval
+        {
+            return Ok(false);
+        }
+        state.last_failback_check = Instant::now();
+
+        #[cfg(feature = "multipool")]
+        self.record_active_job(state.active).await;
+        let config = self.client.connection.lock().await.config().clone();
+        let user_agent = self.client.user_agent();
+        for index in 0..state.active {
+            let endpoint = &self.endpoints[index];
+            let Some(mut connection) = Self::probe(endpoint, config.clone(), &user_agent).await
+            else {
+                continue;
+            };
+            #[cfg(feature = "multipool")]
+            let behind = self.is_behind(endpoint, &connection).await;
+            #[cfg(not(feature = "multipool"))]
+            let behind = false;
+            let _ = connection.close().await;
+            if behind {
+                log_at!(
+                    self.client.verbosity,
+                    Category::Connection,
+                    Level::Info,
+                    "Pool {}:{} is healthy but behind on blocks, not failing back yet",
+                    endpoint.host,
+                    endpoint.port
+                );
+                continue;
+            }
+            match self.client.switch_pool(endpoint).await {
+                Ok(()) => {
+                    log_at!(
+                        self.client.verbosity,
+                        Category::Connection,
+                        Level::Info,
+                        "Failed back to pool {}:{}",
+                        endpoint.host,
+                        endpoint.port
+                    );
+                    #[cfg(feature = "multipool")]
+                    self.forget(state.active);
+                    state.active = index;
+                    state.failures.clear();
+                    return Ok(true);
+                }
+                Err(err) => {
+                    log_at!(
+                        self.client.verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Failing back to {}:{} failed: {err}",
+                        endpoint.host,
+                        endpoint.port
+                    );
+                    // The session may be half moved, continue with the next endpoints
+                    let previous = state.active;
+                    state.active = index;
+                    self.fail_over(&mut state).await?;
+                    return Ok(state.active != previous);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Record the job of the active pool with the selector
+    #[cfg(feature = "multipool")]
+    async fn record_active_job(&self, active: usize) {
+        if let Ok(Some(job)) = self.client.job_manager.get_current_job().await {
+            let pool = self.endpoints[active].addr();
+            self.selector.lock().unwrap().record_job(&pool, &job);
+        }
+    }
+
+    /// Forget the jobs of an endpoint the session left
+    #[cfg(feature = "multipool")]
+    fn forget(&self, index: usize) {
+        let pool = self.endpoints[index].addr();
+        self.selector.lock().unwrap().remove_pool(&pool);
+    }
+
+    /// Connect to a pool on a separate connection, returning it if the pool
+    /// accepts a subscription
+    async fn probe(
+        endpoint: &PoolEndpoint,
+        config: ConnectionConfig,
+        user_agent: &str,
+    ) -> Option<StratumConnection> {
+        let wait = Duration::from_secs(config.timeout);
+        let mut connection =
+            StratumConnection::with_config(endpoint.host.clone(), endpoint.port, config)
+                .await
+                .ok()?;
+        let healthy = connection
+            .send_request_once(MINING_SUBSCRIBE, vec![json!(user_agent)], wait)
+            .await
+            .is_ok_and(|response| response.error.is_none());
+        if !healthy {
+            let _ = connection.close().await;
+            return None;
+        }
+        Some(connection)
+    }
+
+    /// Whether the first job a probed pool announces within
+    /// [`PROBE_JOB_WAIT`] builds on an older block than the active pool's
+    ///
+    /// Authorizes first when the endpoint has credentials, as most pools only
+    /// send jobs to authorized workers. Pools announcing no job aren't behind.
+    #[cfg(feature = "multipool")]
+    async fn is_behind(&self, endpoint: &PoolEndpoint, connection: &StratumConnection) -> bool {
+        let wait = Duration::from_secs(connection.config().timeout);
+        if let Some((username, password)) = &endpoint.credentials {
+            let _ = connection
+                .send_request_once(
+                    MINING_AUTHORIZE,
+                    vec![json!(username), json!(password)],
+                    wait,
+                )
+                .await;
+        }
+
+        let deadline = Instant::now() + PROBE_JOB_WAIT;
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            let Ok(Some(notification)) = connection.poll_notification(left).await else {
+                break;
+            };
+            if notification["method"] != MINING_NOTIFY {
+                continue;
+            }
+            let Some(job) = notification["params"]
+                .as_array()
+                .and_then(|params| JobManager::parse_job(params).ok())
+            else {
+                break;
+            };
+            let mut selector = self.selector.lock().unwrap();
+            selector.record_job(&endpoint.addr(), &job);
+            return selector.is_stale(&endpoint.addr());
+        }
+        false
+    }
+
+    /// Move to the next endpoint if the active one breaches the latency SLA
+    /// and the policy asks for it
+    async fn fail_over_on_latency(&mut self) -> Result<(), StratumError> {
+        if !self.policy.fail_over_on_latency
+            || !self.client.is_latency_sla_violated()
+            || self.client.is_warming_up()
+        {
+            return Ok(());
+        }
+        log_at!(
+            self.client.verbosity,
+            Category::Connection,
+            Level::Warn,
+            "Share acceptance latency of {} breaches its SLA, failing over",
+            self.client.pool()
+        );
+        let state = self.state.clone();
+        let mut state = state.lock().await;
+        self.fail_over(&mut state).await
+    }
+
+    /// Recover when `result` is a connection error, then pass it on
+    async fn recover_from<T>(
+        &mut self,
+        result: Result<T, StratumError>,
+    ) -> Result<T, StratumError> {
+        // Errors are wrapped in their request's context, look through it
+        if let Err(err) = result.as_ref() {
+            if err.kind() == ErrorKind::Connection {
+                log_at!(
+                    self.client.verbosity,
+                    Category::Connection,
+                    Level::Warn,
+                    "Connection to {} failed: {err}",
+                    self.client.pool()
+                );
+                self.recover().await?;
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl StratumClient for FailoverClient {
+    async fn subscribe(&mut self) -> Result<SubscribeResponse, StratumError> {
+        self.client.subscribe().await
+    }
+
+    async fn authorize(
+        &mut self,
+        username: &str,
+        password: &str,
+    ) -> Result<AuthResponse, StratumError> {
+        self.client.authorize(username, password).await
+    }
+
+    /// Submit a share, failing over on connection errors, and on breaches of
+    /// the latency SLA if [`FailoverPolicy::fail_over_on_latency`] is set
+    ///
+    /// A share that couldn't be sent is lost: it was mined on a job of the
+    /// previous connection, so it isn't resubmitted.
+    async fn submit_share(&mut self, share: Share) -> Result<bool, StratumError> {
+        let result = self.client.submit_share(share).await;
+        let result = self.recover_from(result).await;
+        self.fail_over_on_latency().await?;
+        result
+    }
+
+    async fn get_current_job(&mut self) -> Result<Option<Arc<MiningJob>>, StratumError> {
+        self.client.get_current_job().await
+    }
+
+    /// Handle notifications, failing over on connection errors and failing
+    /// back when the primary pool is healthy again
+    async fn handle_notifications(&mut self) -> Result<(), StratumError> {
+        self.fail_back().await?;
+        match self.client.handle_notifications().await {
+            Err(err) if err.kind() == ErrorKind::Connection => {
+                log_at!(
+                    self.client.verbosity,
+                    Category::Connection,
+                    Level::Warn,
+                    "Connection to {} failed: {err}",
+                    self.client.pool()
+                );
+                self.recover().await
+            }
+            #[cfg(feature = "multipool")]
+            Ok(()) => {
+                let active = self.state.lock().await.active;
+                self.record_active_job(active).await;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    async fn get_target(&self) -> Result<MiningTarget, StratumError> {
+        self.client.get_target().await
+    }
+
+    async fn get_server_info(&self) -> Result<ServerInfo, StratumError> {
+        self.client.get_server_info().await
+    }
+
+    async fn reconnect(&mut self) -> Result<(), StratumError> {
+        self.client.reconnect().await
+    }
+
+    async fn close(&mut self) -> Result<(), StratumError> {
+        self.client.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::stats::LatencySla;
+    use crate::stratum::v1::jobs::TestMiner;
+    use crate::stratum::v1::protocol::{MINING_AUTHORIZE, MINING_NOTIFY, MINING_SUBMIT};
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Pool on `listener` answering subscriptions and accepting everything
+    /// else, announcing a job at block height 100 after authorization
+    fn serve(listener: TcpListener) {
+        serve_at(listener, Arc::new(AtomicU32::new(100)));
+    }
+
+    /// Like [`serve`], announcing jobs at the given block height
+    fn serve_at(listener: TcpListener, height: Arc<AtomicU32>) {
+        serve_with(listener, height, Duration::ZERO);
+    }
+
+    /// Like [`serve_at`], answering shares only after `submit_delay`
+    fn serve_with(listener: TcpListener, height: Arc<AtomicU32>, submit_delay: Duration) {
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let height = height.clone();
+                tokio::spawn(async move {
+                    let (read_half, mut writer) = socket.into_split();
+                    let mut reader = BufReader::new(read_half);
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        let request: Value = serde_json::from_str(&line).unwrap();
+                        line.clear();
+                        let result = if request["method"] == MINING_SUBSCRIBE {
+                            json!([[["mining.notify", "1"]], "f000000f", 4])
+                        } else {
+                            json!(true)
+                        };
+                        let mut response =
+                            json!({"id": request["id"], "result": result, "error": null})
+                                .to_string()
+                                + "\n";
+                        if request["method"] == MINING_AUTHORIZE {
+                            response += &format!("{}\n", job_at(height.load(Ordering::SeqCst)));
+                        }
+                        if request["method"] == MINING_SUBMIT {
+                            tokio::time::sleep(submit_delay).await;
+                        }
+                        let _ = writer.write_all(response.as_bytes()).await;
+                    }
+                });
+            }
+        });
+    }
+
+    /// Pool on `listener` answering subscriptions and authorizations, then
+    /// closing the connection, as a pool dying mid-session would
+    fn serve_until_authorized(listener: TcpListener) {
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (read_half, mut writer) = socket.into_split();
+                    let mut reader = BufReader::new(read_half);
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        let request: Value = serde_json::from_str(&line).unwrap();
+                        line.clear();
+                        let result = if request["method"] == MINING_SUBSCRIBE {
+                            json!([[["mining.notify", "1"]], "f000000f", 4])
+                        } else {
+                            json!(true)
+                        };
+                        let response =
+                            json!({"id": request["id"], "result": result, "error": null});
+                        let _ = writer.write_all(format!("{}\n", response).as_bytes()).await;
+                        if request["method"] == MINING_AUTHORIZE {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// `mining.notify` of a job building block `height`
+    fn job_at(height: u32) -> Value {
+        let coinbase1 = format!(
+            "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4b03{}",
+            hex::encode(&height.to_le_bytes()[..3])
+        );
+        json!({
+            "id": null,
+            "method": MINING_NOTIFY,
+            "params": [
+                "job1",
+                format!("{:064x}", height),
+                coinbase1,
+                "ffffffff",
+                [],
+                "20000000",
+                "1d00ffff",
+                "60509af9",
+                true
+            ]
+        })
+    }
+
+    async fn endpoint(listener: &TcpListener) -> PoolEndpoint {
+        let port = listener.local_addr().unwrap().port();
+        PoolEndpoint::new("127.0.0.1", port).with_credentials("worker", "x")
+    }
+
+    #[tokio::test]
+    async fn test_failover_and_failback() {
+        // The primary is down at first
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary_endpoint = endpoint(&primary).await;
+        drop(primary);
+        let backup = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backup_endpoint = endpoint(&backup).await;
+        serve(backup);
+
+        let policy = FailoverPolicy {
+            max_failures: 1,
+            failback_interval: Duration::ZERO,
+            warm_up: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut client = FailoverClient::connect(
+            vec![primary_endpoint.clone(), backup_endpoint.clone()],
+            policy,
+            ConnectionConfig::default(),
+            TestMiner,
+        )
+        .await
+        .unwrap();
+        assert_eq!(client.active().await, 1);
+        assert_eq!(
+            client.client().pool(),
+            format!("127.0.0.1:{}", backup_endpoint.port)
+        );
+
+        // Nothing to fail back to while the primary is down
+        assert!(!client.fail_back().await.unwrap());
+
+        let primary = TcpListener::bind(("127.0.0.1", primary_endpoint.port))
+            .await
+            .unwrap();
+        serve(primary);
+        assert!(client.fail_back().await.unwrap());
+        assert_eq!(client.active().await, 0);
+        assert_eq!(
+            client.client().pool(),
+            format!("127.0.0.1:{}", primary_endpoint.port)
+        );
+        assert_eq!(
+            client.client().auth_state().await,
+            crate::stratum::v1::AuthState::Authorized
+        );
+
+        // A failure beyond the limit moves to the next endpoint
+        client.recover().await.unwrap();
+        assert_eq!(client.active().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_submit_to_closed_pool_fails_over() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary_endpoint = endpoint(&primary).await;
+        serve_until_authorized(primary);
+        let backup = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backup_endpoint = endpoint(&backup).await;
+        serve(backup);
+
+        let policy = FailoverPolicy {
+            max_failures: 1,
+            warm_up: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut client = FailoverClient::connect(
+            vec![primary_endpoint, backup_endpoint.clone()],
+            policy,
+            ConnectionConfig::default(),
+            TestMiner,
+        )
+        .await
+        .unwrap();
+        assert_eq!(client.active().await, 0);
+        let job = job_at(100);
+        client
+            .client()
+            .job_manager
+            .handle_job_notification(job["params"].as_array().unwrap())
+            .await
+            .unwrap();
+
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        let err = client.submit_share(share).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Connection);
+        assert_eq!(client.active().await, 1);
+        assert_eq!(
+            client.client().pool(),
+            format!("127.0.0.1:{}", backup_endpoint.port)
+        );
+    }
+
+    #[cfg(feature = "multipool")]
+    #[tokio::test]
+    async fn test_failback_waits_for_block() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary_endpoint = endpoint(&primary).await;
+        drop(primary);
+        let backup = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backup_endpoint = endpoint(&backup).await;
+        serve_at(backup, Arc::new(AtomicU32::new(101)));
+
+        let policy = FailoverPolicy {
+            failback_interval: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut client = FailoverClient::connect(
+            vec![primary_endpoint.clone(), backup_endpoint],
+            policy,
+            ConnectionConfig::default(),
+            TestMiner,
+        )
+        .await
+        .unwrap();
+        client.handle_notifications().await.unwrap();
+        assert_eq!(client.active().await, 1);
+
+        // The primary is back, but still on the block before the backup's
+        let primary_height = Arc::new(AtomicU32::new(100));
+        let primary = TcpListener::bind(("127.0.0.1", primary_endpoint.port))
+            .await
+            .unwrap();
+        serve_at(primary, primary_height.clone());
+        assert!(!client.fail_back().await.unwrap());
+        assert_eq!(client.active().await, 1);
+
+        primary_height.store(101, Ordering::SeqCst);
+        assert!(client.fail_back().await.unwrap());
+        assert_eq!(client.active().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_latency_sla_fails_over() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary_endpoint = endpoint(&primary).await;
+        serve_with(
+            primary,
+            Arc::new(AtomicU32::new(100)),
+            Duration::from_millis(50),
+        );
+        let backup = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backup_endpoint = endpoint(&backup).await;
+        serve(backup);
+
+        let policy = FailoverPolicy {
+            warm_up: Duration::ZERO,
+            ..Default::default()
+        };
+        assert!(!policy.fail_over_on_latency);
+        let mut client = FailoverClient::connect(
+            vec![primary_endpoint, backup_endpoint],
+            FailoverPolicy {
+                fail_over_on_latency: true,
+                ..policy
+            },
+            ConnectionConfig::default(),
+            TestMiner,
+        )
+        .await
+        .unwrap();
+        client
+            .client()
+            .set_latency_sla(Some(LatencySla {
+                percentile: 50.0,
+                threshold: Duration::from_millis(10),
+                min_samples: 1,
+            }))
+            .await;
+        let job = job_at(100);
+        client
+            .client()
+            .job_manager
+            .handle_job_notification(job["params"].as_array().unwrap())
+            .await
+            .unwrap();
+
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(client.submit_share(share).await.unwrap());
+        assert_eq!(client.active().await, 1);
+        assert!(!client.client().is_latency_sla_violated());
+    }
+
+    #[tokio::test]
+    async fn test_failures_count_while_warming_up() {
+        let primary = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary_endpoint = endpoint(&primary).await;
+        serve(primary);
+        let backup = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backup_endpoint = endpoint(&backup).await;
+        serve(backup);
+
+        let policy = FailoverPolicy {
+            max_failures: 2,
+            warm_up: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let mut client = FailoverClient::connect(
+            vec![primary_endpoint, backup_endpoint],
+            policy,
+            ConnectionConfig::default(),
+            TestMiner,
+        )
+        .await
+        .unwrap();
+        assert!(client.client().is_warming_up());
+
+        // Each reconnect warms up again, but the failures still add up
+        client.recover().await.unwrap();
+        assert_eq!(client.active().await, 0);
+        assert!(client.client().is_warming_up());
+        client.recover().await.unwrap();
+        assert_eq!(client.active().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_endpoint_reachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = endpoint(&listener).await;
+        drop(listener);
+
+        let result = FailoverClient::connect(
+            vec![endpoint],
+            FailoverPolicy::default(),
+            ConnectionConfig::default(),
+            TestMiner,
+        )
+        .await;
+        assert_eq!(
+            result.err().map(|err| err.kind()),
+            Some(ErrorKind::Connection)
+        );
+
+        let result = FailoverClient::connect(
+            vec![],
+            FailoverPolicy::default(),
+            ConnectionConfig::default(),
+            TestMiner,
+        )
+        .await;
+        assert!(matches!(result, Err(StratumError::Config(_))));
+    }
+}
@@ -1,103 +1,966 @@
-use crate::stratum::miner::Miner;
+use super::protocol::DEFAULT_VERSION_ROLLING_MASK;
+use crate::stratum::contention::{Contention, LockSite};
+use crate::stratum::events::{self, StratumEvent};
+use crate::stratum::header::{self, BlockHeader};
+use crate::stratum::miner::{Miner, MinerControl, ShareSink, SharedMiner};
+#[cfg(feature = "profiling")]
+use crate::stratum::profiling::{Scope, Timer};
+use crate::stratum::target::Target;
+use crate::stratum::verbosity::{log_at, Category, Verbosity};
 use crate::stratum::{error::StratumError, types::*};
 use async_trait::async_trait;
 use hex;
+use log::Level;
 use rand::{thread_rng, Rng};
 use serde_json::Value;
-use std::sync::atomic::AtomicBool;
-use std::{
-    collections::HashMap,
-    sync::Arc,
-    time::{Duration, Instant},
-};
-use tokio::sync::Mutex;
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// Result produced by a [`Miner`]: a share, with the extranonce2, ntime and
+/// version bits it was mined at, and the job it was found on
+pub type MinerResult = Result<(Share, Arc<MiningJob>), StratumError>;
+
+/// Why job dispatching is paused, see [`JobManager::pause_for`]
+///
+/// Each reason is tracked apart, so e.g. a throttle policy resuming after the
+/// device cooled down doesn't lift a pause of the schedule or the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PauseReason {
+    /// Paused with [`JobManager::pause`]
+    Manual,
+    /// Outside the windows of a mining schedule
+    Schedule,
+    /// Paused by a throttle policy
+    Throttle,
+}
+
+impl PauseReason {
+    fn bit(self) -> u8 {
+        match self {
+            PauseReason::Manual => 1,
+            PauseReason::Schedule => 1 << 1,
+            PauseReason::Throttle => 1 << 2,
+        }
+    }
+}
+
+/// Number of recent jobs kept to resolve delayed shares against
+pub const JOB_HISTORY_LEN: usize = 16;
+
+/// How long after the pool's first difficulty a difficulty floor lifts by
+/// itself, for pools without vardiff, see [`JobManager::set_difficulty_floor`]
+pub const DIFFICULTY_FLOOR_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Local difficulty floor, see [`JobManager::set_difficulty_floor`]
+#[derive(Debug, Clone)]
+struct DifficultyFloor {
+    target: MiningTarget,
+    /// When the floor lifts by itself, set once the pool's difficulty arrives
+    expires_at: Option<Instant>,
+}
+
+/// Identifies one instance of a job
+///
+/// Some pools recycle short job ids quickly, so the id alone can refer to
+/// different jobs over time. Together with the previous block hash and ntime
+/// it identifies the job a share was mined on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JobKey {
+    pub job_id: String,
+    pub prev_hash: String,
+    pub ntime: String,
+}
+
+impl JobKey {
+    /// Key of the given job
+    pub fn of(job: &MiningJob) -> Self {
+        Self {
+            job_id: job.job_id.clone(),
+            prev_hash: job.prev_hash.clone(),
+            ntime: job.ntime.clone(),
+        }
+    }
+}
+
+/// Disagreement between the difficulty the pool set last and the targets
+/// derived from it, found by [`JobManager::check_consistency`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Inconsistency {
+    /// The stored or published target isn't the one of the pool's last
+    /// difficulty
+    TargetDrift {
+        /// Difficulty of the stored target, `None` if there is none
+        stored: Option<f64>,
+        received: f64,
+    },
+    /// The job handed to the miner carries no target, or another than the
+    /// pool's or the override's
+    JobTarget {
+        job_id: String,
+        /// Difficulty of the job's target, `None` if it has none
+        difficulty: Option<f64>,
+        expected: f64,
+    },
+}
+
+/// Extranonce assigned to the session by the pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extranonce {
+    pub extranonce1: String,
+    pub extranonce2_size: usize,
+}
+
+/// What to do when the sequential extranonce2 values of a job run out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Extranonce2Exhaustion {
+    /// Advance ntime by a second and start over, up to
+    /// [`Extranonce2Config::max_ntime_roll`]; then wait for a new job
+    #[default]
+    RollNtime,
+    /// Stop handing out values until the pool sends a new job, asking for
+    /// one right away
+    RequestJob,
+    /// Start over on the same ntime, skipping the values shares were
+    /// submitted with so they aren't found and submitted again
+    Wrap,
+}
+
+/// Sequential extranonce2 allocation, see [`JobManager::next_extranonce2`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extranonce2Config {
+    pub on_exhaustion: Extranonce2Exhaustion,
+    /// Fraction of a job's extranonce2 space in use at which
+    /// [`StratumEvent::Extranonce2Low`] is emitted
+    pub warn_at: f64,
+    /// Most seconds ntime is rolled past the job's
+    pub max_ntime_roll: u32,
+}
+
+impl Default for Extranonce2Config {
+    fn default() -> Self {
+        Self {
+            on_exhaustion: Extranonce2Exhaustion::default(),
+            warn_at: 0.9,
+            max_ntime_roll: 600,
+        }
+    }
+}
+
+/// How long shares of a job are still submitted after a newer clean-jobs
+/// notify replaced it, see [`JobManager::set_submit_window`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitWindow {
+    pub window: Duration,
+    pub on_late: LateShare,
+}
+
+/// What to do with shares submitted past their [`SubmitWindow`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LateShare {
+    /// Drop them without contacting the pool
+    #[default]
+    Refuse,
+    /// Submit them anyway, logging a warning
+    Flag,
+}
+
+/// Recent job with when it was received
+struct ReceivedJob {
+    job: Arc<MiningJob>,
+    at: Instant,
+}
+
+/// Extranonce2 handed out for the current job, with the ntime to mine it at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extranonce2Slot {
+    pub job_id: String,
+    pub extranonce2: String,
+    /// The job's ntime, or later if it was rolled
+    pub ntime: String,
+}
+
+/// Outcome of allocating an extranonce2
+pub(crate) enum Allocation {
+    Slot(Extranonce2Slot),
+    /// The job's space ran out; `newly` is set the first time it's reported
+    Exhausted {
+        job_id: String,
+        newly: bool,
+    },
+}
+
+impl Allocation {
+    pub(crate) fn into_slot(self) -> Result<Extranonce2Slot, StratumError> {
+        match self {
+            Allocation::Slot(slot) => Ok(slot),
+            Allocation::Exhausted { job_id, .. } => Err(StratumError::InvalidJob(format!(
+                "Extranonce2 space of job {} is exhausted",
+                job_id
+            ))),
+        }
+    }
+}
+
+/// Extranonce2 values handed out for one job
+struct Extranonce2Sequence {
+    job: JobKey,
+    next: u64,
+    /// Seconds ntime was rolled past the job's
+    ntime_roll: u32,
+    warned: bool,
+    wrapped: bool,
+    exhausted: bool,
+    /// Values shares were submitted with on the current ntime
+    shares: HashSet<u64>,
+}
+
+impl Extranonce2Sequence {
+    fn new(job: JobKey) -> Self {
+        Self {
+            job,
+            next: 0,
+            ntime_roll: 0,
+            warned: false,
+            wrapped: false,
+            exhausted: false,
+            shares: HashSet::new(),
+        }
+    }
+
+    /// Whether `ntime` is one the sequence rolled `job`'s to
+    fn rolled_to(&self, job: &MiningJob, ntime: &str) -> bool {
+        if self.job != JobKey::of(job) {
+            return false;
+        }
+        let (Ok(base), Ok(ntime)) = (
+            u32::from_str_radix(&job.ntime, 16),
+            u32::from_str_radix(ntime, 16),
+        ) else {
+            return false;
+        };
+        ntime > base && ntime - base <= self.ntime_roll
+    }
+}
+
+/// Seconds a share's ntime may be rolled past its job's, the two hours
+/// blocks are allowed into the future
+pub const MAX_NTIME_ROLL: u32 = 7200;
+
+/// Seconds `ntime` is rolled past `job`'s, `None` if it's earlier, further
+/// than [`MAX_NTIME_ROLL`] or not valid hex
+fn ntime_roll(job: &MiningJob, ntime: &str) -> Option<u32> {
+    if ntime.len() != 8 {
+        return None;
+    }
+    let base = u32::from_str_radix(&job.ntime, 16).ok()?;
+    let roll = u32::from_str_radix(ntime, 16).ok()?.checked_sub(base)?;
+    (roll <= MAX_NTIME_ROLL).then_some(roll)
+}
+
+/// Session generation, shared between the job manager and its worker
+///
+/// Bumped whenever work of the session so far becomes invalid, on reconnects
+/// and extranonce changes. Jobs carry the generation they were received in.
+#[derive(Debug, Default)]
+struct Generation {
+    current: AtomicU64,
+    /// Miner results and shares dropped for being from an earlier generation
+    stale_dropped: AtomicU64,
+}
+
+impl Generation {
+    fn current(&self) -> u64 {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    fn bump(&self) -> u64 {
+        self.current.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Whether work of `generation` is stale, counting it as dropped if so
+    fn drop_if_stale(&self, generation: u64) -> bool {
+        let stale = generation != self.current();
+        if stale {
+            self.stale_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        stale
+    }
+}
+
+/// Job handed to a worker, with the extranonce2 its miner works on
+type Dispatch = (Arc<MiningJob>, Extranonce2Slot);
+
+/// Channel to the background worker feeding jobs to the miner
+struct Worker {
+    jobs: tokio::sync::mpsc::UnboundedSender<Dispatch>,
+    handle: JoinHandle<()>,
+    /// Miner task of the current job
+    miner_task: Arc<std::sync::Mutex<Option<JoinHandle<()>>>>,
+}
+
+/// State shared between the job manager and its background worker
+#[derive(Clone)]
+struct WorkerState {
+    result_tx: tokio::sync::mpsc::UnboundedSender<MinerResult>,
+    currently_running_job_id: Arc<Mutex<Option<JobKey>>>,
+    currently_running_merkle_root: Arc<Mutex<Option<Vec<String>>>>,
+    /// When a miner task was asked to stop without having stopped yet
+    cancel_requested_at: Arc<std::sync::Mutex<Option<Instant>>>,
+    paused: Arc<watch::Sender<bool>>,
+    /// Results of jobs from an earlier generation are dropped
+    generation: Arc<Generation>,
+    events: broadcast::Sender<StratumEvent>,
+    verbosity: Arc<Verbosity>,
+}
+
+impl WorkerState {
+    fn emit(&self, event: StratumEvent) {
+        if self.verbosity.event_enabled(event.kind()) {
+            let _ = self.events.send(event);
+        }
+    }
+}
 
 /// Manages mining jobs and targets with validation and history tracking
 #[derive(Clone)]
 pub struct JobManager {
-    job_from_stratum_tx: tokio::sync::mpsc::UnboundedSender<MiningJob>,
-    is_alive: Arc<AtomicBool>,
-    pub result_receiver: Arc<
-        Mutex<Option<tokio::sync::mpsc::UnboundedReceiver<Result<(u32, MiningJob), StratumError>>>>,
-    >,
-    enqueued_job: Arc<Mutex<Option<MiningJob>>>,
+    worker: Arc<std::sync::Mutex<Worker>>,
+    spawn_worker: Arc<dyn Fn() -> Worker + Send + Sync>,
+    /// Workers of miners registered after the first, see
+    /// [`add_miner`](Self::add_miner)
+    added_workers: Arc<std::sync::Mutex<Vec<Worker>>>,
+    worker_state: WorkerState,
+    pub result_receiver: Arc<Mutex<Option<tokio::sync::mpsc::UnboundedReceiver<MinerResult>>>>,
+    enqueued_job: Arc<Mutex<Option<Arc<MiningJob>>>>,
     enqueued_difficulty: Arc<Mutex<Option<MiningTarget>>>,
-    currently_running_job_id: Arc<Mutex<Option<String>>>,
+    /// Target of the difficulty the pool set last, see
+    /// [`check_consistency`](Self::check_consistency)
+    received_difficulty: Arc<std::sync::Mutex<Option<MiningTarget>>>,
+    currently_running_job_id: Arc<Mutex<Option<JobKey>>>,
     currently_running_merkle_root: Arc<Mutex<Option<Vec<String>>>>,
+    cancel_requested_at: Arc<std::sync::Mutex<Option<Instant>>>,
+    history: Arc<Mutex<VecDeque<ReceivedJob>>>,
+    last_job_at: Arc<Mutex<Option<Instant>>>,
+    /// When a job was last handed to the worker
+    last_dispatch_at: Arc<std::sync::Mutex<Option<Instant>>>,
+    extranonce: Arc<std::sync::Mutex<Option<Extranonce>>>,
+    generation: Arc<Generation>,
+    extranonce2_config: Arc<std::sync::Mutex<Extranonce2Config>>,
+    extranonce2_sequence: Arc<std::sync::Mutex<Option<Extranonce2Sequence>>>,
+    /// Set when a dispatched job's extranonce2 space runs out, see
+    /// [`take_job_wanted`](Self::take_job_wanted)
+    job_wanted: Arc<AtomicBool>,
+    /// Version rolling mask granted by the pool
+    version_mask: Arc<std::sync::Mutex<Option<u32>>>,
+    /// Target handed to the miner instead of the pool's, see
+    /// [`set_target_override`](Self::set_target_override)
+    target_override: Arc<std::sync::Mutex<Option<MiningTarget>>>,
+    submit_window: Arc<std::sync::Mutex<Option<SubmitWindow>>>,
+    /// Target shares must meet until the pool's difficulty catches up, see
+    /// [`set_difficulty_floor`](Self::set_difficulty_floor)
+    difficulty_floor: Arc<std::sync::Mutex<Option<DifficultyFloor>>>,
+    /// See [`set_difficulty_multiplier`](Self::set_difficulty_multiplier)
+    difficulty_multiplier: Arc<std::sync::Mutex<f64>>,
+    /// Whether any [`PauseReason`] holds dispatching paused
+    paused: Arc<watch::Sender<bool>>,
+    /// Bits of the [`PauseReason`]s currently set
+    pause_reasons: Arc<std::sync::Mutex<u8>>,
+    /// Latest job handed to the miner
+    jobs: Arc<watch::Sender<Option<Arc<MiningJob>>>>,
+    /// Latest target set by the pool
+    targets: Arc<watch::Sender<Option<MiningTarget>>>,
+    pub(crate) miner_control: Arc<dyn MinerControl>,
+    events: broadcast::Sender<StratumEvent>,
+    /// Verbosity of logs and events, shared with the client
+    pub(crate) verbosity: Arc<Verbosity>,
+    /// Lock wait counters, shared with the client
+    pub(crate) contention: Arc<Contention>,
+}
+
+/// Spawn the worker running the miner on the latest job
+fn spawn_worker<M: Miner>(miner: M, state: WorkerState) -> Worker {
+    let (jobs, mut rx) = tokio::sync::mpsc::unbounded_channel::<Dispatch>();
+    let miner_task = Arc::new(std::sync::Mutex::new(None::<JoinHandle<()>>));
+    let worker_miner_task = miner_task.clone();
+    let mut paused_rx = state.paused.subscribe();
+
+    let background_worker = async move {
+        let mut current_running_task_canceller = None;
+        let mut latest_job: Option<Dispatch> = None;
+
+        loop {
+            tokio::select! {
+                job = rx.recv() => {
+                    let Some(job) = job else { break };
+                    latest_job = Some(job);
+                }
+                changed = paused_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    if *paused_rx.borrow_and_update() {
+                        if current_running_task_canceller.take().is_some() {
+                            state
+                                .cancel_requested_at
+                                .lock()
+                                .unwrap()
+                                .get_or_insert_with(Instant::now);
+                            log_at!(state.verbosity, Category::Jobs, Level::Info, "Miner task cancelled because mining was paused");
+                        }
+                        continue;
+                    }
+                    // Resumed, restart the miner on the latest job
+                }
+            }
+
+            if *paused_rx.borrow() {
+                log_at!(
+                    state.verbosity,
+                    Category::Jobs,
+                    Level::Info,
+                    "Mining is paused, holding job until resumed"
+                );
+                continue;
+            }
+
+            let Some((job, slot)) = latest_job.clone() else {
+                continue;
+            };
+
+            if current_running_task_canceller.take().is_some() {
+                state
+                    .cancel_requested_at
+                    .lock()
+                    .unwrap()
+                    .get_or_insert_with(Instant::now);
+                log_at!(
+                    state.verbosity,
+                    Category::Jobs,
+                    Level::Warn,
+                    "Miner task cancelled because a newer job was received"
+                );
+            }
+
+            *state.currently_running_job_id.lock().await = Some(JobKey::of(&job));
+            *state.currently_running_merkle_root.lock().await = Some(job.merkle_branch.clone());
+
+            let miner = miner.clone();
+            let generation = job.generation;
+            let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+            current_running_task_canceller = Some(stop_tx);
+
+            let state = state.clone();
+            let span = tracing::info_span!(
+                target: Category::Jobs.target(),
+                "job",
+                job_id = %job.job_id,
+                generation
+            );
+            let cancellable_task = tokio::spawn(async move {
+                let sink_state = state.clone();
+                let device = miner.device_id();
+                let shares = ShareSink::new(job.clone(), slot, move |res: MinerResult| {
+                    let state = &sink_state;
+                    if state.generation.drop_if_stale(generation) {
+                        log_at!(
+                            state.verbosity,
+                            Category::Shares,
+                            Level::Warn,
+                            "Dropping miner result of a job from a previous session generation"
+                        );
+                        return false;
+                    }
+                    if let Ok((share, job)) = &res {
+                        state.emit(StratumEvent::ShareFound {
+                            device: device.clone(),
+                            job: job.clone(),
+                            share: share.clone(),
+                        });
+                    }
+                    if let Err(err) = state.result_tx.send(res) {
+                        log_at!(
+                            state.verbosity,
+                            Category::Shares,
+                            Level::Error,
+                            "Failed to send miner result: {err}"
+                        );
+                        return false;
+                    }
+                    true
+                });
+                let miner_task = miner.mine(job, shares.clone());
+
+                tokio::select! {
+                    _ = stop_rx => {
+                        shares.cancel();
+                        log_at!(state.verbosity, Category::Jobs, Level::Warn, "Miner task cancelled");
+                    }
+                    _ = miner_task => {}
+                }
+
+                state.cancel_requested_at.lock().unwrap().take();
+                let _ = state.currently_running_job_id.lock().await.take();
+                let _ = state.currently_running_merkle_root.lock().await.take();
+            }.instrument(span));
+
+            *worker_miner_task.lock().unwrap() = Some(cancellable_task);
+        }
+    };
+
+    Worker {
+        jobs,
+        handle: tokio::spawn(background_worker),
+        miner_task,
+    }
 }
 
 impl JobManager {
     /// Create a new job manager
     pub fn new<M: Miner>(miner: M) -> Self {
-        let (job_from_stratum_tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<MiningJob>();
+        Self::with_events(miner, events::channel())
+    }
+
+    /// Create a new job manager publishing to the given event channel
+    pub fn with_events<M: Miner>(miner: M, events: broadcast::Sender<StratumEvent>) -> Self {
         let (result_tx, result_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let (paused, _) = watch::channel(false);
+        let miner_control: Arc<dyn MinerControl> = Arc::new(SharedMiner::new(miner.clone()));
+
+        let state = WorkerState {
+            result_tx,
+            currently_running_job_id: Arc::new(Mutex::new(None)),
+            currently_running_merkle_root: Arc::new(Mutex::new(None)),
+            cancel_requested_at: Arc::new(std::sync::Mutex::new(None)),
+            paused: Arc::new(paused),
+            generation: Arc::new(Generation::default()),
+            events: events.clone(),
+            verbosity: Arc::new(Verbosity::new()),
+        };
+        let worker_state = state.clone();
+        // Behind a lock so the closure is `Sync` without the miner being so
+        let miner = std::sync::Mutex::new(miner);
+        let spawn_worker: Arc<dyn Fn() -> Worker + Send + Sync> = Arc::new(move || {
+            let miner = miner.lock().unwrap().clone();
+            spawn_worker(miner, worker_state.clone())
+        });
 
-        let currently_running_job_id = Arc::new(Mutex::new(None));
-        let currently_running_job_id_clone = currently_running_job_id.clone();
-        let currently_running_merkle_root = Arc::new(Mutex::new(None));
-        let currently_running_merkle_root_clone = currently_running_merkle_root.clone();
+        Self {
+            worker: Arc::new(std::sync::Mutex::new(spawn_worker())),
+            spawn_worker,
+            added_workers: Arc::new(std::sync::Mutex::new(Vec::new())),
+            worker_state: state.clone(),
+            result_receiver: Arc::new(Mutex::new(Some(result_receiver))),
+            enqueued_job: Arc::new(Mutex::new(None)),
+            enqueued_difficulty: Arc::new(Mutex::new(None)),
+            received_difficulty: Arc::new(std::sync::Mutex::new(None)),
+            currently_running_job_id: state.currently_running_job_id,
+            currently_running_merkle_root: state.currently_running_merkle_root,
+            cancel_requested_at: state.cancel_requested_at,
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(JOB_HISTORY_LEN))),
+            last_job_at: Arc::new(Mutex::new(None)),
+            last_dispatch_at: Arc::new(std::sync::Mutex::new(None)),
+            extranonce: Arc::new(std::sync::Mutex::new(None)),
+            generation: state.generation,
+            extranonce2_config: Arc::new(std::sync::Mutex::new(Extranonce2Config::default())),
+            extranonce2_sequence: Arc::new(std::sync::Mutex::new(None)),
+            job_wanted: Arc::new(AtomicBool::new(false)),
+            version_mask: Arc::new(std::sync::Mutex::new(None)),
+            target_override: Arc::new(std::sync::Mutex::new(None)),
+            submit_window: Arc::new(std::sync::Mutex::new(None)),
+            difficulty_floor: Arc::new(std::sync::Mutex::new(None)),
+            difficulty_multiplier: Arc::new(std::sync::Mutex::new(1.0)),
+            paused: state.paused,
+            pause_reasons: Arc::new(std::sync::Mutex::new(0)),
+            jobs: Arc::new(watch::channel(None).0),
+            targets: Arc::new(watch::channel(None).0),
+            miner_control,
+            events,
+            verbosity: state.verbosity,
+            contention: Arc::new(Contention::new()),
+        }
+    }
 
-        let is_alive = Arc::new(AtomicBool::new(true));
-        let is_alive_clone = is_alive.clone();
+    /// Set the version rolling mask granted by the pool, used to rebuild the
+    /// block version of shares with version bits
+    pub fn set_version_mask(&self, mask: Option<u32>) {
+        *self.version_mask.lock().unwrap() = mask;
+    }
 
-        let background_worker = async move {
-            if !is_alive_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                return;
-            }
+    /// Hand jobs to the miner at `difficulty` instead of the pool's, or at
+    /// the pool's again with `None`
+    ///
+    /// Shares are still validated against the pool's target, so results
+    /// only meeting the override are never submitted. Takes effect from the
+    /// next job dispatched.
+    pub fn set_target_override(&self, difficulty: Option<f64>) {
+        *self.target_override.lock().unwrap() = difficulty.map(|d| self.target_of(d));
+    }
 
-            let mut current_running_task_canceller = None;
+    /// Target jobs are handed to the miner at instead of the pool's
+    pub fn target_override(&self) -> Option<MiningTarget> {
+        self.target_override.lock().unwrap().clone()
+    }
 
-            while let Some(job) = rx.recv().await {
-                if current_running_task_canceller.take().is_some() {
-                    log::warn!(target: "stratum", "Miner task cancelled because a newer job was received");
-                }
+    /// Only pass shares meeting `difficulty` until the pool sets a real one,
+    /// or lift the floor with `None`
+    ///
+    /// Smooths the start of a session on pools starting every miner at a low
+    /// difficulty, which fast hardware floods with shares before vardiff
+    /// catches up. The floor lifts by itself once the pool sets a difficulty
+    /// at least as high, or changes its difficulty, showing vardiff at work.
+    /// Pools without vardiff never do, so the floor also lifts
+    /// [`DIFFICULTY_FLOOR_TIMEOUT`] after the pool's first difficulty.
+    pub fn set_difficulty_floor(&self, difficulty: Option<f64>) {
+        let received = self.received_difficulty.lock().unwrap().is_some();
+        *self.difficulty_floor.lock().unwrap() = difficulty.map(|d| DifficultyFloor {
+            target: self.target_of(d),
+            expires_at: received.then(|| Instant::now() + DIFFICULTY_FLOOR_TIMEOUT),
+        });
+    }
 
-                *currently_running_job_id_clone.lock().await = Some(job.job_id.clone());
-                *currently_running_merkle_root_clone.lock().await = Some(job.merkle_branch.clone());
+    /// See [`set_difficulty_floor`](Self::set_difficulty_floor)
+    pub fn difficulty_floor(&self) -> Option<f64> {
+        self.active_difficulty_floor().map(|floor| floor.difficulty)
+    }
 
-                let miner = miner.clone();
-                let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
-                current_running_task_canceller = Some(stop_tx);
+    /// Target of the difficulty floor, lifting it once it expired
+    fn active_difficulty_floor(&self) -> Option<MiningTarget> {
+        let mut floor = self.difficulty_floor.lock().unwrap();
+        let expired = floor
+            .as_ref()?
+            .expires_at
+            .is_some_and(|expires_at| Instant::now() >= expires_at);
+        if expired {
+            let floor = floor.take()?;
+            log_at!(
+                self.verbosity,
+                Category::Difficulty,
+                Level::Info,
+                "Pool difficulty stayed below the local floor of {} for {:?}, lifting it",
+                floor.target.difficulty,
+                DIFFICULTY_FLOOR_TIMEOUT
+            );
+            return None;
+        }
+        floor.as_ref().map(|floor| floor.target.clone())
+    }
 
-                let result_tx = result_tx.clone();
+    /// Take pool difficulties as `multiplier` times the Bitcoin difficulty
+    /// of the same target
+    ///
+    /// Pools for scrypt and other algorithms scale the difficulty they
+    /// report, 65536 being common for scrypt. Difficulties the pool sets and
+    /// the ones passed to [`set_target_override`](Self::set_target_override)
+    /// and [`set_difficulty_floor`](Self::set_difficulty_floor) are in the
+    /// pool's units and converted to targets with the multiplier. Takes
+    /// effect from the next difficulty set.
+    pub fn set_difficulty_multiplier(&self, multiplier: f64) {
+        *self.difficulty_multiplier.lock().unwrap() = multiplier;
+    }
 
-                let currently_running_job_id_clone = currently_running_job_id_clone.clone();
-                let currently_running_merkle_root_clone =
-                    currently_running_merkle_root_clone.clone();
+    /// See [`set_difficulty_multiplier`](Self::set_difficulty_multiplier)
+    pub fn difficulty_multiplier(&self) -> f64 {
+        *self.difficulty_multiplier.lock().unwrap()
+    }
 
-                let cancellable_task = tokio::spawn(async move {
-                    let miner_task = miner.on_job_received(job);
+    /// Target of a difficulty in the pool's units
+    fn target_of(&self, difficulty: f64) -> MiningTarget {
+        MiningTarget::scaled(difficulty, self.difficulty_multiplier())
+    }
 
-                    tokio::select! {
-                        _ = stop_rx => {
-                            log::warn!(target: "stratum", "Miner task cancelled");
-                        }
-                        res = miner_task => {
-                            if let Err(err) = result_tx.send(res) {
-                                log::error!(target: "stratum", "Failed to send miner result: {err}");
-                            }
-                        }
-                    }
+    /// Lift the difficulty floor once the pool's `difficulty` makes it moot,
+    /// or start its timeout on the pool's first difficulty
+    fn lift_difficulty_floor(&self, difficulty: f64, previous: Option<f64>) {
+        let mut floor = self.difficulty_floor.lock().unwrap();
+        let Some(current) = floor.as_mut() else {
+            return;
+        };
+        current
+            .expires_at
+            .get_or_insert_with(|| Instant::now() + DIFFICULTY_FLOOR_TIMEOUT);
+        let floor_difficulty = current.target.difficulty;
+        let changed = previous.is_some_and(|previous| previous != difficulty);
+        if difficulty >= floor_difficulty || changed {
+            log_at!(
+                self.verbosity,
+                Category::Difficulty,
+                Level::Info,
+                "Pool difficulty {difficulty} arrived, lifting the local floor of {floor_difficulty}"
+            );
+            floor.take();
+        }
+    }
 
-                    let _ = currently_running_job_id_clone.lock().await.take();
-                    let _ = currently_running_merkle_root_clone.lock().await.take();
-                });
+    /// Limit how long shares of a job are submitted once a newer clean-jobs
+    /// notify replaced it, or lift the limit with `None`
+    ///
+    /// Pools reject such shares as stale whatever their job id, so slow
+    /// devices are better off not submitting them. The window counts from
+    /// when the replacing job was received; shares of jobs no longer in the
+    /// history of the last [`JOB_HISTORY_LEN`] jobs are not checked.
+    pub fn set_submit_window(&self, window: Option<SubmitWindow>) {
+        *self.submit_window.lock().unwrap() = window;
+    }
 
-                drop(cancellable_task);
-            }
+    /// See [`set_submit_window`](Self::set_submit_window)
+    pub fn submit_window(&self) -> Option<SubmitWindow> {
+        *self.submit_window.lock().unwrap()
+    }
+
+    /// Time since a newer clean-jobs notify replaced `job`, if one did
+    pub async fn superseded_for(&self, job: &MiningJob) -> Option<Duration> {
+        let history = self
+            .contention
+            .lock(LockSite::JobState, &self.history)
+            .await;
+        let key = JobKey::of(job);
+        let received = history
+            .iter()
+            .rposition(|received| JobKey::of(&received.job) == key)?;
+        history
+            .iter()
+            .skip(received + 1)
+            .find(|newer| newer.job.clean_jobs == Some(true))
+            .map(|newer| newer.at.elapsed())
+    }
+
+    /// Per-category verbosity of the job manager's logs and events
+    pub fn verbosity(&self) -> &Verbosity {
+        &self.verbosity
+    }
+
+    fn emit(&self, event: StratumEvent) {
+        if self.verbosity.event_enabled(event.kind()) {
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Time since a job was last handed to the miner
+    pub fn last_dispatch_age(&self) -> Option<Duration> {
+        self.last_dispatch_at.lock().ok()?.map(|at| at.elapsed())
+    }
+
+    /// Time a miner task has been ignoring a request to stop, if any
+    pub fn unresponsive_miner_age(&self) -> Option<Duration> {
+        self.cancel_requested_at.lock().ok()?.map(|at| at.elapsed())
+    }
+
+    /// Replace the background worker, e.g. after it wedged
+    ///
+    /// The old worker and its miner task are aborted; a miner blocking its
+    /// thread can't be interrupted, but is no longer waited for. The latest
+    /// job is handed to the new worker right away.
+    pub async fn restart_worker(&self) -> Result<(), StratumError> {
+        let worker = (self.spawn_worker)();
+        let old = std::mem::replace(&mut *self.worker.lock().unwrap(), worker);
+        old.handle.abort();
+        if let Some(task) = old.miner_task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.cancel_requested_at.lock().unwrap().take();
+        self.currently_running_job_id.lock().await.take();
+        self.currently_running_merkle_root.lock().await.take();
+        log_at!(
+            self.verbosity,
+            Category::Jobs,
+            Level::Warn,
+            "Restarted the miner worker"
+        );
+        self.maybe_run_job().await
+    }
+
+    /// Stop the miner workers, waiting for them and their miner tasks to
+    /// exit
+    ///
+    /// Jobs are no longer handed to any miner, added ones included, until
+    /// [`restart_worker`](Self::restart_worker) starts a new worker.
+    pub async fn shutdown(&self) {
+        let mut workers = std::mem::take(&mut *self.added_workers.lock().unwrap());
+        let idle = Worker {
+            jobs: tokio::sync::mpsc::unbounded_channel().0,
+            handle: tokio::spawn(async {}),
+            miner_task: Arc::new(std::sync::Mutex::new(None)),
         };
+        workers.push(std::mem::replace(&mut *self.worker.lock().unwrap(), idle));
+        for worker in workers {
+            // Stopped first so it can't start another miner task
+            worker.handle.abort();
+            let _ = worker.handle.await;
+            let miner_task = worker.miner_task.lock().unwrap().take();
+            if let Some(task) = miner_task {
+                task.abort();
+                let _ = task.await;
+            }
+        }
+        self.cancel_requested_at.lock().unwrap().take();
+        self.currently_running_job_id.lock().await.take();
+        self.currently_running_merkle_root.lock().await.take();
+    }
+
+    /// Register another miner, e.g. a device attached after the session
+    /// started, to be handed the same jobs as the first
+    ///
+    /// The job the miners are working on, with its target, is replayed to
+    /// the new miner right away instead of it waiting for the pool's next
+    /// job. Its results arrive with those of the other miners.
+    pub fn add_miner<M: Miner>(&self, miner: M) {
+        let worker = spawn_worker(miner, self.worker_state.clone());
+        let job = self.jobs.borrow().clone();
+        if let Some(job) = job {
+            log_at!(
+                self.verbosity,
+                Category::Jobs,
+                Level::Info,
+                "Replaying job {} to a newly added miner",
+                job.job_id
+            );
+            if let Some(slot) = self.dispatch_slot(&job) {
+                let _ = worker.jobs.send((job, slot));
+            }
+        }
+        self.added_workers.lock().unwrap().push(worker);
+    }
 
-        tokio::spawn(background_worker);
+    /// Stop dispatching jobs to the miner and cancel the running miner task
+    ///
+    /// Jobs and difficulty changes are still tracked while paused so mining
+    /// resumes on the latest job. Same as [`pause_for`](Self::pause_for) with
+    /// [`PauseReason::Manual`].
+    pub fn pause(&self) {
+        self.pause_for(PauseReason::Manual);
+    }
 
-        Self {
-            job_from_stratum_tx,
-            is_alive,
-            result_receiver: Arc::new(Mutex::new(Some(result_receiver))),
-            enqueued_job: Arc::new(Mutex::new(None)),
-            enqueued_difficulty: Arc::new(Mutex::new(None)),
-            currently_running_job_id,
-            currently_running_merkle_root,
+    /// Lift a [`pause`](Self::pause), resuming once no other reason holds
+    /// mining paused
+    pub fn resume(&self) {
+        self.resume_for(PauseReason::Manual);
+    }
+
+    /// Pause job dispatching for `reason`, on top of any other reasons
+    pub fn pause_for(&self, reason: PauseReason) {
+        let mut reasons = self.pause_reasons.lock().unwrap();
+        *reasons |= reason.bit();
+        self.set_paused(*reasons != 0);
+    }
+
+    /// Lift the pause for `reason`; dispatching resumes, restarting the miner
+    /// on the latest job, once no reason remains
+    pub fn resume_for(&self, reason: PauseReason) {
+        let mut reasons = self.pause_reasons.lock().unwrap();
+        *reasons &= !reason.bit();
+        self.set_paused(*reasons != 0);
+    }
+
+    /// Only wake the worker when dispatching actually stops or restarts, so
+    /// resuming while running doesn't restart the miner on the same job
+    fn set_paused(&self, paused: bool) {
+        self.paused.send_if_modified(|current| {
+            let modified = *current != paused;
+            *current = paused;
+            modified
+        });
+    }
+
+    /// Check whether job dispatching is paused, for any reason
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Check whether job dispatching is paused for `reason`
+    pub fn is_paused_for(&self, reason: PauseReason) -> bool {
+        *self.pause_reasons.lock().unwrap() & reason.bit() != 0
+    }
+
+    /// Watch the jobs handed to the miner, with their target set
+    ///
+    /// The value is reset to `None` when jobs are invalidated, e.g. by an
+    /// extranonce change.
+    pub fn jobs(&self) -> watch::Receiver<Option<Arc<MiningJob>>> {
+        self.jobs.subscribe()
+    }
+
+    /// Watch the target set by the pool
+    pub fn targets(&self) -> watch::Receiver<Option<MiningTarget>> {
+        self.targets.subscribe()
+    }
+
+    /// Extranonce of the session, once subscribed
+    pub async fn extranonce(&self) -> Option<Extranonce> {
+        self.extranonce.lock().unwrap().clone()
+    }
+
+    /// Switch to a new extranonce, e.g. on `mining.set_extranonce` or when a
+    /// re-subscription is assigned a different one
+    ///
+    /// Jobs received under a different extranonce1 build a different coinbase,
+    /// so on a change the current job, the job history and the extranonce2
+    /// sequence are dropped until the pool sends the next job. A miner task
+    /// still running on a dropped job has its result discarded, and shares of
+    /// dropped jobs no longer validate. Returns whether the extranonce changed.
+    pub async fn set_extranonce(&self, extranonce: Extranonce) -> bool {
+        // Same lock order as maybe_run_job, so no job slips in between
+        let mut enqueued_job = self
+            .contention
+            .lock(LockSite::JobState, &self.enqueued_job)
+            .await;
+        let mut history = self
+            .contention
+            .lock(LockSite::JobState, &self.history)
+            .await;
+        let mut current = self.extranonce.lock().unwrap();
+        if current.as_ref() == Some(&extranonce) {
+            return false;
+        }
+
+        let had_extranonce = current.replace(extranonce).is_some();
+        if had_extranonce {
+            log_at!(
+                self.verbosity,
+                Category::Difficulty,
+                Level::Info,
+                "Extranonce changed, dropping jobs built on the previous one"
+            );
+            enqueued_job.take();
+            history.clear();
+            self.jobs.send_replace(None);
+            self.generation.bump();
+            self.extranonce2_sequence.lock().unwrap().take();
         }
+        true
+    }
+
+    /// Current session generation, see [`MiningJob::generation`]
+    pub fn generation(&self) -> u64 {
+        self.generation.current()
+    }
+
+    /// Start a new session generation, e.g. after reconnecting or switching
+    /// pools, returning it
+    ///
+    /// Miner results and shares of jobs received before are dropped rather
+    /// than submitted to the new session, and counted in
+    /// [`stale_results_dropped`](Self::stale_results_dropped).
+    pub fn new_generation(&self) -> u64 {
+        self.generation.bump()
+    }
+
+    /// Number of miner results and shares dropped for being from an earlier
+    /// session generation
+    pub fn stale_results_dropped(&self) -> u64 {
+        self.generation.stale_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Whether work of `generation` is from an earlier session generation,
+    /// counting it as dropped if so
+    pub(crate) fn drop_if_stale(&self, generation: u64) -> bool {
+        self.generation.drop_if_stale(generation)
     }
 
     /// Generate a random extranonce2 value of the specified size
@@ -108,8 +971,181 @@ impl JobManager {
         hex::encode(bytes)
     }
 
+    /// Set how sequential extranonce2 values are handed out
+    pub fn set_extranonce2_config(&self, config: Extranonce2Config) {
+        *self.extranonce2_config.lock().unwrap() = config;
+    }
+
+    /// Next sequential extranonce2 for the current job
+    ///
+    /// Values count up from zero for each job. When the job's space is about
+    /// to run out [`StratumEvent::Extranonce2Low`] is emitted, and when it
+    /// has run out the [`Extranonce2Config::on_exhaustion`] policy applies.
+    /// Fails while the space is exhausted until the pool sends a new job.
+    pub async fn next_extranonce2(&self) -> Result<Extranonce2Slot, StratumError> {
+        self.allocate_extranonce2().await?.into_slot()
+    }
+
+    pub(crate) async fn allocate_extranonce2(&self) -> Result<Allocation, StratumError> {
+        let job = self.get_job_or_error().await?;
+        self.allocate_extranonce2_for(&job)
+    }
+
+    /// Extranonce2 size of the session, once subscribed
+    fn extranonce2_size(&self) -> Option<usize> {
+        self.extranonce
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|extranonce| extranonce.extranonce2_size)
+    }
+
+    /// Extranonce2 for a miner starting on `job`, `None` if the job's space
+    /// is exhausted
+    ///
+    /// Before subscribing there is no extranonce2 to allocate, and the miner
+    /// gets an empty one.
+    fn dispatch_slot(&self, job: &MiningJob) -> Option<Extranonce2Slot> {
+        match self.allocate_extranonce2_for(job) {
+            Ok(Allocation::Slot(slot)) => Some(slot),
+            Ok(Allocation::Exhausted { newly, .. }) => {
+                if newly {
+                    self.job_wanted.store(true, Ordering::SeqCst);
+                }
+                None
+            }
+            Err(_) => Some(Extranonce2Slot {
+                job_id: job.job_id.clone(),
+                extranonce2: String::new(),
+                ntime: job.ntime.clone(),
+            }),
+        }
+    }
+
+    /// Whether a job's extranonce2 space ran out on dispatch since last
+    /// asked, so the pool should be asked for a new job
+    pub(crate) fn take_job_wanted(&self) -> bool {
+        self.job_wanted.swap(false, Ordering::SeqCst)
+    }
+
+    fn allocate_extranonce2_for(&self, job: &MiningJob) -> Result<Allocation, StratumError> {
+        let size = self
+            .extranonce2_size()
+            .ok_or_else(|| StratumError::Protocol("Not subscribed".into()))?;
+        let config = *self.extranonce2_config.lock().unwrap();
+        let space = 256u64.checked_pow(size as u32).unwrap_or(u64::MAX);
+
+        let mut sequence = self.extranonce2_sequence.lock().unwrap();
+        let key = JobKey::of(job);
+        if sequence.as_ref().is_none_or(|sequence| sequence.job != key) {
+            *sequence = Some(Extranonce2Sequence::new(key));
+        }
+        let sequence = sequence.as_mut().unwrap();
+
+        loop {
+            if sequence.exhausted {
+                return Ok(Allocation::Exhausted {
+                    job_id: job.job_id.clone(),
+                    newly: false,
+                });
+            }
+            if sequence.next >= space {
+                match config.on_exhaustion {
+                    Extranonce2Exhaustion::RollNtime
+                        if sequence.ntime_roll < config.max_ntime_roll =>
+                    {
+                        sequence.ntime_roll += 1;
+                        sequence.shares.clear();
+                    }
+                    Extranonce2Exhaustion::Wrap if sequence.shares.len() < space as usize => {
+                        sequence.wrapped = true;
+                    }
+                    _ => {
+                        log_at!(
+                            self.verbosity,
+                            Category::Jobs,
+                            Level::Warn,
+                            "Extranonce2 space of job {} is exhausted, waiting for a new job",
+                            job.job_id
+                        );
+                        sequence.exhausted = true;
+                        return Ok(Allocation::Exhausted {
+                            job_id: job.job_id.clone(),
+                            newly: true,
+                        });
+                    }
+                }
+                log_at!(
+                    self.verbosity,
+                    Category::Jobs,
+                    Level::Info,
+                    "Extranonce2 space of job {} is exhausted, starting over ({:?})",
+                    job.job_id,
+                    config.on_exhaustion
+                );
+                sequence.next = 0;
+                sequence.warned = false;
+            }
+
+            let value = sequence.next;
+            sequence.next += 1;
+            if sequence.wrapped && sequence.shares.contains(&value) {
+                continue;
+            }
+
+            if !sequence.warned && sequence.next as f64 >= space as f64 * config.warn_at {
+                sequence.warned = true;
+                self.emit(StratumEvent::Extranonce2Low {
+                    job_id: job.job_id.clone(),
+                    remaining: space - sequence.next,
+                    space,
+                });
+            }
+
+            let ntime = match sequence.ntime_roll {
+                0 => job.ntime.clone(),
+                roll => {
+                    let base = u32::from_str_radix(&job.ntime, 16)
+                        .map_err(|_| StratumError::InvalidJob("Invalid ntime".into()))?;
+                    format!("{:08x}", base.wrapping_add(roll))
+                }
+            };
+            let bytes = value.to_be_bytes();
+            let extranonce2 = if size <= bytes.len() {
+                hex::encode(&bytes[bytes.len() - size..])
+            } else {
+                format!("{}{}", "00".repeat(size - bytes.len()), hex::encode(bytes))
+            };
+            return Ok(Allocation::Slot(Extranonce2Slot {
+                job_id: job.job_id.clone(),
+                extranonce2,
+                ntime,
+            }));
+        }
+    }
+
+    /// Remember the extranonce2 of a submitted share, so wrapping skips it
+    fn note_share_extranonce2(&self, job: &MiningJob, share: &Share) {
+        let mut sequence = self.extranonce2_sequence.lock().unwrap();
+        let Some(sequence) = sequence.as_mut() else {
+            return;
+        };
+        let current_ntime = match sequence.ntime_roll {
+            0 => job.ntime == share.ntime,
+            _ => sequence.rolled_to(job, &share.ntime),
+        };
+        if sequence.job != JobKey::of(job) || !current_ntime {
+            return;
+        }
+        if let Ok(value) = u64::from_str_radix(&share.extranonce2, 16) {
+            sequence.shares.insert(value);
+        }
+    }
+
     /// Validate a mining job notification
-    fn parse_job(params: &[Value]) -> Result<MiningJob, StratumError> {
+    pub(crate) fn parse_job(params: &[Value]) -> Result<MiningJob, StratumError> {
+        #[cfg(feature = "profiling")]
+        let _profile = Timer::enter(Scope::Parse);
         if params.len() < 8 {
             return Err(StratumError::InvalidJob("Incomplete job parameters".into()));
         }
@@ -189,24 +1225,11 @@ impl JobManager {
             ntime: ntime.to_string(),
             clean_jobs,
             target: None,
+            raw_params: Arc::new(params.to_vec()),
+            generation: 0,
         })
     }
 
-    /// Calculate target from difficulty
-    fn calculate_target(difficulty: f64) -> [u8; 32] {
-        let mut final_target = [0u8; 32];
-
-        // For difficulty 1, target is:
-        // 0x00000000ffff0000000000000000000000000000000000000000000000000000
-
-        // Calculate mantissa with better precision
-        let mantissa = (0xffff as f64 / difficulty) as u16;
-        final_target[2] = (mantissa >> 8) as u8; // High byte
-        final_target[3] = mantissa as u8; // Low byte
-
-        final_target
-    }
-
     /// Handle a new difficulty notification
     /// Step 1: Receive difficulty notification
     pub async fn handle_difficulty_notification(
@@ -227,14 +1250,29 @@ impl JobManager {
             return Err(StratumError::Protocol("Difficulty must be positive".into()));
         }
 
-        let final_target = Self::calculate_target(difficulty);
-        let mut lock = self.enqueued_difficulty.lock().await;
-        *lock = Some(MiningTarget {
-            difficulty,
-            target: hex::encode(&final_target),
-        });
-
+        log_at!(
+            self.verbosity,
+            Category::Difficulty,
+            Level::Info,
+            "Pool set difficulty {difficulty}"
+        );
+        let target = self.target_of(difficulty);
+        let mut lock = self
+            .contention
+            .lock(LockSite::JobState, &self.enqueued_difficulty)
+            .await;
+        let previous = lock.replace(target.clone()).map(|target| target.difficulty);
+        // Updated under the lock so the consistency check never sees them apart
+        *self.received_difficulty.lock().unwrap() = Some(target.clone());
+        self.targets.send_replace(Some(target));
         drop(lock);
+        self.lift_difficulty_floor(difficulty, previous);
+        if previous != Some(difficulty) {
+            self.emit(StratumEvent::DifficultyChanged {
+                difficulty,
+                previous,
+            });
+        }
 
         self.maybe_run_job().await
     }
@@ -242,66 +1280,212 @@ impl JobManager {
     /// Handle a new job notification
     /// Step 2: Receive job, expect a difficulty notification
     pub async fn handle_job_notification(&self, params: &[Value]) -> Result<(), StratumError> {
-        let job = Self::parse_job(params)?;
-        let mut lock = self.enqueued_job.lock().await;
-        *lock = Some(job.clone());
+        let mut job = Self::parse_job(params)?;
+        job.generation = self.generation.current();
+        let job = Arc::new(job);
+        *self.last_job_at.lock().await = Some(Instant::now());
+        let mut lock = self
+            .contention
+            .lock(LockSite::JobState, &self.enqueued_job)
+            .await;
+        let previous = lock.replace(job.clone());
         drop(lock);
 
+        let mut history = self
+            .contention
+            .lock(LockSite::JobState, &self.history)
+            .await;
+        if history.len() >= JOB_HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(ReceivedJob {
+            job: job.clone(),
+            at: Instant::now(),
+        });
+        drop(history);
+
+        self.emit(StratumEvent::NewJob { job: job.clone() });
+        if previous.is_some_and(|previous| previous.prev_hash != job.prev_hash) {
+            self.emit(StratumEvent::NewBlock {
+                prev_hash: job.prev_hash.clone(),
+                height_hint: job.height(),
+            });
+        }
+
         // Now that we have received the difficulty, we can send the job to the background processor
         self.maybe_run_job().await
     }
 
     pub async fn maybe_run_job(&self) -> Result<(), StratumError> {
         // TODO: Refactor all this into a single Mutex wrapper
-        let mut enqueued_job = self.enqueued_job.lock().await;
-        let enqueued_difficulty = self.enqueued_difficulty.lock().await;
+        let mut enqueued_job = self
+            .contention
+            .lock(LockSite::JobState, &self.enqueued_job)
+            .await;
+        let enqueued_difficulty = self
+            .contention
+            .lock(LockSite::JobState, &self.enqueued_difficulty)
+            .await;
         let currently_running_job_id = self.currently_running_job_id.lock().await;
         let currently_running_merkle_root = self.currently_running_merkle_root.lock().await;
 
         match (enqueued_job.clone(), enqueued_difficulty.clone()) {
             (Some(mut job), Some(difficulty)) => {
-                let job_ids_changed =
-                    job.job_id != *currently_running_job_id.clone().unwrap_or_default();
-                let merkle_root_changed =
-                    job.merkle_branch != currently_running_merkle_root.clone().unwrap_or_default();
+                let job_ids_changed = currently_running_job_id.as_ref() != Some(&JobKey::of(&job));
+                let merkle_root_changed = job.merkle_branch.as_slice()
+                    != currently_running_merkle_root.as_deref().unwrap_or_default();
                 let needs_update = job.clean_jobs.is_some();
 
                 let needs_to_run = job_ids_changed || merkle_root_changed || needs_update;
 
                 if needs_to_run {
-                    job.target = Some(difficulty);
+                    // Only copies the job when it's shared and the target changed
+                    if job.target.as_ref() != Some(&difficulty) {
+                        Arc::make_mut(&mut job).target = Some(difficulty);
+                    }
                     *enqueued_job = Some(job.clone());
-                    log::info!(target: "stratum", "Execution criteria met. Running job: {job:?}");
-
-                    self.job_from_stratum_tx.send(job).map_err(|err| {
-                        StratumError::Io(format!(
-                            "Failed to send job to job_from_stratum channel - {err}"
-                        ))
-                    })?;
+                    // The job kept for validating shares keeps the pool's target
+                    if let Some(target) = self.target_override() {
+                        Arc::make_mut(&mut job).target = Some(target);
+                    }
+                    log_at!(
+                        self.verbosity,
+                        Category::Jobs,
+                        Level::Info,
+                        "Execution criteria met. Running job: {job:?}"
+                    );
+                    self.jobs.send_replace(Some(job.clone()));
+
+                    *self.last_dispatch_at.lock().unwrap() = Some(Instant::now());
+                    // Each miner gets its own extranonce2, so they don't
+                    // search the same space. Added miners whose worker
+                    // stopped are forgotten.
+                    self.added_workers.lock().unwrap().retain(|worker| {
+                        self.dispatch_slot(&job)
+                            .is_none_or(|slot| worker.jobs.send((job.clone(), slot)).is_ok())
+                    });
+                    if let Some(slot) = self.dispatch_slot(&job) {
+                        self.worker
+                            .lock()
+                            .unwrap()
+                            .jobs
+                            .send((job, slot))
+                            .map_err(|err| {
+                                StratumError::Io(format!(
+                                    "Failed to send job to job_from_stratum channel - {err}"
+                                ))
+                            })?;
+                    }
                 } else {
-                    log::warn!(target: "stratum", "Job does not meet the criteria to run: job_ids_changed: {job_ids_changed}, merkle_root_changed: {merkle_root_changed}");
+                    log_at!(self.verbosity, Category::Jobs, Level::Warn, "Job does not meet the criteria to run: job_ids_changed: {job_ids_changed}, merkle_root_changed: {merkle_root_changed}");
                 }
             }
 
             _ => {
-                log::warn!(target: "stratum", "Still waiting for both job and difficulty to be set ...");
+                log_at!(
+                    self.verbosity,
+                    Category::Jobs,
+                    Level::Warn,
+                    "Still waiting for both job and difficulty to be set ..."
+                );
             }
         }
 
         Ok(())
     }
 
-    async fn get_job_or_error(&self) -> Result<MiningJob, StratumError> {
-        self.enqueued_job
-            .lock()
-            .await
+    /// Check the stored target and the job handed to the miner against the
+    /// difficulty the pool set last, correcting what disagrees
+    ///
+    /// They can drift apart when notifications race a reconnect. A drifted
+    /// target is replaced with the pool's, and a job without the right target
+    /// is handed to the miner again with it. Each finding is logged and
+    /// emitted as a [`StratumEvent::TargetInconsistency`]. Nothing is checked
+    /// before the pool sets a difficulty.
+    pub async fn check_consistency(&self) -> Result<Vec<Inconsistency>, StratumError> {
+        let mut found = Vec::new();
+        {
+            // Locked in the order of `maybe_run_job`, so no dispatch is half done
+            let _enqueued_job = self
+                .contention
+                .lock(LockSite::JobState, &self.enqueued_job)
+                .await;
+            let mut stored = self
+                .contention
+                .lock(LockSite::JobState, &self.enqueued_difficulty)
+                .await;
+            let Some(received) = self.received_difficulty.lock().unwrap().clone() else {
+                return Ok(found);
+            };
+
+            let published = self.targets.borrow().clone();
+            let drifted = [stored.as_ref(), published.as_ref()]
+                .into_iter()
+                .find(|target| *target != Some(&received));
+            if let Some(drifted) = drifted {
+                found.push(Inconsistency::TargetDrift {
+                    stored: drifted.map(|target| target.difficulty),
+                    received: received.difficulty,
+                });
+                *stored = Some(received.clone());
+                self.targets.send_replace(Some(received.clone()));
+            }
+
+            let dispatched = self.jobs.borrow().clone();
+            if let Some(job) = dispatched {
+                let override_target = self.target_override();
+                let expected = override_target.as_ref().unwrap_or(&received);
+                let target = job.target.as_ref();
+                if target != Some(&received) && target != Some(expected) {
+                    found.push(Inconsistency::JobTarget {
+                        job_id: job.job_id.clone(),
+                        difficulty: target.map(|target| target.difficulty),
+                        expected: expected.difficulty,
+                    });
+                }
+            }
+        }
+        if found.is_empty() {
+            return Ok(found);
+        }
+
+        for inconsistency in &found {
+            log_at!(
+                self.verbosity,
+                Category::Difficulty,
+                Level::Warn,
+                "Correcting inconsistent target: {inconsistency:?}"
+            );
+            self.emit(StratumEvent::TargetInconsistency {
+                inconsistency: inconsistency.clone(),
+            });
+        }
+        // Forgetting the running job hands the current one over again
+        self.currently_running_job_id.lock().await.take();
+        self.maybe_run_job().await?;
+        Ok(found)
+    }
+
+    async fn get_job_or_error(&self) -> Result<Arc<MiningJob>, StratumError> {
+        self.contention
+            .lock(LockSite::JobState, &self.enqueued_job)
+            .await
             .clone()
             .ok_or_else(|| StratumError::Protocol("No job available".into()))
     }
 
+    /// Time since the pool sent the last valid job
+    pub async fn last_job_age(&self) -> Option<Duration> {
+        self.last_job_at.lock().await.map(|at| at.elapsed())
+    }
+
     /// Get the current mining job if available
-    pub async fn get_current_job(&self) -> Result<Option<MiningJob>, StratumError> {
-        Ok(self.enqueued_job.lock().await.clone())
+    pub async fn get_current_job(&self) -> Result<Option<Arc<MiningJob>>, StratumError> {
+        Ok(self
+            .contention
+            .lock(LockSite::JobState, &self.enqueued_job)
+            .await
+            .clone())
     }
 
     /// Get the current target if available
@@ -313,9 +1497,59 @@ impl JobManager {
             .ok_or_else(|| StratumError::Protocol("No target set".into()))
     }
 
+    /// Look up a recent job by its full key
+    pub async fn find_job(&self, key: &JobKey) -> Option<Arc<MiningJob>> {
+        self.contention
+            .lock(LockSite::JobState, &self.history)
+            .await
+            .iter()
+            .rev()
+            .find(|received| JobKey::of(&received.job) == *key)
+            .map(|received| received.job.clone())
+    }
+
+    /// Resolve the job instance a share was mined on
+    ///
+    /// Matches on the job id, so shares with a rolled ntime still resolve.
+    /// When the pool reused the id, the most recent instance whose ntime the
+    /// share's could have been rolled from is preferred, so a delayed share
+    /// isn't attributed to a newer job reusing the same id; failing that,
+    /// the most recent instance of the current generation.
+    pub async fn job_for_share(&self, share: &Share) -> Option<Arc<MiningJob>> {
+        let history = self
+            .contention
+            .lock(LockSite::JobState, &self.history)
+            .await;
+        let sequence = self.extranonce2_sequence.lock().unwrap();
+        let generation = self.generation.current();
+        let instances: Vec<&Arc<MiningJob>> = history
+            .iter()
+            .rev()
+            .map(|received| &received.job)
+            .filter(|job| job.job_id == share.job_id)
+            .collect();
+        instances
+            .iter()
+            .find(|job| {
+                job.ntime == share.ntime
+                    || sequence
+                        .as_ref()
+                        .is_some_and(|sequence| sequence.rolled_to(job, &share.ntime))
+            })
+            .or_else(|| {
+                instances
+                    .iter()
+                    .find(|job| ntime_roll(job, &share.ntime).is_some())
+            })
+            .or_else(|| instances.iter().find(|job| job.generation == generation))
+            .or_else(|| instances.first())
+            .map(|job| Arc::clone(job))
+    }
+
     /// Validate a share submission
     pub async fn validate_share(&self, share: &Share) -> Result<bool, StratumError> {
-        let job = self.get_job_or_error().await?;
+        // Make sure there's a job at all before looking the share's job up
+        self.get_job_or_error().await?;
 
         // Validate nonce format
         if share.nonce.len() != 8 {
@@ -332,19 +1566,101 @@ impl JobManager {
                 "Extranonce2 must be hex encoded".into(),
             ));
         }
+        let size = self.extranonce2_size();
+        if let Some(size) = size.filter(|size| share.extranonce2.len() != size * 2) {
+            return Err(StratumError::InvalidJob(format!(
+                "Extranonce2 must be {} bytes",
+                size
+            )));
+        }
 
-        // Validate ntime
-        if share.ntime != job.ntime {
-            return Err(StratumError::InvalidJob("Invalid ntime".into()));
+        // Validate the share belongs to a known job, at an ntime it allows
+        let Some(job) = self.job_for_share(share).await else {
+            return Err(StratumError::InvalidJob(format!(
+                "Unknown job {}",
+                share.job_id
+            )));
+        };
+        if ntime_roll(&job, &share.ntime).is_none() {
+            return Err(StratumError::InvalidJob(format!(
+                "ntime {} out of range for job {} at {}",
+                share.ntime, share.job_id, job.ntime
+            )));
         }
+        self.note_share_extranonce2(&job, share);
 
-        // In a real implementation, we would:
-        // 1. Reconstruct the block header
-        // 2. Hash it
-        // 3. Compare against target
+        // Without the extranonce1 and target the hash can't be checked
+        let Some(extranonce) = self.extranonce.lock().unwrap().clone() else {
+            return Ok(true);
+        };
+        let target = match job.target.clone() {
+            Some(target) => target,
+            None => match self
+                .contention
+                .lock(LockSite::JobState, &self.enqueued_difficulty)
+                .await
+                .clone()
+            {
+                Some(target) => target,
+                None => return Ok(true),
+            },
+        };
+        let target = match self.active_difficulty_floor() {
+            Some(floor) if floor.difficulty > target.difficulty => floor,
+            _ => target,
+        };
+        let target = target
+            .to_target()
+            .ok_or_else(|| StratumError::InvalidJob(format!("Invalid target {}", target.target)))?;
 
-        // For now, just validate formats
-        Ok(true)
+        let header = self.share_header(&job, &extranonce.extranonce1, share)?;
+        Ok(target.is_met_by(&header.hash()))
+    }
+
+    /// Difficulty the hash of a share meets, in the pool's units
+    ///
+    /// Usually well above the difficulty the share was submitted at. `None`
+    /// when the share's header can't be built, e.g. for an unknown job.
+    pub async fn share_difficulty(&self, share: &Share) -> Option<f64> {
+        let job = self.job_for_share(share).await?;
+        let extranonce1 = self
+            .extranonce
+            .lock()
+            .unwrap()
+            .as_ref()?
+            .extranonce1
+            .clone();
+        let header = self.share_header(&job, &extranonce1, share).ok()?;
+        Some(Target::from_bytes(&header.hash()).difficulty() * self.difficulty_multiplier())
+    }
+
+    /// Header of a share of `job`, with its version bits rolled in
+    fn share_header(
+        &self,
+        job: &MiningJob,
+        extranonce1: &str,
+        share: &Share,
+    ) -> Result<BlockHeader, StratumError> {
+        let mut version = BlockHeader::job_version(job)?;
+        if let Some(version_bits) = &share.version_bits {
+            let bits = u32::from_str_radix(version_bits, 16).map_err(|_| {
+                StratumError::InvalidJob(format!("Invalid version bits {}", version_bits))
+            })?;
+            let mask = self
+                .version_mask
+                .lock()
+                .unwrap()
+                .unwrap_or(DEFAULT_VERSION_ROLLING_MASK);
+            version = header::rolled_version(version, bits, mask);
+        }
+        BlockHeader::from_job(
+            job,
+            extranonce1,
+            &share.extranonce2,
+            &share.ntime,
+            &share.nonce,
+            version,
+        )
     }
 }
 
@@ -353,7 +1669,10 @@ pub struct TestMiner;
 
 #[async_trait]
 impl Miner for TestMiner {
-    async fn on_job_received(&self, job: MiningJob) -> Result<(u32, MiningJob), StratumError> {
+    async fn on_job_received(
+        &self,
+        job: Arc<MiningJob>,
+    ) -> Result<(u32, Arc<MiningJob>), StratumError> {
         log::info!(target: "stratum", "Received job: {job:?}");
         tokio::time::sleep(Duration::from_millis(1000)).await;
         Ok((0, job))
@@ -363,6 +1682,7 @@ impl Miner for TestMiner {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stratum::target::Target;
     use serde_json::json;
 
     fn create_valid_job_params() -> Vec<Value> {
@@ -402,6 +1722,19 @@ mod tests {
         assert!(manager.handle_job_notification(&invalid).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_raw_params_kept() {
+        let manager = JobManager::new(TestMiner);
+        let mut params = create_valid_job_params();
+        params.push(json!({"algo": "sha256d"}));
+        manager.handle_job_notification(&params).await.unwrap();
+
+        let job = manager.get_current_job().await.unwrap().unwrap();
+        assert_eq!(*job.raw_params, params);
+        assert_eq!(job.raw_param(9), Some(&json!({"algo": "sha256d"})));
+        assert_eq!(job.raw_param(10), None);
+    }
+
     #[tokio::test]
     async fn test_difficulty_handling() {
         let manager = JobManager::new(TestMiner);
@@ -439,10 +1772,38 @@ mod tests {
         let target = manager.get_target().await.unwrap();
         assert_eq!(target.difficulty, 2.0);
 
-        // Target should be half of max target
-        let _max_target = [0u8; 32];
-        let actual_target = hex::decode(target.target).unwrap();
-        assert_eq!(actual_target[2], 0x7f); // 0xff / 2
+        // Target should be half of the difficulty 1 target
+        assert_eq!(target.to_target(), Some(Target::from_difficulty(2.0)));
+        let actual_target = target.bytes().unwrap();
+        assert_eq!(actual_target[4..7], [0x7f, 0xff, 0x80]);
+        assert_eq!(target.nbits(), Some(0x1c7fff80));
+    }
+
+    #[tokio::test]
+    async fn test_difficulty_changed_event() {
+        let events = events::channel();
+        let mut rx = events.subscribe();
+        let manager = JobManager::with_events(TestMiner, events);
+
+        for difficulty in [1.0, 1.0, 4.0] {
+            manager
+                .handle_difficulty_notification(&[json!(difficulty)])
+                .await
+                .unwrap();
+        }
+
+        // Repeating the current difficulty isn't a change
+        let mut changes = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let StratumEvent::DifficultyChanged {
+                difficulty,
+                previous,
+            } = event
+            {
+                changes.push((difficulty, previous));
+            }
+        }
+        assert_eq!(changes, [(1.0, None), (4.0, Some(1.0))]);
     }
 
     #[tokio::test]
@@ -459,6 +1820,7 @@ mod tests {
             extranonce2: "00000000".to_string(),
             ntime: "60509af9".to_string(),
             nonce: "00000000".to_string(),
+            version_bits: None,
         };
         assert!(manager.validate_share(&share).await.unwrap());
 
@@ -477,6 +1839,940 @@ mod tests {
         assert!(manager.validate_share(&invalid).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_share_above_target() {
+        let manager = JobManager::new(TestMiner);
+        manager
+            .set_extranonce(Extranonce {
+                extranonce1: "08000002".into(),
+                extranonce2_size: 4,
+            })
+            .await;
+        let mut params = create_valid_job_params();
+        params[4] = json!(["ab".repeat(32)]);
+        manager.handle_job_notification(&params).await.unwrap();
+        manager
+            .handle_difficulty_notification(&[json!(65535.0)])
+            .await
+            .unwrap();
+
+        let share = Share {
+            job_id: "job123".to_string(),
+            extranonce2: "00000000".to_string(),
+            ntime: "60509af9".to_string(),
+            nonce: "00000000".to_string(),
+            version_bits: None,
+        };
+        assert!(!manager.validate_share(&share).await.unwrap());
+
+        let rolled = Share {
+            version_bits: Some("zz".to_string()),
+            ..share
+        };
+        assert!(manager.validate_share(&rolled).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_target_override() {
+        let manager = JobManager::new(TestMiner);
+        manager
+            .set_extranonce(Extranonce {
+                extranonce1: "08000002".into(),
+                extranonce2_size: 4,
+            })
+            .await;
+        manager.set_target_override(Some(1e-10));
+        let mut jobs = manager.jobs();
+        let mut params = create_valid_job_params();
+        params[4] = json!(["ab".repeat(32)]);
+        manager.handle_job_notification(&params).await.unwrap();
+        manager
+            .handle_difficulty_notification(&[json!(65535.0)])
+            .await
+            .unwrap();
+
+        // The miner mines at the override, shares are checked at the pool's
+        let mined = jobs.borrow_and_update().clone().unwrap();
+        assert_eq!(mined.target, Some(MiningTarget::from_difficulty(1e-10)));
+        assert_eq!(manager.get_target().await.unwrap().difficulty, 65535.0);
+        let share = Share {
+            job_id: "job123".to_string(),
+            extranonce2: "00000000".to_string(),
+            ntime: "60509af9".to_string(),
+            nonce: "00000000".to_string(),
+            version_bits: None,
+        };
+        assert!(!manager.validate_share(&share).await.unwrap());
+
+        manager.set_target_override(None);
+        manager.maybe_run_job().await.unwrap();
+        let mined = jobs.borrow_and_update().clone().unwrap();
+        assert_eq!(mined.target.as_ref().unwrap().difficulty, 65535.0);
+    }
+
+    #[tokio::test]
+    async fn test_difficulty_floor() {
+        let manager = JobManager::new(TestMiner);
+        manager
+            .set_extranonce(Extranonce {
+                extranonce1: "08000002".into(),
+                extranonce2_size: 4,
+            })
+            .await;
+        manager.set_difficulty_floor(Some(65535.0));
+        let mut params = create_valid_job_params();
+        params[4] = json!(["ab".repeat(32)]);
+        manager.handle_job_notification(&params).await.unwrap();
+        let share = Share {
+            job_id: "job123".to_string(),
+            extranonce2: "00000000".to_string(),
+            ntime: "60509af9".to_string(),
+            nonce: "00000000".to_string(),
+            version_bits: None,
+        };
+
+        // The pool's starting difficulty doesn't lift the floor
+        manager
+            .handle_difficulty_notification(&[json!(1e-10)])
+            .await
+            .unwrap();
+        assert_eq!(manager.difficulty_floor(), Some(65535.0));
+        assert!(!manager.validate_share(&share).await.unwrap());
+
+        // Vardiff adjusting it does
+        manager
+            .handle_difficulty_notification(&[json!(2e-10)])
+            .await
+            .unwrap();
+        assert_eq!(manager.difficulty_floor(), None);
+        assert!(manager.validate_share(&share).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_difficulty_floor_timeout() {
+        let manager = JobManager::new(TestMiner);
+        manager.set_difficulty_floor(Some(65535.0));
+        let expires_at = || {
+            manager
+                .difficulty_floor
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|floor| floor.expires_at)
+        };
+        assert!(expires_at().is_none());
+
+        // The timeout starts with the pool's first difficulty
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        let deadline = expires_at().unwrap();
+        assert!(deadline > Instant::now() + DIFFICULTY_FLOOR_TIMEOUT / 2);
+
+        // The same difficulty again, as pools without vardiff send, doesn't
+        // lift it until the timeout passed
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        assert_eq!(expires_at(), Some(deadline));
+        assert_eq!(manager.difficulty_floor(), Some(65535.0));
+
+        manager
+            .difficulty_floor
+            .lock()
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .expires_at = Some(Instant::now());
+        assert_eq!(manager.difficulty_floor(), None);
+
+        // A floor set after the pool's difficulty times out from then on
+        manager.set_difficulty_floor(Some(65535.0));
+        assert!(expires_at().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_difficulty_multiplier() {
+        let manager = JobManager::new(TestMiner);
+        manager.set_difficulty_multiplier(65536.0);
+        manager
+            .handle_difficulty_notification(&[json!(65536.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        // Reported in the pool's units, targeting Bitcoin difficulty 1
+        let target = manager.get_target().await.unwrap();
+        assert_eq!(target.difficulty, 65536.0);
+        assert_eq!(target.to_target(), Some(Target::from_difficulty(1.0)));
+
+        manager.set_difficulty_floor(Some(131072.0));
+        let floor = manager.difficulty_floor.lock().unwrap().clone().unwrap();
+        assert_eq!(floor.target.to_target(), Some(Target::from_difficulty(2.0)));
+    }
+
+    #[tokio::test]
+    async fn test_check_consistency() {
+        let manager = JobManager::new(TestMiner);
+        let mut events = manager.events.subscribe();
+        assert!(manager.check_consistency().await.unwrap().is_empty());
+
+        manager
+            .handle_difficulty_notification(&[json!(8.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+        assert!(manager.check_consistency().await.unwrap().is_empty());
+
+        // A stale target stored behind the pool's back, and a job handed to
+        // the miner without one
+        *manager.enqueued_difficulty.lock().await = Some(MiningTarget::from_difficulty(2.0));
+        let mut job = (*manager.get_current_job().await.unwrap().unwrap()).clone();
+        job.target = None;
+        manager.jobs.send_replace(Some(Arc::new(job)));
+
+        let found = manager.check_consistency().await.unwrap();
+        assert_eq!(
+            found,
+            vec![
+                Inconsistency::TargetDrift {
+                    stored: Some(2.0),
+                    received: 8.0,
+                },
+                Inconsistency::JobTarget {
+                    job_id: "job123".into(),
+                    difficulty: None,
+                    expected: 8.0,
+                },
+            ]
+        );
+        assert_eq!(manager.get_target().await.unwrap().difficulty, 8.0);
+        let dispatched = manager.jobs().borrow().clone().unwrap();
+        assert_eq!(dispatched.target.as_ref().unwrap().difficulty, 8.0);
+        assert!(manager.check_consistency().await.unwrap().is_empty());
+
+        let reported = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| matches!(event, StratumEvent::TargetInconsistency { .. }))
+            .count();
+        assert_eq!(reported, 2);
+    }
+
+    #[tokio::test]
+    async fn test_superseded_for() {
+        let manager = JobManager::new(TestMiner);
+        let job = |job_id: &str, clean_jobs: bool| {
+            let mut params = create_valid_job_params();
+            params[0] = json!(job_id);
+            params[8] = json!(clean_jobs);
+            params
+        };
+        manager
+            .handle_job_notification(&job("job1", false))
+            .await
+            .unwrap();
+        let first = manager.get_current_job().await.unwrap().unwrap();
+        manager
+            .handle_job_notification(&job("job2", false))
+            .await
+            .unwrap();
+        assert_eq!(manager.superseded_for(&first).await, None);
+
+        manager
+            .handle_job_notification(&job("job3", true))
+            .await
+            .unwrap();
+        let second = manager
+            .find_job(&JobKey {
+                job_id: "job2".into(),
+                ..JobKey::of(&first)
+            })
+            .await
+            .unwrap();
+        let current = manager.get_current_job().await.unwrap().unwrap();
+        assert!(manager.superseded_for(&first).await.unwrap() < Duration::from_secs(1));
+        assert!(manager.superseded_for(&second).await.is_some());
+        assert_eq!(manager.superseded_for(&current).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_reused_job_id() {
+        let manager = JobManager::new(TestMiner);
+
+        let old = create_valid_job_params();
+        manager.handle_job_notification(&old).await.unwrap();
+
+        // The pool reuses the job id after a new block
+        let mut new = old.clone();
+        new[1] = json!("00000000000000000000000000000000000000000000000000000000cafebabe");
+        new[7] = json!("60509b10");
+        manager.handle_job_notification(&new).await.unwrap();
+
+        // A delayed share for the old job resolves to the old instance
+        let delayed = Share {
+            job_id: "job123".to_string(),
+            extranonce2: "00000000".to_string(),
+            ntime: "60509af9".to_string(),
+            nonce: "00000000".to_string(),
+            version_bits: None,
+        };
+        let job = manager.job_for_share(&delayed).await.unwrap();
+        assert_eq!(job.prev_hash, old[1]);
+        assert!(manager.validate_share(&delayed).await.unwrap());
+
+        let key = JobKey {
+            job_id: "job123".to_string(),
+            prev_hash: new[1].as_str().unwrap().to_string(),
+            ntime: "60509b10".to_string(),
+        };
+        assert_eq!(manager.find_job(&key).await.unwrap().ntime, "60509b10");
+    }
+
+    #[tokio::test]
+    async fn test_rolled_ntime_share() {
+        let manager = JobManager::new(TestMiner);
+        let params = create_valid_job_params();
+        manager.handle_job_notification(&params).await.unwrap();
+
+        // The miner rolled ntime by a minute, the share still has its job
+        let rolled = Share {
+            job_id: "job123".to_string(),
+            extranonce2: "00000000".to_string(),
+            ntime: "60509b35".to_string(),
+            nonce: "00000000".to_string(),
+            version_bits: None,
+        };
+        let job = manager.job_for_share(&rolled).await.unwrap();
+        assert_eq!(job.ntime, "60509af9");
+        assert!(manager.validate_share(&rolled).await.unwrap());
+
+        // Before the job's ntime, or too far past it, the share is invalid
+        for ntime in ["60509af8", "6050b71a"] {
+            let invalid = Share {
+                ntime: ntime.to_string(),
+                ..rolled.clone()
+            };
+            assert!(manager.job_for_share(&invalid).await.is_some());
+            assert!(manager.validate_share(&invalid).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_block_event() {
+        let events = events::channel();
+        let mut rx = events.subscribe();
+        let manager = JobManager::with_events(TestMiner, events);
+
+        let params = create_valid_job_params();
+        manager.handle_job_notification(&params).await.unwrap();
+        // Same prev_hash, no new block
+        manager.handle_job_notification(&params).await.unwrap();
+        for _ in 0..2 {
+            assert!(matches!(rx.try_recv(), Ok(StratumEvent::NewJob { .. })));
+        }
+        assert!(rx.try_recv().is_err());
+
+        let mut next_block = params.clone();
+        next_block[1] = json!("00000000000000000000000000000000000000000000000000000000cafebabe");
+        manager.handle_job_notification(&next_block).await.unwrap();
+
+        match rx.try_recv().unwrap() {
+            StratumEvent::NewJob { job } => assert_eq!(job.prev_hash, next_block[1]),
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        match rx.try_recv().unwrap() {
+            StratumEvent::NewBlock {
+                prev_hash,
+                height_hint,
+            } => {
+                assert_eq!(prev_hash, next_block[1]);
+                assert_eq!(height_hint, None);
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+
+        // Silenced job events aren't emitted
+        manager
+            .verbosity()
+            .set(Category::Jobs, log::LevelFilter::Warn);
+        manager.handle_job_notification(&params).await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[derive(Clone)]
+    struct CountingMiner {
+        started: tokio::sync::mpsc::UnboundedSender<String>,
+    }
+
+    #[async_trait]
+    impl Miner for CountingMiner {
+        async fn on_job_received(
+            &self,
+            job: Arc<MiningJob>,
+        ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+            let _ = self.started.send(job.job_id.clone());
+            std::future::pending::<()>().await;
+            Ok((0, job))
+        }
+    }
+
+    /// Streams nonces 0, 1, 2, ... for each job, handing out its sink
+    #[derive(Clone)]
+    struct StreamingMiner {
+        sinks: tokio::sync::mpsc::UnboundedSender<ShareSink>,
+    }
+
+    #[async_trait]
+    impl Miner for StreamingMiner {
+        async fn on_job_received(
+            &self,
+            job: Arc<MiningJob>,
+        ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+            Ok((0, job))
+        }
+
+        async fn mine(&self, _job: Arc<MiningJob>, shares: ShareSink) {
+            let _ = self.sinks.send(shares.clone());
+            for nonce in 0.. {
+                if !shares.submit(nonce) {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    }
+
+    /// Counts the jobs it mined in a `Cell`, so it is `Send` but not `Sync`
+    #[derive(Clone, Default)]
+    struct CellMiner {
+        jobs: std::cell::Cell<u32>,
+    }
+
+    #[async_trait]
+    impl Miner for CellMiner {
+        fn on_job_received<'a, 'async_trait>(
+            &'a self,
+            job: Arc<MiningJob>,
+        ) -> crate::stratum::miner::MinerFuture<
+            'async_trait,
+            Result<(u32, Arc<MiningJob>), StratumError>,
+        >
+        where
+            'a: 'async_trait,
+            Self: 'async_trait,
+        {
+            self.jobs.set(self.jobs.get() + 1);
+            let nonce = self.jobs.get();
+            Box::pin(async move { Ok((nonce, job)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_miner_need_not_be_sync() {
+        let manager = JobManager::new(CellMiner::default());
+        let mut results = manager.result_receiver.lock().await.take().unwrap();
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        let (share, _) = results.recv().await.unwrap().unwrap();
+        assert_eq!(share.nonce, "00000001");
+        assert_eq!(manager.miner_control.device_stats().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_miner() {
+        let (sinks, mut sinks_rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = JobManager::new(StreamingMiner { sinks });
+        let mut results = manager.result_receiver.lock().await.take().unwrap();
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        for expected in 0..3 {
+            let (share, job) = results.recv().await.unwrap().unwrap();
+            assert_eq!(share.nonce, format!("{:08x}", expected));
+            assert_eq!(
+                (share.job_id.as_str(), job.job_id.as_str()),
+                ("job123", "job123")
+            );
+        }
+
+        // A new job cancels the sink of the previous one
+        let first = sinks_rx.recv().await.unwrap();
+        let mut params = create_valid_job_params();
+        params[0] = json!("job124");
+        manager.handle_job_notification(&params).await.unwrap();
+        let second = sinks_rx.recv().await.unwrap();
+        assert!(first.is_cancelled());
+        assert!(!first.submit(99));
+        assert_eq!(second.job().job_id, "job124");
+        assert!(!second.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_added_miner_gets_latest_job() {
+        let (started, mut started_rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = JobManager::new(CountingMiner { started });
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+        assert_eq!(started_rx.recv().await.unwrap(), "job123");
+
+        // Replayed without waiting for the next notify
+        let (added, mut added_rx) = tokio::sync::mpsc::unbounded_channel();
+        manager.add_miner(CountingMiner { started: added });
+        let job_id = tokio::time::timeout(Duration::from_secs(1), added_rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(job_id.as_deref(), Some("job123"));
+
+        let mut params = create_valid_job_params();
+        params[0] = json!("job124");
+        manager.handle_job_notification(&params).await.unwrap();
+        assert_eq!(started_rx.recv().await.unwrap(), "job124");
+        assert_eq!(added_rx.recv().await.unwrap(), "job124");
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume() {
+        let (started, mut started_rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = JobManager::new(CountingMiner { started });
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+
+        manager.pause();
+        assert!(manager.is_paused());
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        // Nothing is dispatched while paused
+        let dispatched = tokio::time::timeout(Duration::from_millis(100), started_rx.recv()).await;
+        assert!(dispatched.is_err());
+
+        // Resuming restarts the miner on the held job
+        manager.resume();
+        assert!(!manager.is_paused());
+        let job_id = tokio::time::timeout(Duration::from_secs(1), started_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job_id, "job123");
+    }
+
+    #[tokio::test]
+    async fn test_resume_while_running() {
+        let (started, mut started_rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = JobManager::new(CountingMiner { started });
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(1), started_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Resuming without a pause leaves the running miner alone
+        manager.resume();
+        manager.resume_for(PauseReason::Throttle);
+        let restarted = tokio::time::timeout(Duration::from_millis(100), started_rx.recv()).await;
+        assert!(restarted.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pause_reasons() {
+        let (started, mut started_rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = JobManager::new(CountingMiner { started });
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+
+        manager.pause();
+        manager.pause_for(PauseReason::Throttle);
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        // Lifting the throttle's pause leaves the manual one in place
+        manager.resume_for(PauseReason::Throttle);
+        assert!(manager.is_paused());
+        assert!(manager.is_paused_for(PauseReason::Manual));
+        assert!(!manager.is_paused_for(PauseReason::Throttle));
+        let dispatched = tokio::time::timeout(Duration::from_millis(100), started_rx.recv()).await;
+        assert!(dispatched.is_err());
+
+        manager.resume();
+        assert!(!manager.is_paused());
+        let job_id = tokio::time::timeout(Duration::from_secs(1), started_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job_id, "job123");
+    }
+
+    #[derive(Clone)]
+    struct DeviceMiner;
+
+    #[async_trait]
+    impl Miner for DeviceMiner {
+        async fn on_job_received(
+            &self,
+            job: Arc<MiningJob>,
+        ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+            Ok((42, job))
+        }
+
+        fn device_id(&self) -> Option<String> {
+            Some("asic-0".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_share_found_event() {
+        let events = events::channel();
+        let mut rx = events.subscribe();
+        let manager = JobManager::with_events(DeviceMiner, events);
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                match rx.recv().await.unwrap() {
+                    event @ StratumEvent::ShareFound { .. } => return event,
+                    _ => continue,
+                }
+            }
+        });
+        match event.await.unwrap() {
+            StratumEvent::ShareFound { device, job, share } => {
+                assert_eq!(device.as_deref(), Some("asic-0"));
+                assert_eq!(job.job_id, "job123");
+                assert!(job.target.is_some());
+                assert_eq!(share.nonce, "0000002a");
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_extranonce() {
+        let manager = JobManager::new(TestMiner);
+        let extranonce = Extranonce {
+            extranonce1: "08000002".into(),
+            extranonce2_size: 4,
+        };
+        assert!(manager.set_extranonce(extranonce.clone()).await);
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        // Unchanged extranonce keeps the job
+        assert!(!manager.set_extranonce(extranonce).await);
+        assert!(manager.get_current_job().await.unwrap().is_some());
+
+        let share = Share {
+            job_id: "job123".to_string(),
+            extranonce2: "0000".to_string(),
+            ntime: "60509af9".to_string(),
+            nonce: "00000000".to_string(),
+            version_bits: None,
+        };
+        assert!(manager.validate_share(&share).await.is_err());
+
+        // A new extranonce invalidates jobs built on the old one
+        let changed = Extranonce {
+            extranonce1: "08000003".into(),
+            extranonce2_size: 2,
+        };
+        assert!(manager.set_extranonce(changed.clone()).await);
+        assert!(manager.get_current_job().await.unwrap().is_none());
+        assert!(manager.job_for_share(&share).await.is_none());
+        assert_eq!(manager.extranonce().await, Some(changed));
+    }
+
+    async fn sequential_manager(
+        config: Extranonce2Config,
+    ) -> (JobManager, broadcast::Receiver<StratumEvent>) {
+        let events = events::channel();
+        let receiver = events.subscribe();
+        let manager = JobManager::with_events(TestMiner, events);
+        manager.set_extranonce2_config(config);
+        manager
+            .set_extranonce(Extranonce {
+                extranonce1: "08000002".into(),
+                extranonce2_size: 1,
+            })
+            .await;
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+        (manager, receiver)
+    }
+
+    async fn drain(manager: &JobManager, count: usize) -> Vec<Extranonce2Slot> {
+        let mut slots = Vec::new();
+        for _ in 0..count {
+            slots.push(manager.next_extranonce2().await.unwrap());
+        }
+        slots
+    }
+
+    #[tokio::test]
+    async fn test_extranonce2_roll_ntime() {
+        let (manager, mut events) = sequential_manager(Extranonce2Config {
+            max_ntime_roll: 1,
+            ..Default::default()
+        })
+        .await;
+
+        let slots = drain(&manager, 256).await;
+        assert_eq!(slots[0].extranonce2, "00");
+        assert_eq!(slots[255].extranonce2, "ff");
+        assert!(slots.iter().all(|slot| slot.ntime == "60509af9"));
+        let low = std::iter::from_fn(|| events.try_recv().ok())
+            .find(|event| matches!(event, StratumEvent::Extranonce2Low { .. }));
+        assert!(matches!(
+            low,
+            Some(StratumEvent::Extranonce2Low {
+                remaining: 25,
+                space: 256,
+                ..
+            })
+        ));
+
+        // The space starts over one second later, and shares on it resolve
+        let rolled = manager.next_extranonce2().await.unwrap();
+        assert_eq!(rolled.extranonce2, "00");
+        assert_eq!(rolled.ntime, "60509afa");
+        let share = Share {
+            job_id: rolled.job_id,
+            extranonce2: rolled.extranonce2,
+            ntime: rolled.ntime,
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(manager.job_for_share(&share).await.is_some());
+
+        // Past the roll limit the job is exhausted until a new one arrives
+        drain(&manager, 255).await;
+        assert!(manager.next_extranonce2().await.is_err());
+        let mut params = create_valid_job_params();
+        params[0] = json!("job124");
+        manager.handle_job_notification(&params).await.unwrap();
+        assert_eq!(manager.next_extranonce2().await.unwrap().extranonce2, "00");
+    }
+
+    #[tokio::test]
+    async fn test_extranonce2_wrap_skips_shares() {
+        let (manager, _events) = sequential_manager(Extranonce2Config {
+            on_exhaustion: Extranonce2Exhaustion::Wrap,
+            ..Default::default()
+        })
+        .await;
+
+        drain(&manager, 256).await;
+        let share = Share {
+            job_id: "job123".into(),
+            extranonce2: "01".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        manager.validate_share(&share).await.unwrap();
+
+        let wrapped: Vec<_> = drain(&manager, 2)
+            .await
+            .into_iter()
+            .map(|slot| (slot.extranonce2, slot.ntime))
+            .collect();
+        assert_eq!(
+            wrapped,
+            [
+                ("00".to_string(), "60509af9".to_string()),
+                ("02".to_string(), "60509af9".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extranonce2_request_job() {
+        let (manager, _events) = sequential_manager(Extranonce2Config {
+            on_exhaustion: Extranonce2Exhaustion::RequestJob,
+            ..Default::default()
+        })
+        .await;
+
+        drain(&manager, 256).await;
+        assert!(matches!(
+            manager.allocate_extranonce2().await.unwrap(),
+            Allocation::Exhausted { newly: true, .. }
+        ));
+        assert!(matches!(
+            manager.allocate_extranonce2().await.unwrap(),
+            Allocation::Exhausted { newly: false, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_allocates_extranonce2() {
+        let (sinks, mut sinks_rx) = tokio::sync::mpsc::unbounded_channel();
+        let manager = JobManager::new(StreamingMiner {
+            sinks: sinks.clone(),
+        });
+        manager.add_miner(StreamingMiner { sinks });
+        let mut results = manager.result_receiver.lock().await.take().unwrap();
+        manager
+            .set_extranonce(Extranonce {
+                extranonce1: "08000002".into(),
+                extranonce2_size: 1,
+            })
+            .await;
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        // Each miner searches its own extranonce2, and its shares carry it
+        let mut slots = vec![
+            sinks_rx.recv().await.unwrap().slot().extranonce2.clone(),
+            sinks_rx.recv().await.unwrap().slot().extranonce2.clone(),
+        ];
+        slots.sort();
+        assert_eq!(slots, ["00", "01"]);
+        let (share, _) = results.recv().await.unwrap().unwrap();
+        assert!(slots.contains(&share.extranonce2));
+        assert_eq!(manager.next_extranonce2().await.unwrap().extranonce2, "02");
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_exhausted_extranonce2() {
+        let (manager, _events) = sequential_manager(Extranonce2Config {
+            on_exhaustion: Extranonce2Exhaustion::RequestJob,
+            ..Default::default()
+        })
+        .await;
+        drain(&manager, 256).await;
+
+        // No extranonce2 is left to mine the job with, so a new job is wanted
+        manager
+            .handle_difficulty_notification(&[json!(1.0)])
+            .await
+            .unwrap();
+        assert!(manager.jobs().borrow().is_some());
+        assert!(manager.take_job_wanted());
+        assert!(!manager.take_job_wanted());
+        assert!(manager
+            .worker
+            .lock()
+            .unwrap()
+            .miner_task
+            .lock()
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_extranonce_change_drops_in_flight_result() {
+        let manager = JobManager::new(TestMiner);
+        let mut results = manager.result_receiver.lock().await.take().unwrap();
+        manager
+            .set_extranonce(Extranonce {
+                extranonce1: "08000002".into(),
+                extranonce2_size: 4,
+            })
+            .await;
+        manager
+            .handle_difficulty_notification(&[json!(2.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+
+        // The miner is still working on the job when the extranonce changes
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        manager
+            .set_extranonce(Extranonce {
+                extranonce1: "08000003".into(),
+                extranonce2_size: 4,
+            })
+            .await;
+        let result = tokio::time::timeout(Duration::from_millis(1500), results.recv()).await;
+        assert!(result.is_err(), "Result of a dropped job was delivered");
+    }
+
+    #[tokio::test]
+    async fn test_new_generation_drops_in_flight_result() {
+        let manager = JobManager::new(TestMiner);
+        let mut results = manager.result_receiver.lock().await.take().unwrap();
+        manager
+            .handle_difficulty_notification(&[json!(2.0)])
+            .await
+            .unwrap();
+        manager
+            .handle_job_notification(&create_valid_job_params())
+            .await
+            .unwrap();
+        let job = manager.get_current_job().await.unwrap().unwrap();
+        assert_eq!(job.generation, manager.generation());
+
+        // The session is replaced while the miner is still working on the job
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.new_generation(), job.generation + 1);
+        let result = tokio::time::timeout(Duration::from_millis(1500), results.recv()).await;
+        assert!(
+            result.is_err(),
+            "Result of a previous session was delivered"
+        );
+        assert_eq!(manager.stale_results_dropped(), 1);
+    }
+
     #[tokio::test]
     async fn test_generate_extranonce2() {
         let size = 4;
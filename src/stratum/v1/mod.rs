@@ -1,19 +1,104 @@
-mod connection;
+pub mod builder;
+pub mod connection;
+mod dedup;
+pub mod extensions;
+pub mod failover;
 pub mod jobs;
-mod protocol;
+pub mod protocol;
+pub mod quirks;
+pub mod redirect;
+mod reorder;
+pub mod resubmit;
+pub mod schema;
+#[cfg(feature = "tower")]
+pub mod service;
+pub mod socks;
+mod standby;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
+use crate::stratum::accounting::{ShareLedger, ShareReport};
+#[cfg(feature = "address")]
+use crate::stratum::address::{ChainParams, UsernameValidator, ValidationMode};
+#[cfg(feature = "audit")]
+use crate::stratum::audit::{AuditLog, AuditRecord};
+#[cfg(feature = "capture")]
+use crate::stratum::capture::{Capture, CaptureRecorder};
+use crate::stratum::contention::{Contention, LockSite};
+use crate::stratum::error::ErrorContext;
+use crate::stratum::events::{self, DisconnectReason, StratumEvent};
+#[cfg(feature = "export")]
+use crate::stratum::export::{CsvExportConfig, CsvExporter, StatsRow};
+use crate::stratum::hashrate::{self, Hashrate};
+use crate::stratum::health::{Health, HealthCheck, HealthThresholds};
 use crate::stratum::miner::Miner;
+use crate::stratum::password::PoolPassword;
+#[cfg(feature = "profiling")]
+use crate::stratum::profiling::{Scope, Timer};
+#[cfg(feature = "schedule")]
+use crate::stratum::schedule::{MiningSchedule, SCHEDULE_CHECK_INTERVAL};
+use crate::stratum::stats::{
+    self as stats, EarningsEstimate, LatencySla, RewardFeed, SessionSnapshot, SessionStats,
+    ShareCounts, ShutdownReport,
+};
+use crate::stratum::target::Target;
+use crate::stratum::throttle::{ThrottleAction, ThrottlePolicy};
+use crate::stratum::verbosity::{log_at, Category, Verbosity};
+#[cfg(feature = "watchdog")]
+use crate::stratum::watchdog::{KeepaliveConfig, WatchdogConfig};
 use crate::stratum::{error::StratumError, types::*, StratumClient};
 use async_trait::async_trait;
-use connection::StratumConnection;
-use jobs::JobManager;
+use builder::StratumClientBuilder;
+use connection::{
+    ConnectionConfig, ConnectionStats, LifetimeStats, ReconnectPolicy, StratumConnection,
+};
+use dedup::SubmittedShares;
+use extensions::Extensions;
+use failover::PoolEndpoint;
+use jobs::{
+    Allocation, Extranonce, Extranonce2Slot, JobKey, JobManager, LateShare, PauseReason,
+    SubmitWindow,
+};
+use log::Level;
+use protocol::{JsonRpcResponse, Method};
 use protocol::{
-    CLIENT_VERSION, MINING_AUTHORIZE, MINING_NOTIFY, MINING_SET_DIFFICULTY, MINING_SUBMIT,
-    MINING_SUBSCRIBE,
+    CLIENT_RECONNECT, CLIENT_VERSION, DEFAULT_AUTH_TIMEOUT, MINING_AUTHORIZE, MINING_CONFIGURE,
+    MINING_EXTRANONCE_SUBSCRIBE, MINING_NOTIFY, MINING_SET_EXTRANONCE, MINING_SET_VERSION_MASK,
+    MINING_SUBMIT, MINING_SUBSCRIBE, MINING_SUGGEST_DIFFICULTY, MINING_SUGGEST_TARGET,
+    VERSION_ROLLING,
 };
+use quirks::PoolQuirks;
+use redirect::{ReconnectRequest, RedirectPolicy};
+use resubmit::SubmitRetryPolicy;
 use serde_json::{json, Value};
+use standby::StandbyLink;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Mutex, MutexGuard};
+use tokio::task::JoinHandle;
+
+/// Queue of the task writing the audit log, and the task
+#[cfg(feature = "audit")]
+type AuditWriter = (
+    tokio::sync::mpsc::UnboundedSender<AuditRecord>,
+    JoinHandle<()>,
+);
+
+/// Authorization state of the client's worker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthState {
+    Unauthorized,
+    /// `mining.authorize` was sent and the response hasn't arrived yet
+    Pending,
+    Authorized,
+    Rejected,
+}
 
 /// A Stratum V1 protocol client implementation
 ///
@@ -25,71 +110,269 @@ use tokio::sync::Mutex;
 /// - Share submission
 #[derive(Clone)]
 pub struct StratumV1Client {
+    /// Pool address as `host:port`, used to label per-pool statistics
+    pool: Arc<std::sync::Mutex<String>>,
     connection: Arc<Mutex<StratumConnection>>,
     job_manager: JobManager,
     server_info: Arc<Mutex<Option<ServerInfo>>>,
+    stats: Arc<Mutex<SessionStats>>,
+    events: broadcast::Sender<StratumEvent>,
+    verbosity: Arc<Verbosity>,
+    /// Lock wait counters, shared with the connection and job manager
+    contention: Arc<Contention>,
+    stats_ticker: Arc<Mutex<Option<JoinHandle<()>>>>,
+    dispatcher: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Whether the dispatcher restores the session when the connection ends
+    auto_reconnect: Arc<AtomicBool>,
+    reconnector: Arc<Mutex<Option<JoinHandle<()>>>>,
+    auto_submit: Arc<Mutex<Option<AutoSubmit>>>,
+    /// Last error hit in the background, see [`last_error`](Self::last_error)
+    last_error: Arc<std::sync::Mutex<Option<StratumError>>>,
+    #[cfg(feature = "export")]
+    csv_export: Arc<Mutex<Option<JoinHandle<()>>>>,
+    #[cfg(feature = "watchdog")]
+    watchdog: Arc<Mutex<Option<JoinHandle<()>>>>,
+    #[cfg(feature = "watchdog")]
+    keepalive: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Whether the keepalive was started by [`pause`](Self::pause), to be
+    /// stopped again on [`resume`](Self::resume)
+    #[cfg(feature = "watchdog")]
+    pause_keepalive: Arc<AtomicBool>,
+    consistency_check: Arc<Mutex<Option<JoinHandle<()>>>>,
+    #[cfg(feature = "schedule")]
+    scheduler: Arc<Mutex<Option<JoinHandle<()>>>>,
+    throttle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    subscription: Arc<Mutex<Option<SubscribeResponse>>>,
+    standby: Arc<Mutex<Option<StandbyLink>>>,
+    suggested_difficulty: Arc<Mutex<Option<f64>>>,
+    /// Recently submitted shares, to drop duplicates
+    submitted: Arc<std::sync::Mutex<SubmittedShares>>,
+    /// Whether the suggested difficulty filters shares until the pool's
+    /// arrives, see [`set_smooth_difficulty_ramp`](Self::set_smooth_difficulty_ramp)
+    smooth_difficulty_ramp: Arc<AtomicBool>,
+    latency_sla: Arc<Mutex<Option<LatencySla>>>,
+    latency_sla_violated: Arc<AtomicBool>,
+    notify_debounce: Arc<Mutex<Option<Duration>>>,
+    dry_run: Arc<AtomicBool>,
+    auth_state: Arc<Mutex<AuthState>>,
+    auth_timeout: Arc<Mutex<Duration>>,
+    health_thresholds: Arc<Mutex<HealthThresholds>>,
+    quirks: Arc<Mutex<PoolQuirks>>,
+    /// Whether a `Disconnected` event is due when the connection ends
+    connected: Arc<AtomicBool>,
+    /// When the current connection was made
+    connected_at: Arc<std::sync::Mutex<Instant>>,
+    /// See [`set_warm_up`](Self::set_warm_up)
+    warm_up: Arc<std::sync::Mutex<Option<Duration>>>,
+    idle_probe: Arc<Mutex<Option<Duration>>>,
+    /// Credentials of the last authorization, to restore the session
+    credentials: Arc<Mutex<Option<(String, String)>>>,
+    /// Further workers sharing the connection, see
+    /// [`authorize_worker`](Self::authorize_worker)
+    workers: Arc<Mutex<Vec<(String, String)>>>,
+    ledger: Arc<Mutex<ShareLedger>>,
+    version_rolling: Arc<Mutex<Option<VersionRolling>>>,
+    #[cfg(feature = "audit")]
+    audit: Arc<std::sync::Mutex<Option<AuditWriter>>>,
+    /// Sent with `mining.subscribe`
+    user_agent: Arc<std::sync::Mutex<String>>,
+    reconnect_policy: Arc<std::sync::Mutex<ReconnectPolicy>>,
+    redirect_policy: Arc<std::sync::Mutex<RedirectPolicy>>,
+    submit_retry_policy: Arc<std::sync::Mutex<SubmitRetryPolicy>>,
+    extensions: Arc<std::sync::Mutex<Extensions>>,
+}
+
+/// Task submitting the miner's results, with the channel it reads so results
+/// it didn't get to can be counted when it stops
+struct AutoSubmit {
+    task: JoinHandle<()>,
+    results: Arc<Mutex<tokio::sync::mpsc::UnboundedReceiver<jobs::MinerResult>>>,
+    /// Asks the task to stop once the share it is submitting is answered
+    stop: watch::Sender<bool>,
 }
 
 impl StratumV1Client {
     /// Creates a new Stratum V1 client and connects to the specified mining pool
+    ///
+    /// A `stratum+ssl://` host connects over TLS, see
+    /// [`StratumConnection::with_config`].
     pub async fn new<M: Miner>(host: String, port: u16, miner: M) -> Result<Self, StratumError> {
+        Self::with_connection_config(host, port, ConnectionConfig::default(), miner).await
+    }
+
+    /// Creates a new client connecting with custom connection options, e.g. TLS
+    pub async fn with_connection_config<M: Miner>(
+        host: String,
+        port: u16,
+        config: ConnectionConfig,
+        miner: M,
+    ) -> Result<Self, StratumError> {
+        let events = events::channel();
+        let mut connection = StratumConnection::with_config(host, port, config).await?;
+        let pool = format!("{}:{}", connection.host(), connection.port());
+        let _ = events.send(StratumEvent::Connected {
+            addr: pool.clone(),
+            tls: connection.is_tls(),
+        });
+        let job_manager = JobManager::with_events(miner, events.clone());
+        connection.set_contention(job_manager.contention.clone());
         Ok(Self {
-            connection: Arc::new(Mutex::new(StratumConnection::new(host, port).await?)),
-            job_manager: JobManager::new(miner),
+            pool: Arc::new(std::sync::Mutex::new(pool)),
+            connection: Arc::new(Mutex::new(connection)),
+            verbosity: job_manager.verbosity.clone(),
+            contention: job_manager.contention.clone(),
+            job_manager,
             server_info: Arc::new(Mutex::new(None)),
+            stats: Arc::new(Mutex::new(SessionStats::new())),
+            events,
+            stats_ticker: Arc::new(Mutex::new(None)),
+            dispatcher: Arc::new(Mutex::new(None)),
+            auto_reconnect: Arc::new(AtomicBool::new(false)),
+            reconnector: Arc::new(Mutex::new(None)),
+            auto_submit: Arc::new(Mutex::new(None)),
+            last_error: Arc::new(std::sync::Mutex::new(None)),
+            #[cfg(feature = "export")]
+            csv_export: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "watchdog")]
+            watchdog: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "watchdog")]
+            keepalive: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "watchdog")]
+            pause_keepalive: Arc::new(AtomicBool::new(false)),
+            consistency_check: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "schedule")]
+            scheduler: Arc::new(Mutex::new(None)),
+            throttle: Arc::new(Mutex::new(None)),
+            subscription: Arc::new(Mutex::new(None)),
+            standby: Arc::new(Mutex::new(None)),
+            suggested_difficulty: Arc::new(Mutex::new(None)),
+            submitted: Arc::new(std::sync::Mutex::new(SubmittedShares::default())),
+            smooth_difficulty_ramp: Arc::new(AtomicBool::new(false)),
+            latency_sla: Arc::new(Mutex::new(None)),
+            latency_sla_violated: Arc::new(AtomicBool::new(false)),
+            notify_debounce: Arc::new(Mutex::new(None)),
+            dry_run: Arc::new(AtomicBool::new(false)),
+            auth_state: Arc::new(Mutex::new(AuthState::Unauthorized)),
+            auth_timeout: Arc::new(Mutex::new(Duration::from_secs(DEFAULT_AUTH_TIMEOUT))),
+            health_thresholds: Arc::new(Mutex::new(HealthThresholds::default())),
+            quirks: Arc::new(Mutex::new(PoolQuirks::default())),
+            connected: Arc::new(AtomicBool::new(true)),
+            connected_at: Arc::new(std::sync::Mutex::new(Instant::now())),
+            warm_up: Arc::new(std::sync::Mutex::new(None)),
+            idle_probe: Arc::new(Mutex::new(None)),
+            credentials: Arc::new(Mutex::new(None)),
+            workers: Arc::new(Mutex::new(Vec::new())),
+            ledger: Arc::new(Mutex::new(ShareLedger::new())),
+            version_rolling: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "audit")]
+            audit: Arc::new(std::sync::Mutex::new(None)),
+            user_agent: Arc::new(std::sync::Mutex::new(CLIENT_VERSION.to_string())),
+            reconnect_policy: Arc::new(std::sync::Mutex::new(ReconnectPolicy::default())),
+            redirect_policy: Arc::new(std::sync::Mutex::new(RedirectPolicy::default())),
+            submit_retry_policy: Arc::new(std::sync::Mutex::new(SubmitRetryPolicy::default())),
+            extensions: Arc::new(std::sync::Mutex::new(Extensions::new())),
         })
     }
 
+    /// Start configuring a client, see [`StratumClientBuilder`]
+    pub fn builder() -> StratumClientBuilder {
+        StratumClientBuilder::new()
+    }
+
+    /// Creates a client connected to a local replay of a recorded capture
+    ///
+    /// The pool side of the capture is replayed with its original timing
+    /// divided by `speed`, see [`Capture::replay`]. This reproduces the pool
+    /// behavior from a bug report exactly.
+    #[cfg(feature = "capture")]
+    pub async fn from_capture<M: Miner>(
+        capture: &Capture,
+        speed: f64,
+        miner: M,
+    ) -> Result<Self, StratumError> {
+        let (host, port) = capture.replay(speed).await?;
+        Self::new(host, port, miner).await
+    }
+
+    /// Record the traffic with the pool, or stop recording with `None`
+    #[cfg(feature = "capture")]
+    pub async fn set_recorder(&self, recorder: Option<CaptureRecorder>) {
+        self.lock_connection().await.set_recorder(recorder);
+    }
+
     pub async fn take_result_receiver(
         &self,
-    ) -> Option<tokio::sync::mpsc::UnboundedReceiver<Result<(u32, MiningJob), StratumError>>> {
+    ) -> Option<tokio::sync::mpsc::UnboundedReceiver<jobs::MinerResult>> {
         self.job_manager.result_receiver.lock().await.take()
     }
 
-    /// Convenience method to connect and authenticate with a mining pool in one call
-    pub async fn connect_and_auth<M: Miner>(
-        host: String,
-        port: u16,
-        username: &str,
-        password: &str,
-        miner: M,
-    ) -> Result<Self, StratumError> {
-        let mut client = Self::new(host, port, miner).await?;
+    /// User agent sent when subscribing
+    pub fn user_agent(&self) -> String {
+        self.user_agent.lock().unwrap().clone()
+    }
 
-        // Subscribe first
-        client.subscribe().await?;
+    /// Set the user agent sent with the next subscription
+    pub fn set_user_agent(&self, user_agent: impl Into<String>) {
+        *self.user_agent.lock().unwrap() = user_agent.into();
+    }
 
-        // Then authorize
-        let auth = client.authorize(username, password).await?;
-        if !auth.authorized {
-            return Err(StratumError::AuthenticationFailed(format!(
-                "Pool rejected credentials for user {}",
-                username
-            )));
-        }
+    /// Set how reconnecting after a lost connection is retried
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        *self.reconnect_policy.lock().unwrap() = policy;
+    }
 
-        Ok(client)
+    fn reconnect_policy(&self) -> ReconnectPolicy {
+        self.reconnect_policy.lock().unwrap().clone()
     }
 
-    /// Helper method to generate a unique extranonce2 value
-    pub fn generate_extranonce2(&self, size: usize) -> String {
-        JobManager::generate_extranonce2(size)
+    /// Set which hosts a pool's `client.reconnect` may move the session to
+    pub fn set_redirect_policy(&self, policy: RedirectPolicy) {
+        *self.redirect_policy.lock().unwrap() = policy;
     }
-}
 
-#[async_trait]
-impl StratumClient for StratumV1Client {
-    /// Subscribe to the mining pool
-    ///
-    /// This is typically the first step when connecting to a pool. The pool will respond
-    /// with a subscription ID and extranonce1 value that will be used for mining.
-    async fn subscribe(&mut self) -> Result<SubscribeResponse, StratumError> {
-        let response = self
-            .connection
+    /// Set which `mining.submit` errors resubmit the share
+    pub fn set_submit_retry_policy(&self, policy: SubmitRetryPolicy) {
+        *self.submit_retry_policy.lock().unwrap() = policy;
+    }
+
+    /// Set the pool-specific methods the client handles and sends, see
+    /// [`Extensions`]
+    pub fn set_extensions(&self, extensions: Extensions) {
+        *self.extensions.lock().unwrap() = extensions;
+    }
+
+    /// Send a custom `method` registered with [`Extensions::request`],
+    /// with params built from `args`, and wait for the pool's response
+    pub async fn call_extension(
+        &self,
+        method: &str,
+        args: Value,
+    ) -> Result<JsonRpcResponse, StratumError> {
+        let params = self
+            .extensions
             .lock()
-            .await
-            .send_request(MINING_SUBSCRIBE, vec![json!(CLIENT_VERSION)])
-            .await?;
+            .unwrap()
+            .build_request(method, args)?;
+        let requester = self.lock_connection().await.requester();
+        requester.send_request(method, params).await
+    }
+
+    /// Set the tolerances applied when parsing pool messages, e.g.
+    /// [`PoolQuirks::strict`] to test a pool implementation
+    pub async fn set_quirks(&self, quirks: PoolQuirks) {
+        *self.quirks.lock().await = quirks;
+    }
+
+    /// Parse the result of a `mining.subscribe` request
+    ///
+    /// Some pools omit the extranonce2 size or send it as a string, which is
+    /// accepted as configured by `quirks`. In strict mode any deviation from
+    /// the conventional result is an error.
+    fn parse_subscribe_response(
+        response: JsonRpcResponse,
+        quirks: &PoolQuirks,
+    ) -> Result<SubscribeResponse, StratumError> {
+        schema::validate_response(MINING_SUBSCRIBE, &response)?;
 
         if let Some(error) = response.error {
             return Err(StratumError::SubscriptionFailed(error.to_string()));
@@ -98,6 +381,7 @@ impl StratumClient for StratumV1Client {
         let result = response.result.ok_or_else(|| {
             StratumError::SubscriptionFailed("No result in subscription response".into())
         })?;
+        quirks.mode.check_subscribe(&result)?;
 
         let subscription = result.as_array().ok_or_else(|| {
             StratumError::SubscriptionFailed("Invalid subscription format".into())
@@ -113,236 +397,5048 @@ impl StratumClient for StratumV1Client {
             StratumError::SubscriptionFailed("Invalid subscription details format".into())
         })?;
 
-        if subscription_details.is_empty() {
+        // Some pools send a single pair instead of a list of pairs
+        let pairs: Vec<&Vec<Value>> = if subscription_details.first().is_some_and(Value::is_string)
+        {
+            vec![subscription_details]
+        } else {
+            subscription_details
+                .iter()
+                .map(|detail| {
+                    detail.as_array().ok_or_else(|| {
+                        StratumError::SubscriptionFailed(
+                            "Invalid subscription detail format".into(),
+                        )
+                    })
+                })
+                .collect::<Result<_, _>>()?
+        };
+
+        if pairs.is_empty() {
             return Err(StratumError::SubscriptionFailed(
                 "Empty subscription details".into(),
             ));
         }
 
-        let first_detail = subscription_details[0].as_array().ok_or_else(|| {
-            StratumError::SubscriptionFailed("Invalid subscription detail format".into())
-        })?;
-
-        if first_detail.len() < 2 {
-            return Err(StratumError::SubscriptionFailed(
-                "Invalid subscription detail length".into(),
-            ));
+        let mut subscriptions = HashMap::new();
+        let mut first_id = None;
+        for pair in pairs {
+            let (Some(method), Some(id)) = (pair.first().and_then(Value::as_str), pair.get(1))
+            else {
+                return Err(StratumError::SubscriptionFailed(
+                    "Invalid subscription detail length".into(),
+                ));
+            };
+            let id = match id {
+                Value::String(id) => id.clone(),
+                Value::Number(id) => id.to_string(),
+                _ => {
+                    return Err(StratumError::SubscriptionFailed(
+                        "Invalid subscription ID format".into(),
+                    ))
+                }
+            };
+            first_id.get_or_insert_with(|| id.clone());
+            subscriptions.insert(method.to_string(), id);
         }
 
-        let subscription_id = first_detail[1]
-            .as_str()
-            .ok_or_else(|| {
-                StratumError::SubscriptionFailed("Invalid subscription ID format".into())
-            })?
-            .to_string();
+        // The notify subscription identifies the session, e.g. for resuming it
+        let subscription_id = subscriptions
+            .get(MINING_NOTIFY)
+            .cloned()
+            .or(first_id)
+            .unwrap_or_default();
 
         log::info!("Subscription data: {subscription:?}");
 
         let extranonce1 = subscription[1].as_str().unwrap_or_default().to_string();
 
-        let extranonce2_size = match &subscription[2] {
-            Value::Number(n) => n.as_u64().unwrap_or(0) as usize,
-            Value::Null => 0,
-            _ => subscription[2].as_u64().unwrap_or(0) as usize,
-        };
+        let extranonce2_size = quirks.extranonce2_size(subscription.get(2))?;
 
         Ok(SubscribeResponse {
             subscription_id,
             extranonce1,
             extranonce2_size,
+            subscriptions,
         })
     }
 
-    /// Authorize with the mining pool using worker credentials
+    /// Open a hot-standby connection to the same pool session
     ///
-    /// This should be called after subscribing. The username is typically in the format
-    /// "wallet_address.worker_name" or "username.worker_name" depending on the pool.
-    async fn authorize(
-        &mut self,
+    /// The standby resumes the current subscription (it must be assigned the same
+    /// extranonce1, otherwise shares can't be submitted over it) and authorizes
+    /// with the given credentials. Afterwards, shares whose submission over the
+    /// primary connection takes longer than `latency_threshold` are also sent over
+    /// the standby and the first answer wins.
+    pub async fn enable_standby(
+        &self,
         username: &str,
         password: &str,
-    ) -> Result<AuthResponse, StratumError> {
-        let response = self
-            .connection
-            .lock()
-            .await
-            .send_request(MINING_AUTHORIZE, vec![json!(username), json!(password)])
+        latency_threshold: Duration,
+    ) -> Result<(), StratumError> {
+        let primary = self.subscription.lock().await.clone().ok_or_else(|| {
+            StratumError::SubscriptionFailed(
+                "Subscribe before enabling a standby connection".into(),
+            )
+        })?;
+
+        // Same transport as the primary, e.g. through its proxy or over TLS
+        let (host, port, config) = {
+            let connection = self.lock_connection().await;
+            (
+                connection.host().to_string(),
+                connection.port(),
+                connection.config().clone(),
+            )
+        };
+        let mut standby = StratumConnection::with_config(host, port, config).await?;
+        standby.set_contention(self.contention.clone());
+
+        let response = standby
+            .send_request(
+                MINING_SUBSCRIBE,
+                vec![json!(self.user_agent()), json!(primary.subscription_id)],
+            )
             .await?;
+        let quirks = self.quirks.lock().await.clone();
+        let subscription = Self::parse_subscribe_response(response, &quirks)?;
+        if subscription.extranonce1 != primary.extranonce1 {
+            return Err(StratumError::SubscriptionFailed(format!(
+                "Standby connection was assigned extranonce1 {} instead of {}, pool does not support session resumption",
+                subscription.extranonce1, primary.extranonce1
+            )));
+        }
 
-        log::info!(target: "stratum", "Authorization response: {response:?}");
-        let authorized = response
+        let authorized = standby
+            .send_request(MINING_AUTHORIZE, vec![json!(username), json!(password)])
+            .await?
             .result
-            .unwrap_or(json!(false))
-            .as_bool()
+            .and_then(|r| r.as_bool())
             .unwrap_or(false);
+        if !authorized {
+            return Err(StratumError::AuthenticationFailed(format!(
+                "Pool rejected credentials for user {} on standby connection",
+                username
+            )));
+        }
 
-        Ok(AuthResponse {
-            authorized,
-            message: None,
-        })
+        *self.standby.lock().await = Some(StandbyLink {
+            connection: Arc::new(Mutex::new(standby)),
+            latency_threshold,
+        });
+
+        Ok(())
     }
 
-    /// Submit a solved share to the mining pool
+    /// Close the hot-standby connection, if any
+    pub async fn disable_standby(&self) -> Result<(), StratumError> {
+        if let Some(standby) = self.standby.lock().await.take() {
+            standby.connection.lock().await.close().await?;
+        }
+        Ok(())
+    }
+
+    /// Convenience method to connect and authenticate with a mining pool in one call
     ///
-    /// Returns true if the share was accepted, false if it was rejected.
-    /// The share should be generated based on the current mining job and target difficulty.
-    async fn submit_share(&mut self, share: Share) -> Result<bool, StratumError> {
-        let response = self
-            .connection
-            .lock()
-            .await
-            .send_request(
-                MINING_SUBMIT,
-                vec![
-                    json!(share.job_id),
-                    json!(share.extranonce2),
-                    json!(share.ntime),
-                    json!(share.nonce),
-                ],
-            )
-            .await?;
+    /// With the `address` feature, the username is first checked as a
+    /// Bitcoin address, warning when it isn't one; use
+    /// [`StratumClientBuilder::username_validator`] to refuse it instead or
+    /// check another chain.
+    pub async fn connect_and_auth<M: Miner>(
+        host: String,
+        port: u16,
+        username: &str,
+        password: &str,
+        miner: M,
+    ) -> Result<Self, StratumError> {
+        let builder = Self::builder()
+            .pool(host, port)
+            .credentials(username, password);
+        #[cfg(feature = "address")]
+        let builder = builder.username_validator(UsernameValidator::new(
+            ChainParams::bitcoin(),
+            ValidationMode::Warn,
+        ));
+        builder.miner(miner).build().await
+    }
 
-        Ok(response
-            .result
-            .unwrap_or(json!(false))
-            .as_bool()
-            .unwrap_or(false))
+    /// Helper method to generate a unique extranonce2 value
+    pub fn generate_extranonce2(&self, size: usize) -> String {
+        JobManager::generate_extranonce2(size)
     }
 
-    /// Get the current mining job if one is available
+    /// Next sequential extranonce2 for the current job, see
+    /// [`JobManager::next_extranonce2`]
     ///
-    /// New jobs are received through notifications, so this returns None if no job
-    /// has been received yet. Use handle_notifications() to process new jobs.
-    async fn get_current_job(&mut self) -> Result<Option<MiningJob>, StratumError> {
-        self.job_manager.get_current_job().await
+    /// When the job's space runs out for good, the pool is asked for a new
+    /// job by suggesting the current difficulty again, which makes most
+    /// pools send one.
+    pub async fn next_extranonce2(&self) -> Result<Extranonce2Slot, StratumError> {
+        let allocation = self.job_manager.allocate_extranonce2().await?;
+        if let Allocation::Exhausted { newly: true, .. } = allocation {
+            self.request_job().await;
+        }
+        allocation.into_slot()
     }
 
-    /// Process any pending notifications from the mining pool
-    ///
-    /// This should be called regularly to receive new jobs and difficulty updates.
-    /// It processes one notification at a time, so call it in a loop during mining.
-    async fn handle_notifications(&mut self) -> Result<(), StratumError> {
-        let notification = self.connection.lock().await.read_notification().await?;
-        log::info!(target: "stratum", "Received raw notification: {notification:?}");
-        if let Some(method) = notification.get("method").and_then(Value::as_str) {
-            match method {
-                MINING_NOTIFY => {
-                    if let Some(params) = notification.get("params").and_then(Value::as_array) {
-                        self.job_manager.handle_job_notification(params).await?;
-                    }
-                }
-                MINING_SET_DIFFICULTY => {
-                    if let Some(params) = notification.get("params").and_then(Value::as_array) {
-                        self.job_manager
-                            .handle_difficulty_notification(params)
-                            .await?;
-                    }
-                }
-                _ => {} // Unknown method, ignore
-            }
+    /// Ask the pool for a new job without waiting for it
+    async fn request_job(&self) {
+        let Some(target) = self.job_manager.targets().borrow().clone() else {
+            return;
+        };
+        let requester = self.lock_connection().await.requester();
+        let params = vec![json!(target.difficulty)];
+        let wait = Duration::from_secs(protocol::DEFAULT_TIMEOUT);
+        if let Err(err) = requester
+            .send_request_detached(MINING_SUGGEST_DIFFICULTY, params, wait)
+            .await
+        {
+            log_at!(
+                self.verbosity,
+                Category::Jobs,
+                Level::Debug,
+                "Requesting a new job failed: {err}"
+            );
         }
+    }
 
+    /// Ask the pool to set the session's difficulty to `difficulty`
+    ///
+    /// Miners that know their hashrate can suggest a fitting starting
+    /// difficulty, best right after subscribing, instead of waiting for the
+    /// pool's vardiff to converge. The pool answers with
+    /// `mining.set_difficulty` if it honors the suggestion; many pools don't
+    /// answer the request itself, so this returns once it is sent.
+    pub async fn suggest_difficulty(&self, difficulty: f64) -> Result<(), StratumError> {
+        if !difficulty.is_finite() || difficulty <= 0.0 {
+            return Err(StratumError::Config(format!(
+                "Invalid difficulty {} to suggest",
+                difficulty
+            )));
+        }
+        self.send_suggestion(MINING_SUGGEST_DIFFICULTY, json!(difficulty))
+            .await?;
+        self.set_suggested_difficulty(difficulty).await;
         Ok(())
     }
 
-    /// Get the current mining target
-    async fn get_target(&self) -> Result<MiningTarget, StratumError> {
-        self.job_manager.get_target().await
+    /// Limit how long shares of replaced jobs are submitted, see
+    /// [`JobManager::set_submit_window`]
+    pub fn set_submit_window(&self, window: Option<SubmitWindow>) {
+        self.job_manager.set_submit_window(window);
     }
 
-    /// Get server information
-    async fn get_server_info(&self) -> Result<ServerInfo, StratumError> {
-        self.server_info
+    /// Mine at `difficulty` locally instead of the pool's difficulty, or at
+    /// the pool's again with `None`
+    ///
+    /// Meant for hardware bring-up and testing: at a near-trivial difficulty
+    /// the miner finds results right away, exercising the whole pipeline,
+    /// while only results meeting the pool's target are submitted. See
+    /// [`JobManager::set_target_override`].
+    pub async fn set_target_override(&self, difficulty: Option<f64>) -> Result<(), StratumError> {
+        if let Some(difficulty) = difficulty {
+            if !difficulty.is_finite() || difficulty <= 0.0 {
+                return Err(StratumError::Config(format!(
+                    "Invalid target override difficulty {}",
+                    difficulty
+                )));
+            }
+        }
+        self.job_manager.set_target_override(difficulty);
+        // Hand the current job to the miner again at the new target
+        self.job_manager.maybe_run_job().await
+    }
+
+    /// Ask the pool to set the session's target to `target`, 64 hex digits
+    ///
+    /// The target counterpart of [`suggest_difficulty`](Self::suggest_difficulty),
+    /// for pools supporting `mining.suggest_target`.
+    pub async fn suggest_target(&self, target: &str) -> Result<(), StratumError> {
+        let target = Target::from_hex(target)
+            .ok_or_else(|| StratumError::Config(format!("Invalid target {} to suggest", target)))?;
+        self.send_suggestion(MINING_SUGGEST_TARGET, json!(target.to_hex()))
+            .await?;
+        let difficulty = target.difficulty() * self.job_manager.difficulty_multiplier();
+        self.set_suggested_difficulty(difficulty).await;
+        Ok(())
+    }
+
+    /// Take the pool's difficulties as `multiplier` times the Bitcoin
+    /// difficulty of the same target, as scrypt pools report 65536 times
+    ///
+    /// Targets are computed with the multiplier while difficulties in events,
+    /// statistics and suggestions stay in the pool's units, matching its
+    /// dashboard; hashrate estimates divide it out again. Set it before
+    /// subscribing, see [`JobManager::set_difficulty_multiplier`].
+    pub async fn set_difficulty_multiplier(&self, multiplier: f64) -> Result<(), StratumError> {
+        if !multiplier.is_finite() || multiplier <= 0.0 {
+            return Err(StratumError::Config(format!(
+                "Invalid difficulty multiplier {}",
+                multiplier
+            )));
+        }
+        self.job_manager.set_difficulty_multiplier(multiplier);
+        self.stats
             .lock()
             .await
-            .clone()
-            .ok_or_else(|| StratumError::Protocol("No server info available".into()))
+            .set_difficulty_multiplier(multiplier);
+        Ok(())
     }
 
-    /// Reconnect to the mining server
-    async fn reconnect(&mut self) -> Result<(), StratumError> {
-        self.connection.lock().await.reconnect().await
+    async fn set_suggested_difficulty(&self, difficulty: f64) {
+        *self.suggested_difficulty.lock().await = Some(difficulty);
+        if self.smooth_difficulty_ramp.load(Ordering::Relaxed) {
+            self.job_manager.set_difficulty_floor(Some(difficulty));
+        }
     }
 
-    /// Close the connection
-    async fn close(&mut self) -> Result<(), StratumError> {
-        self.connection.lock().await.close().await
+    /// Hold shares to the suggested difficulty until the pool sets its own
+    ///
+    /// Some pools start every miner at difficulty 1, which fast hardware
+    /// floods with shares before vardiff catches up. With smoothing on, a
+    /// difficulty suggested through [`suggest_difficulty`](Self::suggest_difficulty),
+    /// [`suggest_target`](Self::suggest_target) or the `d=` password option
+    /// also filters shares locally, see [`JobManager::set_difficulty_floor`].
+    pub fn set_smooth_difficulty_ramp(&self, enabled: bool) {
+        self.smooth_difficulty_ramp
+            .store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.job_manager.set_difficulty_floor(None);
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::stratum::v1::jobs::TestMiner;
-    use tokio::io::{AsyncReadExt, AsyncWriteExt};
-    use tokio::net::TcpListener;
+    async fn send_suggestion(&self, method: &str, value: Value) -> Result<(), StratumError> {
+        log_at!(
+            self.verbosity,
+            Category::Difficulty,
+            Level::Info,
+            "Suggesting {} {}",
+            method,
+            value
+        );
+        let requester = self.lock_connection().await.requester();
+        let wait = Duration::from_secs(protocol::DEFAULT_TIMEOUT);
+        let sent = requester
+            .send_request_detached(method, vec![value], wait)
+            .await;
+        self.emit_disconnect(requester.take_disconnect());
+        sent
+    }
 
-    async fn setup_mock_server() -> (TcpListener, String, u16) {
-        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-        let addr = listener.local_addr().unwrap();
-        (listener, addr.ip().to_string(), addr.port())
+    /// Authorize one of several workers sharing the connection
+    ///
+    /// Hosting gateways can run many customers' workers over one connection,
+    /// TLS session included: each sub-account is authorized on its own and
+    /// submits through [`submit_share_as`](Self::submit_share_as). Unlike
+    /// [`authorize`](StratumClient::authorize) this leaves the session's own
+    /// credentials and [`auth_state`](Self::auth_state) alone. Authorized
+    /// workers are authorized again when the session is restored.
+    pub async fn authorize_worker(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<AuthResponse, StratumError> {
+        let response = self.request_authorization(username, password).await?;
+        let mut workers = self.workers.lock().await;
+        workers.retain(|(known, _)| known != username);
+        if response.authorized {
+            workers.push((username.to_string(), password.to_string()));
+        }
+        Ok(response)
     }
 
-    #[tokio::test]
-    async fn test_subscribe() {
-        let (listener, host, port) = setup_mock_server().await;
+    /// Usernames of the workers authorized through
+    /// [`authorize_worker`](Self::authorize_worker)
+    pub async fn workers(&self) -> Vec<String> {
+        self.workers
+            .lock()
+            .await
+            .iter()
+            .map(|(username, _)| username.clone())
+            .collect()
+    }
 
-        tokio::spawn(async move {
-            let (mut socket, _) = listener.accept().await.unwrap();
-            let mut buf = [0; 1024];
-            socket.read(&mut buf).await.unwrap();
+    /// Send `mining.authorize` for `username` and read the pool's verdict
+    async fn request_authorization(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<AuthResponse, StratumError> {
+        let auth_timeout = *self.auth_timeout.lock().await;
+        let requester = self.lock_connection().await.requester();
+        let response = requester
+            .send_request_once(
+                MINING_AUTHORIZE,
+                vec![json!(username), json!(password)],
+                auth_timeout,
+            )
+            .await;
+        self.emit_disconnect(requester.take_disconnect());
 
-            let response = json!({
-                "id": 1,
-                "result": [
-                    [
-                        ["mining.set_difficulty", "1"],
-                        ["mining.notify", "1"]
-                    ],
-                    "extranonce1",
-                    10,
-                ],
-                "error": null
-            });
+        log_at!(
+            self.verbosity,
+            Category::Connection,
+            Level::Info,
+            "Authorization response: {response:?}"
+        );
+        let response = response.and_then(|response| {
+            schema::validate_response(MINING_AUTHORIZE, &response)?;
+            Ok(response)
+        })?;
 
-            socket
-                .write_all(format!("{}\n", response).as_bytes())
-                .await
-                .unwrap();
+        let message = response.error_message();
+        let authorized = response.error.is_none()
+            && response
+                .result
+                .unwrap_or(json!(false))
+                .as_bool()
+                .unwrap_or(false);
+        #[cfg(feature = "audit")]
+        self.audit(AuditRecord::Authorized {
+            pool: self.pool(),
+            username: username.to_string(),
+            authorized,
         });
 
-        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
-        let response = client.subscribe().await.unwrap();
+        if !authorized {
+            log_at!(
+                self.verbosity,
+                Category::Connection,
+                Level::Warn,
+                "Authorization of {username} rejected: {}",
+                message.as_deref().unwrap_or("no reason given")
+            );
+        }
 
-        assert_eq!(response.subscription_id, "1");
-        assert_eq!(response.extranonce1, "extranonce1");
-        assert_eq!(response.extranonce2_size, 10);
+        Ok(AuthResponse {
+            authorized,
+            reject_reason: message.as_deref().and_then(AuthRejectReason::classify),
+            message,
+        })
     }
 
-    #[tokio::test]
-    async fn test_authorize() {
+    /// Submit a share found by `device`, attributing its outcome to that device
+    ///
+    /// For miners feeding results from several devices; per-device counts
+    /// show up in [`SessionSnapshot::devices`]. [`submit_share`](StratumClient::submit_share)
+    /// attributes shares to the miner's [`device_id`](Miner::device_id).
+    ///
+    /// Shares whose header hash is above the target are rejected locally,
+    /// returning `false` without contacting the pool, as are shares of jobs
+    /// from before the last reconnect, see [`JobManager::new_generation`],
+    /// and shares submitted before.
+    pub async fn submit_share_from(
+        &mut self,
+        device: Option<&str>,
+        share: Share,
+    ) -> Result<bool, StratumError> {
+        self.submit(device, None, share).await
+    }
+
+    /// Submit a share on behalf of `worker`, one of the
+    /// [`workers`](Self::workers) sharing the connection
+    ///
+    /// The share's outcome is attributed to the worker as a device, see
+    /// [`submit_share_from`](Self::submit_share_from), and its difficulty
+    /// recorded for the worker in the [`share_ledger`](Self::share_ledger).
+    pub async fn submit_share_as(
+        &mut self,
+        worker: &str,
+        share: Share,
+    ) -> Result<bool, StratumError> {
+        if !self.workers().await.iter().any(|known| known == worker) {
+            return Err(StratumError::Config(format!(
+                "Worker {} is not authorized on this connection",
+                worker
+            )));
+        }
+        self.submit(Some(worker), Some(worker), share).await
+    }
+
+    /// Submit `share` as `worker`, or else the session's username
+    async fn submit(
+        &mut self,
+        device: Option<&str>,
+        worker: Option<&str>,
+        share: Share,
+    ) -> Result<bool, StratumError> {
+        #[cfg(feature = "profiling")]
+        let _profile = Timer::start(Scope::Submit);
+        let job = self.job_manager.job_for_share(&share).await;
+        let job_generation = job.as_ref().map(|job| job.generation);
+        if self.drop_stale_share(device, &share, job_generation) {
+            return Ok(false);
+        }
+        if let Some(job) = &job {
+            if self.drop_late_share(device, &share, job).await {
+                return Ok(false);
+            }
+        }
+        match self.job_manager.validate_share(&share).await {
+            Ok(true) => {}
+            Ok(false) => {
+                // Expected of shares found at an easier target override, or
+                // below the difficulty floor
+                let expected = self.job_manager.target_override().is_some()
+                    || self.job_manager.difficulty_floor().is_some();
+                let level = if expected { Level::Info } else { Level::Warn };
+                log_at!(
+                    self.verbosity,
+                    Category::Shares,
+                    level,
+                    "Share for job {} is above the target, not submitting",
+                    share.job_id
+                );
+                self.emit(StratumEvent::ShareDiscarded {
+                    device: device.map(String::from),
+                    job_id: share.job_id.clone(),
+                    reject_reason: RejectReason::LowDifficulty,
+                });
+                return Ok(false);
+            }
+            Err(e) => {
+                log_at!(
+                    self.verbosity,
+                    Category::Shares,
+                    Level::Warn,
+                    "Share for job {} is invalid, not submitting: {}",
+                    share.job_id,
+                    e
+                );
+                self.emit(StratumEvent::ShareDiscarded {
+                    device: device.map(String::from),
+                    job_id: share.job_id.clone(),
+                    reject_reason: RejectReason::Other,
+                });
+                return Err(e);
+            }
+        }
+        let difficulty = self
+            .job_manager
+            .get_target()
+            .await
+            .ok()
+            .map(|t| t.difficulty);
+        let worker = match worker {
+            Some(worker) => worker.to_string(),
+            None => self
+                .credentials
+                .lock()
+                .await
+                .as_ref()
+                .map(|(username, _)| username.clone())
+                .unwrap_or_default(),
+        };
+        let mut params = vec![
+            json!(worker),
+            json!(share.job_id),
+            json!(share.extranonce2),
+            json!(share.ntime),
+            json!(share.nonce),
+        ];
+        if let Some(version_bits) = &share.version_bits {
+            let bits = u32::from_str_radix(version_bits, 16).map_err(|_| {
+                StratumError::InvalidJob(format!("Invalid version bits {}", version_bits))
+            })?;
+            match *self.version_rolling.lock().await {
+                Some(rolling) if rolling.allows(bits) => params.push(json!(version_bits)),
+                Some(rolling) => {
+                    return Err(StratumError::InvalidJob(format!(
+                        "Version bits {} outside the granted mask {:08x}",
+                        version_bits, rolling.mask
+                    )))
+                }
+                None => {
+                    return Err(StratumError::InvalidJob(
+                        "Version bits given but version rolling wasn't negotiated".into(),
+                    ))
+                }
+            }
+        }
+
+        // Shares are only remembered once the pool answered them, see
+        // `remember_submitted`, so a share that was never judged can be retried
+        if let Some(job) = &job {
+            let submitted = self
+                .submitted
+                .lock()
+                .unwrap()
+                .contains(&JobKey::of(job), &share);
+            if submitted {
+                log_at!(
+                    self.verbosity,
+                    Category::Shares,
+                    Level::Info,
+                    "Share for job {} was submitted before, not submitting",
+                    share.job_id
+                );
+                self.emit(StratumEvent::ShareDiscarded {
+                    device: device.map(String::from),
+                    job_id: share.job_id.clone(),
+                    reject_reason: RejectReason::Duplicate,
+                });
+                return Ok(false);
+            }
+        }
+
+        if self.is_dry_run() {
+            log_at!(
+                self.verbosity,
+                Category::Shares,
+                Level::Info,
+                "Dry run, not sending {} {}",
+                MINING_SUBMIT,
+                Value::Array(params)
+            );
+            return Ok(true);
+        }
+
+        self.probe_if_idle().await?;
+        // Reconnecting after an idle close starts a new session
+        if self.drop_stale_share(device, &share, job_generation) {
+            return Ok(false);
+        }
+        let generation = job_generation.unwrap_or_else(|| self.job_manager.generation());
+
+        // Only hold the connection long enough to get a handle on it, so
+        // concurrent submits and notification handling don't queue up behind
+        // each other's round trips
+        let requester = self.lock_connection().await.requester();
+        let standby = self.standby.lock().await.clone();
+        let retry_policy = self.submit_retry_policy.lock().unwrap().clone();
+        let mut attempts = 0;
+        let mut submitted_at;
+        let response = loop {
+            submitted_at = Instant::now();
+            let response = match &standby {
+                Some(standby) => {
+                    standby::submit_racing(requester.clone(), standby.clone(), params.clone()).await
+                }
+                None => requester.send_request(MINING_SUBMIT, params.clone()).await,
+            };
+            self.emit_disconnect(requester.take_disconnect());
+            attempts += 1;
+            let delay = match &response {
+                Err(err) => retry_policy.delay_after(err, attempts),
+                Ok(_) => None,
+            };
+            let Some(delay) = delay else {
+                break response;
+            };
+            log_at!(
+                self.verbosity,
+                Category::Shares,
+                Level::Info,
+                "Pool asked to resubmit the share for job {}, retrying in {:?}",
+                share.job_id,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+        };
+
+        let latency = submitted_at.elapsed();
+        self.record_submit_latency(latency).await;
+
+        let response = response.and_then(|response| {
+            schema::validate_response(MINING_SUBMIT, &response)?;
+            Ok(response)
+        });
+        let accepted = match response {
+            Ok(response) => {
+                self.remember_submitted(job.as_ref(), &share);
+                response
+                    .result
+                    .unwrap_or(json!(false))
+                    .as_bool()
+                    .unwrap_or(false)
+            }
+            Err(err) => {
+                // Pool error responses arrive as Rpc errors, or Protocol errors
+                // carrying the error as JSON; anything else failed before the
+                // pool answered
+                let quirks = self.quirks.lock().await;
+                let verdict = match err.root() {
+                    StratumError::Rpc { error, message } => {
+                        Some(quirks.rejects.from_rpc(*error, message))
+                    }
+                    StratumError::Protocol(error) => serde_json::from_str(error)
+                        .ok()
+                        .map(|error| quirks.rejects.from_error(&error)),
+                    _ => None,
+                };
+                drop(quirks);
+                let Some(reject_reason) = verdict else {
+                    log_at!(
+                        self.verbosity,
+                        Category::Shares,
+                        Level::Warn,
+                        "Submitting share for job {} failed: {}",
+                        share.job_id,
+                        err
+                    );
+                    self.emit(StratumEvent::ShareSubmitFailed {
+                        device: device.map(String::from),
+                        job_id: share.job_id.clone(),
+                        generation,
+                        error: err.to_string(),
+                    });
+                    self.stats.lock().await.record_submit_failed();
+                    return Err(err);
+                };
+                self.remember_submitted(job.as_ref(), &share);
+                if self.verbosity.enabled(Category::Shares, Level::Info) {
+                    tracing::info!(
+                        target: Category::Shares.target(),
+                        job_id = %share.job_id,
+                        worker,
+                        accepted = false,
+                        reject_reason = ?reject_reason,
+                        error = %err,
+                        "Share rejected"
+                    );
+                }
+                let job_shares = {
+                    let mut stats = self.stats.lock().await;
+                    stats.record_rejected(reject_reason);
+                    if let Some(device) = device {
+                        stats.record_device_share(device, false, difficulty);
+                    }
+                    stats.record_job_share(&share.job_id, Some(reject_reason))
+                };
+                self.emit(StratumEvent::ShareRejected {
+                    device: device.map(String::from),
+                    job_id: share.job_id.clone(),
+                    generation,
+                    reason: Some(err.to_string()),
+                    reject_reason,
+                    job_shares,
+                });
+                return Err(err);
+            }
+        };
+        #[cfg(feature = "audit")]
+        self.audit(AuditRecord::Share {
+            pool: self.pool(),
+            share: share.clone(),
+            accepted,
+        });
+
+        if let (true, Some(difficulty)) = (accepted, difficulty) {
+            self.ledger
+                .lock()
+                .await
+                .record(&self.pool(), &worker, difficulty);
+        }
+
+        if self.verbosity.enabled(Category::Shares, Level::Info) {
+            tracing::info!(
+                target: Category::Shares.target(),
+                job_id = %share.job_id,
+                worker,
+                accepted,
+                difficulty,
+                latency_ms = latency.as_millis() as u64,
+                "Share {}",
+                if accepted { "accepted" } else { "rejected" }
+            );
+        }
+        // Pools answering just `false` give no reason
+        let rejection = (!accepted).then_some(RejectReason::Other);
+        let share_difficulty = match accepted {
+            true => self.job_manager.share_difficulty(&share).await,
+            false => None,
+        };
+        let job_shares = {
+            let mut stats = self.stats.lock().await;
+            match rejection {
+                None => stats.record_accepted(difficulty, latency),
+                Some(reason) => stats.record_rejected(reason),
+            }
+            if let Some(share_difficulty) = share_difficulty {
+                stats.record_share_difficulty(share_difficulty);
+            }
+            if let Some(device) = device {
+                stats.record_device_share(device, accepted, difficulty);
+            }
+            stats.record_job_share(&share.job_id, rejection)
+        };
+        self.emit(match rejection {
+            None => StratumEvent::ShareAccepted {
+                device: device.map(String::from),
+                job_id: share.job_id.clone(),
+                generation,
+                difficulty,
+                latency,
+                job_shares,
+            },
+            Some(reject_reason) => StratumEvent::ShareRejected {
+                device: device.map(String::from),
+                job_id: share.job_id.clone(),
+                generation,
+                reason: None,
+                reject_reason,
+                job_shares,
+            },
+        });
+
+        Ok(accepted)
+    }
+
+    /// Whether `share` is of a job from an earlier session generation, which
+    /// must not be submitted to the current session
+    fn drop_stale_share(
+        &self,
+        device: Option<&str>,
+        share: &Share,
+        job_generation: Option<u64>,
+    ) -> bool {
+        let stale =
+            job_generation.is_some_and(|generation| self.job_manager.drop_if_stale(generation));
+        if stale {
+            log_at!(
+                self.verbosity,
+                Category::Shares,
+                Level::Warn,
+                "Share for job {} is from a previous session generation, not submitting",
+                share.job_id
+            );
+            self.emit(StratumEvent::ShareDiscarded {
+                device: device.map(String::from),
+                job_id: share.job_id.clone(),
+                reject_reason: RejectReason::Stale,
+            });
+        }
+        stale
+    }
+
+    /// Remember a share the pool answered, accepted or not, so submitting it
+    /// again is dropped as a duplicate
+    fn remember_submitted(&self, job: Option<&Arc<MiningJob>>, share: &Share) {
+        if let Some(job) = job {
+            self.submitted
+                .lock()
+                .unwrap()
+                .insert(JobKey::of(job), share);
+        }
+    }
+
+    /// Whether `share` is past the submit window of its job and must not be
+    /// submitted, see [`JobManager::set_submit_window`]
+    async fn drop_late_share(&self, device: Option<&str>, share: &Share, job: &MiningJob) -> bool {
+        let Some(window) = self.job_manager.submit_window() else {
+            return false;
+        };
+        let Some(superseded) = self.job_manager.superseded_for(job).await else {
+            return false;
+        };
+        if superseded <= window.window {
+            return false;
+        }
+        let late = superseded - window.window;
+        match window.on_late {
+            LateShare::Refuse => {
+                log_at!(
+                    self.verbosity,
+                    Category::Shares,
+                    Level::Warn,
+                    "Share for job {} is {:?} past its submit window, not submitting",
+                    share.job_id,
+                    late
+                );
+                self.emit(StratumEvent::ShareDiscarded {
+                    device: device.map(String::from),
+                    job_id: share.job_id.clone(),
+                    reject_reason: RejectReason::Stale,
+                });
+                true
+            }
+            LateShare::Flag => {
+                log_at!(
+                    self.verbosity,
+                    Category::Shares,
+                    Level::Warn,
+                    "Share for job {} is {:?} past its submit window, submitting anyway",
+                    share.job_id,
+                    late
+                );
+                false
+            }
+        }
+    }
+
+    /// The JSON-RPC request path as a tower service, see [`service::RequestService`]
+    #[cfg(feature = "tower")]
+    pub fn request_service(&self) -> service::RequestService {
+        service::RequestService::new(self.connection.clone())
+    }
+
+    /// Accepted share difficulty per pool, worker and day
+    pub async fn share_ledger(&self) -> ShareLedger {
+        self.ledger.lock().await.clone()
+    }
+
+    /// Daily accepted share value, for payout reconciliation
+    pub async fn share_report(&self) -> ShareReport {
+        self.ledger.lock().await.report()
+    }
+
+    /// Negotiate version rolling (ASICBoost) through `mining.configure`
+    ///
+    /// Should be called before subscribing. Requests rolling of the bits in
+    /// `mask`, see [`DEFAULT_VERSION_ROLLING_MASK`](protocol::DEFAULT_VERSION_ROLLING_MASK),
+    /// and passes the grant on to the miner. Returns `None` when the pool
+    /// declines or doesn't support `mining.configure`. The full outcome is
+    /// kept in [`ServerInfo::configure`].
+    pub async fn configure_version_rolling(
+        &self,
+        mask: u32,
+        min_bit_count: u32,
+    ) -> Result<Option<VersionRolling>, StratumError> {
+        let params = vec![
+            json!([VERSION_ROLLING]),
+            json!({
+                "version-rolling.mask": format!("{:08x}", mask),
+                "version-rolling.min-bit-count": min_bit_count,
+            }),
+        ];
+        let response = self
+            .connection
+            .lock()
+            .await
+            .send_request(MINING_CONFIGURE, params)
+            .await?;
+        schema::validate_response(MINING_CONFIGURE, &response)?;
+
+        let granted = match (&response.error, &response.result) {
+            (Some(error), _) if !error.is_null() => {
+                log_at!(
+                    self.verbosity,
+                    Category::Connection,
+                    Level::Info,
+                    "Pool doesn't support {}: {}",
+                    MINING_CONFIGURE,
+                    response.error_message().unwrap_or_default()
+                );
+                None
+            }
+            (_, Some(result)) if result[VERSION_ROLLING] == json!(true) => {
+                let pool_mask = result["version-rolling.mask"]
+                    .as_str()
+                    .and_then(|mask| u32::from_str_radix(mask, 16).ok())
+                    .ok_or_else(|| {
+                        StratumError::Protocol(format!(
+                            "Invalid version-rolling.mask in {} response: {}",
+                            MINING_CONFIGURE, result
+                        ))
+                    })?;
+                Some(VersionRolling {
+                    mask: pool_mask & mask,
+                    min_bit_count: result["version-rolling.min-bit-count"]
+                        .as_u64()
+                        .map_or(min_bit_count, |count| count as u32),
+                })
+            }
+            _ => None,
+        };
+
+        let supported = response.error.as_ref().is_none_or(Value::is_null);
+        let (accepted, ignored) = if granted.is_some() {
+            (vec![VERSION_ROLLING.to_string()], vec![])
+        } else {
+            (vec![], vec![VERSION_ROLLING.to_string()])
+        };
+        self.server_info
+            .lock()
+            .await
+            .get_or_insert_with(ServerInfo::default)
+            .configure = Some(ConfigureResult {
+            supported,
+            accepted,
+            ignored,
+            version_rolling: granted,
+            result: response.result.filter(|_| supported),
+        });
+        self.set_version_rolling(granted).await;
+        Ok(granted)
+    }
+
+    /// Ask the pool to push extranonce changes with `mining.set_extranonce`
+    ///
+    /// Required by NiceHash and some other pools, which otherwise drop the
+    /// connection when they need to move the session to another extranonce.
+    /// Should be called after subscribing. Returns whether the pool accepted;
+    /// pools not knowing the method answer with an error, reported as `false`.
+    pub async fn extranonce_subscribe(&self) -> Result<bool, StratumError> {
+        let response = self
+            .connection
+            .lock()
+            .await
+            .send_request(MINING_EXTRANONCE_SUBSCRIBE, vec![])
+            .await?;
+        schema::validate_response(MINING_EXTRANONCE_SUBSCRIBE, &response)?;
+
+        if let Some(message) = response.error_message() {
+            log_at!(
+                self.verbosity,
+                Category::Connection,
+                Level::Info,
+                "Pool doesn't support {}: {}",
+                MINING_EXTRANONCE_SUBSCRIBE,
+                message
+            );
+            return Ok(false);
+        }
+        Ok(response.result == Some(json!(true)))
+    }
+
+    /// Apply a `mining.set_extranonce` notification
+    async fn handle_set_extranonce(&self, params: &[Value]) -> Result<(), StratumError> {
+        let extranonce1 = params
+            .first()
+            .and_then(Value::as_str)
+            .filter(|extranonce1| hex::decode(extranonce1).is_ok())
+            .ok_or_else(|| {
+                StratumError::Protocol(format!("Invalid {} params", MINING_SET_EXTRANONCE))
+            })?;
+        let quirks = self.quirks.lock().await.clone();
+        let extranonce2_size = quirks.extranonce2_size(params.get(1))?;
+
+        if let Some(subscription) = self.subscription.lock().await.as_mut() {
+            subscription.extranonce1 = extranonce1.to_string();
+            subscription.extranonce2_size = extranonce2_size;
+        }
+        let changed = self
+            .job_manager
+            .set_extranonce(Extranonce {
+                extranonce1: extranonce1.to_string(),
+                extranonce2_size,
+            })
+            .await;
+        if changed {
+            log_at!(
+                self.verbosity,
+                Category::Difficulty,
+                Level::Info,
+                "Pool set extranonce1 {} with extranonce2 size {}",
+                extranonce1,
+                extranonce2_size
+            );
+        }
+        Ok(())
+    }
+
+    /// Version rolling granted by the pool, if any
+    pub async fn version_rolling(&self) -> Option<VersionRolling> {
+        *self.version_rolling.lock().await
+    }
+
+    async fn set_version_rolling(&self, rolling: Option<VersionRolling>) {
+        *self.version_rolling.lock().await = rolling;
+        if let Some(configure) = self
+            .server_info
+            .lock()
+            .await
+            .as_mut()
+            .and_then(|info| info.configure.as_mut())
+        {
+            configure.version_rolling = rolling;
+        }
+        self.job_manager
+            .set_version_mask(rolling.map(|rolling| rolling.mask));
+        self.job_manager
+            .miner_control
+            .set_version_rolling(rolling)
+            .await;
+    }
+
+    /// Estimate earnings from the accepted share rate over the trailing
+    /// `window` and the network difficulty of the current job
+    ///
+    /// Returns `None` until a job was received.
+    pub async fn estimate_earnings(
+        &self,
+        window: Duration,
+        feed: &dyn RewardFeed,
+    ) -> Option<EarningsEstimate> {
+        let job = self.job_manager.get_current_job().await.ok()??;
+        let hashrate = self.stats.lock().await.summary(window).hashrate;
+        stats::estimate_earnings(hashrate, &job.nbits, feed)
+    }
+
+    /// Statistics of the current pool connection
+    pub async fn connection_stats(&self) -> ConnectionStats {
+        self.lock_connection().await.stats().await
+    }
+
+    /// Connection counters summed over every reconnect, see
+    /// [`StratumConnection::lifetime_stats`]
+    pub async fn lifetime_connection_stats(&self) -> LifetimeStats {
+        self.lock_connection().await.lifetime_stats().await
+    }
+
+    /// Estimated hashrate from the shares the pool accepted, over the last
+    /// 1, 5 and 15 minutes
+    ///
+    /// See [`recommend_difficulty`](Self::recommend_difficulty) for a
+    /// difficulty to suggest at this rate.
+    pub async fn hashrate(&self) -> Hashrate {
+        self.stats.lock().await.hashrate()
+    }
+
+    /// Difficulty in the pool's units at which a miner hashing at `hashrate`
+    /// H/s finds a share every `share_interval` on average, to pass to
+    /// [`suggest_difficulty`](Self::suggest_difficulty)
+    ///
+    /// Like [`hashrate::recommend_difficulty`], scaled by the
+    /// [difficulty multiplier](Self::set_difficulty_multiplier).
+    pub fn recommend_difficulty(&self, hashrate: f64, share_interval: Duration) -> Option<f64> {
+        hashrate::recommend_difficulty(hashrate, share_interval)
+            .map(|difficulty| difficulty * self.job_manager.difficulty_multiplier())
+    }
+
+    /// Accepted and rejected shares of job `job_id` by reject reason, if
+    /// shares were submitted for it recently
+    ///
+    /// Session-wide counts are in the [`session_snapshot`](Self::session_snapshot).
+    pub async fn job_shares(&self, job_id: &str) -> Option<ShareCounts> {
+        self.stats.lock().await.job(job_id).copied()
+    }
+
+    /// Get a snapshot of the session statistics and records
+    pub async fn session_snapshot(&self) -> SessionSnapshot {
+        let mut snapshot = self.stats.lock().await.snapshot();
+        snapshot.lock_contention = self.contention.snapshot();
+        snapshot.stale_results_dropped = self.job_manager.stale_results_dropped();
+        snapshot
+    }
+
+    /// Lock the connection, recording the wait
+    ///
+    /// Requests should go through a [`Requester`](connection::Requester)
+    /// rather than hold the connection while waiting for their response.
+    async fn lock_connection(&self) -> MutexGuard<'_, StratumConnection> {
+        self.contention
+            .lock(LockSite::Connection, &self.connection)
+            .await
+    }
+
+    /// Difficulty last suggested to the pool, through
+    /// [`suggest_difficulty`](Self::suggest_difficulty),
+    /// [`suggest_target`](Self::suggest_target) or the `d=` password option
+    pub async fn suggested_difficulty(&self) -> Option<f64> {
+        *self.suggested_difficulty.lock().await
+    }
+
+    /// Pause mining without dropping the pool session
+    ///
+    /// The running miner task is cancelled and no new jobs are dispatched to the
+    /// miner until [`resume`](Self::resume) is called. Notifications are still
+    /// processed so the session stays in sync with the pool.
+    ///
+    /// Without shares going out the connection may go quiet, and pools or
+    /// NAT boxes drop quiet connections. With the `watchdog` feature a
+    /// default keepalive, see `set_keepalive`, pings the pool while paused,
+    /// unless one is configured already; without it, the session may have to
+    /// be restored after a long pause.
+    pub async fn pause(&self) {
+        self.job_manager.pause();
+        #[cfg(feature = "watchdog")]
+        {
+            let mut keepalive = self.keepalive.lock().await;
+            if keepalive.is_none() {
+                *keepalive = Some(self.spawn_keepalive(KeepaliveConfig::default()));
+                self.pause_keepalive.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Register another miner to be handed the pool's jobs, starting on the
+    /// current one right away, see [`JobManager::add_miner`]
+    ///
+    /// Results of all miners are submitted alike; give each miner a
+    /// [`device_id`](Miner::device_id) to tell them apart in events.
+    pub fn add_miner<M: Miner>(&self, miner: M) {
+        self.job_manager.add_miner(miner);
+    }
+
+    /// Resume mining on the latest job received from the pool, unless a
+    /// schedule or throttle policy still holds it paused
+    ///
+    /// Stops the keepalive [`pause`](Self::pause) started, if any.
+    pub async fn resume(&self) {
+        self.job_manager.resume();
+        #[cfg(feature = "watchdog")]
+        {
+            let mut keepalive = self.keepalive.lock().await;
+            if self.pause_keepalive.swap(false, Ordering::SeqCst) {
+                if let Some(handle) = keepalive.take() {
+                    handle.abort();
+                }
+            }
+        }
+    }
+
+    /// Check whether mining is paused
+    pub fn is_paused(&self) -> bool {
+        self.job_manager.is_paused()
+    }
+
+    /// Only mine inside the windows of the given schedule
+    ///
+    /// The schedule pauses and resumes mining as windows open and close. Its
+    /// pause is tracked apart from a manual [`pause`](Self::pause) or one of a
+    /// throttle policy, so mining only runs once none of them holds it paused.
+    /// Passing `None` removes the schedule and lifts its pause.
+    #[cfg(feature = "schedule")]
+    pub async fn set_schedule(&self, schedule: Option<MiningSchedule>) {
+        let mut scheduler = self.scheduler.lock().await;
+        if let Some(handle) = scheduler.take() {
+            handle.abort();
+        }
+
+        let Some(schedule) = schedule else {
+            self.job_manager.resume_for(PauseReason::Schedule);
+            return;
+        };
+
+        let job_manager = self.job_manager.clone();
+        *scheduler = Some(tokio::spawn(async move {
+            let mut timer = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+            loop {
+                timer.tick().await;
+                let active = schedule.is_active_now();
+                let paused = job_manager.is_paused_for(PauseReason::Schedule);
+                if active && paused {
+                    log_at!(
+                        job_manager.verbosity,
+                        Category::Jobs,
+                        Level::Info,
+                        "Mining schedule window opened, resuming"
+                    );
+                    job_manager.resume_for(PauseReason::Schedule);
+                } else if !active && !paused {
+                    log_at!(
+                        job_manager.verbosity,
+                        Category::Jobs,
+                        Level::Info,
+                        "Mining schedule window closed, pausing"
+                    );
+                    job_manager.pause_for(PauseReason::Schedule);
+                }
+            }
+        }));
+    }
+
+    /// Consult a throttle policy with the miner's device stats
+    ///
+    /// The policy runs every [`ThrottlePolicy::interval`] and may pause, resume
+    /// or change the intensity of the miner. Its resume only lifts its own
+    /// pause, not a manual or scheduled one. Passing `None` removes the policy
+    /// and lifts its pause.
+    pub async fn set_throttle_policy(&self, policy: Option<Box<dyn ThrottlePolicy>>) {
+        let mut throttle = self.throttle.lock().await;
+        if let Some(handle) = throttle.take() {
+            handle.abort();
+        }
+
+        let Some(mut policy) = policy else {
+            self.job_manager.resume_for(PauseReason::Throttle);
+            return;
+        };
+
+        let job_manager = self.job_manager.clone();
+        *throttle = Some(tokio::spawn(async move {
+            let mut timer = tokio::time::interval(policy.interval());
+            loop {
+                timer.tick().await;
+                let Some(stats) = job_manager.miner_control.device_stats().await else {
+                    continue;
+                };
+
+                let paused = job_manager.is_paused_for(PauseReason::Throttle);
+                match policy.evaluate(&stats, paused) {
+                    ThrottleAction::None => {}
+                    ThrottleAction::Pause => {
+                        log_at!(
+                            job_manager.verbosity,
+                            Category::Jobs,
+                            Level::Info,
+                            "Throttle policy paused mining: {stats:?}"
+                        );
+                        job_manager.pause_for(PauseReason::Throttle);
+                    }
+                    ThrottleAction::Resume => {
+                        log_at!(
+                            job_manager.verbosity,
+                            Category::Jobs,
+                            Level::Info,
+                            "Throttle policy resumed mining: {stats:?}"
+                        );
+                        job_manager.resume_for(PauseReason::Throttle);
+                    }
+                    ThrottleAction::SetIntensity(intensity) => {
+                        let intensity = intensity.clamp(0.0, 1.0);
+                        log_at!(
+                            job_manager.verbosity,
+                            Category::Jobs,
+                            Level::Info,
+                            "Throttle policy set intensity to {intensity}"
+                        );
+                        job_manager.miner_control.set_intensity(intensity).await;
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Watch share acceptance latency against an SLA
+    ///
+    /// A `LatencySlaViolated` event is emitted each time the pool's latency
+    /// starts breaching the objective. A [`FailoverClient`](failover::FailoverClient)
+    /// fails over on it if its policy's
+    /// [`fail_over_on_latency`](failover::FailoverPolicy::fail_over_on_latency) is set.
+    pub async fn set_latency_sla(&self, sla: Option<LatencySla>) {
+        *self.latency_sla.lock().await = sla;
+        self.latency_sla_violated.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether the current pool's acceptance latency breaches the SLA
+    pub fn is_latency_sla_violated(&self) -> bool {
+        self.latency_sla_violated.load(Ordering::SeqCst)
+    }
+
+    /// Record a submit round trip and check it against the latency SLA
+    async fn record_submit_latency(&self, latency: Duration) {
+        let mut stats = self.stats.lock().await;
+        stats.record_submit_latency(&self.pool(), latency);
+
+        let Some(sla) = *self.latency_sla.lock().await else {
+            return;
+        };
+        let Some(tracker) = stats.submit_latency(&self.pool()) else {
+            return;
+        };
+
+        let violated = sla.is_violated(tracker);
+        if violated && !self.latency_sla_violated.swap(true, Ordering::SeqCst) {
+            let latency = tracker.percentile(sla.percentile).unwrap_or_default();
+            log_at!(
+                self.verbosity,
+                Category::Shares,
+                Level::Warn,
+                "Share acceptance latency p{} of {} is {:?}, above {:?}",
+                sla.percentile,
+                self.pool(),
+                latency,
+                sla.threshold
+            );
+            self.emit(StratumEvent::LatencySlaViolated {
+                pool: self.pool(),
+                latency,
+                threshold: sla.threshold,
+            });
+        } else if !violated {
+            self.latency_sla_violated.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Record handshakes, jobs and submitted shares to an audit log, or stop
+    /// recording with `None`
+    ///
+    /// Entries are written by a blocking task, off the paths handling shares
+    /// and jobs. Replacing or stopping the log waits until the entries queued
+    /// for the previous one are written.
+    #[cfg(feature = "audit")]
+    pub async fn set_audit_log(&self, log: Option<AuditLog>) {
+        let writer = log.map(|mut log| {
+            let (records, mut queue) = tokio::sync::mpsc::unbounded_channel::<AuditRecord>();
+            let task = tokio::task::spawn_blocking(move || {
+                while let Some(record) = queue.blocking_recv() {
+                    if let Err(e) = log.append(record) {
+                        log::warn!(target: "stratum", "Failed to write the audit log: {e}");
+                    }
+                }
+            });
+            (records, task)
+        });
+        let previous = std::mem::replace(&mut *self.audit.lock().unwrap(), writer);
+        if let Some((records, task)) = previous {
+            drop(records);
+            let _ = task.await;
+        }
+    }
+
+    /// Queue a record for the audit log, if any
+    #[cfg(feature = "audit")]
+    fn audit(&self, record: AuditRecord) {
+        if let Some((records, _)) = self.audit.lock().unwrap().as_ref() {
+            let _ = records.send(record);
+        }
+    }
+
+    /// Address of the current pool as `host:port`
+    pub fn pool(&self) -> String {
+        self.pool.lock().unwrap().clone()
+    }
+
+    /// Subscribe to events emitted by this client
+    pub fn events(&self) -> broadcast::Receiver<StratumEvent> {
+        self.events.subscribe()
+    }
+
+    /// Per-category verbosity of this client's logs and events
+    ///
+    /// E.g. `client.verbosity().set(Category::Shares, LevelFilter::Warn)`
+    /// keeps connection logs while silencing per-share ones.
+    pub fn verbosity(&self) -> &Verbosity {
+        &self.verbosity
+    }
+
+    /// Current authorization state
+    pub async fn auth_state(&self) -> AuthState {
+        *self.auth_state.lock().await
+    }
+
+    /// How long `authorize()` waits for the pool's response
+    ///
+    /// Notifications the pool sends before answering are kept and processed
+    /// by the next `handle_notifications()` calls.
+    pub async fn set_auth_timeout(&self, timeout: Duration) {
+        *self.auth_timeout.lock().await = timeout;
+    }
+
+    /// Set the limits [`health`](HealthCheck::health) reports are judged by
+    pub async fn set_health_thresholds(&self, thresholds: HealthThresholds) {
+        *self.health_thresholds.lock().await = thresholds;
+    }
+
+    /// Enable or disable dry run mode
+    ///
+    /// In dry run mode the client subscribes, authorizes and processes jobs as
+    /// usual but never sends `mining.submit`. Shares are logged instead and
+    /// reported as accepted without being counted in the session statistics,
+    /// which makes it safe to try a new miner against a production pool.
+    pub fn set_dry_run(&self, dry_run: bool) {
+        self.dry_run.store(dry_run, Ordering::SeqCst);
+    }
+
+    /// Check whether dry run mode is enabled
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::SeqCst)
+    }
+
+    /// Probe the socket before submitting after `idle` without traffic
+    ///
+    /// If the probe finds the socket closed, the client reconnects and
+    /// restores the session before submitting instead of spending the
+    /// submit's retries on a dead socket. Passing `None` disables probing.
+    pub async fn set_idle_probe(&self, idle: Option<Duration>) {
+        *self.idle_probe.lock().await = idle;
+    }
+
+    /// Reconnect and restore the session if an idle connection was closed
+    async fn probe_if_idle(&mut self) -> Result<(), StratumError> {
+        let Some(idle) = *self.idle_probe.lock().await else {
+            return Ok(());
+        };
+
+        let requester = self.lock_connection().await.requester();
+        if requester.idle_time().await < idle || requester.probe().await {
+            return Ok(());
+        }
+
+        log_at!(
+            self.verbosity,
+            Category::Connection,
+            Level::Warn,
+            "Connection to {} closed while idle, reconnecting before submit",
+            self.pool()
+        );
+        self.restore_session(None, DisconnectReason::LocalClose)
+            .await
+    }
+
+    /// Move the session to another pool
+    ///
+    /// Connects to `endpoint`, subscribes again if the session was
+    /// subscribed, and authorizes with the endpoint's credentials or else the
+    /// last ones used. Grants negotiated with the previous pool, such as
+    /// version rolling, and a hot-standby link are dropped. If the pool can't
+    /// be reached, the current connection is kept.
+    pub async fn switch_pool(&mut self, endpoint: &PoolEndpoint) -> Result<(), StratumError> {
+        // The SLA is checked per pool, start over on the new one
+        self.latency_sla_violated.store(false, Ordering::SeqCst);
+        self.restore_session(Some(endpoint), DisconnectReason::LocalClose)
+            .await
+    }
+
+    /// Reconnect, to `endpoint` if given, and restore the subscription and
+    /// authorization of the session
+    ///
+    /// `closing` is reported as the disconnect reason if the connection was
+    /// still up. Errors carry the pool being reconnected to as context.
+    async fn restore_session(
+        &mut self,
+        endpoint: Option<&PoolEndpoint>,
+        closing: DisconnectReason,
+    ) -> Result<(), StratumError> {
+        let pool = match endpoint {
+            Some(endpoint) => format!("{}:{}", endpoint.host, endpoint.port),
+            None => self.pool(),
+        };
+        self.reconnect_and_restore(endpoint, closing)
+            .await
+            .map_err(|err| err.context(ErrorContext::new("restore session").pool(pool)))
+    }
+
+    async fn reconnect_and_restore(
+        &mut self,
+        endpoint: Option<&PoolEndpoint>,
+        closing: DisconnectReason,
+    ) -> Result<(), StratumError> {
+        // Hold notifications back until the subscription is restored, so jobs
+        // of the new session aren't dropped by its extranonce arriving later
+        let dispatching = self
+            .dispatcher
+            .lock()
+            .await
+            .take()
+            .inspect(JoinHandle::abort)
+            .is_some();
+        let resubscribed = self.resubscribe(endpoint, closing).await;
+        if dispatching {
+            self.start_dispatcher().await;
+        }
+        resubscribed?;
+
+        let credentials = match endpoint.and_then(|endpoint| endpoint.credentials.clone()) {
+            Some(credentials) => Some(credentials),
+            None => self.credentials.lock().await.clone(),
+        };
+        if let Some((username, password)) = credentials {
+            self.authorize(&username, &password).await?;
+        }
+        let workers = self.workers.lock().await.clone();
+        for (username, password) in workers {
+            if let Err(err) = self.authorize_worker(&username, &password).await {
+                log_at!(
+                    self.verbosity,
+                    Category::Connection,
+                    Level::Warn,
+                    "Authorizing worker {username} again failed: {err}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Reconnect, to `endpoint` if given, and subscribe again if the session
+    /// was subscribed
+    ///
+    /// A subscription assigned a different extranonce1 replaces the previous
+    /// one throughout the job manager, see [`JobManager::set_extranonce`].
+    async fn resubscribe(
+        &mut self,
+        endpoint: Option<&PoolEndpoint>,
+        closing: DisconnectReason,
+    ) -> Result<(), StratumError> {
+        let mut connection = self.lock_connection().await;
+        let reason = connection.take_disconnect();
+        let closed_addr = self.pool();
+        match endpoint {
+            Some(endpoint) => {
+                self.emit(StratumEvent::Reconnecting {
+                    addr: format!("{}:{}", endpoint.host, endpoint.port),
+                });
+                if let Err(err) = connection.connect_to(&endpoint.host, endpoint.port).await {
+                    if let Some(reason) = reason {
+                        self.disconnected(reason);
+                    }
+                    return Err(err);
+                }
+                self.disconnected(reason.unwrap_or(closing.clone()));
+                *self.pool.lock().unwrap() = format!("{}:{}", connection.host(), connection.port());
+                self.standby.lock().await.take();
+                self.version_rolling.lock().await.take();
+            }
+            None => {
+                self.disconnected(reason.unwrap_or(closing.clone()));
+                self.emit(StratumEvent::Reconnecting { addr: self.pool() });
+                connection.reconnect_with(&self.reconnect_policy()).await?;
+            }
+        }
+        self.emit_closed_stats(&mut connection, closed_addr);
+        let tls = connection.is_tls();
+        drop(connection);
+        self.job_manager.new_generation();
+        self.connected(tls);
+
+        let previous = self.subscription.lock().await.clone();
+        if let Some(previous) = previous {
+            let subscription = self.subscribe().await?;
+            if subscription.extranonce1 != previous.extranonce1 {
+                log_at!(
+                    self.verbosity,
+                    Category::Connection,
+                    Level::Info,
+                    "Pool assigned extranonce1 {} instead of {} on resubscribing",
+                    subscription.extranonce1,
+                    previous.extranonce1
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Restart the mining pipeline when it wedges
+    ///
+    /// Every `config.check_interval` the watchdog checks whether jobs still
+    /// reach the miner and miner tasks stop when asked to. If not, it emits a
+    /// `WatchdogRestart` event, restarts the miner worker and reconnects,
+    /// restoring the session. Passing `None` stops the watchdog.
+    #[cfg(feature = "watchdog")]
+    pub async fn set_watchdog(&self, config: Option<WatchdogConfig>) {
+        let mut watchdog = self.watchdog.lock().await;
+        if let Some(handle) = watchdog.take() {
+            handle.abort();
+        }
+
+        let Some(config) = config else {
+            return;
+        };
+
+        let mut client = self.clone();
+        *watchdog = Some(tokio::spawn(async move {
+            let mut timer = tokio::time::interval(config.check_interval);
+            let mut watching_since = Instant::now();
+            loop {
+                timer.tick().await;
+                let job_manager = &client.job_manager;
+                let since_dispatch = job_manager
+                    .last_dispatch_age()
+                    .map_or(watching_since.elapsed(), |age| {
+                        age.min(watching_since.elapsed())
+                    });
+                let Some(reason) = config.detect(
+                    client.connected.load(Ordering::SeqCst),
+                    job_manager.is_paused(),
+                    since_dispatch,
+                    job_manager.unresponsive_miner_age(),
+                ) else {
+                    continue;
+                };
+
+                log_at!(
+                    client.verbosity,
+                    Category::Connection,
+                    Level::Warn,
+                    "Mining pipeline of {} wedged ({reason:?}), restarting",
+                    client.pool()
+                );
+                client.emit(StratumEvent::WatchdogRestart { reason });
+                if let Err(e) = client.job_manager.restart_worker().await {
+                    log_at!(
+                        client.verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Failed to restart the miner worker: {e}"
+                    );
+                }
+                if let Err(e) = client
+                    .restore_session(None, DisconnectReason::LocalClose)
+                    .await
+                {
+                    log_at!(
+                        client.verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Failed to restore the session: {e}"
+                    );
+                }
+                watching_since = Instant::now();
+            }
+        }));
+    }
+
+    /// Check every `interval` that the target and the job handed to the
+    /// miner agree with the pool's last difficulty, see
+    /// [`JobManager::check_consistency`]
+    ///
+    /// Discrepancies are corrected and reported as `TargetInconsistency`
+    /// events. Passing `None` stops the checks.
+    pub async fn set_consistency_check(&self, interval: Option<Duration>) {
+        let mut consistency_check = self.consistency_check.lock().await;
+        if let Some(handle) = consistency_check.take() {
+            handle.abort();
+        }
+
+        let Some(interval) = interval else {
+            return;
+        };
+
+        let client = self.clone();
+        *consistency_check = Some(tokio::spawn(async move {
+            let mut timer = tokio::time::interval(interval);
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                if let Err(e) = client.job_manager.check_consistency().await {
+                    log_at!(
+                        client.verbosity,
+                        Category::Difficulty,
+                        Level::Warn,
+                        "Failed to correct an inconsistent target: {e}"
+                    );
+                }
+            }
+        }));
+    }
+
+    /// Ping the pool when the connection has been quiet for a while
+    ///
+    /// After `config.idle` without a message either way, the session's
+    /// credentials are sent again with `mining.authorize`, which every pool
+    /// answers and which leaves an authorized worker authorized. Any answer,
+    /// an error included, shows the pool is alive. Without one within
+    /// `config.timeout` the client emits a `ConnectionStale` event and
+    /// reconnects, restoring the session. Sessions that haven't authorized
+    /// yet aren't pinged. Passing `None` stops the pings.
+    #[cfg(feature = "watchdog")]
+    pub async fn set_keepalive(&self, config: Option<KeepaliveConfig>) {
+        let mut keepalive = self.keepalive.lock().await;
+        if let Some(handle) = keepalive.take() {
+            handle.abort();
+        }
+        // A keepalive set here outlives a pause
+        self.pause_keepalive.store(false, Ordering::SeqCst);
+        *keepalive = config.map(|config| self.spawn_keepalive(config));
+    }
+
+    /// Start the task pinging the pool, see [`set_keepalive`](Self::set_keepalive)
+    #[cfg(feature = "watchdog")]
+    fn spawn_keepalive(&self, config: KeepaliveConfig) -> JoinHandle<()> {
+        let mut client = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let requester = client.lock_connection().await.requester();
+                let idle = requester.idle_time().await;
+                if idle < config.idle {
+                    tokio::time::sleep(config.idle - idle).await;
+                    continue;
+                }
+                let credentials = client.credentials.lock().await.clone();
+                let Some((username, password)) = credentials else {
+                    tokio::time::sleep(config.idle).await;
+                    continue;
+                };
+                if !client.connected.load(Ordering::SeqCst) {
+                    // Restoring the connection is up to whoever noticed it
+                    // ended
+                    tokio::time::sleep(config.idle).await;
+                    continue;
+                }
+
+                let ping = requester.send_request_once(
+                    MINING_AUTHORIZE,
+                    vec![json!(username), json!(password)],
+                    config.timeout,
+                );
+                if ping.await.is_ok() {
+                    continue;
+                }
+                log_at!(
+                    client.verbosity,
+                    Category::Connection,
+                    Level::Warn,
+                    "{} didn't answer a keepalive ping after {:?} idle, reconnecting",
+                    client.pool(),
+                    idle
+                );
+                client.emit(StratumEvent::ConnectionStale { idle });
+                if let Err(e) = client
+                    .restore_session(None, DisconnectReason::Timeout)
+                    .await
+                {
+                    log_at!(
+                        client.verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Failed to restore the session: {e}"
+                    );
+                    tokio::time::sleep(config.idle).await;
+                }
+            }
+        })
+    }
+
+    /// Emit a `ConnectionClosedStats` event for the connection a reconnect
+    /// replaced
+    fn emit_closed_stats(&self, connection: &mut StratumConnection, addr: String) {
+        if let Some(stats) = connection.take_closed_stats() {
+            self.emit(StratumEvent::ConnectionClosedStats { addr, stats });
+        }
+    }
+
+    /// Emit a `Disconnected` event if the connection observed its end
+    fn emit_disconnect(&self, reason: Option<DisconnectReason>) {
+        if let Some(reason) = reason {
+            self.disconnected(reason);
+        }
+    }
+
+    /// Emit a `Disconnected` event, once per connection
+    fn disconnected(&self, reason: DisconnectReason) {
+        if self.connected.swap(false, Ordering::SeqCst) {
+            log_at!(
+                self.verbosity,
+                Category::Connection,
+                Level::Warn,
+                "Disconnected from {}: {:?}",
+                self.pool(),
+                reason
+            );
+            if let DisconnectReason::Error(err) = &reason {
+                self.record_error(StratumError::Connection(err.clone()));
+            }
+            self.emit(StratumEvent::Disconnected { reason });
+        }
+    }
+
+    /// Broadcast an event unless its category is silenced
+    fn emit(&self, event: StratumEvent) {
+        if self.verbosity.event_enabled(event.kind()) {
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Emit a `Connected` event for a new connection
+    fn connected(&self, tls: bool) {
+        self.connected.store(true, Ordering::SeqCst);
+        *self.connected_at.lock().unwrap() = Instant::now();
+        self.emit(StratumEvent::Connected {
+            addr: self.pool(),
+            tls,
+        });
+    }
+
+    /// Give each new connection a grace period of `warm_up`, or none
+    ///
+    /// The first shares after connecting or switching pools are often stale,
+    /// mined on jobs of the previous session. While warming up, the reject
+    /// rate doesn't degrade [`health`](HealthCheck::health) and latency SLA
+    /// breaches don't fail over, see
+    /// [`FailoverPolicy::warm_up`](failover::FailoverPolicy::warm_up).
+    pub fn set_warm_up(&self, warm_up: Option<Duration>) {
+        *self.warm_up.lock().unwrap() = warm_up;
+    }
+
+    /// Whether the current connection is within its warm-up grace period
+    pub fn is_warming_up(&self) -> bool {
+        let connected_at = *self.connected_at.lock().unwrap();
+        self.warm_up
+            .lock()
+            .unwrap()
+            .is_some_and(|warm_up| connected_at.elapsed() < warm_up)
+    }
+
+    /// Hold job notifications for `window` to collapse bursts
+    ///
+    /// Behind TLS terminators and proxies, notifications can arrive bunched
+    /// and out of order. When a job arrives, the client keeps reading for up
+    /// to `window` and only dispatches the latest relevant job of the burst.
+    /// Passing `None` dispatches every job immediately. The burst is read
+    /// through a [read loop](StratumConnection::start_read_loop), started on
+    /// the first one, so submits aren't held up while it is collected.
+    pub async fn set_notify_debounce(&self, window: Option<Duration>) {
+        *self.notify_debounce.lock().await = window;
+    }
+
+    /// Process notifications in a background task
+    ///
+    /// A read loop takes over the socket: responses are routed to the requests
+    /// waiting for them and notifications are dispatched as they arrive, so
+    /// [`handle_notifications`](StratumClient::handle_notifications) must no
+    /// longer be called. Jobs and targets are published on [`jobs`](Self::jobs)
+    /// and [`targets`](Self::targets). The dispatcher survives reconnects and
+    /// runs until [`stop_dispatcher`](Self::stop_dispatcher) or
+    /// [`close`](StratumClient::close).
+    pub async fn start_dispatcher(&self) {
+        let mut dispatcher = self.dispatcher.lock().await;
+        if let Some(handle) = dispatcher.take() {
+            handle.abort();
+        }
+
+        let mut connection = self.lock_connection().await;
+        connection.start_read_loop();
+        let inbox = connection.inbox();
+        drop(connection);
+
+        let client = self.clone();
+        *dispatcher = Some(tokio::spawn(async move {
+            loop {
+                let notification = inbox.next_notification().await;
+                if notification.is_null() {
+                    // The connection ended, a reconnect starts a new dispatcher
+                    client.emit_disconnect(client.lock_connection().await.take_disconnect());
+                    if client.auto_reconnect.load(Ordering::SeqCst) {
+                        client.start_reconnector().await;
+                    }
+                    break;
+                }
+                if let Err(e) = client.dispatch_notification(&notification).await {
+                    log::warn!(target: "stratum", "Failed to handle notification {notification}: {e}");
+                }
+            }
+        }));
+    }
+
+    /// Stop the background dispatcher
+    ///
+    /// The read loop keeps routing responses, notifications are buffered
+    /// for [`handle_notifications`](StratumClient::handle_notifications).
+    pub async fn stop_dispatcher(&self) {
+        if let Some(handle) = self.dispatcher.lock().await.take() {
+            handle.abort();
+        }
+    }
+
+    /// Restore the session on a new connection whenever the connection is
+    /// lost
+    ///
+    /// The dispatcher notices the connection ending, so it must be running.
+    /// Attempts follow the [`ReconnectPolicy`]; once they run out, the client
+    /// tries again every [`ReconnectPolicy::max_delay`] until it reconnects,
+    /// auto-reconnect is turned off or the client is closed.
+    pub async fn set_auto_reconnect(&self, enabled: bool) {
+        self.auto_reconnect.store(enabled, Ordering::SeqCst);
+        if !enabled {
+            if let Some(handle) = self.reconnector.lock().await.take() {
+                handle.abort();
+            }
+        }
+    }
+
+    /// Start restoring the session, unless that is already under way
+    async fn start_reconnector(&self) {
+        let mut reconnector = self.reconnector.lock().await;
+        if reconnector.as_ref().is_none_or(JoinHandle::is_finished) {
+            *reconnector = Some(tokio::spawn(self.clone().recover()));
+        }
+    }
+
+    /// Restore the session after the connection was lost
+    ///
+    /// Boxed, since the dispatcher it restarts may start it again.
+    fn recover(mut self) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            loop {
+                // Someone else may have reconnected meanwhile
+                if self.lock_connection().await.requester().probe().await {
+                    return;
+                }
+                let Err(err) = self
+                    .restore_session(None, DisconnectReason::PeerClosed)
+                    .await
+                else {
+                    return;
+                };
+                let delay = self.reconnect_policy().max_delay;
+                log_at!(
+                    self.verbosity,
+                    Category::Connection,
+                    Level::Warn,
+                    "Reconnecting to {} failed, retrying in {:?}: {err}",
+                    self.pool(),
+                    delay
+                );
+                self.record_error(err);
+                tokio::time::sleep(delay).await;
+            }
+        })
+    }
+
+    /// Submit the miner's results as they come in
+    ///
+    /// Takes the [`take_result_receiver`](Self::take_result_receiver)
+    /// channel, failing if it was taken, and runs until the client is closed.
+    /// Each result is submitted with the extranonce2, ntime and version bits
+    /// the miner reported it at, see [`ShareSink`](crate::stratum::miner::ShareSink).
+    ///
+    /// Shares are validated before submitting like with
+    /// [`submit_share`](StratumClient::submit_share). Every outcome is
+    /// reported as a `ShareAccepted`, `ShareRejected` or `ShareDiscarded`
+    /// event.
+    pub async fn start_auto_submit(&self) -> Result<(), StratumError> {
+        let results = self
+            .take_result_receiver()
+            .await
+            .ok_or_else(|| StratumError::Config("Miner results are already taken".into()))?;
+        let results = Arc::new(Mutex::new(results));
+        let task_results = results.clone();
+        let (stop, mut stopping) = watch::channel(false);
+        let mut client = self.clone();
+        let task = tokio::spawn(async move {
+            let mut results = task_results.lock().await;
+            loop {
+                let result = tokio::select! {
+                    result = results.recv() => result,
+                    // Results still waiting are left to whoever stopped the task
+                    _ = stopping.changed() => None,
+                };
+                let Some(result) = result else { break };
+                client.submit_result(result).await;
+            }
+        });
+        let auto_submit = AutoSubmit {
+            task,
+            results,
+            stop,
+        };
+        if let Some(previous) = self.auto_submit.lock().await.replace(auto_submit) {
+            previous.task.abort();
+        }
+        Ok(())
+    }
+
+    /// Stop submitting the miner's results
+    ///
+    /// Returns the number of results that were waiting to be submitted and
+    /// are dropped.
+    pub async fn stop_auto_submit(&self) -> usize {
+        let Some(auto_submit) = self.auto_submit.lock().await.take() else {
+            return 0;
+        };
+        auto_submit.task.abort();
+        let _ = auto_submit.task.await;
+        let mut results = auto_submit.results.lock().await;
+        let mut dropped = 0;
+        while results.try_recv().is_ok() {
+            dropped += 1;
+        }
+        dropped
+    }
+
+    /// Stop submitting the miner's results once those waiting are submitted
+    ///
+    /// The share being submitted is answered first. Returns the number of
+    /// waiting results submitted on stopping.
+    pub async fn flush_auto_submit(&mut self) -> usize {
+        let Some(auto_submit) = self.auto_submit.lock().await.take() else {
+            return 0;
+        };
+        let _ = auto_submit.stop.send(true);
+        let _ = auto_submit.task.await;
+        let mut results = auto_submit.results.lock().await;
+        let mut flushed = 0;
+        while let Ok(result) = results.try_recv() {
+            self.submit_result(result).await;
+            flushed += 1;
+        }
+        flushed
+    }
+
+    /// Submit a miner result, see [`start_auto_submit`](Self::start_auto_submit)
+    async fn submit_result(&mut self, result: jobs::MinerResult) {
+        let share = match result {
+            Ok((share, _)) => share,
+            Err(err) => {
+                log_at!(
+                    self.verbosity,
+                    Category::Shares,
+                    Level::Warn,
+                    "Miner failed: {err}"
+                );
+                self.record_error(err);
+                return;
+            }
+        };
+        // Outcomes are reported through events
+        if let Err(err) = self.submit_share(share).await {
+            self.record_error(err);
+        }
+    }
+
+    /// Stop the client and wait for its background tasks to exit
+    ///
+    /// Stops the dispatcher, reconnects and every timer (statistics, CSV
+    /// export, watchdog, keepalive, consistency check, schedule and
+    /// throttle), cancels the miner workers, submits the miner results still
+    /// waiting if auto-submit runs, and closes the connection. Dropping a
+    /// client instead leaves its tasks running.
+    ///
+    /// Returns a summary of the session, for an end-of-session log line.
+    pub async fn shutdown(&mut self) -> Result<ShutdownReport, StratumError> {
+        self.set_auto_reconnect(false).await;
+        let tasks = [
+            &self.dispatcher,
+            &self.reconnector,
+            &self.stats_ticker,
+            #[cfg(feature = "export")]
+            &self.csv_export,
+            #[cfg(feature = "watchdog")]
+            &self.watchdog,
+            #[cfg(feature = "watchdog")]
+            &self.keepalive,
+            &self.consistency_check,
+            #[cfg(feature = "schedule")]
+            &self.scheduler,
+            &self.throttle,
+        ];
+        for task in tasks {
+            let handle = task.lock().await.take();
+            if let Some(handle) = handle {
+                handle.abort();
+                let _ = handle.await;
+            }
+        }
+        self.job_manager.shutdown().await;
+        // Only the waiting results that never reached the pool count as dropped
+        let failures = self.session_snapshot().await.submit_failures;
+        let flushed = self.flush_auto_submit().await;
+        if flushed > 0 {
+            log_at!(
+                self.verbosity,
+                Category::Shares,
+                Level::Info,
+                "Submitted {} pending shares on shutdown",
+                flushed
+            );
+        }
+        self.close().await?;
+
+        let snapshot = self.session_snapshot().await;
+        let report = ShutdownReport {
+            uptime: snapshot.uptime,
+            shares: snapshot.shares,
+            best_share_difficulty: snapshot.records.best_share_difficulty,
+            pending_shares_dropped: (snapshot.submit_failures - failures) as usize,
+            last_error: self.last_error(),
+        };
+        log_at!(
+            self.verbosity,
+            Category::Connection,
+            Level::Info,
+            "Session ended: {}",
+            report
+        );
+        Ok(report)
+    }
+
+    /// Last error hit in the background: failed automatic submits and
+    /// reconnects, and errors that ended a connection
+    pub fn last_error(&self) -> Option<StratumError> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    fn record_error(&self, err: StratumError) {
+        *self.last_error.lock().unwrap() = Some(err);
+    }
+
+    /// Restart the dispatcher on a new connection, if it was running
+    async fn restart_dispatcher(&self) {
+        if self.dispatcher.lock().await.is_some() {
+            self.start_dispatcher().await;
+        }
+    }
+
+    /// Watch the jobs handed to the miner, see [`JobManager::jobs`]
+    pub fn jobs(&self) -> watch::Receiver<Option<Arc<MiningJob>>> {
+        self.job_manager.jobs()
+    }
+
+    /// Watch the target set by the pool
+    pub fn targets(&self) -> watch::Receiver<Option<MiningTarget>> {
+        self.job_manager.targets()
+    }
+
+    /// Route a notification to the job manager
+    async fn dispatch_notification(&self, notification: &Value) -> Result<(), StratumError> {
+        let mode = self.quirks.lock().await.mode;
+        mode.check_notification(notification)?;
+        if let Some(method) = notification.get("method").and_then(Value::as_str) {
+            match Method::from(method) {
+                Method::Notify => {
+                    if let Some(params) = notification.get("params").and_then(Value::as_array) {
+                        self.job_manager.handle_job_notification(params).await?;
+                        #[cfg(feature = "audit")]
+                        if let Some(job) = self.job_manager.get_current_job().await? {
+                            self.audit(AuditRecord::Job {
+                                pool: self.pool(),
+                                job,
+                            });
+                        }
+                    }
+                }
+                Method::SetDifficulty => {
+                    if let Some(params) = notification.get("params").and_then(Value::as_array) {
+                        self.job_manager
+                            .handle_difficulty_notification(params)
+                            .await?;
+                    }
+                }
+                Method::SetExtranonce => {
+                    if let Some(params) = notification.get("params").and_then(Value::as_array) {
+                        self.handle_set_extranonce(params).await?;
+                    }
+                }
+                Method::SetVersionMask => {
+                    let mask = notification
+                        .get("params")
+                        .and_then(|params| params.get(0))
+                        .and_then(Value::as_str)
+                        .and_then(|mask| u32::from_str_radix(mask, 16).ok());
+                    let current = *self.version_rolling.lock().await;
+                    match (mask, current) {
+                        (Some(mask), Some(rolling)) => {
+                            self.set_version_rolling(Some(VersionRolling { mask, ..rolling }))
+                                .await;
+                        }
+                        (None, _) => {
+                            log::warn!(target: "stratum", "Invalid {}: {}", MINING_SET_VERSION_MASK, notification);
+                        }
+                        _ => {}
+                    }
+                }
+                Method::Reconnect => {
+                    let params = notification
+                        .get("params")
+                        .and_then(Value::as_array)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    self.handle_reconnect(params).await?;
+                }
+                Method::ShowMessage => {
+                    if let Some(message) = notification
+                        .get("params")
+                        .and_then(|params| params.get(0))
+                        .and_then(Value::as_str)
+                    {
+                        self.handle_show_message(message).await;
+                    }
+                }
+                Method::GetVersion => {
+                    // Sent as a request, answered with the user agent
+                    if let Some(id) = notification.get("id").filter(|id| !id.is_null()) {
+                        let requester = self.lock_connection().await.requester();
+                        requester
+                            .send_response(id.clone(), json!(self.user_agent()))
+                            .await?;
+                    }
+                }
+                method @ Method::Unknown(_) => self.handle_extension(&method, notification).await?,
+                _ => {} // Client-to-pool method, ignore
+            }
+        }
+        if self.job_manager.take_job_wanted() {
+            self.request_job().await;
+        }
+
+        Ok(())
+    }
+
+    /// Pass a method this client doesn't implement to its registered
+    /// handler, if any, answering the pool when it sent a request
+    ///
+    /// A failing handler is logged and kept as the
+    /// [`last_error`](Self::last_error) rather than ending notification
+    /// handling. Requests nobody answers get a -32601 error, or -32603 when
+    /// their handler failed, so the pool isn't left waiting.
+    async fn handle_extension(&self, method: &Method, message: &Value) -> Result<(), StratumError> {
+        let handler = self.extensions.lock().unwrap().handler(method);
+        let params = message
+            .get("params")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let reply = match handler {
+            Some(handler) => match handler.handle(params).await {
+                Ok(Some(result)) => Ok(result),
+                Ok(None) => Err((-32601, "Method not found".to_string())),
+                Err(err) => {
+                    log_at!(
+                        self.verbosity,
+                        Category::Connection,
+                        Level::Warn,
+                        "Handler of {} failed: {err}",
+                        method
+                    );
+                    let reply = Err((-32603, err.to_string()));
+                    self.record_error(err);
+                    reply
+                }
+            },
+            None => Err((-32601, "Method not found".to_string())),
+        };
+        let Some(id) = message.get("id").filter(|id| !id.is_null()) else {
+            return Ok(()); // Notification, nobody to answer
+        };
+        let requester = self.lock_connection().await.requester();
+        match reply {
+            Ok(result) => requester.send_response(id.clone(), result).await,
+            Err((code, error)) => {
+                requester
+                    .send_error_response(id.clone(), code, &error)
+                    .await
+            }
+        }
+    }
+
+    /// Apply a `client.reconnect`, moving the session if the redirect policy
+    /// allows the target
+    ///
+    /// The move runs in the background while the dispatcher is running, as
+    /// it restarts the dispatcher; otherwise it completes before returning,
+    /// including the wait the pool asked for.
+    async fn handle_reconnect(&self, params: &[Value]) -> Result<(), StratumError> {
+        let request = ReconnectRequest::parse(params)?;
+        let (host, port) = {
+            let connection = self.lock_connection().await;
+            (connection.host().to_string(), connection.port())
+        };
+        let target = PoolEndpoint::new(
+            request.host.unwrap_or_else(|| host.clone()),
+            request.port.unwrap_or(port),
+        );
+        let followed = self
+            .redirect_policy
+            .lock()
+            .unwrap()
+            .allows(&host, &target.host);
+        let to = format!("{}:{}", target.host, target.port);
+        self.emit(StratumEvent::PoolRedirect {
+            from: self.pool(),
+            to: to.clone(),
+            followed,
+        });
+        if !followed {
+            log_at!(
+                self.verbosity,
+                Category::Connection,
+                Level::Warn,
+                "Ignoring {} to {}, not allowed by the redirect policy",
+                CLIENT_RECONNECT,
+                to
+            );
+            return Ok(());
+        }
+        log_at!(
+            self.verbosity,
+            Category::Connection,
+            Level::Info,
+            "Pool asked to reconnect to {} in {:?}",
+            to,
+            request.wait
+        );
+
+        let redirect = self.clone().follow_redirect(target, request.wait);
+        if self.dispatcher.lock().await.is_none() {
+            return redirect.await;
+        }
+        let verbosity = self.verbosity.clone();
+        tokio::spawn(async move {
+            if let Err(err) = redirect.await {
+                log_at!(
+                    verbosity,
+                    Category::Connection,
+                    Level::Warn,
+                    "Following {} to {} failed: {err}",
+                    CLIENT_RECONNECT,
+                    to
+                );
+            }
+        });
+        Ok(())
+    }
+
+    /// Move the session to `target` after `wait`
+    ///
+    /// Boxed, since the dispatcher it restarts may call it again.
+    fn follow_redirect(
+        mut self,
+        target: PoolEndpoint,
+        wait: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<(), StratumError>> + Send>> {
+        Box::pin(async move {
+            tokio::time::sleep(wait).await;
+            self.restore_session(Some(&target), DisconnectReason::PoolReconnect)
+                .await
+        })
+    }
+
+    /// Log and emit a pool message, keeping the first one as the message of
+    /// the day
+    async fn handle_show_message(&self, message: &str) {
+        log_at!(
+            self.verbosity,
+            Category::Connection,
+            Level::Info,
+            "Message from {}: {}",
+            self.pool(),
+            message
+        );
+        self.emit(StratumEvent::PoolMessage {
+            pool: self.pool(),
+            message: message.to_string(),
+        });
+
+        let mut server_info = self.server_info.lock().await;
+        let info = server_info.get_or_insert_with(ServerInfo::default);
+        if info.motd.is_none() {
+            info.motd = Some(message.to_string());
+            self.emit(StratumEvent::Motd {
+                message: message.to_string(),
+            });
+        }
+    }
+
+    /// Emit a `StatsTick` event every `interval`, summarizing that interval
+    ///
+    /// Passing `None` stops any previously started ticker.
+    pub async fn set_stats_interval(&self, interval: Option<Duration>) {
+        let mut ticker = self.stats_ticker.lock().await;
+        if let Some(handle) = ticker.take() {
+            handle.abort();
+        }
+
+        let Some(interval) = interval else {
+            return;
+        };
+
+        let stats = self.stats.clone();
+        let events = self.events.clone();
+        *ticker = Some(tokio::spawn(async move {
+            let mut timer = tokio::time::interval(interval);
+            // The first tick completes immediately
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                let summary = stats.lock().await.summary(interval);
+                // No receivers is not an error, the event is simply dropped
+                let _ = events.send(StratumEvent::StatsTick(summary));
+            }
+        }));
+    }
+
+    /// Append a row of statistics to CSV files every `config.interval`
+    ///
+    /// Each row covers the shares, hashrate and reconnects of its interval,
+    /// see [`CsvExporter`] for the file rotation. Passing `None` stops the export.
+    #[cfg(feature = "export")]
+    pub async fn set_csv_export(&self, config: Option<CsvExportConfig>) {
+        let mut export = self.csv_export.lock().await;
+        if let Some(handle) = export.take() {
+            handle.abort();
+        }
+
+        let Some(config) = config else {
+            return;
+        };
+
+        let stats = self.stats.clone();
+        let mut events = self.events.subscribe();
+        *export = Some(tokio::spawn(async move {
+            let mut timer = tokio::time::interval(config.interval);
+            let mut exporter = CsvExporter::new(config);
+            // The first tick completes immediately
+            timer.tick().await;
+            let mut previous = stats.lock().await.snapshot();
+            let mut reconnects = 0;
+            loop {
+                tokio::select! {
+                    _ = timer.tick() => {
+                        let current = stats.lock().await.snapshot();
+                        let row = StatsRow::between(&previous, &current, reconnects);
+                        if let Err(e) = exporter.write_row(&row) {
+                            log::warn!(target: "stratum", "Failed to export statistics: {e}");
+                        }
+                        previous = current;
+                        reconnects = 0;
+                    }
+                    event = events.recv() => match event {
+                        Ok(StratumEvent::Connected { .. }) => reconnects += 1,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                        _ => {}
+                    },
+                }
+            }
+        }));
+    }
+}
+
+#[async_trait]
+impl HealthCheck for StratumV1Client {
+    async fn health(&self) -> Health {
+        let (last_share_age, reject_percent) = {
+            let stats = self.stats.lock().await;
+            (
+                stats.last_share_age(),
+                stats.summary(stats::RECENT_SHARE_HORIZON).reject_percent,
+            )
+        };
+        let mut thresholds = *self.health_thresholds.lock().await;
+        if self.is_warming_up() {
+            thresholds.reject_rate_degraded = None;
+            thresholds.reject_rate_unhealthy = None;
+        }
+        Health::new(
+            self.connected.load(Ordering::SeqCst),
+            self.auth_state().await == AuthState::Authorized,
+            self.job_manager.last_job_age().await,
+            last_share_age,
+            reject_percent / 100.0,
+            &thresholds,
+        )
+    }
+}
+
+#[async_trait]
+impl StratumClient for StratumV1Client {
+    /// Subscribe to the mining pool
+    ///
+    /// This is typically the first step when connecting to a pool. The pool will respond
+    /// with a subscription ID and extranonce1 value that will be used for mining.
+    async fn subscribe(&mut self) -> Result<SubscribeResponse, StratumError> {
+        let requester = self.lock_connection().await.requester();
+        let response = requester
+            .send_request(MINING_SUBSCRIBE, vec![json!(self.user_agent())])
+            .await;
+        self.emit_disconnect(requester.take_disconnect());
+        let response = response?;
+
+        let quirks = self.quirks.lock().await.clone();
+        let subscription = Self::parse_subscribe_response(response, &quirks)?;
+        *self.subscription.lock().await = Some(subscription.clone());
+        #[cfg(feature = "audit")]
+        self.audit(AuditRecord::Subscribed {
+            pool: self.pool(),
+            extranonce1: subscription.extranonce1.clone(),
+            extranonce2_size: subscription.extranonce2_size,
+        });
+        self.job_manager
+            .set_extranonce(Extranonce {
+                extranonce1: subscription.extranonce1.clone(),
+                extranonce2_size: subscription.extranonce2_size,
+            })
+            .await;
+
+        let mut server_info = self.server_info.lock().await;
+        let info = server_info.get_or_insert_with(ServerInfo::default);
+        info.connection_id = subscription.subscription_id.clone();
+        info.subscriptions = subscription.subscriptions.clone();
+        drop(server_info);
+
+        Ok(subscription)
+    }
+
+    /// Authorize with the mining pool using worker credentials
+    ///
+    /// This should be called after subscribing. The username is typically in the format
+    /// "wallet_address.worker_name" or "username.worker_name" depending on the pool.
+    async fn authorize(
+        &mut self,
+        username: &str,
+        password: &str,
+    ) -> Result<AuthResponse, StratumError> {
+        *self.credentials.lock().await = Some((username.to_string(), password.to_string()));
+        *self.auth_state.lock().await = AuthState::Pending;
+        let response = match self.request_authorization(username, password).await {
+            Ok(response) => response,
+            Err(err) => {
+                *self.auth_state.lock().await = AuthState::Unauthorized;
+                return Err(err);
+            }
+        };
+
+        if let Some(difficulty) = password
+            .parse::<PoolPassword>()
+            .ok()
+            .and_then(|options| options.suggested_difficulty())
+        {
+            log_at!(
+                self.verbosity,
+                Category::Difficulty,
+                Level::Info,
+                "Password requests difficulty {difficulty}"
+            );
+            self.set_suggested_difficulty(difficulty).await;
+        }
+
+        *self.auth_state.lock().await = if response.authorized {
+            AuthState::Authorized
+        } else {
+            AuthState::Rejected
+        };
+        Ok(response)
+    }
+
+    /// Submit a solved share to the mining pool
+    ///
+    /// Returns true if the share was accepted, false if it was rejected.
+    /// The share should be generated based on the current mining job and target difficulty.
+    async fn submit_share(&mut self, share: Share) -> Result<bool, StratumError> {
+        let device = self.job_manager.miner_control.device_id();
+        self.submit_share_from(device.as_deref(), share).await
+    }
+
+    /// Get the current mining job if one is available
+    ///
+    /// New jobs are received through notifications, so this returns None if no job
+    /// has been received yet. Use handle_notifications() to process new jobs.
+    async fn get_current_job(&mut self) -> Result<Option<Arc<MiningJob>>, StratumError> {
+        self.job_manager.get_current_job().await
+    }
+
+    /// Process any pending notifications from the mining pool
+    ///
+    /// This should be called regularly to receive new jobs and difficulty updates.
+    /// It processes one notification at a time, so call it in a loop during mining.
+    async fn handle_notifications(&mut self) -> Result<(), StratumError> {
+        // Wait on the read loop's inbox rather than the socket, so submits
+        // and requests don't queue behind the connection lock meanwhile
+        let (requester, inbox) = {
+            let mut connection = self.lock_connection().await;
+            connection.start_read_loop();
+            (connection.requester(), connection.inbox())
+        };
+        let notification = inbox.next_notification().await;
+        self.emit_disconnect(requester.take_disconnect());
+        log_at!(
+            self.verbosity,
+            Category::Jobs,
+            Level::Info,
+            "Received raw notification: {notification:?}"
+        );
+
+        let debounce = *self.notify_debounce.lock().await;
+        let is_job = notification.get("method").and_then(Value::as_str) == Some(MINING_NOTIFY);
+        let mut batch = vec![notification];
+        if let (true, Some(window)) = (is_job, debounce) {
+            let deadline = tokio::time::Instant::now() + window;
+            while let Ok(notification) =
+                tokio::time::timeout_at(deadline, inbox.next_notification()).await
+            {
+                if notification.is_null() {
+                    break;
+                }
+                batch.push(notification);
+            }
+        }
+
+        for notification in reorder::coalesce(batch) {
+            self.dispatch_notification(&notification).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the current mining target
+    async fn get_target(&self) -> Result<MiningTarget, StratumError> {
+        self.job_manager.get_target().await
+    }
+
+    /// Get server information
+    async fn get_server_info(&self) -> Result<ServerInfo, StratumError> {
+        self.server_info
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| StratumError::Protocol("No server info available".into()))
+    }
+
+    /// Reconnect to the mining server
+    async fn reconnect(&mut self) -> Result<(), StratumError> {
+        let mut connection = self.lock_connection().await;
+        self.disconnected(
+            connection
+                .take_disconnect()
+                .unwrap_or(DisconnectReason::LocalClose),
+        );
+        self.emit(StratumEvent::Reconnecting { addr: self.pool() });
+        connection.reconnect_with(&self.reconnect_policy()).await?;
+        self.emit_closed_stats(&mut connection, self.pool());
+        self.connected(connection.is_tls());
+        drop(connection);
+        self.restart_dispatcher().await;
+        Ok(())
+    }
+
+    /// Close the connection
+    async fn close(&mut self) -> Result<(), StratumError> {
+        #[cfg(feature = "watchdog")]
+        self.set_watchdog(None).await;
+        #[cfg(feature = "watchdog")]
+        self.set_keepalive(None).await;
+        self.set_auto_reconnect(false).await;
+        self.stop_auto_submit().await;
+        self.stop_dispatcher().await;
+        self.lock_connection().await.close().await?;
+        self.disconnected(DisconnectReason::LocalClose);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::error::StratumRpcError;
+    use crate::stratum::stats::ShareCounts;
+    use crate::stratum::v1::jobs::TestMiner;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Hand `client` a job `job1` at `ntime`, for shares to validate against
+    async fn receive_job(client: &StratumV1Client, ntime: &str) {
+        client
+            .job_manager
+            .handle_job_notification(&[
+                json!("job1"),
+                json!("4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000"),
+                json!("01000000"),
+                json!("02000000"),
+                json!([]),
+                json!("00000002"),
+                json!("1c2ac4af"),
+                json!(ntime),
+                json!(true),
+            ])
+            .await
+            .unwrap();
+    }
+
+    /// Client of `pool`, connected over its in-memory transport
+    #[cfg(feature = "testing")]
+    async fn connect_mock(pool: &crate::stratum::testing::MockPool) -> StratumV1Client {
+        StratumV1Client::with_connection_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+            TestMiner,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn setup_mock_server() -> (TcpListener, String, u16) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr.ip().to_string(), addr.port())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+
+            let response = json!({
+                "id": 1,
+                "result": [
+                    [
+                        ["mining.set_difficulty", "1"],
+                        ["mining.notify", "1"]
+                    ],
+                    "extranonce1",
+                    10,
+                ],
+                "error": null
+            });
+
+            socket
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let response = client.subscribe().await.unwrap();
+
+        assert_eq!(response.subscription_id, "1");
+        assert_eq!(response.extranonce1, "extranonce1");
+        assert_eq!(response.extranonce2_size, 10);
+
+        let server_info = client.get_server_info().await.unwrap();
+        assert_eq!(server_info.connection_id, "1");
+        assert_eq!(server_info.subscriptions.len(), 2);
+    }
+
+    #[test]
+    fn test_subscribe_formats() {
+        let parse = |result: Value, quirks: &PoolQuirks| {
+            StratumV1Client::parse_subscribe_response(JsonRpcResponse::ok(1, result), quirks)
+        };
+        let tolerant = PoolQuirks::default();
+        let details = json!([["mining.notify", "ae6812eb4cd7735a302a8a9dd95cf71f"]]);
+
+        // Common format
+        let response = parse(json!([details, "08000002", 4]), &tolerant).unwrap();
+        assert_eq!(response.subscription_id, "ae6812eb4cd7735a302a8a9dd95cf71f");
+        assert_eq!(response.extranonce2_size, 4);
+
+        // Without extranonce2_size
+        let response = parse(json!([details, "08000002"]), &tolerant).unwrap();
+        assert_eq!(response.extranonce1, "08000002");
+        assert_eq!(response.extranonce2_size, quirks::DEFAULT_EXTRANONCE2_SIZE);
+
+        // extranonce2_size as a string
+        let response = parse(json!([details, "08000002", "8"]), &tolerant).unwrap();
+        assert_eq!(response.extranonce2_size, 8);
+        assert!(parse(json!([details, "08000002", "8"]), &PoolQuirks::strict()).is_err());
+
+        // The notify subscription id is used even when it isn't listed first
+        let details = json!([["mining.set_difficulty", "d1"], ["mining.notify", "n1"]]);
+        let response = parse(json!([details, "08000002", 4]), &tolerant).unwrap();
+        assert_eq!(response.subscription_id, "n1");
+        assert_eq!(response.subscriptions["mining.set_difficulty"], "d1");
+
+        // A single pair instead of a list of pairs
+        let response = parse(json!([["mining.notify", 7], "08000002", 4]), &tolerant).unwrap();
+        assert_eq!(response.subscription_id, "7");
+
+        let custom = PoolQuirks {
+            default_extranonce2_size: 2,
+            ..PoolQuirks::default()
+        };
+        let response = parse(json!([details, "08000002", null]), &custom).unwrap();
+        assert_eq!(response.extranonce2_size, 2);
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+
+            let response = json!({
+                "id": 1,
+                "result": true,
+                "error": null
+            });
+
+            socket
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let response = client.authorize("user", "pass").await.unwrap();
+
+        assert!(response.authorized);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_authorize_password_difficulty() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new();
+        let mut client = connect_mock(&pool).await;
+        client.authorize("user", "pass").await.unwrap();
+        assert_eq!(client.suggested_difficulty().await, None);
+
+        let response = client.authorize("user", "d=512,c=BTC").await.unwrap();
+        assert!(response.authorized);
+        assert_eq!(client.suggested_difficulty().await, Some(512.0));
+    }
+
+    #[tokio::test]
+    async fn test_replay_from_capture() {
+        let (listener, host, port) = setup_mock_server().await;
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let response = json!({
+                "id": 1,
+                "result": [[["mining.notify", "sub1"]], "abcd0001", 4],
+                "error": null
+            });
+            let notify = json!({
+                "id": null,
+                "method": "mining.notify",
+                "params": [
+                    "job1",
+                    "00000000000000000000000000000000000000000000000000000000deadbeef",
+                    "01000000", "02000000", [], "20000000", "1d00ffff", "60509af9", true
+                ]
+            });
+            socket
+                .write_all(format!("{}\n{}\n", response, notify).as_bytes())
+                .await
+                .unwrap();
+        });
+
+        // Record a session against the mock pool
+        let recorder = CaptureRecorder::new();
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.set_recorder(Some(recorder.clone())).await;
+        client.subscribe().await.unwrap();
+        client.handle_notifications().await.unwrap();
+        let capture = Capture::from_jsonl(&recorder.capture().to_jsonl()).unwrap();
+        assert_eq!(capture.records.len(), 3);
+
+        // Replaying it reproduces the same session
+        let mut replayed = StratumV1Client::from_capture(&capture, 10.0, TestMiner)
+            .await
+            .unwrap();
+        let subscription = replayed.subscribe().await.unwrap();
+        assert_eq!(subscription.extranonce1, "abcd0001");
+        replayed.handle_notifications().await.unwrap();
+        let job = replayed.get_current_job().await.unwrap().unwrap();
+        assert_eq!(job.job_id, "job1");
+    }
+
+    #[tokio::test]
+    async fn test_authorize_after_notifications() {
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+
+            // The pool sends the difficulty before answering the authorize
+            let difficulty = json!({"id": null, "method": "mining.set_difficulty", "params": [8]});
+            socket
+                .write_all(format!("{}\n", difficulty).as_bytes())
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let response = json!({"id": 1, "result": true, "error": null});
+            socket
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        assert_eq!(client.auth_state().await, AuthState::Unauthorized);
+        let response = client.authorize("user", "x").await.unwrap();
+        assert!(response.authorized);
+        assert_eq!(client.auth_state().await, AuthState::Authorized);
+
+        // The interleaved notification is still delivered
+        let notification = client.connection.lock().await.read_notification().await;
+        assert_eq!(notification.unwrap()["params"][0], 8);
+    }
+
+    #[tokio::test]
+    async fn test_workers_share_connection() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+        let (submitted_tx, mut submitted) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(read_half).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let result = match request["method"].as_str().unwrap() {
+                    MINING_AUTHORIZE => json!(request["params"][0] != "mallory"),
+                    MINING_SUBMIT => {
+                        submitted_tx.send(request["params"][0].clone()).unwrap();
+                        json!(true)
+                    }
+                    _ => json!(true),
+                };
+                let response = json!({"id": request["id"], "result": result, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.authorize("gateway", "x").await.unwrap();
+        assert!(
+            client
+                .authorize_worker("alice", "x")
+                .await
+                .unwrap()
+                .authorized
+        );
+        assert!(
+            client
+                .authorize_worker("bob", "x")
+                .await
+                .unwrap()
+                .authorized
+        );
+        assert!(
+            !client
+                .authorize_worker("mallory", "x")
+                .await
+                .unwrap()
+                .authorized
+        );
+        assert_eq!(client.workers().await, vec!["alice", "bob"]);
+        assert_eq!(client.auth_state().await, AuthState::Authorized);
+
+        receive_job(&client, "504e86b9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "504e86b9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(client.submit_share_as("bob", share.clone()).await.unwrap());
+        assert_eq!(submitted.recv().await.unwrap(), "bob");
+        let share = Share {
+            nonce: "00000001".into(),
+            ..share
+        };
+        assert!(client.submit_share(share.clone()).await.unwrap());
+        assert_eq!(submitted.recv().await.unwrap(), "gateway");
+        assert!(matches!(
+            client.submit_share_as("mallory", share).await,
+            Err(StratumError::Config(_))
+        ));
+
+        let devices = client.session_snapshot().await.devices;
+        assert_eq!(devices["bob"].shares_accepted, 1);
+        assert!(!devices.contains_key("mallory"));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_rejected() {
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let response =
+                json!({"id": 1, "result": null, "error": [24, "Unauthorized worker", null]});
+            socket
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let response = client.authorize("user", "x").await.unwrap();
+        assert!(!response.authorized);
+        assert_eq!(response.message.as_deref(), Some("Unauthorized worker"));
+        assert_eq!(
+            response.reject_reason,
+            Some(AuthRejectReason::UnknownWorker)
+        );
+        assert_eq!(client.auth_state().await, AuthState::Rejected);
+    }
+
+    #[tokio::test]
+    async fn test_authorize_timeout() {
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.set_auth_timeout(Duration::from_millis(100)).await;
+        assert!(client.authorize("user", "x").await.is_err());
+        assert_eq!(client.auth_state().await, AuthState::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn test_connection_lifecycle_events() {
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            // The first connection is closed by the pool right away
+            let (socket, _) = listener.accept().await.unwrap();
+            drop(socket);
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let mut events = client.events();
+        client.handle_notifications().await.unwrap();
+        client.reconnect().await.unwrap();
+        client.close().await.unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert!(matches!(
+            received.as_slice(),
+            [
+                StratumEvent::Disconnected {
+                    reason: DisconnectReason::PeerClosed
+                },
+                StratumEvent::Reconnecting { .. },
+                StratumEvent::ConnectionClosedStats { .. },
+                StratumEvent::Connected { tls: false, .. },
+                StratumEvent::Disconnected {
+                    reason: DisconnectReason::LocalClose
+                },
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_idle_probe_reconnects() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+        let subscribe_result = json!([[["mining.notify", "sub1"]], "abcd0001", 4]);
+
+        tokio::spawn(async move {
+            // The first connection subscribes and is then closed by the pool
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let response = json!({"id": 1, "result": subscribe_result, "error": null});
+            writer
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+            drop((reader, writer));
+
+            // The client reconnects, subscribes again and submits
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            for result in [subscribe_result, json!(true)] {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                let id: Value = serde_json::from_str::<Value>(&line).unwrap()["id"].clone();
+                let response = json!({"id": id, "result": result, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.subscribe().await.unwrap();
+        client.set_idle_probe(Some(Duration::ZERO)).await;
+        // Give the pool time to close the connection
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        receive_job(&client, "60509af9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        // The probe reconnects, which makes the share's job stale
+        assert!(!client.submit_share(share.clone()).await.unwrap());
+        receive_job(&client, "60509b10").await;
+        let share = Share {
+            ntime: "60509b10".into(),
+            ..share
+        };
+        assert!(client.submit_share(share).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_share_outcome_events() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            for (result, error) in [
+                (json!(true), json!(null)),
+                (json!(null), json!([23, "Low difficulty share", null])),
+            ] {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let response = json!({"id": request["id"], "result": result, "error": error});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        receive_job(&client, "60509af9").await;
+        let mut events = client.events();
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(client
+            .submit_share_from(Some("asic-0"), share.clone())
+            .await
+            .unwrap());
+        let share = Share {
+            nonce: "00000001".into(),
+            ..share
+        };
+        let err = client.submit_share(share).await.unwrap_err();
+        assert!(matches!(
+            err.root(),
+            StratumError::Rpc {
+                error: StratumRpcError::LowDifficulty,
+                ..
+            }
+        ));
+        assert_eq!(err.contexts()[0].operation, MINING_SUBMIT);
+
+        match events.try_recv().unwrap() {
+            StratumEvent::ShareAccepted { device, job_id, .. } => {
+                assert_eq!(device.as_deref(), Some("asic-0"));
+                assert_eq!(job_id, "job1");
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        match events.try_recv().unwrap() {
+            StratumEvent::ShareRejected {
+                device,
+                reason,
+                reject_reason,
+                job_shares,
+                ..
+            } => {
+                assert_eq!(device, None);
+                assert!(reason.unwrap().contains("Low difficulty share"));
+                assert_eq!(reject_reason, RejectReason::LowDifficulty);
+                assert_eq!((job_shares.accepted, job_shares.rejected), (1, 1));
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+
+        let shares = client.session_snapshot().await.shares;
+        assert_eq!(
+            shares,
+            ShareCounts {
+                accepted: 1,
+                rejected: 1,
+                low_difficulty: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(client.job_shares("job1").await, Some(shares));
+    }
+
+    #[tokio::test]
+    async fn test_submit_retried_on_try_again() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            for (result, error) in [
+                (json!(null), json!([-3, "Busy, try again", null])),
+                (json!(true), json!(null)),
+                (json!(null), json!([-3, "Busy, try again", null])),
+                (json!(null), json!([-3, "Busy, try again", null])),
+            ] {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let response = json!({"id": request["id"], "result": result, "error": error});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.set_submit_retry_policy(SubmitRetryPolicy::new().retry(
+            -3,
+            Duration::from_millis(10),
+            2,
+        ));
+        receive_job(&client, "60509af9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(client.submit_share(share.clone()).await.unwrap());
+        // Out of attempts, the last error is returned
+        let share = Share {
+            nonce: "00000001".into(),
+            ..share
+        };
+        let err = client.submit_share(share).await.unwrap_err();
+        assert!(err.to_string().contains("try again"), "{}", err);
+        let shares = client.session_snapshot().await.shares;
+        assert_eq!((shares.accepted, shares.rejected), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_version_rolling() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+        let (submitted_tx, mut submitted) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                line.clear();
+                let result = match request["method"].as_str().unwrap() {
+                    MINING_CONFIGURE => {
+                        assert_eq!(request["params"][0], json!(["version-rolling"]));
+                        json!({"version-rolling": true, "version-rolling.mask": "1fffe000"})
+                    }
+                    _ => {
+                        submitted_tx.send(request["params"].clone()).unwrap();
+                        json!(true)
+                    }
+                };
+                let response = json!({"id": request["id"], "result": result, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let granted = client
+            .configure_version_rolling(0xffffffff, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(granted.mask, 0x1fffe000);
+        assert_eq!(granted.min_bit_count, 2);
+        assert_eq!(client.version_rolling().await, Some(granted));
+        let configure = client.get_server_info().await.unwrap().configure.unwrap();
+        assert!(configure.supported && configure.asic_boost());
+        assert_eq!(configure.accepted, [VERSION_ROLLING]);
+        assert!(configure.ignored.is_empty());
+        assert_eq!(configure.version_rolling, Some(granted));
+
+        receive_job(&client, "60509af9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: Some("00002000".into()),
+        };
+        assert!(client.submit_share(share.clone()).await.unwrap());
+        assert_eq!(submitted.recv().await.unwrap()[5], "00002000");
+
+        // Bits outside the mask are never sent
+        let outside = Share {
+            version_bits: Some("80000000".into()),
+            ..share
+        };
+        assert!(matches!(
+            client.submit_share(outside).await,
+            Err(StratumError::InvalidJob(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_motd() {
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            for message in ["Maintenance at 12:00 UTC", "Second message"] {
+                let notification =
+                    json!({"id": null, "method": "client.show_message", "params": [message]});
+                socket
+                    .write_all(format!("{}\n", notification).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let mut events = client.events();
+        client.handle_notifications().await.unwrap();
+        client.handle_notifications().await.unwrap();
+
+        let info = client.get_server_info().await.unwrap();
+        assert_eq!(info.motd.as_deref(), Some("Maintenance at 12:00 UTC"));
+        let mut messages = vec![];
+        while let Ok(event) = events.try_recv() {
+            match event {
+                StratumEvent::PoolMessage { message, .. } => messages.push(message),
+                StratumEvent::Motd { message } => assert_eq!(message, "Maintenance at 12:00 UTC"),
+                other => panic!("Unexpected event: {other:?}"),
+            }
+        }
+        assert_eq!(messages, ["Maintenance at 12:00 UTC", "Second message"]);
+    }
+
+    #[tokio::test]
+    async fn test_recommend_difficulty_in_pool_units() {
+        use crate::stratum::stats::HASHES_PER_DIFF1_SHARE;
+
+        let (_listener, host, port) = setup_mock_server().await;
+        let client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let hashrate = 1024.0 * HASHES_PER_DIFF1_SHARE / 10.0;
+        let interval = Duration::from_secs(10);
+        assert_eq!(
+            client.recommend_difficulty(hashrate, interval),
+            Some(1024.0)
+        );
+
+        // Scrypt pools count 65536 times Bitcoin's difficulty
+        client.set_difficulty_multiplier(65536.0).await.unwrap();
+        assert_eq!(
+            client.recommend_difficulty(hashrate, interval),
+            Some(1024.0 * 65536.0)
+        );
+        assert_eq!(client.recommend_difficulty(0.0, interval), None);
+    }
+
+    #[tokio::test]
+    async fn test_suggest_difficulty_and_target() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        // The pool never answers the suggestions
+        let pool = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut lines = BufReader::new(socket).lines();
+            let mut requests = vec![];
+            for _ in 0..2 {
+                let line = lines.next_line().await.unwrap().unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                requests.push((request["method"].clone(), request["params"].clone()));
+            }
+            requests
+        });
+
+        let client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        assert!(matches!(
+            client.suggest_difficulty(0.0).await,
+            Err(StratumError::Config(_))
+        ));
+        assert!(matches!(
+            client.suggest_target("ffff").await,
+            Err(StratumError::Config(_))
+        ));
+
+        let target = "00000000ffff0000000000000000000000000000000000000000000000000000";
+        tokio::time::timeout(Duration::from_secs(1), async {
+            client.suggest_difficulty(1024.0).await.unwrap();
+            client.suggest_target(target).await.unwrap();
+        })
+        .await
+        .unwrap();
+        assert_eq!(client.suggested_difficulty().await, Some(1.0));
+
+        assert_eq!(
+            pool.await.unwrap(),
+            [
+                (json!(MINING_SUGGEST_DIFFICULTY), json!([1024.0])),
+                (json!(MINING_SUGGEST_TARGET), json!([target])),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_version() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        let pool = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let request = json!({"id": 7, "method": protocol::CLIENT_GET_VERSION, "params": []});
+            writer
+                .write_all(format!("{}\n", request).as_bytes())
+                .await
+                .unwrap();
+            let mut line = String::new();
+            BufReader::new(read_half)
+                .read_line(&mut line)
+                .await
+                .unwrap();
+            serde_json::from_str::<Value>(&line).unwrap()
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.set_user_agent("test-miner/0.1");
+        client.handle_notifications().await.unwrap();
+
+        let response = pool.await.unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"], "test-miner/0.1");
+        assert!(response["error"].is_null());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_extensions() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new().answer_with("mining.custom_call", json!("ok"));
+        let mut extensions = Extensions::new();
+        extensions
+            .on("client.custom_thing", |params: &[Value]| {
+                Ok(Some(json!(params.len())))
+            })
+            .request("mining.custom_call", |args| Ok(vec![args]));
+        let mut client = connect_mock(&pool).await;
+        client.set_extensions(extensions);
+        pool.request(7, "client.custom_thing", vec![json!(1), json!(2)]);
+        client.handle_notifications().await.unwrap();
+
+        let response = client
+            .call_extension("mining.custom_call", json!("hello"))
+            .await
+            .unwrap();
+        assert_eq!(response.result, Some(json!("ok")));
+        assert!(client
+            .call_extension("mining.unregistered", json!(null))
+            .await
+            .is_err());
+
+        let received = pool.requests();
+        let (response, call) = (&received[0], &received[1]);
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"], 2);
+        assert_eq!(call["method"], "mining.custom_call");
+        assert_eq!(call["params"], json!(["hello"]));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_extension_failures() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new();
+        let mut extensions = Extensions::new();
+        extensions
+            .on("client.failing", |_: &[Value]| {
+                Err(StratumError::Protocol("Handler broke".into()))
+            })
+            .on("client.silent", |_: &[Value]| Ok(None));
+        let mut client = connect_mock(&pool).await;
+        client.set_extensions(extensions);
+        for (id, method) in [
+            (1, "client.failing"),
+            (2, "client.silent"),
+            (3, "client.unknown"),
+        ] {
+            pool.request(id, method, vec![]);
+            client.handle_notifications().await.unwrap();
+        }
+        // The last answer may still be on its way to the pool
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while pool.requests().len() < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let received = pool.requests();
+        let errors: Vec<_> = received
+            .iter()
+            .map(|r| (&r["id"], &r["error"][0]))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (&json!(1), &json!(-32603)),
+                (&json!(2), &json!(-32601)),
+                (&json!(3), &json!(-32601)),
+            ]
+        );
+        assert!(client
+            .last_error()
+            .unwrap()
+            .to_string()
+            .contains("Handler broke"));
+    }
+
+    #[tokio::test]
+    async fn test_set_extranonce() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                line.clear();
+                let mut reply = match request["method"].as_str().unwrap() {
+                    MINING_SUBSCRIBE => json!({"id": request["id"], "result": [[["mining.notify", "ae6812eb4cd7735a302a8a9dd95cf71f"]], "08000002", 4], "error": null}).to_string(),
+                    MINING_EXTRANONCE_SUBSCRIBE => json!({"id": request["id"], "result": true, "error": null}).to_string(),
+                    _ => unreachable!(),
+                };
+                if request["method"] == MINING_EXTRANONCE_SUBSCRIBE {
+                    let notify = json!({"id": null, "method": "mining.notify", "params": ["job1", "4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000", "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff20020862062f503253482f04b8864e5008", "072f736c7573682f000000000100f2052a010000001976a914d23fcdf86f7e756a64a7a9688ef9903327048ed988ac00000000", [], "00000002", "1c2ac4af", "504e86b9", false]});
+                    let set_extranonce = json!({"id": null, "method": MINING_SET_EXTRANONCE, "params": ["08000003", 2]});
+                    reply = format!("{}\n{}\n{}", reply, notify, set_extranonce);
+                }
+                writer
+                    .write_all(format!("{}\n", reply).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.subscribe().await.unwrap();
+        assert!(client.extranonce_subscribe().await.unwrap());
+
+        client.handle_notifications().await.unwrap();
+        assert!(client
+            .job_manager
+            .get_current_job()
+            .await
+            .unwrap()
+            .is_some());
+
+        client.handle_notifications().await.unwrap();
+        let extranonce = client.job_manager.extranonce().await.unwrap();
+        assert_eq!(extranonce.extranonce1, "08000003");
+        assert_eq!(extranonce.extranonce2_size, 2);
+        let subscription = client.subscription.lock().await.clone().unwrap();
+        assert_eq!(subscription.extranonce1, "08000003");
+        // The job was built on the previous extranonce1
+        assert!(client
+            .job_manager
+            .get_current_job()
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resubscribe_adopts_new_extranonce1() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            for extranonce1 in ["08000002", "08000003"] {
+                let (socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let (read_half, mut writer) = socket.into_split();
+                    let mut lines = BufReader::new(read_half).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let request: Value = serde_json::from_str(&line).unwrap();
+                        let mut reply = json!({"id": request["id"], "result": [[["mining.notify", "1"]], extranonce1, 4], "error": null}).to_string();
+                        // The new session's first job follows the reply right away
+                        if extranonce1 == "08000003" {
+                            let notify = json!({"id": null, "method": "mining.notify", "params": ["job2", "4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000", "01000000", "02000000", [], "00000002", "1c2ac4af", "504e86b9", true]});
+                            reply = format!("{}\n{}", reply, notify);
+                        }
+                        writer
+                            .write_all(format!("{}\n", reply).as_bytes())
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let mut client = StratumV1Client::new(host.clone(), port, TestMiner)
+            .await
+            .unwrap();
+        client.subscribe().await.unwrap();
+        client.start_dispatcher().await;
+
+        client
+            .switch_pool(&failover::PoolEndpoint::new(host, port))
+            .await
+            .unwrap();
+        let job = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                if let Some(job) = client.job_manager.get_current_job().await.unwrap() {
+                    return job;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(job.job_id, "job2");
+        assert_eq!(
+            client.job_manager.extranonce().await.unwrap().extranonce1,
+            "08000003"
+        );
+        client.stop_dispatcher().await;
+    }
+
+    #[tokio::test]
+    async fn test_share_from_previous_session_dropped() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (read_half, mut writer) = socket.into_split();
+                    let mut lines = BufReader::new(read_half).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let request: Value = serde_json::from_str(&line).unwrap();
+                        let response = json!({"id": request["id"], "result": [[["mining.notify", "1"]], "08000002", 4], "error": null});
+                        writer
+                            .write_all(format!("{}\n", response).as_bytes())
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let mut client = StratumV1Client::new(host.clone(), port, TestMiner)
+            .await
+            .unwrap();
+        client.subscribe().await.unwrap();
+        client
+            .job_manager
+            .handle_job_notification(&[
+                json!("job1"),
+                json!("4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000"),
+                json!("01000000"),
+                json!("02000000"),
+                json!([]),
+                json!("00000002"),
+                json!("1c2ac4af"),
+                json!("504e86b9"),
+                json!(true),
+            ])
+            .await
+            .unwrap();
+
+        client
+            .switch_pool(&failover::PoolEndpoint::new(host, port))
+            .await
+            .unwrap();
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "504e86b9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        let mut events = client.events();
+        assert!(!client.submit_share(share).await.unwrap());
+        assert_eq!(client.session_snapshot().await.stale_results_dropped, 1);
+        let discarded =
+            std::iter::from_fn(|| events.try_recv().ok()).find_map(|event| match event {
+                StratumEvent::ShareDiscarded {
+                    job_id,
+                    reject_reason,
+                    ..
+                } => Some((job_id, reject_reason)),
+                _ => None,
+            });
+        assert_eq!(discarded, Some(("job1".into(), RejectReason::Stale)));
+    }
+
+    #[tokio::test]
+    async fn test_share_past_submit_window_refused() {
+        let (listener, host, port) = setup_mock_server().await;
+        tokio::spawn(async move {
+            let _socket = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.set_submit_window(Some(SubmitWindow {
+            window: Duration::ZERO,
+            on_late: LateShare::Refuse,
+        }));
+        let mut params = vec![
+            json!("job1"),
+            json!("4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000"),
+            json!("01000000"),
+            json!("02000000"),
+            json!([]),
+            json!("00000002"),
+            json!("1c2ac4af"),
+            json!("504e86b9"),
+            json!(false),
+        ];
+        client
+            .job_manager
+            .handle_job_notification(&params)
+            .await
+            .unwrap();
+        params[0] = json!("job2");
+        params[8] = json!(true);
+        client
+            .job_manager
+            .handle_job_notification(&params)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut events = client.events();
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "504e86b9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(!client.submit_share(share).await.unwrap());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            StratumEvent::ShareDiscarded {
+                reject_reason: RejectReason::Stale,
+                ..
+            }
+        ));
+    }
+
+    /// Pool answering requests, sending `client.reconnect` with `redirect`
+    /// params after the subscription if given
+    async fn redirecting_pool(listener: TcpListener, redirect: Option<Value>) {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read_half, mut writer) = socket.into_split();
+        let mut lines = BufReader::new(read_half).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let result = match request["method"].as_str().unwrap() {
+                MINING_SUBSCRIBE => json!([[["mining.notify", "1"]], "08000002", 4]),
+                _ => json!(true),
+            };
+            let mut reply =
+                json!({"id": request["id"], "result": result, "error": null}).to_string();
+            if let (MINING_SUBSCRIBE, Some(params)) =
+                (request["method"].as_str().unwrap(), &redirect)
+            {
+                let reconnect = json!({"id": null, "method": CLIENT_RECONNECT, "params": params});
+                reply = format!("{}\n{}", reply, reconnect);
+            }
+            writer
+                .write_all(format!("{}\n", reply).as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_reconnect() {
+        let (first, host, port) = setup_mock_server().await;
+        let (second, _, second_port) = setup_mock_server().await;
+        tokio::spawn(redirecting_pool(
+            first,
+            Some(json!(["127.0.0.1", second_port, 0])),
+        ));
+        tokio::spawn(redirecting_pool(second, None));
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let mut events = client.events();
+        client.subscribe().await.unwrap();
+        client.handle_notifications().await.unwrap();
+
+        assert_eq!(client.pool(), format!("127.0.0.1:{}", second_port));
+        let events: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            StratumEvent::PoolRedirect { followed: true, to, .. } if *to == format!("127.0.0.1:{}", second_port)
+        )));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            StratumEvent::Disconnected {
+                reason: DisconnectReason::PoolReconnect
+            }
+        )));
+        // The session was subscribed again on the new pool
+        assert!(client.subscription.lock().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_client_reconnect_outside_allowlist() {
+        let (listener, host, port) = setup_mock_server().await;
+        tokio::spawn(redirecting_pool(
+            listener,
+            Some(json!(["evil.example.com", 3333])),
+        ));
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let mut events = client.events();
+        client.subscribe().await.unwrap();
+        client.handle_notifications().await.unwrap();
+
+        assert_eq!(client.pool(), format!("127.0.0.1:{}", port));
+        let redirect = std::iter::from_fn(|| events.try_recv().ok())
+            .find(|event| matches!(event, StratumEvent::PoolRedirect { .. }));
+        assert!(matches!(
+            redirect,
+            Some(StratumEvent::PoolRedirect {
+                followed: false,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+
+            for notification in [
+                json!({"id": null, "method": "mining.set_difficulty", "params": [2]}),
+                json!({"id": null, "method": "mining.notify", "params": ["job1", "4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000", "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff20020862062f503253482f04b8864e5008", "072f736c7573682f000000000100f2052a010000001976a914d23fcdf86f7e756a64a7a9688ef9903327048ed988ac00000000", [], "00000002", "1c2ac4af", "504e86b9", true]}),
+            ] {
+                writer
+                    .write_all(format!("{}\n", notification).as_bytes())
+                    .await
+                    .unwrap();
+            }
+
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                line.clear();
+                let response = json!({"id": request["id"], "result": true, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let mut jobs = client.jobs();
+        let targets = client.targets();
+        client.start_dispatcher().await;
+
+        // Jobs arrive without handle_notifications being called
+        let job = tokio::time::timeout(Duration::from_secs(5), jobs.wait_for(Option::is_some))
+            .await
+            .unwrap()
+            .unwrap()
+            .clone()
+            .unwrap();
+        assert_eq!(job.job_id, "job1");
+        assert_eq!(job.target.as_ref().unwrap().difficulty, 2.0);
+        assert_eq!(targets.borrow().as_ref().unwrap().difficulty, 2.0);
+
+        // Requests still get their responses
+        let auth = client.authorize("worker", "x").await.unwrap();
+        assert!(auth.authorized);
+
+        client.close().await.unwrap();
+        assert!(client.dispatcher.lock().await.is_none());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_notify_debounce() {
+        use crate::stratum::testing::MockPool;
+
+        let pool =
+            MockPool::new().subscribe_result(json!([[["mining.notify", "sub1"]], "08000002", 4]));
+        let mut client = connect_mock(&pool).await;
+        client.subscribe().await.unwrap();
+
+        // A burst of a clean job and an update of it for the same block
+        let prev_hash = "00000000000000000000000000000000000000000000000000000000deadbeef";
+        for (job_id, clean) in [("jobA", true), ("jobB", false)] {
+            pool.notify(vec![
+                json!(job_id),
+                json!(prev_hash),
+                json!("01000000"),
+                json!("02000000"),
+                json!([]),
+                json!("00000002"),
+                json!("1c2ac4af"),
+                json!("504e86b9"),
+                json!(clean),
+            ]);
+        }
+
+        receive_job(&client, "504e86b9").await;
+        let window = Duration::from_millis(500);
+        client.set_notify_debounce(Some(window)).await;
+        let mut reader = client.clone();
+        let handled = tokio::spawn(async move { reader.handle_notifications().await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // A submit during the window doesn't wait for it to close
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "504e86b9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        let started = Instant::now();
+        assert!(client.submit_share(share).await.unwrap());
+        assert!(started.elapsed() < window / 2, "{:?}", started.elapsed());
+
+        handled.await.unwrap().unwrap();
+        let job = client.get_current_job().await.unwrap().unwrap();
+        assert_eq!(job.job_id, "jobB");
+        assert_eq!(job.clean_jobs, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_submit_latency_under_notification_load() {
+        check_submit_latency(false).await;
+    }
+
+    #[tokio::test]
+    async fn test_submit_latency_with_manual_notification_loop() {
+        check_submit_latency(true).await;
+    }
+
+    /// Submits must overlap while notifications are read, by the dispatcher
+    /// or by a task calling `handle_notifications()` in a loop
+    async fn check_submit_latency(manual_loop: bool) {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        const POOL_LATENCY: Duration = Duration::from_millis(100);
+        const SUBMITS: usize = 5;
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, writer) = socket.into_split();
+            let writer = Arc::new(Mutex::new(writer));
+
+            // Stream difficulty changes for the whole test. The manual loop
+            // gets them slowly, so it spends most of the test waiting for one
+            let interval = match manual_loop {
+                true => Duration::from_millis(500),
+                false => Duration::from_millis(1),
+            };
+            let notifier = writer.clone();
+            tokio::spawn(async move {
+                for i in 0u64.. {
+                    let notification = json!({"id": null, "method": "mining.set_difficulty", "params": [1 + i % 2]});
+                    let written = notifier
+                        .lock()
+                        .await
+                        .write_all(format!("{}\n", notification).as_bytes())
+                        .await;
+                    if written.is_err() {
+                        break;
+                    }
+                    tokio::time::sleep(interval).await;
+                }
+            });
+
+            // Answer every submit after the same delay, independently
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                line.clear();
+                let writer = writer.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(POOL_LATENCY).await;
+                    let response = json!({"id": request["id"], "result": true, "error": null});
+                    let _ = writer
+                        .lock()
+                        .await
+                        .write_all(format!("{}\n", response).as_bytes())
+                        .await;
+                });
+            }
+        });
+
+        let client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        if manual_loop {
+            let mut reader = client.clone();
+            tokio::spawn(async move { while reader.handle_notifications().await.is_ok() {} });
+        } else {
+            client.start_dispatcher().await;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        receive_job(&client, "60509af9").await;
+        let started = Instant::now();
+        let submits: Vec<_> = (0..SUBMITS)
+            .map(|i| {
+                let mut client = client.clone();
+                tokio::spawn(async move {
+                    let share = Share {
+                        job_id: "job1".into(),
+                        extranonce2: "00000000".into(),
+                        ntime: "60509af9".into(),
+                        nonce: format!("{:08x}", i),
+                        version_bits: None,
+                    };
+                    client.submit_share(share).await
+                })
+            })
+            .collect();
+        for submit in submits {
+            assert!(submit.await.unwrap().unwrap());
+        }
+
+        // Submits overlap instead of queueing behind each other's round trip
+        assert!(
+            started.elapsed() < POOL_LATENCY * (SUBMITS as u32 - 2),
+            "{SUBMITS} submits took {:?}",
+            started.elapsed()
+        );
+
+        let snapshot = client.session_snapshot().await;
+        let contention = snapshot.lock_contention;
+        assert!(contention.site(LockSite::Connection).acquisitions() >= SUBMITS as u64);
+        assert!(contention.site(LockSite::Writer).acquisitions() >= SUBMITS as u64);
+        assert!(contention.site(LockSite::JobState).acquisitions() > 0);
+        #[cfg(feature = "profiling")]
+        {
+            let submit = snapshot.profile.scope(Scope::Submit);
+            assert!(submit.calls >= SUBMITS as u64);
+            assert!(submit.max >= POOL_LATENCY);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watchdog() {
+        use crate::stratum::watchdog::{StallReason, WatchdogConfig};
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        // Pool that never sends a job, accepting reconnects
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (read_half, mut writer) = socket.into_split();
+                    let mut reader = BufReader::new(read_half);
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        let request: Value = serde_json::from_str(&line).unwrap();
+                        line.clear();
+                        let response = json!({"id": request["id"], "result": true, "error": null});
+                        let _ = writer.write_all(format!("{}\n", response).as_bytes()).await;
+                    }
+                });
+            }
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let mut events = client.events();
+        client
+            .set_watchdog(Some(WatchdogConfig {
+                check_interval: Duration::from_millis(20),
+                job_stall: Duration::from_millis(50),
+                ..Default::default()
+            }))
+            .await;
+
+        let mut restarted = false;
+        let mut reconnected = false;
+        while !(restarted && reconnected) {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            match event {
+                StratumEvent::WatchdogRestart {
+                    reason: StallReason::NoJobDispatched { since },
+                } => {
+                    assert!(since > Duration::from_millis(50));
+                    restarted = true;
+                }
+                StratumEvent::Connected { .. } if restarted => reconnected = true,
+                _ => {}
+            }
+        }
+
+        client.close().await.unwrap();
+        assert!(client.watchdog.lock().await.is_none());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_keepalive_reconnects_stale_connection() {
+        use crate::stratum::testing::MockPool;
+        use crate::stratum::watchdog::KeepaliveConfig;
+
+        let pool = MockPool::new();
+        let mut client = connect_mock(&pool).await;
+        client.subscribe().await.unwrap();
+        client.authorize("rig1", "x").await.unwrap();
+        // The connection goes silent, without closing
+        pool.silence();
+        let mut events = client.events();
+        client
+            .set_keepalive(Some(KeepaliveConfig {
+                idle: Duration::from_millis(50),
+                timeout: Duration::from_millis(100),
+            }))
+            .await;
+
+        let mut stale = false;
+        let mut reconnected = false;
+        while !(stale && reconnected) {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            match event {
+                StratumEvent::ConnectionStale { idle } => {
+                    assert!(idle >= Duration::from_millis(50));
+                    stale = true;
+                }
+                StratumEvent::Connected { .. } if stale => reconnected = true,
+                _ => {}
+            }
+        }
+
+        // The new connection answers, so no further reconnects happen
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        while let Ok(event) = events.try_recv() {
+            assert!(!matches!(event, StratumEvent::ConnectionStale { .. }));
+        }
+        client.close().await.unwrap();
+        assert!(client.keepalive.lock().await.is_none());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_pause_keeps_session_alive() {
+        use crate::stratum::testing::MockPool;
+        use crate::stratum::watchdog::KeepaliveConfig;
+
+        let pool = MockPool::new();
+        let mut client = connect_mock(&pool).await;
+        client.pause().await;
+        assert!(client.keepalive.lock().await.is_some());
+        client.resume().await;
+        assert!(client.keepalive.lock().await.is_none());
+
+        // A configured keepalive is left running
+        client.set_keepalive(Some(KeepaliveConfig::default())).await;
+        client.pause().await;
+        client.resume().await;
+        assert!(client.keepalive.lock().await.is_some());
+        client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_throttle_resume_keeps_manual_pause() {
+        use crate::stratum::throttle::DeviceStats;
+
+        /// Reports a cool device, so the policy below always asks to resume
+        #[derive(Clone)]
+        struct CoolMiner;
+
+        #[async_trait]
+        impl Miner for CoolMiner {
+            async fn on_job_received(
+                &self,
+                _job: Arc<MiningJob>,
+            ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+                // Stays paused, never finds a share
+                std::future::pending().await
+            }
+
+            async fn device_stats(&self) -> Option<DeviceStats> {
+                Some(DeviceStats {
+                    temperature_c: Some(40.0),
+                    ..Default::default()
+                })
+            }
+        }
+
+        struct AlwaysResume(Arc<AtomicBool>);
+
+        impl ThrottlePolicy for AlwaysResume {
+            fn evaluate(&mut self, _stats: &DeviceStats, paused: bool) -> ThrottleAction {
+                self.0.store(true, Ordering::SeqCst);
+                assert!(!paused, "the manual pause isn't the policy's");
+                ThrottleAction::Resume
+            }
+
+            fn interval(&self) -> Duration {
+                Duration::from_millis(10)
+            }
+        }
+
+        let (_listener, host, port) = setup_mock_server().await;
+        let client = StratumV1Client::new(host, port, CoolMiner).await.unwrap();
+        client.pause().await;
+
+        let evaluated = Arc::new(AtomicBool::new(false));
+        client
+            .set_throttle_policy(Some(Box::new(AlwaysResume(evaluated.clone()))))
+            .await;
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !evaluated.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(client.is_paused());
+
+        client.set_throttle_policy(None).await;
+        client.resume().await;
+        assert!(!client.is_paused());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_auto_submit_rolled_share() {
+        use crate::stratum::miner::ShareSink;
+        use crate::stratum::testing::MockPool;
+
+        /// Reports one share at its slot and one at a rolled extranonce2
+        #[derive(Clone)]
+        struct RollingMiner;
+
+        #[async_trait]
+        impl Miner for RollingMiner {
+            async fn on_job_received(
+                &self,
+                job: Arc<MiningJob>,
+            ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+                Ok((1, job))
+            }
+
+            async fn mine(&self, job: Arc<MiningJob>, shares: ShareSink) {
+                shares.submit(1);
+                shares.submit_rolled(2, "0000ff00", &job.ntime, None);
+                std::future::pending::<()>().await;
+            }
+        }
+
+        let pool = MockPool::new();
+        let mut client = StratumV1Client::with_connection_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+            RollingMiner,
+        )
+        .await
+        .unwrap();
+        client.subscribe().await.unwrap();
+        client.authorize("rig1", "x").await.unwrap();
+        client.start_auto_submit().await.unwrap();
+        client.start_dispatcher().await;
+        pool.set_difficulty(1e-10);
+        pool.notify(MockPool::job("job1"));
+
+        let submits = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let submits: Vec<Value> = pool
+                    .requests()
+                    .into_iter()
+                    .filter(|request| request["method"] == MINING_SUBMIT)
+                    .map(|request| request["params"].clone())
+                    .collect();
+                if submits.len() == 2 {
+                    return submits;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            submits[0],
+            json!(["rig1", "job1", "00000000", "60509af9", "00000001"])
+        );
+        assert_eq!(
+            submits[1],
+            json!(["rig1", "job1", "0000ff00", "60509af9", "00000002"])
+        );
+        client.stop_auto_submit().await;
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_auto_submit_version_bits() {
+        use crate::stratum::miner::ShareSink;
+        use crate::stratum::testing::MockPool;
+
+        /// Reports a share found with rolled version bits
+        #[derive(Clone)]
+        struct AsicBoostMiner;
+
+        #[async_trait]
+        impl Miner for AsicBoostMiner {
+            async fn on_job_received(
+                &self,
+                job: Arc<MiningJob>,
+            ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+                Ok((7, job))
+            }
+
+            async fn mine(&self, job: Arc<MiningJob>, shares: ShareSink) {
+                let extranonce2 = shares.slot().extranonce2.clone();
+                shares.submit_rolled(7, &extranonce2, &job.ntime, Some(0x00002000));
+                std::future::pending::<()>().await;
+            }
+        }
+
+        let pool = MockPool::new();
+        let mut client = StratumV1Client::with_connection_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+            AsicBoostMiner,
+        )
+        .await
+        .unwrap();
+        client
+            .set_version_rolling(Some(VersionRolling {
+                mask: 0x1fffe000,
+                min_bit_count: 2,
+            }))
+            .await;
+        client.subscribe().await.unwrap();
+        client.authorize("rig1", "x").await.unwrap();
+        client.start_auto_submit().await.unwrap();
+        client.start_dispatcher().await;
+        pool.set_difficulty(1e-10);
+        pool.notify(MockPool::job("job1"));
+
+        let submit = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(request) = pool
+                    .requests()
+                    .into_iter()
+                    .find(|request| request["method"] == MINING_SUBMIT)
+                {
+                    return request["params"].clone();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            submit,
+            json!(["rig1", "job1", "00000000", "60509af9", "00000007", "00002000"])
+        );
+        client.stop_auto_submit().await;
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_auto_submit_reports_outcomes() {
+        use crate::stratum::miner::ShareSink;
+        use crate::stratum::testing::{MockPool, ShareVerdict};
+
+        /// Finds a share, another one, then the first one again
+        #[derive(Clone)]
+        struct RepeatingMiner;
+
+        #[async_trait]
+        impl Miner for RepeatingMiner {
+            async fn on_job_received(
+                &self,
+                job: Arc<MiningJob>,
+            ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+                Ok((1, job))
+            }
+
+            async fn mine(&self, _job: Arc<MiningJob>, shares: ShareSink) {
+                for nonce in [1, 2, 1] {
+                    shares.submit(nonce);
+                }
+                std::future::pending::<()>().await;
+            }
+        }
+
+        let pool = MockPool::new();
+        pool.push_verdict(ShareVerdict::Accept);
+        pool.push_verdict(ShareVerdict::Reject);
+        let mut client = StratumV1Client::with_connection_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+            RepeatingMiner,
+        )
+        .await
+        .unwrap();
+        client.subscribe().await.unwrap();
+        client.authorize("rig1", "x").await.unwrap();
+        let mut events = client.events();
+        client.start_auto_submit().await.unwrap();
+        client.start_dispatcher().await;
+        pool.set_difficulty(1e-10);
+        pool.notify(MockPool::job("job1"));
+
+        let mut outcomes = Vec::new();
+        while outcomes.len() < 3 {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            match event {
+                StratumEvent::ShareAccepted { .. } => outcomes.push("accepted"),
+                StratumEvent::ShareRejected { .. } => outcomes.push("rejected"),
+                StratumEvent::ShareDiscarded {
+                    reject_reason: RejectReason::Duplicate,
+                    ..
+                } => outcomes.push("duplicate"),
+                StratumEvent::ShareDiscarded { reject_reason, .. } => {
+                    panic!("Share discarded as {reject_reason:?}")
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(outcomes, ["accepted", "rejected", "duplicate"]);
+        let submits = pool
+            .requests()
+            .iter()
+            .filter(|request| request["method"] == MINING_SUBMIT)
+            .count();
+        assert_eq!(submits, 2);
+        client.stop_auto_submit().await;
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_submit_failure_not_rejected() {
+        use crate::stratum::testing::MockPool;
+
+        let pool =
+            MockPool::new().subscribe_result(json!([[["mining.notify", "sub1"]], "08000002", 4]));
+        let mut client = connect_mock(&pool).await;
+        client.subscribe().await.unwrap();
+        receive_job(&client, "504e86b9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "504e86b9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(client.submit_share(share.clone()).await.unwrap());
+        // The best share is the difficulty its hash met
+        let best = client
+            .session_snapshot()
+            .await
+            .records
+            .best_share_difficulty;
+        assert!(best.is_some());
+        assert_eq!(best, client.job_manager.share_difficulty(&share).await);
+
+        // The pool drops the connection before answering the next submit
+        pool.disconnect();
+        let mut events = client.events();
+        let share = Share {
+            nonce: "00000001".into(),
+            ..share
+        };
+        assert!(client.submit_share(share).await.is_err());
+        let failed = std::iter::from_fn(|| events.try_recv().ok()).find_map(|event| match event {
+            StratumEvent::ShareSubmitFailed { job_id, .. } => Some(job_id),
+            StratumEvent::ShareRejected { .. } => panic!("Submit failure counted as a reject"),
+            _ => None,
+        });
+        assert_eq!(failed.as_deref(), Some("job1"));
+        let snapshot = client.session_snapshot().await;
+        assert_eq!(snapshot.shares.rejected, 0);
+        assert_eq!(snapshot.submit_failures, 1);
+        assert_eq!(snapshot.current_accept_streak, 1);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_standby_uses_connection_config() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new();
+        let mut client = StratumV1Client::with_connection_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+            TestMiner,
+        )
+        .await
+        .unwrap();
+        client.subscribe().await.unwrap();
+        client
+            .enable_standby("rig1", "x", Duration::from_millis(50))
+            .await
+            .unwrap();
+        // The standby went through the primary's transport too
+        assert_eq!(pool.connections(), 2);
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_shutdown() {
+        use crate::stratum::miner::ShareSink;
+        use crate::stratum::testing::MockPool;
+        use crate::stratum::watchdog::KeepaliveConfig;
+
+        /// Reports a burst of results, then mines until cancelled
+        #[derive(Clone)]
+        struct BurstMiner;
+
+        #[async_trait]
+        impl Miner for BurstMiner {
+            async fn on_job_received(
+                &self,
+                job: Arc<MiningJob>,
+            ) -> Result<(u32, Arc<MiningJob>), StratumError> {
+                Ok((1, job))
+            }
+
+            async fn mine(&self, _job: Arc<MiningJob>, shares: ShareSink) {
+                for nonce in 1..=20 {
+                    shares.submit(nonce);
+                }
+                std::future::pending::<()>().await;
+            }
+        }
+
+        let pool = MockPool::new();
+        let mut client = StratumV1Client::with_connection_config(
+            "mock".into(),
+            0,
+            ConnectionConfig::with_transport(pool.clone()),
+            BurstMiner,
+        )
+        .await
+        .unwrap();
+        client.subscribe().await.unwrap();
+        client.authorize("rig1", "x").await.unwrap();
+        client.start_auto_submit().await.unwrap();
+        client.start_dispatcher().await;
+        client.set_keepalive(Some(KeepaliveConfig::default())).await;
+        let mut events = client.events();
+        pool.set_difficulty(1e-10);
+        pool.notify(MockPool::job("job1"));
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if let StratumEvent::ShareFound { .. } = event {
+                break;
+            }
+        }
+
+        let report = tokio::time::timeout(Duration::from_secs(5), client.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.shares.accepted, 20);
+        assert_eq!(report.pending_shares_dropped, 0);
+        // Every result was submitted, none dropped
+        let submits = pool
+            .requests()
+            .iter()
+            .filter(|request| request["method"] == MINING_SUBMIT)
+            .count();
+        assert_eq!(submits, 20);
+        assert!(client.dispatcher.lock().await.is_none());
+        assert!(client.keepalive.lock().await.is_none());
+        assert!(client.auto_submit.lock().await.is_none());
+        assert!(!client.connected.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        use crate::stratum::audit::HmacSigner;
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                line.clear();
+                let result = if request["method"] == MINING_SUBSCRIBE {
+                    json!([[["mining.notify", "1"]], "f000000f", 4])
+                } else {
+                    json!(true)
+                };
+                let response = json!({"id": request["id"], "result": result, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let path =
+            std::env::temp_dir().join(format!("stratum-client-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client
+            .set_audit_log(Some(
+                AuditLog::open(&path, Some(Box::new(HmacSigner::new("key")))).unwrap(),
+            ))
+            .await;
+        client.subscribe().await.unwrap();
+        client.authorize("worker", "x").await.unwrap();
+        // Stopping the log waits for the queued entries
+        client.set_audit_log(None).await;
+
+        let last = AuditLog::verify(&path, Some(&HmacSigner::new("key")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.seq, 1);
+        assert!(matches!(
+            last.record,
+            AuditRecord::Authorized {
+                authorized: true,
+                ..
+            }
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        use crate::stratum::health::HealthStatus;
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let response = json!({"id": request["id"], "result": true, "error": null});
+            let notify = json!({"id": null, "method": "mining.notify", "params": ["job1", "4d16b6f85af6e2198f44ae2a6de67f78487ae5611b77c6c0440b921e00000000", "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff20020862062f503253482f04b8864e5008", "072f736c7573682f000000000100f2052a010000001976a914d23fcdf86f7e756a64a7a9688ef9903327048ed988ac00000000", [], "00000002", "1c2ac4af", "504e86b9", true]});
+            writer
+                .write_all(format!("{}\n{}\n", response, notify).as_bytes())
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let health = client.health().await;
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert!(health.connected);
+        assert!(!health.authorized);
+
+        client.authorize("worker", "x").await.unwrap();
+        client.handle_notifications().await.unwrap();
+        let health = client.health().await;
+        assert_eq!(health.status, HealthStatus::Healthy);
+        assert!(health.last_job_age.is_some());
+        assert!(health.last_share_age.is_none());
+
+        client
+            .set_health_thresholds(HealthThresholds {
+                job_age_unhealthy: Some(Duration::ZERO),
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(client.health().await.status, HealthStatus::Unhealthy);
+
+        client.close().await.unwrap();
+        assert!(!client.health().await.connected);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_submit() {
         let (listener, host, port) = setup_mock_server().await;
 
+        let (received_tx, received_rx) = tokio::sync::oneshot::channel();
         tokio::spawn(async move {
             let (mut socket, _) = listener.accept().await.unwrap();
             let mut buf = [0; 1024];
-            socket.read(&mut buf).await.unwrap();
+            let read =
+                tokio::time::timeout(Duration::from_millis(200), socket.read(&mut buf)).await;
+            let _ = received_tx.send(matches!(read, Ok(Ok(n)) if n > 0));
+        });
 
-            let response = json!({
-                "id": 1,
-                "result": true,
-                "error": null
-            });
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        client.set_dry_run(true);
+        receive_job(&client, "60509af9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
 
-            socket
+        assert!(client.submit_share(share).await.unwrap());
+        assert!(!received_rx.await.unwrap(), "dry run sent data to the pool");
+        assert_eq!(client.session_snapshot().await.shares.accepted, 0);
+    }
+
+    /// Pool answering every share with `accept`, reporting each submit
+    async fn answering_pool(
+        accept: bool,
+    ) -> (String, u16, tokio::sync::mpsc::UnboundedReceiver<Value>) {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+        let (submits_tx, submits) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(read_half).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let _ = submits_tx.send(request["params"].clone());
+                let response = json!({"id": request["id"], "result": accept, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        (host, port, submits)
+    }
+
+    fn share_at(ntime: &str) -> Share {
+        Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: ntime.into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_share_dropped() {
+        let (host, port, mut submits) = answering_pool(false).await;
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        receive_job(&client, "504e86b9").await;
+
+        let mut events = client.events();
+        // Rejected shares are remembered too, the pool already judged them
+        assert!(!client.submit_share(share_at("504e86b9")).await.unwrap());
+        assert!(!client.submit_share(share_at("504e86b9")).await.unwrap());
+        submits.recv().await.unwrap();
+        assert!(submits.try_recv().is_err());
+        let discarded = std::iter::from_fn(|| events.try_recv().ok()).any(|event| {
+            matches!(
+                event,
+                StratumEvent::ShareDiscarded {
+                    reject_reason: RejectReason::Duplicate,
+                    ..
+                }
+            )
+        });
+        assert!(discarded);
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_share_not_remembered() {
+        let (host, port, mut submits) = answering_pool(true).await;
+        let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        receive_job(&client, "504e86b9").await;
+
+        // A dry run never reaches the pool, so the share can still be sent
+        client.set_dry_run(true);
+        assert!(client.submit_share(share_at("504e86b9")).await.unwrap());
+        client.set_dry_run(false);
+        assert!(client.submit_share(share_at("504e86b9")).await.unwrap());
+        submits.recv().await.unwrap();
+        assert!(!client.submit_share(share_at("504e86b9")).await.unwrap());
+        assert!(submits.try_recv().is_err());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_submit_sends_worker_name() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new();
+        let mut client = connect_mock(&pool).await;
+        client.subscribe().await.unwrap();
+        client.authorize("wallet.rig1", "x").await.unwrap();
+        receive_job(&client, "60509af9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(client.submit_share(share).await.unwrap());
+
+        // The worker name leads the params, as pools expect
+        let submit = pool
+            .requests()
+            .into_iter()
+            .find(|request| request["method"] == MINING_SUBMIT)
+            .unwrap();
+        assert_eq!(
+            submit["params"],
+            json!(["wallet.rig1", "job1", "00000000", "60509af9", "00000000"])
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_invalid_share_not_submitted() {
+        use crate::stratum::testing::MockPool;
+
+        let pool = MockPool::new();
+        let mut client = connect_mock(&pool).await;
+        receive_job(&client, "504e86b9").await;
+        let mut events = client.events();
+        let share = Share {
+            job_id: "job2".into(),
+            extranonce2: "00000000".into(),
+            ntime: "504e86b9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        assert!(matches!(
+            client.submit_share(share).await,
+            Err(StratumError::InvalidJob(_))
+        ));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            StratumEvent::ShareDiscarded {
+                reject_reason: RejectReason::Other,
+                ..
+            }
+        ));
+        assert!(pool.requests().is_empty(), "invalid share sent to the pool");
+    }
+
+    #[tokio::test]
+    async fn test_standby_wins_slow_submit() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let (listener, host, port) = setup_mock_server().await;
+        let subscribe_result = json!([[["mining.notify", "sub1"]], "abcd0001", 4]);
+
+        tokio::spawn(async move {
+            // Primary connection answers subscribe, then stalls on submit
+            let (primary, _) = listener.accept().await.unwrap();
+            let (read_half, mut primary_writer) = primary.into_split();
+            let mut primary_reader = BufReader::new(read_half);
+            let mut line = String::new();
+            primary_reader.read_line(&mut line).await.unwrap();
+            let response = json!({"id": 1, "result": subscribe_result, "error": null});
+            primary_writer
                 .write_all(format!("{}\n", response).as_bytes())
                 .await
                 .unwrap();
+
+            // Standby connection resumes the session and accepts the share
+            let (standby, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = standby.into_split();
+            let mut reader = BufReader::new(read_half);
+            for result in [subscribe_result, json!(true), json!(true)] {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let response = json!({"id": request["id"], "result": result, "error": null});
+                writer
+                    .write_all(format!("{}\n", response).as_bytes())
+                    .await
+                    .unwrap();
+            }
+
+            line.clear();
+            primary_reader.read_line(&mut line).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(2)).await;
         });
 
         let mut client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
-        let response = client.authorize("user", "pass").await.unwrap();
+        client.subscribe().await.unwrap();
+        client
+            .enable_standby("user", "pass", Duration::from_millis(50))
+            .await
+            .unwrap();
 
-        assert!(response.authorized);
+        receive_job(&client, "60509af9").await;
+        let share = Share {
+            job_id: "job1".into(),
+            extranonce2: "00000000".into(),
+            ntime: "60509af9".into(),
+            nonce: "00000000".into(),
+            version_bits: None,
+        };
+        let accepted = tokio::time::timeout(Duration::from_secs(1), client.submit_share(share))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(accepted);
+    }
+
+    #[tokio::test]
+    async fn test_stats_tick() {
+        let (listener, host, port) = setup_mock_server().await;
+        tokio::spawn(async move {
+            let _socket = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let client = StratumV1Client::new(host, port, TestMiner).await.unwrap();
+        let mut events = client.events();
+        client
+            .set_stats_interval(Some(Duration::from_millis(50)))
+            .await;
+
+        let event = tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match event {
+            StratumEvent::StatsTick(summary) => {
+                assert_eq!(summary.window, Duration::from_millis(50));
+                assert_eq!(summary.shares_accepted, 0);
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+
+        client.set_stats_interval(None).await;
     }
 }
@@ -24,6 +24,98 @@ pub const MINING_AUTHORIZE: &str = "mining.authorize";
 pub const MINING_SUBMIT: &str = "mining.submit";
 pub const MINING_NOTIFY: &str = "mining.notify";
 pub const MINING_SET_DIFFICULTY: &str = "mining.set_difficulty";
+pub const MINING_CONFIGURE: &str = "mining.configure";
+pub const MINING_SET_VERSION_MASK: &str = "mining.set_version_mask";
+pub const MINING_EXTRANONCE_SUBSCRIBE: &str = "mining.extranonce.subscribe";
+pub const MINING_SET_EXTRANONCE: &str = "mining.set_extranonce";
+pub const MINING_SUGGEST_DIFFICULTY: &str = "mining.suggest_difficulty";
+pub const MINING_SUGGEST_TARGET: &str = "mining.suggest_target";
+pub const CLIENT_SHOW_MESSAGE: &str = "client.show_message";
+pub const CLIENT_RECONNECT: &str = "client.reconnect";
+pub const CLIENT_GET_VERSION: &str = "client.get_version";
+
+/// Stratum V1 method, as named in JSON-RPC messages
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Method {
+    Subscribe,
+    Authorize,
+    Submit,
+    Notify,
+    SetDifficulty,
+    Configure,
+    SetVersionMask,
+    ExtranonceSubscribe,
+    SetExtranonce,
+    SuggestDifficulty,
+    SuggestTarget,
+    ShowMessage,
+    Reconnect,
+    GetVersion,
+    /// Method this client doesn't implement, such as a pool-specific
+    /// extension, see [`Extensions`](super::extensions::Extensions)
+    Unknown(String),
+}
+
+impl Method {
+    const KNOWN: [Method; 14] = [
+        Method::Subscribe,
+        Method::Authorize,
+        Method::Submit,
+        Method::Notify,
+        Method::SetDifficulty,
+        Method::Configure,
+        Method::SetVersionMask,
+        Method::ExtranonceSubscribe,
+        Method::SetExtranonce,
+        Method::SuggestDifficulty,
+        Method::SuggestTarget,
+        Method::ShowMessage,
+        Method::Reconnect,
+        Method::GetVersion,
+    ];
+
+    /// Name of the method, e.g. `mining.notify`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Subscribe => MINING_SUBSCRIBE,
+            Method::Authorize => MINING_AUTHORIZE,
+            Method::Submit => MINING_SUBMIT,
+            Method::Notify => MINING_NOTIFY,
+            Method::SetDifficulty => MINING_SET_DIFFICULTY,
+            Method::Configure => MINING_CONFIGURE,
+            Method::SetVersionMask => MINING_SET_VERSION_MASK,
+            Method::ExtranonceSubscribe => MINING_EXTRANONCE_SUBSCRIBE,
+            Method::SetExtranonce => MINING_SET_EXTRANONCE,
+            Method::SuggestDifficulty => MINING_SUGGEST_DIFFICULTY,
+            Method::SuggestTarget => MINING_SUGGEST_TARGET,
+            Method::ShowMessage => CLIENT_SHOW_MESSAGE,
+            Method::Reconnect => CLIENT_RECONNECT,
+            Method::GetVersion => CLIENT_GET_VERSION,
+            Method::Unknown(name) => name,
+        }
+    }
+}
+
+impl From<&str> for Method {
+    fn from(name: &str) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|method| method.as_str() == name)
+            .unwrap_or_else(|| Method::Unknown(name.to_string()))
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `mining.configure` extension negotiating version rolling (BIP 310)
+pub const VERSION_ROLLING: &str = "version-rolling";
+
+/// Version bits available for general purpose use (BIP 320)
+pub const DEFAULT_VERSION_ROLLING_MASK: u32 = 0x1fffe000;
 
 /// Client version string sent to pool
 pub const CLIENT_VERSION: &str = "rust-stratum-client/1.0.0";
@@ -31,6 +123,11 @@ pub const CLIENT_VERSION: &str = "rust-stratum-client/1.0.0";
 /// Default timeout for network operations in seconds
 pub const DEFAULT_TIMEOUT: u64 = 20;
 
+/// Default time to wait for a `mining.authorize` response in seconds
+///
+/// Some pools only answer after sending the first difficulty and job.
+pub const DEFAULT_AUTH_TIMEOUT: u64 = 30;
+
 /// Maximum number of retries for failed operations
 pub const MAX_RETRIES: u32 = 3;
 
@@ -110,11 +207,29 @@ impl JsonRpcResponse {
         self.error.is_some()
     }
 
+    /// Get the error code if present, from a Stratum `[code, message, data]`
+    /// triple or a JSON-RPC 2.0 style error object
+    pub fn error_code(&self) -> Option<i64> {
+        match self.error.as_ref()? {
+            Value::Array(items) => items.first().and_then(Value::as_i64),
+            Value::Object(fields) => fields.get("code").and_then(Value::as_i64),
+            _ => None,
+        }
+    }
+
     /// Get the error message if present
+    ///
+    /// Understands plain strings, the Stratum `[code, message, data]` triple
+    /// and JSON-RPC 2.0 style `{"code": .., "message": ..}` objects.
     pub fn error_message(&self) -> Option<String> {
-        self.error
-            .as_ref()
-            .and_then(|e| e.as_str().map(String::from))
+        let error = self.error.as_ref()?;
+        let message = match error {
+            Value::String(message) => Some(message.as_str()),
+            Value::Array(items) => items.get(1).and_then(Value::as_str),
+            Value::Object(fields) => fields.get("message").and_then(Value::as_str),
+            _ => None,
+        };
+        message.map(String::from)
     }
 }
 
@@ -150,6 +265,17 @@ mod tests {
         assert_eq!(req.params, vec![json!("param1")]);
     }
 
+    #[test]
+    fn test_method() {
+        for method in Method::KNOWN {
+            assert_eq!(Method::from(method.as_str()), method);
+        }
+        assert_eq!(Method::from(MINING_NOTIFY), Method::Notify);
+        let custom = Method::from("client.custom_thing");
+        assert_eq!(custom, Method::Unknown("client.custom_thing".into()));
+        assert_eq!(custom.to_string(), "client.custom_thing");
+    }
+
     #[test]
     fn test_subscribe_request() {
         let req = JsonRpcRequest::subscribe(1);
@@ -194,6 +320,15 @@ mod tests {
         assert_eq!(resp.result, None);
         assert_eq!(resp.error, Some(json!("error")));
         assert_eq!(resp.error_message(), Some("error".to_string()));
+
+        let resp = JsonRpcResponse::err(1, json!([24, "Unauthorized worker", null]));
+        assert_eq!(
+            resp.error_message(),
+            Some("Unauthorized worker".to_string())
+        );
+        let resp = JsonRpcResponse::err(1, json!({"code": -1, "message": "Banned"}));
+        assert_eq!(resp.error_message(), Some("Banned".to_string()));
+        assert_eq!(JsonRpcResponse::err(1, json!(null)).error_message(), None);
     }
 
     #[test]
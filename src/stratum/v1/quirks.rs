@@ -0,0 +1,345 @@
+use super::protocol::{MINING_NOTIFY, MINING_SET_DIFFICULTY, MINING_SET_EXTRANONCE};
+use crate::stratum::error::StratumError;
+use crate::stratum::rejects::RejectCatalogue;
+use serde_json::Value;
+
+/// extranonce2 size assumed when a pool doesn't announce one
+pub const DEFAULT_EXTRANONCE2_SIZE: usize = 4;
+
+/// How closely pool messages must follow the Stratum V1 conventions
+///
+/// Both modes parse messages the same way. Strict mode first checks that
+/// they have exactly the shape the conventions describe, which is useful
+/// when testing a pool implementation; lenient mode takes whatever the
+/// parser can make sense of, as mining against real pools needs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+fn deviation(message: &str, field: &str, expected: &str, got: &Value) -> StratumError {
+    StratumError::Protocol(format!(
+        "Non-conforming {}: {} must be {}, got {}",
+        message, field, expected, got
+    ))
+}
+
+/// Check `value` is a hex string of `bytes` bytes, or of any length if `None`
+fn check_hex(
+    message: &str,
+    field: &str,
+    value: Option<&Value>,
+    bytes: Option<usize>,
+) -> Result<(), StratumError> {
+    let value = value.unwrap_or(&Value::Null);
+    let hex = value.as_str().and_then(|hex| hex::decode(hex).ok());
+    match (hex, bytes) {
+        (Some(decoded), Some(bytes)) if decoded.len() == bytes => Ok(()),
+        (Some(_), None) => Ok(()),
+        (_, Some(bytes)) => Err(deviation(
+            message,
+            field,
+            &format!("a {} byte hex string", bytes),
+            value,
+        )),
+        (None, None) => Err(deviation(message, field, "a hex string", value)),
+    }
+}
+
+fn check_param_count(message: &str, params: &[Value], count: usize) -> Result<(), StratumError> {
+    if params.len() == count {
+        return Ok(());
+    }
+    Err(StratumError::Protocol(format!(
+        "Non-conforming {}: expected {} params, got {}",
+        message,
+        count,
+        params.len()
+    )))
+}
+
+impl ParseMode {
+    /// Check a `mining.subscribe` result in strict mode
+    ///
+    /// The result must be `[[[method, id], ...], extranonce1, size]` with
+    /// string ids, a hex extranonce1 and an integer size.
+    pub fn check_subscribe(&self, result: &Value) -> Result<(), StratumError> {
+        const MESSAGE: &str = "mining.subscribe result";
+        if *self == ParseMode::Lenient {
+            return Ok(());
+        }
+        let Some(result) = result.as_array().filter(|result| result.len() == 3) else {
+            return Err(deviation(MESSAGE, "result", "an array of 3", result));
+        };
+        let pairs = result[0].as_array().filter(|pairs| {
+            !pairs.is_empty()
+                && pairs.iter().all(|pair| {
+                    pair.as_array()
+                        .is_some_and(|pair| pair.len() == 2 && pair.iter().all(Value::is_string))
+                })
+        });
+        if pairs.is_none() {
+            return Err(deviation(
+                MESSAGE,
+                "result[0]",
+                "a list of [method, id] string pairs",
+                &result[0],
+            ));
+        }
+        check_hex(MESSAGE, "result[1] (extranonce1)", result.get(1), None)?;
+        if !result[2].is_u64() {
+            return Err(deviation(
+                MESSAGE,
+                "result[2] (extranonce2_size)",
+                "an integer",
+                &result[2],
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a notification from the pool in strict mode
+    ///
+    /// Notifications must carry a null id and a params array. Those of
+    /// `mining.notify`, `mining.set_difficulty` and `mining.set_extranonce`
+    /// must have exactly the expected params, with hex fields of the right
+    /// length.
+    pub fn check_notification(&self, notification: &Value) -> Result<(), StratumError> {
+        if *self == ParseMode::Lenient {
+            return Ok(());
+        }
+        let method = notification["method"].as_str().unwrap_or_default();
+        if !notification.get("id").is_some_and(Value::is_null) {
+            return Err(deviation(
+                method,
+                "id",
+                "null",
+                notification.get("id").unwrap_or(&Value::Null),
+            ));
+        }
+        let Some(params) = notification.get("params").and_then(Value::as_array) else {
+            return Err(deviation(
+                method,
+                "params",
+                "an array",
+                notification.get("params").unwrap_or(&Value::Null),
+            ));
+        };
+
+        match method {
+            MINING_NOTIFY => {
+                check_param_count(method, params, 9)?;
+                if !params[0].is_string() {
+                    return Err(deviation(
+                        method,
+                        "params[0] (job_id)",
+                        "a string",
+                        &params[0],
+                    ));
+                }
+                check_hex(method, "params[1] (prev_hash)", params.get(1), Some(32))?;
+                check_hex(method, "params[2] (coinbase1)", params.get(2), None)?;
+                check_hex(method, "params[3] (coinbase2)", params.get(3), None)?;
+                let Some(branches) = params[4].as_array() else {
+                    return Err(deviation(
+                        method,
+                        "params[4] (merkle_branch)",
+                        "an array",
+                        &params[4],
+                    ));
+                };
+                for (index, branch) in branches.iter().enumerate() {
+                    let field = format!("params[4][{}] (merkle_branch)", index);
+                    check_hex(method, &field, Some(branch), Some(32))?;
+                }
+                check_hex(method, "params[5] (version)", params.get(5), Some(4))?;
+                check_hex(method, "params[6] (nbits)", params.get(6), Some(4))?;
+                check_hex(method, "params[7] (ntime)", params.get(7), Some(4))?;
+                if !params[8].is_boolean() {
+                    return Err(deviation(
+                        method,
+                        "params[8] (clean_jobs)",
+                        "a bool",
+                        &params[8],
+                    ));
+                }
+            }
+            MINING_SET_DIFFICULTY => {
+                check_param_count(method, params, 1)?;
+                if !params[0].is_number() {
+                    return Err(deviation(method, "params[0]", "a number", &params[0]));
+                }
+            }
+            MINING_SET_EXTRANONCE => {
+                check_param_count(method, params, 2)?;
+                check_hex(method, "params[0] (extranonce1)", params.first(), None)?;
+                if !params[1].is_u64() {
+                    return Err(deviation(
+                        method,
+                        "params[1] (extranonce2_size)",
+                        "an integer",
+                        &params[1],
+                    ));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Tolerances for pools deviating from the common Stratum V1 message formats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolQuirks {
+    /// extranonce2 size used when the subscribe result omits it or sends null
+    pub default_extranonce2_size: usize,
+    /// Accept numbers sent as strings, e.g. `"4"` or `"0x4"`, and fall back
+    /// to the defaults when a number can't be read at all
+    pub coerce_string_numbers: bool,
+    /// Reject messages of the pool, to classify rejected shares by
+    pub rejects: RejectCatalogue,
+    /// Whether messages are checked against the conventions before parsing
+    pub mode: ParseMode,
+}
+
+impl Default for PoolQuirks {
+    fn default() -> Self {
+        Self {
+            default_extranonce2_size: DEFAULT_EXTRANONCE2_SIZE,
+            coerce_string_numbers: true,
+            rejects: RejectCatalogue::default(),
+            mode: ParseMode::Lenient,
+        }
+    }
+}
+
+impl PoolQuirks {
+    /// Strict parsing, rejecting any deviation from the common formats
+    pub fn strict() -> Self {
+        Self {
+            default_extranonce2_size: DEFAULT_EXTRANONCE2_SIZE,
+            coerce_string_numbers: false,
+            rejects: RejectCatalogue::default(),
+            mode: ParseMode::Strict,
+        }
+    }
+
+    /// Read an unsigned number, coercing strings if enabled
+    pub fn coerce_u64(&self, value: &Value) -> Option<u64> {
+        match value {
+            Value::Number(n) => n.as_u64(),
+            Value::String(s) if self.coerce_string_numbers => {
+                let s = s.trim();
+                match s.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                    None => s.parse().ok(),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// extranonce2 size from the third element of a subscribe result
+    pub fn extranonce2_size(&self, value: Option<&Value>) -> Result<usize, StratumError> {
+        match value {
+            None | Some(Value::Null) => Ok(self.default_extranonce2_size),
+            Some(value) => match self.coerce_u64(value) {
+                Some(size) => Ok(size as usize),
+                None if self.coerce_string_numbers => {
+                    log::warn!(
+                        target: "stratum",
+                        "Invalid extranonce2_size {}, using {}",
+                        value,
+                        self.default_extranonce2_size
+                    );
+                    Ok(self.default_extranonce2_size)
+                }
+                None => Err(StratumError::SubscriptionFailed(format!(
+                    "Invalid extranonce2_size {}",
+                    value
+                ))),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extranonce2_size() {
+        let quirks = PoolQuirks::default();
+        assert_eq!(quirks.extranonce2_size(Some(&json!(8))).unwrap(), 8);
+        assert_eq!(quirks.extranonce2_size(Some(&json!("8"))).unwrap(), 8);
+        assert_eq!(quirks.extranonce2_size(Some(&json!("0x8"))).unwrap(), 8);
+        assert_eq!(
+            quirks.extranonce2_size(None).unwrap(),
+            DEFAULT_EXTRANONCE2_SIZE
+        );
+        assert_eq!(
+            quirks.extranonce2_size(Some(&json!(null))).unwrap(),
+            DEFAULT_EXTRANONCE2_SIZE
+        );
+        assert_eq!(
+            quirks.extranonce2_size(Some(&json!("eight"))).unwrap(),
+            DEFAULT_EXTRANONCE2_SIZE
+        );
+
+        let strict = PoolQuirks::strict();
+        assert!(strict.extranonce2_size(Some(&json!("8"))).is_err());
+        assert!(strict.extranonce2_size(Some(&json!("eight"))).is_err());
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let strict = ParseMode::Strict;
+        let subscribe = json!([[["mining.notify", "ae6812eb4cd7735a"]], "08000002", 4]);
+        strict.check_subscribe(&subscribe).unwrap();
+        // A single pair instead of a list, and a string size
+        let single_pair = json!([["mining.notify", "ae6812eb4cd7735a"], "08000002", 4]);
+        assert!(strict.check_subscribe(&single_pair).is_err());
+        let string_size = json!([[["mining.notify", "1"]], "08000002", "4"]);
+        assert!(strict.check_subscribe(&string_size).is_err());
+        ParseMode::Lenient.check_subscribe(&single_pair).unwrap();
+
+        let notify = json!({
+            "id": null,
+            "method": "mining.notify",
+            "params": [
+                "job1",
+                "00000000000000000000000000000000000000000000000000000000deadbeef",
+                "01000000",
+                "02000000",
+                ["00000000000000000000000000000000000000000000000000000000deadbeef"],
+                "20000000",
+                "1d00ffff",
+                "60509af9",
+                true
+            ]
+        });
+        strict.check_notification(&notify).unwrap();
+        let mut short_branch = notify.clone();
+        short_branch["params"][4][0] = json!("deadbeef");
+        let err = strict.check_notification(&short_branch).unwrap_err();
+        assert!(err.to_string().contains("params[4][0]"), "{}", err);
+        let mut no_clean_jobs = notify.clone();
+        no_clean_jobs["params"].as_array_mut().unwrap().pop();
+        assert!(strict.check_notification(&no_clean_jobs).is_err());
+        let mut numbered = notify.clone();
+        numbered["id"] = json!(7);
+        assert!(strict.check_notification(&numbered).is_err());
+        ParseMode::Lenient
+            .check_notification(&no_clean_jobs)
+            .unwrap();
+
+        let difficulty = json!({"id": null, "method": "mining.set_difficulty", "params": ["2"]});
+        assert!(strict.check_notification(&difficulty).is_err());
+        let extranonce =
+            json!({"id": null, "method": "mining.set_extranonce", "params": ["08000002", 4]});
+        strict.check_notification(&extranonce).unwrap();
+    }
+}
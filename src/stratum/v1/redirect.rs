@@ -0,0 +1,148 @@
+use super::protocol::CLIENT_RECONNECT;
+use crate::stratum::error::StratumError;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Longest wait before following a `client.reconnect` that is honored
+pub const MAX_RECONNECT_WAIT: Duration = Duration::from_secs(300);
+
+/// Which `client.reconnect` targets the client follows
+///
+/// Pools send `client.reconnect` to rebalance miners, but a compromised or
+/// spoofed pool could use it to move miners elsewhere, so only the current
+/// host is trusted by default.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum RedirectPolicy {
+    /// Follow reconnects to the current host only, on any port
+    #[default]
+    SameHost,
+    /// Follow reconnects to the current host and the listed ones; an entry
+    /// `*.example.com` matches any subdomain of `example.com`
+    Allow(Vec<String>),
+    /// Follow reconnects to any host
+    AllowAll,
+    /// Ignore `client.reconnect`
+    Deny,
+}
+
+impl RedirectPolicy {
+    /// Whether a reconnect from `current` to `target` may be followed
+    pub fn allows(&self, current: &str, target: &str) -> bool {
+        let same_host = target.eq_ignore_ascii_case(current);
+        match self {
+            RedirectPolicy::SameHost => same_host,
+            RedirectPolicy::Allow(hosts) => {
+                same_host || hosts.iter().any(|host| host_matches(host, target))
+            }
+            RedirectPolicy::AllowAll => true,
+            RedirectPolicy::Deny => false,
+        }
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.len().checked_sub(domain.len() + 1).is_some_and(|dot| {
+            host.as_bytes()[dot] == b'.' && host[dot + 1..].eq_ignore_ascii_case(domain)
+        }),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Target of a `client.reconnect`, defaulting to the current pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ReconnectRequest {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub wait: Duration,
+}
+
+impl ReconnectRequest {
+    /// Parse `[host, port, wait]`, each of which may be missing
+    ///
+    /// Ports and waits are accepted as numbers or strings.
+    pub fn parse(params: &[Value]) -> Result<Self, StratumError> {
+        let invalid = || StratumError::Protocol(format!("Invalid {} params", CLIENT_RECONNECT));
+        let number = |value: &Value| match value {
+            Value::Number(number) => number.as_u64(),
+            Value::String(number) => number.parse().ok(),
+            _ => None,
+        };
+
+        let host = match params.first() {
+            None | Some(Value::Null) => None,
+            Some(Value::String(host)) if host.is_empty() => None,
+            Some(Value::String(host)) => Some(host.clone()),
+            Some(_) => return Err(invalid()),
+        };
+        let port = match params.get(1) {
+            None | Some(Value::Null) => None,
+            Some(port) => Some(
+                number(port)
+                    .and_then(|port| u16::try_from(port).ok())
+                    .filter(|port| *port != 0)
+                    .ok_or_else(invalid)?,
+            ),
+        };
+        let wait = match params.get(2) {
+            None | Some(Value::Null) => Duration::ZERO,
+            Some(wait) => Duration::from_secs(number(wait).ok_or_else(invalid)?),
+        };
+
+        Ok(Self {
+            host,
+            port,
+            wait: wait.min(MAX_RECONNECT_WAIT),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_policy() {
+        let current = "pool.example.com";
+        assert!(RedirectPolicy::SameHost.allows(current, "POOL.example.com"));
+        assert!(!RedirectPolicy::SameHost.allows(current, "evil.com"));
+
+        let allow = RedirectPolicy::Allow(vec!["*.example.com".into(), "backup.net".into()]);
+        assert!(allow.allows(current, "eu.example.com"));
+        assert!(allow.allows(current, "backup.net"));
+        assert!(!allow.allows(current, "example.com"));
+        assert!(!allow.allows(current, "evilexample.com"));
+
+        assert!(RedirectPolicy::AllowAll.allows(current, "evil.com"));
+        assert!(!RedirectPolicy::Deny.allows(current, current));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ReconnectRequest::parse(&[json!("eu.example.com"), json!("3334"), json!(5)]).unwrap(),
+            ReconnectRequest {
+                host: Some("eu.example.com".into()),
+                port: Some(3334),
+                wait: Duration::from_secs(5),
+            }
+        );
+        assert_eq!(
+            ReconnectRequest::parse(&[]).unwrap(),
+            ReconnectRequest {
+                host: None,
+                port: None,
+                wait: Duration::ZERO,
+            }
+        );
+        assert_eq!(
+            ReconnectRequest::parse(&[json!(""), json!(3333), json!(86400)])
+                .unwrap()
+                .wait,
+            MAX_RECONNECT_WAIT
+        );
+        assert!(ReconnectRequest::parse(&[json!(1)]).is_err());
+        assert!(ReconnectRequest::parse(&[json!("host"), json!(70000)]).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use super::protocol::MINING_NOTIFY;
+use serde_json::Value;
+
+fn is_notify(notification: &Value) -> bool {
+    notification.get("method").and_then(Value::as_str) == Some(MINING_NOTIFY)
+}
+
+fn notify_params(notification: &Value) -> Option<&Vec<Value>> {
+    notification.get("params").and_then(Value::as_array)
+}
+
+fn prev_hash(notification: &Value) -> Option<&str> {
+    notify_params(notification)?.get(1).and_then(Value::as_str)
+}
+
+fn clean_jobs(notification: &Value) -> bool {
+    notify_params(notification)
+        .and_then(|params| params.get(8))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Collapse a burst of notifications so only the latest relevant job is dispatched
+///
+/// The last `clean_jobs` notify of the burst marks the newest block. Jobs sent
+/// after it for another `prev_hash` arrived out of order and are dropped, and
+/// of the remaining jobs only the latest is kept since it supersedes the
+/// others. If a dropped job of the kept job's block had `clean_jobs` set, so
+/// does the kept one, or miners would carry on with work of the old block.
+/// Other notifications keep their relative order, ahead of the job.
+pub(crate) fn coalesce(batch: Vec<Value>) -> Vec<Value> {
+    let boundary = batch.iter().rposition(|n| is_notify(n) && clean_jobs(n));
+    let newest_block = boundary.and_then(|i| prev_hash(&batch[i]).map(String::from));
+
+    let latest_job = batch
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| is_notify(n))
+        .filter(|(i, n)| match (boundary, &newest_block) {
+            (Some(boundary), Some(block)) => *i <= boundary || prev_hash(n) == Some(block),
+            _ => true,
+        })
+        .map(|(i, _)| i)
+        .next_back();
+    // The kept job is of the newest block, so clean if that block's clean
+    // job was dropped
+    let clean = boundary.is_some();
+
+    let mut coalesced = Vec::with_capacity(batch.len());
+    let mut job = None;
+    for (i, notification) in batch.into_iter().enumerate() {
+        if !is_notify(&notification) {
+            coalesced.push(notification);
+        } else if Some(i) == latest_job {
+            let mut notification = notification;
+            if clean {
+                if let Some(flag) = notification
+                    .get_mut("params")
+                    .and_then(Value::as_array_mut)
+                    .and_then(|params| params.get_mut(8))
+                {
+                    *flag = Value::Bool(true);
+                }
+            }
+            job = Some(notification);
+        } else {
+            log::debug!(target: "stratum", "Dropping superseded job notification: {notification:?}");
+        }
+    }
+    coalesced.extend(job);
+    coalesced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn notify(job_id: &str, prev_hash: &str, clean: bool) -> Value {
+        json!({
+            "id": null,
+            "method": MINING_NOTIFY,
+            "params": [job_id, prev_hash, "", "", [], "20000000", "1d00ffff", "60509af9", clean]
+        })
+    }
+
+    fn job_ids(batch: &[Value]) -> Vec<&str> {
+        batch
+            .iter()
+            .filter(|n| is_notify(n))
+            .filter_map(|n| notify_params(n)?.first()?.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_keeps_latest_job() {
+        let difficulty = json!({"id": null, "method": "mining.set_difficulty", "params": [2]});
+        let batch = vec![
+            notify("1", "a", true),
+            difficulty.clone(),
+            notify("2", "a", false),
+        ];
+        let coalesced = coalesce(batch);
+        assert_eq!(coalesced[0], difficulty);
+        assert_eq!(job_ids(&coalesced), ["2"]);
+    }
+
+    #[test]
+    fn test_drops_out_of_order_jobs() {
+        // A job for the previous block arrives after the new block's clean job
+        let batch = vec![
+            notify("1", "a", false),
+            notify("2", "b", true),
+            notify("3", "a", false),
+        ];
+        assert_eq!(job_ids(&coalesce(batch)), ["2"]);
+
+        let batch = vec![notify("2", "b", true), notify("4", "b", false)];
+        assert_eq!(job_ids(&coalesce(batch)), ["4"]);
+    }
+
+    #[test]
+    fn test_keeps_clean_jobs() {
+        // The kept job takes over the dropped clean job of its block
+        let coalesced = coalesce(vec![notify("1", "a", true), notify("2", "a", false)]);
+        assert_eq!(job_ids(&coalesced), ["2"]);
+        assert!(clean_jobs(&coalesced[0]));
+
+        let coalesced = coalesce(vec![
+            notify("1", "a", true),
+            notify("2", "b", true),
+            notify("3", "b", false),
+        ]);
+        assert_eq!(job_ids(&coalesced), ["3"]);
+        assert!(clean_jobs(&coalesced[0]));
+
+        // Without a clean job in the burst it stays an update
+        let coalesced = coalesce(vec![notify("1", "a", false), notify("2", "a", false)]);
+        assert_eq!(job_ids(&coalesced), ["2"]);
+        assert!(!clean_jobs(&coalesced[0]));
+    }
+
+    #[test]
+    fn test_passes_through_other_messages() {
+        let batch = vec![json!({"id": 1, "result": true, "error": null})];
+        assert_eq!(coalesce(batch.clone()), batch);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::stratum::error::StratumError;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How a share is resubmitted after a `mining.submit` error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitRetry {
+    /// Wait before resubmitting
+    pub delay: Duration,
+    /// Submissions of the share in total, the first one included
+    pub max_attempts: u32,
+}
+
+/// Which `mining.submit` errors resubmit the share, by error code
+///
+/// Some pools answer with an error meaning "resubmit shortly" when they are
+/// briefly busy. Unlike the transport retries of
+/// [`ConnectionConfig::max_retries`](super::connection::ConnectionConfig::max_retries),
+/// which resend requests that got no answer, these resend shares the pool
+/// answered. No error is retried by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubmitRetryPolicy {
+    retries: HashMap<i64, SubmitRetry>,
+}
+
+impl SubmitRetryPolicy {
+    /// Policy retrying no error
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resubmit after `delay` when the pool answers with error `code`, up to
+    /// `max_attempts` submissions in total
+    pub fn retry(mut self, code: i64, delay: Duration, max_attempts: u32) -> Self {
+        self.retries.insert(
+            code,
+            SubmitRetry {
+                delay,
+                max_attempts,
+            },
+        );
+        self
+    }
+
+    /// How errors with `code` are retried
+    pub fn get(&self, code: i64) -> Option<&SubmitRetry> {
+        self.retries.get(&code)
+    }
+
+    /// Wait before resubmitting a share whose `attempts`th submission failed
+    /// with `err`, `None` if it isn't resubmitted
+    pub fn delay_after(&self, err: &StratumError, attempts: u32) -> Option<Duration> {
+        let retry = self.get(error_code(err)?)?;
+        (attempts < retry.max_attempts).then_some(retry.delay)
+    }
+}
+
+/// Error code of a pool's error response
+fn error_code(err: &StratumError) -> Option<i64> {
+    match err.root() {
+        StratumError::Rpc { error, .. } => Some(error.code()),
+        StratumError::Protocol(error) => match serde_json::from_str(error).ok()? {
+            Value::Array(items) => items.first().and_then(Value::as_i64),
+            Value::Object(fields) => fields.get("code").and_then(Value::as_i64),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::error::{ErrorContext, StratumRpcError};
+
+    #[test]
+    fn test_delay_after() {
+        let policy = SubmitRetryPolicy::new()
+            .retry(-3, Duration::from_millis(200), 3)
+            .retry(20, Duration::from_millis(50), 2);
+        let busy = StratumError::Protocol(r#"[-3,"Try again",null]"#.into());
+        assert_eq!(
+            policy.delay_after(&busy, 1),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(
+            policy.delay_after(&busy, 2),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(policy.delay_after(&busy, 3), None);
+
+        let other = StratumError::Rpc {
+            error: StratumRpcError::Other,
+            message: "Busy".into(),
+        }
+        .context(ErrorContext::new("mining.submit"));
+        assert_eq!(
+            policy.delay_after(&other, 1),
+            Some(Duration::from_millis(50))
+        );
+
+        let object = StratumError::Protocol(r#"{"code":-3,"message":"later"}"#.into());
+        assert!(policy.delay_after(&object, 1).is_some());
+        let stale = StratumError::Rpc {
+            error: StratumRpcError::Stale,
+            message: "Stale".into(),
+        };
+        assert_eq!(policy.delay_after(&stale, 1), None);
+        assert_eq!(
+            policy.delay_after(&StratumError::Connection("closed".into()), 1),
+            None
+        );
+    }
+}
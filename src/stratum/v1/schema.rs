@@ -0,0 +1,153 @@
+use super::protocol::{
+    JsonRpcResponse, MINING_AUTHORIZE, MINING_CONFIGURE, MINING_EXTRANONCE_SUBSCRIBE,
+    MINING_SUBMIT, MINING_SUBSCRIBE,
+};
+use crate::stratum::error::StratumError;
+use serde_json::Value;
+
+fn malformed(method: &str, field: &str, expected: &str, got: &Value) -> StratumError {
+    StratumError::Protocol(format!(
+        "Malformed {} response: {} must be {}, got {}",
+        method, field, expected, got
+    ))
+}
+
+/// Check a response against the expected schema for the request's method
+///
+/// Error responses and methods without a known schema are accepted as is.
+/// Otherwise the returned error names the malformed field, e.g.
+/// `Malformed mining.subscribe response: result[1] (extranonce1) must be a string, got 5`.
+pub fn validate_response(method: &str, response: &JsonRpcResponse) -> Result<(), StratumError> {
+    if response
+        .error
+        .as_ref()
+        .is_some_and(|error| !error.is_null())
+    {
+        return Ok(());
+    }
+
+    let result = response.result.as_ref().unwrap_or(&Value::Null);
+    match method {
+        MINING_SUBSCRIBE => validate_subscribe(result),
+        MINING_AUTHORIZE | MINING_SUBMIT | MINING_EXTRANONCE_SUBSCRIBE => match result {
+            Value::Bool(_) => Ok(()),
+            other => Err(malformed(method, "result", "a boolean", other)),
+        },
+        MINING_CONFIGURE => match result {
+            Value::Object(_) => Ok(()),
+            other => Err(malformed(method, "result", "an object", other)),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn validate_subscribe(result: &Value) -> Result<(), StratumError> {
+    let method = MINING_SUBSCRIBE;
+    let Value::Array(items) = result else {
+        return Err(malformed(method, "result", "an array", result));
+    };
+    if items.len() < 2 {
+        return Err(malformed(
+            method,
+            "result",
+            "an array of at least 2 elements",
+            result,
+        ));
+    }
+
+    match &items[0] {
+        Value::Array(details) => {
+            // Either a single (method, id) pair or a list of pairs
+            let pairs: Vec<&Value> = if details.first().is_some_and(Value::is_string) {
+                vec![&items[0]]
+            } else {
+                details.iter().collect()
+            };
+            for (i, pair) in pairs.into_iter().enumerate() {
+                let valid = pair.as_array().is_some_and(|pair| {
+                    pair.len() >= 2
+                        && pair[0].is_string()
+                        && (pair[1].is_string() || pair[1].is_number())
+                });
+                if !valid {
+                    return Err(malformed(
+                        method,
+                        &format!("result[0][{}] (subscription)", i),
+                        "a [method, id] pair",
+                        pair,
+                    ));
+                }
+            }
+        }
+        other => {
+            return Err(malformed(
+                method,
+                "result[0] (subscriptions)",
+                "an array",
+                other,
+            ))
+        }
+    }
+
+    if !items[1].is_string() {
+        return Err(malformed(
+            method,
+            "result[1] (extranonce1)",
+            "a string",
+            &items[1],
+        ));
+    }
+
+    match items.get(2) {
+        None | Some(Value::Null) | Some(Value::Number(_)) | Some(Value::String(_)) => Ok(()),
+        Some(other) => Err(malformed(
+            method,
+            "result[2] (extranonce2_size)",
+            "a number",
+            other,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error_text(method: &str, result: Value) -> String {
+        validate_response(method, &JsonRpcResponse::ok(1, result))
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_valid_responses() {
+        let subscribe = json!([[["mining.notify", "1"]], "08000002", 4]);
+        assert!(validate_response(MINING_SUBSCRIBE, &JsonRpcResponse::ok(1, subscribe)).is_ok());
+        assert!(validate_response(MINING_SUBMIT, &JsonRpcResponse::ok(1, json!(true))).is_ok());
+        assert!(validate_response(MINING_CONFIGURE, &JsonRpcResponse::ok(1, json!({}))).is_ok());
+        // Error responses carry no result
+        assert!(validate_response(
+            MINING_AUTHORIZE,
+            &JsonRpcResponse::err(1, json!([24, "Unauthorized", null]))
+        )
+        .is_ok());
+        assert!(validate_response("mining.unknown", &JsonRpcResponse::ok(1, json!(5))).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_field_is_named() {
+        assert!(
+            error_text(MINING_SUBSCRIBE, json!([[["mining.notify", "1"]], 5, 4]))
+                .contains("result[1] (extranonce1) must be a string, got 5")
+        );
+        assert!(
+            error_text(MINING_SUBSCRIBE, json!([[["mining.notify"]], "00", 4]))
+                .contains("result[0][0] (subscription)")
+        );
+        assert!(error_text(MINING_SUBSCRIBE, json!([[], "00", [4]]))
+            .contains("result[2] (extranonce2_size)"));
+        assert!(error_text(MINING_AUTHORIZE, json!("yes")).contains("result must be a boolean"));
+        assert!(error_text(MINING_CONFIGURE, json!([])).contains("must be an object"));
+    }
+}
@@ -0,0 +1,135 @@
+use super::connection::StratumConnection;
+use super::protocol::{JsonRpcRequest, JsonRpcResponse};
+use crate::stratum::error::StratumError;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tower_service::Service;
+
+/// The JSON-RPC request path of a connection as a [`tower_service::Service`]
+///
+/// Each call sends the request once and waits for its response, without the
+/// connection's own retries, so timeouts, rate limits and retries can be
+/// composed from tower middleware instead. The request id is replaced by the
+/// connection's next id. Error responses are returned as responses, not
+/// errors, leaving it to the caller to interpret them.
+#[derive(Clone)]
+pub struct RequestService {
+    connection: Arc<Mutex<StratumConnection>>,
+}
+
+impl RequestService {
+    pub(crate) fn new(connection: Arc<Mutex<StratumConnection>>) -> Self {
+        Self { connection }
+    }
+}
+
+impl Service<JsonRpcRequest> for RequestService {
+    type Response = JsonRpcResponse;
+    type Error = StratumError;
+    type Future = Pin<Box<dyn Future<Output = Result<JsonRpcResponse, StratumError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), StratumError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: JsonRpcRequest) -> Self::Future {
+        let connection = self.connection.clone();
+        Box::pin(async move {
+            // Release the connection before waiting, so concurrent calls,
+            // share submits and reconnects aren't held up by this response
+            let (requester, wait) = {
+                let connection = connection.lock().await;
+                // Bound the wait by the connection timeout so a silent pool
+                // can't hang a service without a timeout layer
+                let wait = Duration::from_secs(connection.config().timeout);
+                (connection.requester(), wait)
+            };
+            requester
+                .send_request_once(&request.method, request.params, wait)
+                .await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tower::{ServiceBuilder, ServiceExt};
+
+    async fn service(respond: bool) -> RequestService {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read_half, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(read_half);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: JsonRpcRequest = serde_json::from_str(line.trim()).unwrap();
+                line.clear();
+                if respond {
+                    let response = JsonRpcResponse::ok(request.id, json!(request.method));
+                    let response = serde_json::to_string(&response).unwrap();
+                    writer
+                        .write_all(format!("{}\n", response).as_bytes())
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let connection = StratumConnection::new(addr.ip().to_string(), addr.port())
+            .await
+            .unwrap();
+        RequestService::new(Arc::new(Mutex::new(connection)))
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let response = service(true)
+            .await
+            .oneshot(JsonRpcRequest::new(
+                0,
+                "mining.extranonce.subscribe",
+                vec![],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.result, Some(json!("mining.extranonce.subscribe")));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_layer() {
+        let mut service = ServiceBuilder::new()
+            .timeout(Duration::from_millis(50))
+            .service(service(false).await);
+
+        let result = service
+            .ready()
+            .await
+            .unwrap()
+            .call(JsonRpcRequest::new(0, "mining.ping", vec![]))
+            .await;
+        assert!(result.unwrap_err().is::<tower::timeout::error::Elapsed>());
+    }
+
+    #[tokio::test]
+    async fn test_call_releases_connection() {
+        let mut service = service(false).await;
+        let connection = service.connection.clone();
+        let call = tokio::spawn(service.call(JsonRpcRequest::new(0, "mining.ping", vec![])));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!call.is_finished());
+        assert!(connection.try_lock().is_ok());
+        call.abort();
+    }
+}
@@ -0,0 +1,200 @@
+use crate::stratum::error::StratumError;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+
+/// Default SOCKS port of a local Tor daemon
+pub const TOR_SOCKS_ADDR: &str = "127.0.0.1:9050";
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// SOCKS5 proxy pool connections are made through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    /// `host:port` of the proxy
+    pub addr: String,
+    /// Let the proxy resolve pool host names (SOCKS5h), so no DNS query
+    /// leaves this machine; required for `.onion` hosts
+    pub remote_dns: bool,
+}
+
+impl Socks5Proxy {
+    /// Proxy at `addr` resolving host names itself, like Tor
+    pub fn remote_dns(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            remote_dns: true,
+        }
+    }
+
+    /// Open a connection to `host` on `port` through the proxy
+    ///
+    /// `.onion` hosts are refused unless the proxy resolves names, since
+    /// resolving them locally fails and leaks the lookup.
+    pub(crate) async fn connect(&self, host: &str, port: u16) -> Result<TcpStream, StratumError> {
+        let target = format!("{}:{}", host, port);
+        let failed = |reason: String| {
+            StratumError::Connection(format!(
+                "Failed to connect to {} through proxy {} - {}",
+                target, self.addr, reason
+            ))
+        };
+
+        let address = if self.remote_dns {
+            if host.len() > u8::MAX as usize {
+                return Err(StratumError::Config(format!("Host name {} too long", host)));
+            }
+            let mut address = vec![ATYP_DOMAIN, host.len() as u8];
+            address.extend_from_slice(host.as_bytes());
+            address
+        } else if is_onion(host) {
+            return Err(onion_without_proxy(host));
+        } else {
+            let ip = lookup_host(&target)
+                .await
+                .map_err(|e| failed(e.to_string()))?
+                .next()
+                .ok_or_else(|| failed("no address found".into()))?
+                .ip();
+            match ip {
+                std::net::IpAddr::V4(ip) => [&[ATYP_IPV4][..], &ip.octets()].concat(),
+                std::net::IpAddr::V6(ip) => [&[ATYP_IPV6][..], &ip.octets()].concat(),
+            }
+        };
+
+        let mut stream = TcpStream::connect(&self.addr)
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        let io = |e: std::io::Error| failed(e.to_string());
+
+        stream.write_all(&[VERSION, 1, NO_AUTH]).await.map_err(io)?;
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await.map_err(io)?;
+        if choice != [VERSION, NO_AUTH] {
+            return Err(failed("proxy requires authentication".into()));
+        }
+
+        let mut request = vec![VERSION, CONNECT, 0];
+        request.extend_from_slice(&address);
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await.map_err(io)?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await.map_err(io)?;
+        if reply[1] != 0 {
+            return Err(failed(reply_error(reply[1]).into()));
+        }
+        // Skip the address the proxy bound to
+        let bound = match reply[3] {
+            ATYP_IPV4 => 4,
+            ATYP_IPV6 => 16,
+            ATYP_DOMAIN => stream.read_u8().await.map_err(io)? as usize,
+            other => return Err(failed(format!("invalid address type {}", other))),
+        };
+        let mut skipped = vec![0u8; bound + 2];
+        stream.read_exact(&mut skipped).await.map_err(io)?;
+        Ok(stream)
+    }
+}
+
+/// Whether `host` is a Tor onion service address
+pub fn is_onion(host: &str) -> bool {
+    host.trim_end_matches('.')
+        .rsplit('.')
+        .next()
+        .is_some_and(|tld| tld.eq_ignore_ascii_case("onion"))
+}
+
+pub(crate) fn onion_without_proxy(host: &str) -> StratumError {
+    StratumError::Config(format!(
+        "Connecting to {} requires a SOCKS5 proxy resolving host names, see ConnectionConfig::tor",
+        host
+    ))
+}
+
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Proxy accepting one CONNECT, answering with `reply` and returning the
+    /// requested address
+    async fn proxy(reply: u8) -> (String, tokio::task::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            socket.read_exact(&mut greeting).await.unwrap();
+            socket.write_all(&[VERSION, NO_AUTH]).await.unwrap();
+            let mut header = [0u8; 5];
+            socket.read_exact(&mut header).await.unwrap();
+            let mut address = vec![0u8; header[4] as usize + 2];
+            socket.read_exact(&mut address).await.unwrap();
+            socket
+                .write_all(&[VERSION, reply, 0, ATYP_IPV4, 127, 0, 0, 1, 0x0d, 0x05])
+                .await
+                .unwrap();
+            socket.write_all(b"hello").await.unwrap();
+            address
+        });
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn test_connect_resolves_remotely() {
+        let (addr, requested) = proxy(0).await;
+        let host = "pool2abcdefghijklmnop.onion";
+        let mut stream = Socks5Proxy::remote_dns(addr)
+            .connect(host, 3333)
+            .await
+            .unwrap();
+        let mut greeting = [0u8; 5];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(&greeting, b"hello");
+
+        let requested = requested.await.unwrap();
+        assert_eq!(&requested[..host.len()], host.as_bytes());
+        assert_eq!(&requested[host.len()..], &3333u16.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_connect_refused() {
+        let (addr, _) = proxy(5).await;
+        let err = Socks5Proxy::remote_dns(addr)
+            .connect("pool.example.com", 3333)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("connection refused"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_onion_requires_remote_dns() {
+        let proxy = Socks5Proxy {
+            addr: TOR_SOCKS_ADDR.into(),
+            remote_dns: false,
+        };
+        let err = proxy.connect("pool.onion", 3333).await.unwrap_err();
+        assert!(matches!(err, StratumError::Config(_)));
+        assert!(is_onion("pool2abcdef.ONION."));
+        assert!(!is_onion("onion.example.com"));
+    }
+}
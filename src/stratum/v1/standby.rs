@@ -0,0 +1,99 @@
+use super::connection::{Requester, StratumConnection};
+use super::protocol::{JsonRpcResponse, MINING_SUBMIT};
+use crate::stratum::error::StratumError;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// Hot-standby connection to the same pool session used to race slow submits
+#[derive(Clone)]
+pub(crate) struct StandbyLink {
+    pub(crate) connection: Arc<Mutex<StratumConnection>>,
+    /// Primary submit latency after which the share is also sent over the standby
+    pub(crate) latency_threshold: Duration,
+}
+
+fn spawn_submit(
+    requester: Requester,
+    params: Vec<Value>,
+) -> JoinHandle<Result<JsonRpcResponse, StratumError>> {
+    tokio::spawn(async move { requester.send_request(MINING_SUBMIT, params).await })
+}
+
+fn join_result(
+    result: Result<Result<JsonRpcResponse, StratumError>, tokio::task::JoinError>,
+) -> Result<JsonRpcResponse, StratumError> {
+    result.map_err(|e| StratumError::Connection(format!("Submit task failed - {}", e)))?
+}
+
+/// Report the outcome of the submit that lost the race
+///
+/// The same share reached the pool twice, so a duplicate rejection on the losing
+/// connection is expected and must not be counted against the session.
+fn finish_loser(
+    loser: JoinHandle<Result<JsonRpcResponse, StratumError>>,
+    loser_name: &'static str,
+) {
+    tokio::spawn(async move {
+        match join_result(loser.await) {
+            Ok(response) => {
+                log::debug!(target: "stratum", "Ignoring {loser_name} submit result after race: {response}");
+            }
+            Err(err) => {
+                log::debug!(target: "stratum", "Ignoring {loser_name} submit error after race: {err}");
+            }
+        }
+    });
+}
+
+/// Submit a share over the primary connection, racing the standby if the
+/// primary doesn't answer within the standby's latency threshold
+///
+/// The first successful response wins. The losing request is left to complete in
+/// the background so its response is consumed and never mistaken for the answer
+/// to a later request.
+pub(crate) async fn submit_racing(
+    primary: Requester,
+    standby: StandbyLink,
+    params: Vec<Value>,
+) -> Result<JsonRpcResponse, StratumError> {
+    let mut primary_task = spawn_submit(primary, params.clone());
+
+    tokio::select! {
+        result = &mut primary_task => return join_result(result),
+        _ = tokio::time::sleep(standby.latency_threshold) => {}
+    }
+
+    log::warn!(
+        target: "stratum",
+        "Primary submit exceeded {:?}, racing standby connection",
+        standby.latency_threshold
+    );
+    let standby_requester = standby.connection.lock().await.requester();
+    let mut standby_task = spawn_submit(standby_requester, params);
+
+    tokio::select! {
+        result = &mut primary_task => match join_result(result) {
+            Ok(response) => {
+                finish_loser(standby_task, "standby");
+                Ok(response)
+            }
+            Err(err) => {
+                log::warn!(target: "stratum", "Primary submit failed, waiting for standby: {err}");
+                join_result(standby_task.await).or(Err(err))
+            }
+        },
+        result = &mut standby_task => match join_result(result) {
+            Ok(response) => {
+                finish_loser(primary_task, "primary");
+                Ok(response)
+            }
+            Err(err) => {
+                log::warn!(target: "stratum", "Standby submit failed, waiting for primary: {err}");
+                join_result(primary_task.await)
+            }
+        },
+    }
+}
@@ -0,0 +1,244 @@
+use crate::stratum::error::StratumError;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use tokio_rustls::TlsConnector;
+
+/// TLS options for `stratum+ssl://` pool connections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Name sent through SNI and checked against the pool certificate,
+    /// defaults to the connection host
+    pub server_name: Option<String>,
+    /// DER encoded certificates trusted in addition to the bundled web PKI
+    /// roots, e.g. a pool's private CA
+    pub extra_roots: Vec<Vec<u8>>,
+    /// Skip certificate validation entirely
+    ///
+    /// Only meant for pools with self-signed certificates on trusted
+    /// networks; the connection is then open to interception.
+    pub accept_invalid_certs: bool,
+}
+
+impl TlsConfig {
+    fn client_config(&self) -> Result<ClientConfig, StratumError> {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| StratumError::Config(format!("Invalid TLS configuration - {}", e)))?;
+
+        let config = if self.accept_invalid_certs {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+                .with_no_client_auth()
+        } else {
+            let mut roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            for der in &self.extra_roots {
+                roots.add(CertificateDer::from(der.clone())).map_err(|e| {
+                    StratumError::Config(format!("Invalid root certificate - {}", e))
+                })?;
+            }
+            builder.with_root_certificates(roots).with_no_client_auth()
+        };
+        Ok(config)
+    }
+
+    /// Run the TLS handshake over an established TCP connection to `host`
+    pub(crate) async fn connect(
+        &self,
+        stream: TcpStream,
+        host: &str,
+    ) -> Result<TlsStream<TcpStream>, StratumError> {
+        let name = self.server_name.as_deref().unwrap_or(host);
+        let server_name = ServerName::try_from(name.to_string()).map_err(|e| {
+            StratumError::Config(format!("Invalid TLS server name {} - {}", name, e))
+        })?;
+
+        TlsConnector::from(Arc::new(self.client_config()?))
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| {
+                StratumError::Connection(format!("TLS handshake with {} failed - {}", host, e))
+            })
+    }
+}
+
+/// Certificate verifier accepting any certificate, still checking signatures
+/// so the handshake itself stays well-formed
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::v1::connection::{ConnectionConfig, StratumConnection};
+    use serde_json::json;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::PrivateKeyDer;
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    const CA: &str = include_str!("../../../tests/data/tls/ca.pem");
+    const CERT: &str = include_str!("../../../tests/data/tls/localhost.pem");
+    const KEY: &str = include_str!("../../../tests/data/tls/localhost.key");
+
+    /// TLS pool answering every request with `true`
+    async fn tls_server() -> u16 {
+        let certs = CertificateDer::pem_slice_iter(CERT.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_slice(KEY.as_bytes()).unwrap();
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let Ok(stream) = acceptor.accept(socket).await else {
+                    continue;
+                };
+                let (read_half, mut writer) = tokio::io::split(stream);
+                let mut reader = BufReader::new(read_half);
+                let mut line = String::new();
+                while matches!(reader.read_line(&mut line).await, Ok(n) if n > 0) {
+                    let request: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+                    line.clear();
+                    let response = json!({"id": request["id"], "result": true, "error": null});
+                    writer
+                        .write_all(format!("{}\n", response).as_bytes())
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+        port
+    }
+
+    fn config(tls: TlsConfig) -> ConnectionConfig {
+        ConnectionConfig {
+            tls: Some(tls),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_extra_root() {
+        let port = tls_server().await;
+        let ca = CertificateDer::from_pem_slice(CA.as_bytes()).unwrap();
+        let tls = TlsConfig {
+            server_name: Some("localhost".into()),
+            extra_roots: vec![ca.to_vec()],
+            ..Default::default()
+        };
+
+        let conn = StratumConnection::with_config("127.0.0.1".into(), port, config(tls))
+            .await
+            .unwrap();
+        assert!(conn.is_tls());
+        let response = conn.send_request("mining.ping", vec![]).await.unwrap();
+        assert_eq!(response.result, Some(json!(true)));
+    }
+
+    #[tokio::test]
+    async fn test_certificate_validation() {
+        let port = tls_server().await;
+
+        // Unknown CA
+        let tls = TlsConfig {
+            server_name: Some("localhost".into()),
+            ..Default::default()
+        };
+        let result = StratumConnection::with_config("127.0.0.1".into(), port, config(tls)).await;
+        assert!(matches!(result, Err(StratumError::Connection(_))));
+
+        // Name mismatch
+        let ca = CertificateDer::from_pem_slice(CA.as_bytes()).unwrap();
+        let tls = TlsConfig {
+            server_name: Some("pool.example.com".into()),
+            extra_roots: vec![ca.to_vec()],
+            ..Default::default()
+        };
+        let result = StratumConnection::with_config("127.0.0.1".into(), port, config(tls)).await;
+        assert!(matches!(result, Err(StratumError::Connection(_))));
+
+        let tls = TlsConfig {
+            accept_invalid_certs: true,
+            ..Default::default()
+        };
+        let conn = StratumConnection::with_config("127.0.0.1".into(), port, config(tls))
+            .await
+            .unwrap();
+        assert!(conn.send_request("mining.ping", vec![]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ssl_scheme() {
+        let port = tls_server().await;
+        // The scheme alone enables TLS, which fails on the test certificate
+        let result = StratumConnection::new("stratum+ssl://localhost".into(), port).await;
+        assert!(matches!(result, Err(StratumError::Connection(_))));
+    }
+}
@@ -0,0 +1,154 @@
+use super::connection::{ConnectionConfig, Reader, TcpTransport, Transport, Writer};
+use crate::stratum::error::StratumError;
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, DuplexStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Bytes buffered between the connection and the WebSocket in each direction
+const BRIDGE_BUFFER: usize = 64 * 1024;
+
+/// Stratum over WebSocket, for `ws://` and `wss://` pools
+///
+/// Each JSON-RPC message travels as one text frame. The WebSocket runs on
+/// top of [`TcpTransport`], so proxy and TLS options apply as for
+/// `stratum+tcp://` and `stratum+ssl://` pools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketTransport {
+    /// Path requested in the WebSocket handshake, e.g. `/stratum`
+    pub path: String,
+}
+
+impl WebSocketTransport {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Default for WebSocketTransport {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn open(
+        &self,
+        host: &str,
+        port: u16,
+        config: &ConnectionConfig,
+    ) -> Result<(Reader, Writer), StratumError> {
+        let (reader, writer) = TcpTransport.open(host, port, config).await?;
+        #[cfg(feature = "tls")]
+        let scheme = if config.tls.is_some() { "wss" } else { "ws" };
+        #[cfg(not(feature = "tls"))]
+        let scheme = "ws";
+        let url = format!("{}://{}:{}{}", scheme, host, port, self.path);
+        let (socket, _) =
+            tokio_tungstenite::client_async(url.as_str(), tokio::io::join(reader, writer))
+                .await
+                .map_err(|e| {
+                    StratumError::Connection(format!(
+                        "WebSocket handshake with {} failed - {}",
+                        url, e
+                    ))
+                })?;
+
+        let (local, remote) = tokio::io::duplex(BRIDGE_BUFFER);
+        tokio::spawn(bridge(socket, remote));
+        let (reader, writer) = tokio::io::split(local);
+        Ok((Box::new(reader), Box::new(writer)))
+    }
+}
+
+/// Relay lines written to `lines` as text frames and frames received as
+/// lines, until either side closes
+async fn bridge<S>(socket: WebSocketStream<S>, lines: DuplexStream)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut sink, mut frames) = socket.split();
+    let (read_half, mut write_half) = tokio::io::split(lines);
+    let mut outgoing = BufReader::new(read_half).lines();
+    loop {
+        tokio::select! {
+            line = outgoing.next_line() => match line {
+                Ok(Some(line)) => {
+                    if sink.send(Message::Text(line)).await.is_err() {
+                        break;
+                    }
+                }
+                _ => {
+                    let _ = sink.close().await;
+                    break;
+                }
+            },
+            frame = frames.next() => {
+                let mut text = match frame {
+                    Some(Ok(Message::Text(text))) => text.into_bytes(),
+                    Some(Ok(Message::Binary(bytes))) => bytes,
+                    // Pings are answered by the WebSocket itself
+                    Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => continue,
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                };
+                if !text.ends_with(b"\n") {
+                    text.push(b'\n');
+                }
+                if write_half.write_all(&text).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    // Dropping the halves ends the connection's reads with EOF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::v1::connection::StratumConnection;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_request_over_websocket() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (path_tx, path_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            // The handshake's error type is fixed by tungstenite
+            #[allow(clippy::result_large_err)]
+            let callback =
+                |request: &tokio_tungstenite::tungstenite::handshake::server::Request, response| {
+                    path_tx.send(request.uri().path().to_string()).unwrap();
+                    Ok(response)
+                };
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let response = json!({"id": request["id"], "result": true, "error": null});
+                // Frames need not end in a newline
+                socket
+                    .send(Message::Text(response.to_string()))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let conn = StratumConnection::with_config(
+            "ws://127.0.0.1/stratum".into(),
+            port,
+            ConnectionConfig::default(),
+        )
+        .await
+        .unwrap();
+        let response = conn.send_request("mining.ping", vec![]).await.unwrap();
+        assert_eq!(response.result, Some(json!(true)));
+        assert_eq!(path_rx.await.unwrap(), "/stratum");
+    }
+}
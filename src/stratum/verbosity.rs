@@ -0,0 +1,139 @@
+use crate::stratum::events::EventKind;
+use log::{Level, LevelFilter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Area of client activity whose logs and events are filtered together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Connecting, disconnecting, authorization and pool messages
+    Connection,
+    /// Job notifications and handing jobs to the miner
+    Jobs,
+    /// Share discovery and submission
+    Shares,
+    /// Difficulty and extranonce changes
+    Difficulty,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Connection,
+        Category::Jobs,
+        Category::Shares,
+        Category::Difficulty,
+    ];
+
+    /// Log target of this category, e.g. `stratum::shares`
+    pub const fn target(self) -> &'static str {
+        match self {
+            Category::Connection => "stratum::connection",
+            Category::Jobs => "stratum::jobs",
+            Category::Shares => "stratum::shares",
+            Category::Difficulty => "stratum::difficulty",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Per-category verbosity of a client's logs and events
+///
+/// Filtering happens where logs and events are emitted, before the logger's
+/// own filter, so a production deployment can keep connection logs while
+/// silencing per-share ones. Events count as `Info`: they are dropped when
+/// their category is set below it. Everything is enabled by default.
+#[derive(Debug)]
+pub struct Verbosity {
+    levels: [AtomicUsize; Category::ALL.len()],
+}
+
+impl Default for Verbosity {
+    fn default() -> Self {
+        Self {
+            levels: Category::ALL.map(|_| AtomicUsize::new(LevelFilter::Trace as usize)),
+        }
+    }
+}
+
+impl Verbosity {
+    /// Verbosity with everything enabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the most verbose level emitted for `category`
+    pub fn set(&self, category: Category, level: LevelFilter) {
+        self.levels[category.index()].store(level as usize, Ordering::Relaxed);
+    }
+
+    /// Set the most verbose level emitted for every category
+    pub fn set_all(&self, level: LevelFilter) {
+        for category in Category::ALL {
+            self.set(category, level);
+        }
+    }
+
+    /// Most verbose level emitted for `category`
+    pub fn level(&self, category: Category) -> LevelFilter {
+        let level = self.levels[category.index()].load(Ordering::Relaxed);
+        LevelFilter::iter()
+            .find(|filter| *filter as usize == level)
+            .unwrap_or(LevelFilter::Trace)
+    }
+
+    /// Whether a log of `category` at `level` is emitted
+    pub fn enabled(&self, category: Category, level: Level) -> bool {
+        level <= self.level(category)
+    }
+
+    /// Whether events of `kind` are emitted
+    pub fn event_enabled(&self, kind: EventKind) -> bool {
+        kind.category()
+            .is_none_or(|category| self.enabled(category, Level::Info))
+    }
+}
+
+/// Log under a category's target if its verbosity allows the level
+macro_rules! log_at {
+    ($verbosity:expr, $category:expr, $level:expr, $($arg:tt)+) => {{
+        let category = $category;
+        if $verbosity.enabled(category, $level) {
+            log::log!(target: category.target(), $level, $($arg)+);
+        }
+    }};
+}
+pub(crate) use log_at;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let verbosity = Verbosity::new();
+        assert!(verbosity.enabled(Category::Shares, Level::Trace));
+
+        verbosity.set(Category::Shares, LevelFilter::Warn);
+        assert_eq!(verbosity.level(Category::Shares), LevelFilter::Warn);
+        assert!(verbosity.enabled(Category::Shares, Level::Error));
+        assert!(!verbosity.enabled(Category::Shares, Level::Info));
+        assert!(verbosity.enabled(Category::Connection, Level::Info));
+
+        verbosity.set_all(LevelFilter::Off);
+        assert!(!verbosity.enabled(Category::Connection, Level::Error));
+    }
+
+    #[test]
+    fn test_event_filtering() {
+        let verbosity = Verbosity::new();
+        verbosity.set(Category::Shares, LevelFilter::Warn);
+        assert!(!verbosity.event_enabled(EventKind::ShareFound));
+        assert!(verbosity.event_enabled(EventKind::Connected));
+
+        // Statistics aren't tied to a category
+        verbosity.set_all(LevelFilter::Off);
+        assert!(verbosity.event_enabled(EventKind::StatsTick));
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+/// Limits after which the mining pipeline is considered wedged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// How often the pipeline is checked
+    pub check_interval: Duration,
+    /// Time connected and not paused without a job handed to the miner
+    pub job_stall: Duration,
+    /// Time a miner task may ignore a request to stop
+    pub miner_stall: Duration,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(30),
+            job_stall: Duration::from_secs(5 * 60),
+            miner_stall: Duration::from_secs(30),
+        }
+    }
+}
+
+/// When to check that a quiet pool connection is still alive
+///
+/// Pools and the NAT boxes in between drop idle connections without
+/// closing them, which the client otherwise only notices when its next
+/// submit times out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// Silence after which the pool is pinged
+    pub idle: Duration,
+    /// Time the pool has to answer the ping before the connection is
+    /// considered stale
+    pub timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(90),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Why the watchdog restarted the mining pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StallReason {
+    /// No job was handed to the miner for `since`
+    NoJobDispatched { since: Duration },
+    /// A miner task has ignored a request to stop for `since`
+    MinerUnresponsive { since: Duration },
+}
+
+impl WatchdogConfig {
+    /// Check the pipeline state against the limits
+    ///
+    /// `since_dispatch` is the time since a job was last handed to the miner,
+    /// or since watching started if that's more recent. Job stalls are only
+    /// reported while connected and not paused.
+    pub fn detect(
+        &self,
+        connected: bool,
+        paused: bool,
+        since_dispatch: Duration,
+        unresponsive_miner: Option<Duration>,
+    ) -> Option<StallReason> {
+        if let Some(since) = unresponsive_miner.filter(|since| *since > self.miner_stall) {
+            return Some(StallReason::MinerUnresponsive { since });
+        }
+        (connected && !paused && since_dispatch > self.job_stall).then_some(
+            StallReason::NoJobDispatched {
+                since: since_dispatch,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let config = WatchdogConfig::default();
+        let minute = Duration::from_secs(60);
+
+        assert_eq!(config.detect(true, false, minute, None), None);
+        assert_eq!(
+            config.detect(true, false, 10 * minute, None),
+            Some(StallReason::NoJobDispatched { since: 10 * minute })
+        );
+        // Paused or disconnected clients aren't expected to receive jobs
+        assert_eq!(config.detect(true, true, 10 * minute, None), None);
+        assert_eq!(config.detect(false, false, 10 * minute, None), None);
+
+        assert_eq!(
+            config.detect(false, true, minute, Some(minute)),
+            Some(StallReason::MinerUnresponsive { since: minute })
+        );
+        assert_eq!(
+            config.detect(true, false, minute, Some(Duration::from_secs(1))),
+            None
+        );
+    }
+}
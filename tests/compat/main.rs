@@ -0,0 +1,73 @@
+//! End-to-end tests against real pool software running in containers
+//!
+//! Catches dialect differences the mock servers in the other tests can't.
+//! Requires Docker and is skipped unless `STRATUM_COMPAT_TESTS` is set. By
+//! default a pinned public-pool image mines on a Bitcoin Core regtest node:
+//!
+//! ```text
+//! STRATUM_COMPAT_TESTS=1 cargo test --features compat-tests --test compat
+//! ```
+//!
+//! See [`pool::PoolConfig`] for testing other pool software.
+
+mod pool;
+
+use pool::{Pool, PoolConfig};
+use rust_stratum::stratum::prelude::*;
+use rust_stratum::stratum::v1::jobs::TestMiner;
+use std::error::Error;
+use tokio::time::{timeout, Duration};
+
+/// How long to wait for the pool's first job
+const FIRST_JOB_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[tokio::test]
+async fn test_pool_mining_flow() -> Result<(), Box<dyn Error>> {
+    let Some(config) = PoolConfig::from_env() else {
+        return Ok(());
+    };
+    let pool = Pool::start(config).await?;
+
+    let mut client = StratumV1Client::new(pool.host.clone(), pool.port, TestMiner).await?;
+
+    let subscription = client.subscribe().await?;
+    assert!(!subscription.extranonce1.is_empty());
+    assert!(subscription.extranonce2_size > 0);
+
+    let auth = client.authorize(&pool.username, "x").await?;
+    assert!(auth.authorized, "authorize rejected: {:?}", auth.message);
+
+    // Process notifications until the first job arrives
+    let job = timeout(FIRST_JOB_TIMEOUT, async {
+        loop {
+            client.handle_notifications().await?;
+            if let Some(job) = client.get_current_job().await? {
+                return Ok::<_, StratumError>(job);
+            }
+        }
+    })
+    .await??;
+    assert_eq!(job.prev_hash.len(), 64);
+    assert!(client.get_target().await.is_ok());
+
+    // A random share is almost certainly rejected, but must get an answer
+    let share = Share {
+        job_id: job.job_id.clone(),
+        extranonce2: "00".repeat(subscription.extranonce2_size),
+        ntime: job.ntime.clone(),
+        nonce: "00000000".into(),
+        version_bits: None,
+    };
+    let accepted = client.submit_share(share).await;
+    assert!(
+        matches!(
+            accepted.as_ref().map_err(StratumError::root),
+            Ok(_) | Err(StratumError::Protocol(_) | StratumError::Rpc { .. })
+        ),
+        "submit got no answer: {:?}",
+        accepted
+    );
+
+    client.close().await?;
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use std::error::Error;
+use testcontainers::core::{CmdWaitFor, ExecCommand, IntoContainerPort, WaitFor};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Stratum port the pool listens on inside the container by default
+const DEFAULT_STRATUM_PORT: u16 = 3333;
+
+/// Pool tested against by default, public-pool backed by the regtest node
+const DEFAULT_POOL_IMAGE: &str = "getumbrel/public-pool";
+const DEFAULT_POOL_TAG: &str = "v0.2.2";
+
+/// Bitcoin Core image of the regtest node the default pool mines on
+const NODE_IMAGE: &str = "bitcoin/bitcoin";
+const NODE_TAG: &str = "27.1";
+const NODE_RPC_PORT: u16 = 18443;
+const NODE_RPC_USER: &str = "compat";
+const NODE_RPC_PASSWORD: &str = "compat";
+
+/// Blocks mined before the pool starts, so the node leaves initial block
+/// download and its coinbase outputs mature
+const NODE_BLOCKS: u32 = 101;
+
+/// Default worker name, a regtest address the pool pays out to
+const DEFAULT_USERNAME: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+/// How long the pool gets to accept connections after its container started
+const POOL_START_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pool software under test, configured through the environment
+///
+/// Without `STRATUM_COMPAT_IMAGE` the tests run against a pinned public-pool
+/// image mining on a Bitcoin Core regtest node, started next to it. Any other
+/// image runs on its own and must come with whatever it needs.
+///
+/// - `STRATUM_COMPAT_IMAGE`: image name, e.g. a ckpool image
+/// - `STRATUM_COMPAT_TAG`: image tag, defaults to the pinned tag of the
+///   default image and to `latest` for others
+/// - `STRATUM_COMPAT_PORT`: stratum port inside the container, defaults to 3333
+/// - `STRATUM_COMPAT_READY`: log line printed once the pool accepts miners,
+///   otherwise the stratum port is polled
+/// - `STRATUM_COMPAT_USER`: worker name to authorize with
+pub struct PoolConfig {
+    pub image: String,
+    pub tag: String,
+    pub port: u16,
+    pub ready_message: Option<String>,
+    pub username: String,
+    /// Whether to start the regtest node the default pool mines on
+    pub regtest: bool,
+}
+
+impl PoolConfig {
+    /// Read the configuration, or `None` if compat tests are disabled
+    pub fn from_env() -> Option<Self> {
+        if std::env::var("STRATUM_COMPAT_TESTS").is_err() {
+            eprintln!("STRATUM_COMPAT_TESTS not set, skipping pool compatibility tests");
+            return None;
+        }
+
+        let image = std::env::var("STRATUM_COMPAT_IMAGE").ok();
+        let regtest = image.is_none();
+        let default_tag = if regtest { DEFAULT_POOL_TAG } else { "latest" };
+        Some(Self {
+            image: image.unwrap_or_else(|| DEFAULT_POOL_IMAGE.into()),
+            tag: std::env::var("STRATUM_COMPAT_TAG").unwrap_or_else(|_| default_tag.into()),
+            port: std::env::var("STRATUM_COMPAT_PORT")
+                .ok()
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(DEFAULT_STRATUM_PORT),
+            ready_message: std::env::var("STRATUM_COMPAT_READY").ok(),
+            username: std::env::var("STRATUM_COMPAT_USER")
+                .unwrap_or_else(|_| format!("{DEFAULT_USERNAME}.compat")),
+            regtest,
+        })
+    }
+}
+
+/// A running pool container, with its regtest node if it has one
+pub struct Pool {
+    // Dropping the containers stops them, the pool before its node
+    _container: ContainerAsync<GenericImage>,
+    _node: Option<ContainerAsync<GenericImage>>,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+}
+
+impl Pool {
+    /// Start the configured pool image and wait until it is ready
+    pub async fn start(config: PoolConfig) -> Result<Self, Box<dyn Error>> {
+        // Containers of concurrent runs must not share names
+        let network = format!("stratum-compat-{}", std::process::id());
+
+        let mut image =
+            GenericImage::new(config.image, config.tag).with_exposed_port(config.port.tcp());
+        if let Some(message) = &config.ready_message {
+            image = image.with_wait_for(WaitFor::message_on_stdout(message));
+        }
+        let mut image = image.with_env_var("RUST_LOG", "info");
+
+        let node = if config.regtest {
+            let node_name = format!("{network}-bitcoind");
+            let node = start_node(&network, &node_name).await?;
+            image = image
+                .with_network(&network)
+                .with_env_var("NETWORK", "regtest")
+                .with_env_var("BITCOIN_RPC_URL", format!("http://{node_name}"))
+                .with_env_var("BITCOIN_RPC_PORT", NODE_RPC_PORT.to_string())
+                .with_env_var("BITCOIN_RPC_USER", NODE_RPC_USER)
+                .with_env_var("BITCOIN_RPC_PASSWORD", NODE_RPC_PASSWORD)
+                .with_env_var("STRATUM_PORT", config.port.to_string());
+            Some(node)
+        } else {
+            None
+        };
+
+        let container = image.start().await?;
+        let host = container.get_host().await?.to_string();
+        let port = container.get_host_port_ipv4(config.port).await?;
+        if config.ready_message.is_none() {
+            wait_for_port(&host, port).await?;
+        }
+
+        Ok(Self {
+            _container: container,
+            _node: node,
+            host,
+            port,
+            username: config.username,
+        })
+    }
+}
+
+/// Start a Bitcoin Core regtest node and mine past initial block download
+async fn start_node(
+    network: &str,
+    name: &str,
+) -> Result<ContainerAsync<GenericImage>, Box<dyn Error>> {
+    let rpc_user = format!("-rpcuser={NODE_RPC_USER}");
+    let rpc_password = format!("-rpcpassword={NODE_RPC_PASSWORD}");
+    let node = GenericImage::new(NODE_IMAGE, NODE_TAG)
+        .with_wait_for(WaitFor::message_on_stdout("init message: Done loading"))
+        .with_network(network)
+        .with_container_name(name)
+        .with_cmd([
+            "bitcoind",
+            "-regtest",
+            "-server",
+            "-printtoconsole",
+            "-rpcbind=0.0.0.0",
+            "-rpcallowip=0.0.0.0/0",
+            &rpc_user,
+            &rpc_password,
+        ])
+        .start()
+        .await?;
+
+    let mut mined = node
+        .exec(
+            ExecCommand::new([
+                "bitcoin-cli",
+                "-regtest",
+                &rpc_user,
+                &rpc_password,
+                "generatetoaddress",
+                &NODE_BLOCKS.to_string(),
+                DEFAULT_USERNAME,
+            ])
+            .with_cmd_ready_condition(CmdWaitFor::exit_code(0)),
+        )
+        .await?;
+    if mined.exit_code().await? != Some(0) {
+        let stderr = String::from_utf8_lossy(&mined.stderr_to_vec().await?).into_owned();
+        return Err(format!("Mining regtest blocks failed: {stderr}").into());
+    }
+    Ok(node)
+}
+
+/// Wait until the pool accepts connections on its stratum port
+async fn wait_for_port(host: &str, port: u16) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + POOL_START_TIMEOUT;
+    loop {
+        match TcpStream::connect((host, port)).await {
+            Ok(_) => return Ok(()),
+            Err(err) if Instant::now() >= deadline => {
+                return Err(format!("Pool not listening on {host}:{port}: {err}").into())
+            }
+            Err(_) => sleep(Duration::from_millis(500)).await,
+        }
+    }
+}
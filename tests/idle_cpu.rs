@@ -0,0 +1,72 @@
+//! An idle session must not burn CPU: every background task waits on the
+//! socket, a channel or a timer instead of polling
+//!
+//! Runs as its own test binary, so the process CPU time it reads is the
+//! session's alone.
+
+#![cfg(target_os = "linux")]
+
+use rust_stratum::stratum::testpool::TestPoolServer;
+use rust_stratum::stratum::v1::jobs::TestMiner;
+use rust_stratum::stratum::v1::StratumV1Client;
+use rust_stratum::stratum::watchdog::{KeepaliveConfig, WatchdogConfig};
+use rust_stratum::stratum::StratumClient;
+use std::time::Duration;
+
+/// Time the session is left idle
+const IDLE: Duration = Duration::from_secs(2);
+
+/// CPU time the idle session may use, 5% of one core
+const CPU_BUDGET: Duration = Duration::from_millis(100);
+
+/// User plus system CPU time of this process so far
+fn cpu_time() -> Duration {
+    let stat = std::fs::read_to_string("/proc/self/stat").unwrap();
+    // Fields after the command name, which is parenthesized and may contain spaces
+    let fields: Vec<&str> = stat[stat.rfind(')').unwrap() + 2..]
+        .split_whitespace()
+        .collect();
+    // utime and stime, fields 14 and 15 of proc(5), in clock ticks of 10ms
+    // on every Linux configuration Rust supports
+    let ticks: u64 = fields[11].parse::<u64>().unwrap() + fields[12].parse::<u64>().unwrap();
+    Duration::from_millis(ticks * 10)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_idle_session_uses_no_cpu() {
+    let pool = TestPoolServer::builder()
+        .job_interval(Duration::from_secs(3600))
+        .bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    let mut client = StratumV1Client::new("127.0.0.1".into(), pool.addr().port(), TestMiner)
+        .await
+        .unwrap();
+    client.subscribe().await.unwrap();
+    client.authorize("rig1", "x").await.unwrap();
+
+    // Everything a long-running session typically has going
+    client.start_auto_submit().await.unwrap();
+    client.set_auto_reconnect(true).await;
+    client.start_dispatcher().await;
+    client.set_keepalive(Some(KeepaliveConfig::default())).await;
+    client.set_watchdog(Some(WatchdogConfig::default())).await;
+    client
+        .set_stats_interval(Some(Duration::from_secs(60)))
+        .await;
+
+    // Let the first job reach the miner and its share be submitted
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    let before = cpu_time();
+    tokio::time::sleep(IDLE).await;
+    let used = cpu_time() - before;
+    assert!(
+        used <= CPU_BUDGET,
+        "Idle session used {:?} of CPU in {:?}",
+        used,
+        IDLE
+    );
+
+    client.shutdown().await.unwrap();
+}
@@ -1,19 +1,29 @@
 use serde_json::json;
 use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     net::TcpListener,
-    time::Duration,
+    sync::{broadcast::error::TryRecvError, Mutex},
+    time::{timeout, Duration},
 };
 
 // Import from our crate
 use rust_stratum::stratum::v1::jobs::TestMiner;
 use rust_stratum::stratum::{
-    create_client,
-    types::{Share, StratumVersion},
+    create_client, create_client_from_url,
+    error::StratumError,
+    events::StratumEvent,
+    stats::LATENCY_SAMPLE_WINDOW,
+    testing::SubmitExpectations,
+    types::{RejectReason, Share, StratumVersion},
+    url::PoolUrl,
+    v1::StratumV1Client,
+    StratumClient,
 };
 
-async fn setup_test_server(difficulty: f64) -> (TcpListener, String, u16) {
+async fn setup_test_server(_difficulty: f64) -> (TcpListener, String, u16) {
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
     println!("Test server listening on port {}", addr.port());
@@ -22,12 +32,20 @@ async fn setup_test_server(difficulty: f64) -> (TcpListener, String, u16) {
 
 #[tokio::test]
 async fn test_full_mining_cycle() -> Result<(), Box<dyn Error>> {
-    let difficulty = 2.0;
+    // Low enough for the nonce-0 share below to meet the target
+    let difficulty = 1e-10;
     let (listener, host, port) = setup_test_server(difficulty).await;
+    let expectations = Arc::new(
+        SubmitExpectations::new()
+            .worker("test.worker1")
+            .extranonce2_size(4)
+            .job("job1"),
+    );
+    let server_expectations = expectations.clone();
 
     // Spawn test server and keep handle
     let server = tokio::spawn(async move {
-        let (mut socket, _) = listener.accept().await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
         let (read_half, write_half) = socket.into_split();
         let mut reader = BufReader::new(read_half);
         let mut writer = write_half;
@@ -43,8 +61,7 @@ async fn test_full_mining_cycle() -> Result<(), Box<dyn Error>> {
                     ["mining.set_difficulty", "1"],
                     ["mining.notify", "1"]
                 ],
-                "extranonce1",
-                "extranonce2",
+                "08000002",
                 4
             ],
             "error": null
@@ -75,7 +92,7 @@ async fn test_full_mining_cycle() -> Result<(), Box<dyn Error>> {
                 "00000000deadbeef00000000deadbeef00000000deadbeef00000000deadbeef",
                 "01000000",
                 "02000000",
-                ["1234567890abcdef"],
+                ["1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"],
                 "00000001",
                 "1d00ffff",
                 "60509af9",
@@ -100,6 +117,7 @@ async fn test_full_mining_cycle() -> Result<(), Box<dyn Error>> {
         // Handle share submission
         buf.clear();
         reader.read_line(&mut buf).await.unwrap();
+        server_expectations.check_request(&serde_json::from_str(&buf).unwrap());
         let submit_response = json!({
             "id": 3,
             "result": true,
@@ -162,10 +180,11 @@ async fn test_full_mining_cycle() -> Result<(), Box<dyn Error>> {
     // Submit share
     let job = client.get_current_job().await?.unwrap();
     let share = Share {
-        job_id: job.job_id,
+        job_id: job.job_id.clone(),
         extranonce2: "00000000".to_string(),
-        ntime: job.ntime,
+        ntime: job.ntime.clone(),
         nonce: "00000000".to_string(),
+        version_bits: None,
     };
 
     let accepted = client.submit_share(share).await?;
@@ -173,6 +192,8 @@ async fn test_full_mining_cycle() -> Result<(), Box<dyn Error>> {
 
     // Wait for server to finish
     server.await?;
+    assert_eq!(expectations.checked(), 1);
+    expectations.verify();
 
     Ok(())
 }
@@ -183,7 +204,7 @@ async fn test_reconnection() -> Result<(), Box<dyn Error>> {
 
     // Spawn test server 1 and keep handle
     let server1 = tokio::spawn(async move {
-        let (mut socket, _) = listener.accept().await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
         let (read_half, write_half) = socket.into_split();
         let mut reader = BufReader::new(read_half);
         let mut writer = write_half;
@@ -288,7 +309,7 @@ async fn test_reconnection() -> Result<(), Box<dyn Error>> {
     let server2 = tokio::spawn(async move {
         // Signal that we have server 2 address
         let _ = tx.send((host, port));
-        let (mut socket, _) = listener.accept().await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
         let (read_half, write_half) = socket.into_split();
         let mut reader = BufReader::new(read_half);
         let mut writer = write_half;
@@ -298,7 +319,7 @@ async fn test_reconnection() -> Result<(), Box<dyn Error>> {
         reader.read_line(&mut buf).await.unwrap();
 
         let subscribe_response = json!({
-            "id": 3,
+            "id": 1,
             "result": [
                 [
                     ["mining.set_difficulty", "1"],
@@ -318,7 +339,7 @@ async fn test_reconnection() -> Result<(), Box<dyn Error>> {
         buf.clear();
         reader.read_line(&mut buf).await.unwrap();
         let auth_response = json!({
-            "id": 4,
+            "id": 2,
             "result": true,
             "error": null
         });
@@ -389,6 +410,51 @@ async fn test_reconnection() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_create_client_from_url() -> Result<(), Box<dyn Error>> {
+    let (listener, host, port) = setup_test_server(1.0).await;
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read_half, mut writer) = socket.into_split();
+        let mut lines = BufReader::new(read_half).lines();
+        let mut methods = Vec::new();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+            let result = match request["method"].as_str() {
+                Some("mining.subscribe") => json!([[["mining.notify", "1"]], "f000000f", 4]),
+                _ => json!(true),
+            };
+            methods.push((request["method"].clone(), request["params"].clone()));
+            let response = json!({"id": request["id"], "result": result, "error": null});
+            writer
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+        }
+        methods
+    });
+
+    let url: PoolUrl = format!("stratum+tcp://wallet.worker1:x@{}:{}", host, port).parse()?;
+    let mut client = create_client_from_url(&url, TestMiner).await?;
+    client.close().await?;
+
+    let methods = server.await?;
+    assert_eq!(methods[0].0, "mining.subscribe");
+    assert_eq!(
+        methods[1],
+        (json!("mining.authorize"), json!(["wallet.worker1", "x"]))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_client_v2_unsupported() -> Result<(), Box<dyn Error>> {
+    let url: PoolUrl = "stratum2+tcp://pool.example.com:3336".parse()?;
+    let result = create_client_from_url(&url, TestMiner).await;
+    assert!(matches!(result, Err(StratumError::Config(_))));
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_error_handling() -> Result<(), Box<dyn Error>> {
     // Try connecting to invalid address
@@ -411,7 +477,7 @@ async fn test_error_handling() -> Result<(), Box<dyn Error>> {
 
     // Spawn test server and keep handle
     let server = tokio::spawn(async move {
-        let (mut socket, _) = listener.accept().await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
         let (read_half, write_half) = socket.into_split();
         let mut reader = BufReader::new(read_half);
         let mut writer = write_half;
@@ -450,3 +516,235 @@ async fn test_error_handling() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Mock pool for the soak test: answers every request, floods jobs, changes
+/// difficulty and drops the connection after every `drop_every` accepted shares
+async fn soak_pool(listener: TcpListener, drop_every: u64, accepted: Arc<AtomicU64>) {
+    let mut next_prev_hash = 0u64;
+    loop {
+        let Ok((socket, _)) = listener.accept().await else {
+            return;
+        };
+        let (read_half, write_half) = socket.into_split();
+        let mut reader = BufReader::new(read_half);
+        let writer = Arc::new(Mutex::new(write_half));
+
+        // Job floods with a vardiff change after each one
+        let flood_writer = writer.clone();
+        next_prev_hash += 1;
+        let first_block = next_prev_hash * 1_000_000;
+        let flood = tokio::spawn(async move {
+            for block in first_block.. {
+                let mut burst = String::new();
+                for job in 0..50 {
+                    let notify = json!({
+                        "method": "mining.notify",
+                        "params": [
+                            format!("{:x}-{}", block, job),
+                            format!("{:064x}", block),
+                            "01000000",
+                            "02000000",
+                            ["1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"],
+                            "20000000",
+                            "1d00ffff",
+                            "60509af9",
+                            job == 0
+                        ]
+                    });
+                    burst.push_str(&format!("{}\n", notify));
+                }
+                // Vardiff low enough for the client's nonce-0 shares to pass
+                // local validation
+                let difficulty = json!({
+                    "method": "mining.set_difficulty",
+                    "params": [1e-12 * (1 << (block % 8)) as f64]
+                });
+                burst.push_str(&format!("{}\n", difficulty));
+                if writer_write(&flood_writer, &burst).await.is_err() {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        });
+
+        let mut line = String::new();
+        while matches!(reader.read_line(&mut line).await, Ok(n) if n > 0) {
+            let request: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+            line.clear();
+            let result = match request["method"].as_str() {
+                Some("mining.subscribe") => {
+                    json!([[["mining.notify", "soak"]], "08000002", 4])
+                }
+                _ => json!(true),
+            };
+            let response = json!({"id": request["id"], "result": result, "error": null});
+            if writer_write(&writer, &format!("{}\n", response))
+                .await
+                .is_err()
+            {
+                break;
+            }
+
+            if request["method"] == "mining.submit" {
+                let count = accepted.fetch_add(1, Ordering::SeqCst) + 1;
+                if count.is_multiple_of(drop_every) {
+                    // Injected disconnect, right after the response so no
+                    // share is left in doubt
+                    break;
+                }
+            }
+        }
+        flood.abort();
+    }
+}
+
+async fn writer_write(
+    writer: &Mutex<tokio::net::tcp::OwnedWriteHalf>,
+    data: &str,
+) -> std::io::Result<()> {
+    writer.lock().await.write_all(data.as_bytes()).await
+}
+
+/// Resident set size in bytes, where the platform exposes it
+fn resident_memory() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+/// Long-run stability harness
+///
+/// Keeps a client connected to a mock pool with injected reconnects, vardiff
+/// changes and job floods, checking that no call hangs, memory stays flat and
+/// the share counts match the pool's. Runs for `STRATUM_SOAK_SECS` seconds,
+/// two hours by default:
+///
+/// ```text
+/// STRATUM_SOAK_SECS=600 cargo test --release --test integration_tests test_soak -- --ignored --nocapture
+/// ```
+#[tokio::test]
+#[ignore = "long-running soak test"]
+async fn test_soak() -> Result<(), Box<dyn Error>> {
+    const STUCK_TIMEOUT: Duration = Duration::from_secs(30);
+    const MAX_MEMORY_GROWTH: u64 = 64 * 1024 * 1024;
+    let duration = Duration::from_secs(
+        std::env::var("STRATUM_SOAK_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(2 * 60 * 60),
+    );
+
+    let (listener, host, port) = setup_test_server(1.0).await;
+    let accepted = Arc::new(AtomicU64::new(0));
+    tokio::spawn(soak_pool(listener, 500, accepted.clone()));
+
+    let mut client = StratumV1Client::new(host, port, TestMiner).await?;
+    let mut events = client.events();
+    client.subscribe().await?;
+    client.authorize("soak.worker", "x").await?;
+
+    let started = std::time::Instant::now();
+    let warmed_up = started + duration / 10;
+    let mut baseline_memory = None;
+    let mut reconnects = 0u64;
+    let mut attempts = 0u64;
+    let mut submitted = 0u64;
+    let mut discarded = 0u64;
+    let mut failed = 0u64;
+    let mut notifications = 0u64;
+
+    while started.elapsed() < duration {
+        timeout(STUCK_TIMEOUT, client.handle_notifications())
+            .await
+            .expect("handle_notifications hung")?;
+        notifications += 1;
+
+        // Submit a share every few notifications
+        let mut disconnected = false;
+        if notifications.is_multiple_of(10) {
+            if let Some(job) = client.get_current_job().await? {
+                attempts += 1;
+                let share = Share {
+                    job_id: job.job_id.clone(),
+                    extranonce2: format!("{:08x}", attempts),
+                    ntime: job.ntime.clone(),
+                    nonce: "00000000".to_string(),
+                    version_bits: None,
+                };
+                match timeout(STUCK_TIMEOUT, client.submit_share(share))
+                    .await
+                    .expect("submit_share hung")
+                {
+                    Ok(true) => submitted += 1,
+                    // Shares of a job from before the last reconnect are
+                    // dropped locally, the pool answers all others with true
+                    Ok(false) => discarded += 1,
+                    // The pool dropped us while jobs were still buffered
+                    Err(_) => {
+                        failed += 1;
+                        disconnected = true;
+                    }
+                }
+            }
+        }
+
+        loop {
+            match events.try_recv() {
+                Ok(StratumEvent::Disconnected { .. }) => disconnected = true,
+                Ok(StratumEvent::ShareDiscarded { reject_reason, .. }) => {
+                    assert_eq!(reject_reason, RejectReason::Stale, "share discarded");
+                }
+                Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        if disconnected {
+            timeout(STUCK_TIMEOUT, async {
+                client.reconnect().await?;
+                client.subscribe().await?;
+                client.authorize("soak.worker", "x").await
+            })
+            .await
+            .expect("reconnect hung")?;
+            reconnects += 1;
+        }
+
+        if baseline_memory.is_none() && std::time::Instant::now() >= warmed_up {
+            baseline_memory = resident_memory();
+        }
+    }
+
+    let snapshot = client.session_snapshot().await;
+    println!(
+        "soak: {:?}, {} notifications, {} shares, {} discarded, {} failed, {} reconnects",
+        started.elapsed(),
+        notifications,
+        submitted,
+        discarded,
+        failed,
+        reconnects
+    );
+    assert!(reconnects > 0, "no reconnects were injected");
+    assert_eq!(snapshot.shares.accepted, submitted);
+    // Submits cut off by the injected disconnects aren't the pool's rejects
+    assert_eq!(snapshot.shares.rejected, 0);
+    assert_eq!(snapshot.submit_failures, failed);
+    assert_eq!(accepted.load(Ordering::SeqCst), submitted);
+    assert!(snapshot
+        .submit_latency
+        .values()
+        .all(|latency| latency.samples <= LATENCY_SAMPLE_WINDOW));
+    assert!(client.get_target().await.is_ok());
+
+    if let (Some(baseline), Some(current)) = (baseline_memory, resident_memory()) {
+        let growth = current.saturating_sub(baseline);
+        assert!(
+            growth < MAX_MEMORY_GROWTH,
+            "memory grew by {} bytes",
+            growth
+        );
+    }
+
+    client.close().await?;
+    Ok(())
+}